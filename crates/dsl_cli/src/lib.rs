@@ -0,0 +1,440 @@
+//! Terminal front end for the DSL: `dsl run`, `dsl check`, and `dsl fmt`.
+
+use serde_json::{Map, Value};
+use std::io::{BufRead, Write};
+
+pub const USAGE: &str = "usage:
+  dsl run <program.dsl> [--fixtures <fixtures.json>] [--params <params.json>]
+          [--const NAME=json]... [--format text|json] [--explain]
+          [--strict] [--coverage]
+  dsl check <program.dsl>
+  dsl fmt <program.dsl> [--write | --check]
+  dsl repl [--fixtures <fixtures.json>]
+  dsl gen <spec.json> [--out <fixtures.json>]";
+
+const REPL_HELP: &str = "statements run when terminated with ';' (input may span lines)
+  :explain   show explain output of the last statement
+  :bindings  list bound names
+  :reset     drop all bindings and kv stores
+  :help      show this help
+  :quit      exit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Executes one CLI invocation (`args` excludes the binary name), writing normal
+/// output to `out`. Errors are returned for the caller to report.
+pub fn run_cli(args: &[String], out: &mut dyn Write) -> Result<(), String> {
+    let (command, rest) = args.split_first().ok_or_else(|| USAGE.to_string())?;
+    match command.as_str() {
+        "run" => cmd_run(rest, out),
+        "check" => cmd_check(rest, out),
+        "fmt" => cmd_fmt(rest, out),
+        "repl" => cmd_repl(rest, out),
+        "gen" => cmd_gen(rest, out),
+        "help" | "--help" | "-h" => write_out(out, &format!("{USAGE}\n")),
+        _ => Err(format!("unknown command: {command}\n{USAGE}")),
+    }
+}
+
+fn cmd_run(args: &[String], out: &mut dyn Write) -> Result<(), String> {
+    let mut program_path = None;
+    let mut fixtures_path = None;
+    let mut params_path = None;
+    let mut options = dsl_runtime::RunOptions::default();
+    let mut format = OutputFormat::Text;
+    let mut explain = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--fixtures" => fixtures_path = Some(flag_value(&mut iter, "--fixtures")?),
+            "--params" => params_path = Some(flag_value(&mut iter, "--params")?),
+            "--const" => {
+                let (name, value) = parse_const(&flag_value(&mut iter, "--const")?)?;
+                options.constants.insert(name, value);
+            }
+            "--format" => {
+                format = match flag_value(&mut iter, "--format")?.as_str() {
+                    "text" => OutputFormat::Text,
+                    "json" => OutputFormat::Json,
+                    other => {
+                        return Err(format!("unknown --format: {other} (expected text or json)"))
+                    }
+                }
+            }
+            "--explain" => explain = true,
+            "--strict" => options.strict = true,
+            "--coverage" => options.coverage = true,
+            _ => set_positional(&mut program_path, arg)?,
+        }
+    }
+
+    let program = read_file(&program_path.ok_or_else(|| USAGE.to_string())?)?;
+    let fixtures = load_fixtures(fixtures_path)?;
+
+    if let Some(path) = params_path {
+        match serde_json::from_str(&read_file(&path)?) {
+            Ok(Value::Object(params)) => options.params = params,
+            Ok(_) => return Err(format!("{path}: params must be a JSON object")),
+            Err(e) => return Err(format!("invalid params json in {path}: {e}")),
+        }
+    }
+
+    let outputs = dsl_runtime::run_with_options(&program, fixtures, &options)?;
+    let text = match format {
+        OutputFormat::Text => render_text(&outputs, explain),
+        OutputFormat::Json => render_json(&outputs, explain),
+    };
+    write_out(out, &text)
+}
+
+fn cmd_check(args: &[String], out: &mut dyn Write) -> Result<(), String> {
+    let mut program_path = None;
+    for arg in args {
+        set_positional(&mut program_path, arg)?;
+    }
+    let path = program_path.ok_or_else(|| USAGE.to_string())?;
+    dsl_runtime::compile(&read_file(&path)?).map_err(|e| format!("{path}: {e}"))?;
+    write_out(out, &format!("{path}: ok\n"))
+}
+
+fn cmd_fmt(args: &[String], out: &mut dyn Write) -> Result<(), String> {
+    let mut program_path = None;
+    let mut write = false;
+    let mut check = false;
+    for arg in args {
+        match arg.as_str() {
+            "--write" => write = true,
+            "--check" => check = true,
+            _ => set_positional(&mut program_path, arg)?,
+        }
+    }
+    if write && check {
+        return Err("--write and --check cannot be combined".to_string());
+    }
+
+    let path = program_path.ok_or_else(|| USAGE.to_string())?;
+    let src = read_file(&path)?;
+    let formatted = dsl_syntax::format_source(&src).map_err(|e| format!("{path}: {e}"))?;
+    if check {
+        if formatted != src {
+            return Err(format!("{path}: not formatted"));
+        }
+        return Ok(());
+    }
+    if write {
+        return std::fs::write(&path, formatted).map_err(|e| format!("{path}: {e}"));
+    }
+    write_out(out, &formatted)
+}
+
+fn cmd_repl(args: &[String], out: &mut dyn Write) -> Result<(), String> {
+    let mut fixtures_path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--fixtures" => fixtures_path = Some(flag_value(&mut iter, "--fixtures")?),
+            _ => return Err(format!("unexpected argument: {arg}\n{USAGE}")),
+        }
+    }
+    let mut session = dsl_runtime::Session::new(load_fixtures(fixtures_path)?)?;
+    run_repl(&mut session, &mut std::io::stdin().lock(), out)
+}
+
+fn cmd_gen(args: &[String], out: &mut dyn Write) -> Result<(), String> {
+    let mut spec_path = None;
+    let mut out_path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--out" => out_path = Some(flag_value(&mut iter, "--out")?),
+            _ => set_positional(&mut spec_path, arg)?,
+        }
+    }
+
+    let path = spec_path.ok_or_else(|| USAGE.to_string())?;
+    let spec = serde_json::from_str(&read_file(&path)?)
+        .map_err(|e| format!("invalid spec json in {path}: {e}"))?;
+    let fixtures = dsl_runtime::generate_fixtures(&spec).map_err(|e| format!("{path}: {e}"))?;
+    let mut text = serde_json::to_string_pretty(&fixtures).map_err(|e| e.to_string())?;
+    text.push('\n');
+    match out_path {
+        Some(out_path) => std::fs::write(&out_path, text).map_err(|e| format!("{out_path}: {e}")),
+        None => write_out(out, &text),
+    }
+}
+
+/// Reads statements from `input` until EOF or `:quit`, printing each statement's
+/// tables and logs. Evaluation errors are printed and the loop continues.
+pub fn run_repl(
+    session: &mut dsl_runtime::Session,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> Result<(), String> {
+    let mut buffer = String::new();
+    loop {
+        write_out(out, if buffer.is_empty() { "dsl> " } else { "...> " })?;
+        out.flush().map_err(|e| e.to_string())?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return write_out(out, "\n");
+        }
+
+        if buffer.is_empty() && line.trim_start().starts_with(':') {
+            match line.trim() {
+                ":quit" | ":q" => return Ok(()),
+                ":help" => write_out(out, &format!("{REPL_HELP}\n"))?,
+                ":explain" => {
+                    for explain_line in session.last_explain() {
+                        write_out(out, &format!("{explain_line}\n"))?;
+                    }
+                }
+                ":bindings" => {
+                    for (name, kind) in session.bindings() {
+                        write_out(out, &format!("{name}: {kind}\n"))?;
+                    }
+                }
+                ":reset" => {
+                    session.reset();
+                    write_out(out, "session reset\n")?;
+                }
+                other => write_out(out, &format!("unknown command: {other} (try :help)\n"))?,
+            }
+            continue;
+        }
+
+        buffer.push_str(&line);
+        if buffer.trim().is_empty() {
+            buffer.clear();
+            continue;
+        }
+        if !buffer.trim_end().ends_with(';') {
+            continue;
+        }
+
+        let text = match session.eval(&buffer) {
+            Ok(outputs) => render_text(&outputs, false),
+            Err(e) => format!("error: {e}\n"),
+        };
+        buffer.clear();
+        write_out(out, &text)?;
+    }
+}
+
+/// Renders warnings, then tables as aligned text grids, followed by logs and any
+/// coverage report.
+pub fn render_text(outputs: &dsl_runtime::Outputs, explain: bool) -> String {
+    let mut out = String::new();
+    for warning in &outputs.warnings {
+        out.push_str(&format!("warning: {warning}\n"));
+    }
+    if explain {
+        out.push_str("== explain ==\n");
+        for line in &outputs.explain {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    for (name, rows) in &outputs.tables {
+        out.push_str(&format!("== table {name} ==\n"));
+        out.push_str(&render_table(rows, outputs.column_order.get(name)));
+    }
+    for (name, records) in &outputs.logs {
+        out.push_str(&format!("== log {name} ==\n"));
+        for record in records {
+            out.push_str(&record.line());
+            out.push('\n');
+        }
+    }
+    if let Some(coverage) = &outputs.coverage {
+        out.push_str("== coverage ==\n");
+        for entry in coverage {
+            out.push_str(&format!("{entry}\n"));
+        }
+    }
+    out
+}
+
+/// Renders outputs as one pretty-printed JSON object.
+pub fn render_json(outputs: &dsl_runtime::Outputs, explain: bool) -> String {
+    let mut body = Map::new();
+    body.insert(
+        "tables".to_string(),
+        Value::Object(
+            outputs
+                .tables
+                .iter()
+                .map(|(name, rows)| (name.clone(), Value::Array(rows.clone())))
+                .collect(),
+        ),
+    );
+    body.insert(
+        "logs".to_string(),
+        Value::Object(
+            outputs
+                .logs
+                .iter()
+                .map(|(name, records)| {
+                    let lines = records.iter().map(|record| Value::String(record.line()));
+                    (name.clone(), Value::Array(lines.collect()))
+                })
+                .collect(),
+        ),
+    );
+    if !outputs.warnings.is_empty() {
+        body.insert(
+            "warnings".to_string(),
+            Value::Array(
+                outputs
+                    .warnings
+                    .iter()
+                    .map(|warning| Value::String(warning.to_string()))
+                    .collect(),
+            ),
+        );
+    }
+    if let Some(coverage) = &outputs.coverage {
+        body.insert(
+            "coverage".to_string(),
+            Value::Array(
+                coverage
+                    .iter()
+                    .map(dsl_runtime::CoverageEntry::to_json)
+                    .collect(),
+            ),
+        );
+    }
+    if explain {
+        body.insert(
+            "explain".to_string(),
+            Value::Array(outputs.explain.iter().cloned().map(Value::String).collect()),
+        );
+    }
+    let mut text = serde_json::to_string_pretty(&Value::Object(body)).unwrap_or_default();
+    text.push('\n');
+    text
+}
+
+/// Record rows get one column per key (sorted, like the web playground, unless the
+/// program gave `ui.table(columns=...)`); any other rows are shown in a single
+/// `value` column.
+fn render_table(rows: &[Value], order: Option<&Vec<String>>) -> String {
+    let all_records = rows.iter().all(|row| matches!(row, Value::Object(_)));
+    let (columns, cells): (Vec<String>, Vec<Vec<String>>) = if all_records && !rows.is_empty() {
+        let columns = order.cloned().unwrap_or_else(|| {
+            let mut columns: Vec<String> = Vec::new();
+            for row in rows {
+                if let Value::Object(map) = row {
+                    for key in map.keys() {
+                        if !columns.contains(key) {
+                            columns.push(key.clone());
+                        }
+                    }
+                }
+            }
+            columns.sort();
+            columns
+        });
+        let cells = rows
+            .iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|column| match row {
+                        Value::Object(map) => cell(map.get(column).unwrap_or(&Value::Null)),
+                        _ => cell(&Value::Null),
+                    })
+                    .collect()
+            })
+            .collect();
+        (columns, cells)
+    } else {
+        (
+            vec!["value".to_string()],
+            rows.iter().map(|row| vec![cell(row)]).collect(),
+        )
+    };
+
+    let mut widths: Vec<usize> = columns.iter().map(|c| c.chars().count()).collect();
+    for row in &cells {
+        for (idx, value) in row.iter().enumerate() {
+            widths[idx] = widths[idx].max(value.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str(&render_row(&columns, &widths));
+    out.push_str(
+        &widths
+            .iter()
+            .map(|w| "-".repeat(*w))
+            .collect::<Vec<_>>()
+            .join("-+-"),
+    );
+    out.push('\n');
+    for row in &cells {
+        out.push_str(&render_row(row, &widths));
+    }
+    out
+}
+
+fn render_row(values: &[String], widths: &[usize]) -> String {
+    let line = values
+        .iter()
+        .zip(widths)
+        .map(|(value, width)| format!("{value:<width$}"))
+        .collect::<Vec<_>>()
+        .join(" | ");
+    format!("{}\n", line.trim_end())
+}
+
+fn cell(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn flag_value(iter: &mut std::slice::Iter<'_, String>, flag: &str) -> Result<String, String> {
+    iter.next()
+        .cloned()
+        .ok_or_else(|| format!("{flag} requires a value"))
+}
+
+fn set_positional(slot: &mut Option<String>, arg: &str) -> Result<(), String> {
+    if arg.starts_with("--") {
+        return Err(format!("unknown flag: {arg}\n{USAGE}"));
+    }
+    if slot.is_some() {
+        return Err(format!("unexpected argument: {arg}\n{USAGE}"));
+    }
+    *slot = Some(arg.to_string());
+    Ok(())
+}
+
+/// `NAME=VALUE`; `VALUE` is JSON, or a plain string when it does not parse.
+fn parse_const(arg: &str) -> Result<(String, Value), String> {
+    let (name, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("--const expects NAME=VALUE, got {arg}"))?;
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+    Ok((name.to_string(), value))
+}
+
+fn load_fixtures(path: Option<String>) -> Result<Value, String> {
+    match path {
+        Some(path) => serde_json::from_str(&read_file(&path)?)
+            .map_err(|e| format!("invalid fixtures json in {path}: {e}")),
+        None => Ok(Value::Object(Map::new())),
+    }
+}
+
+fn read_file(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))
+}
+
+fn write_out(out: &mut dyn Write, text: &str) -> Result<(), String> {
+    out.write_all(text.as_bytes()).map_err(|e| e.to_string())
+}
@@ -0,0 +1,8 @@
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut stdout = std::io::stdout();
+    if let Err(e) = dsl_cli::run_cli(&args, &mut stdout) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,296 @@
+use dsl_cli::run_cli;
+use std::path::PathBuf;
+
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dsl_cli_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("temp dir should be creatable");
+    let path = dir.join(name);
+    std::fs::write(&path, contents).expect("temp file should be writable");
+    path
+}
+
+fn cli(args: &[&str]) -> Result<String, String> {
+    let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    let mut out = Vec::new();
+    run_cli(&args, &mut out)?;
+    Ok(String::from_utf8(out).expect("cli output should be utf8"))
+}
+
+#[test]
+fn run_prints_aligned_text_tables_and_logs() {
+    let program = temp_file(
+        "run_text.dsl",
+        r#"
+input.json("rows") |> json |> ui.table("people");
+input.json("rows") |> json |> map(_.id) |> ui.log("ids");
+"#,
+    );
+    let fixtures = temp_file(
+        "run_text.json",
+        r#"{"rows": [{"id": 1, "name": "Ada"}, {"id": 22, "name": "Lin"}]}"#,
+    );
+
+    let out = cli(&[
+        "run",
+        program.to_str().unwrap(),
+        "--fixtures",
+        fixtures.to_str().unwrap(),
+    ])
+    .expect("run should succeed");
+
+    assert_eq!(
+        out,
+        "== table people ==\nid | name\n---+------\n1  | \"Ada\"\n22 | \"Lin\"\n== log ids ==\n1\n22\n"
+    );
+}
+
+#[test]
+fn run_prints_json_when_requested() {
+    let program = temp_file(
+        "run_json.dsl",
+        r#"input.json("xs") |> json |> ui.table("out");"#,
+    );
+    let fixtures = temp_file("run_json.json", r#"{"xs": [1]}"#);
+
+    let out = cli(&[
+        "run",
+        program.to_str().unwrap(),
+        "--fixtures",
+        fixtures.to_str().unwrap(),
+        "--format",
+        "json",
+    ])
+    .expect("run should succeed");
+
+    let body = serde_json::from_str(&out).expect("output should be json");
+    assert_eq!(
+        body,
+        serde_json::json!({"logs": {}, "tables": {"out": [1]}})
+    );
+}
+
+#[test]
+fn run_fills_params_from_file() {
+    let program = temp_file(
+        "run_params.dsl",
+        r#"input.json("xs") |> json |> filter(_ > $min) |> ui.table("out");"#,
+    );
+    let fixtures = temp_file("run_params.json", r#"{"xs": [1, 2, 3]}"#);
+    let params = temp_file("run_params_params.json", r#"{"min": 2}"#);
+
+    let out = cli(&[
+        "run",
+        program.to_str().unwrap(),
+        "--fixtures",
+        fixtures.to_str().unwrap(),
+        "--params",
+        params.to_str().unwrap(),
+        "--format",
+        "json",
+    ])
+    .expect("run should succeed");
+    let body = serde_json::from_str(&out).expect("output should be json");
+    assert_eq!(
+        body,
+        serde_json::json!({"logs": {}, "tables": {"out": [3]}})
+    );
+}
+
+#[test]
+fn run_applies_const_overrides() {
+    let program = temp_file(
+        "run_const.dsl",
+        r#"const REGION := "eu"; const MIN := 1;
+input.json("xs") |> json |> filter(_ > MIN) |> map(REGION) |> ui.table("out");"#,
+    );
+    let fixtures = temp_file("run_const.json", r#"{"xs": [1, 2, 3]}"#);
+
+    let out = cli(&[
+        "run",
+        program.to_str().unwrap(),
+        "--fixtures",
+        fixtures.to_str().unwrap(),
+        "--const",
+        "REGION=us",
+        "--const",
+        "MIN=2",
+        "--format",
+        "json",
+    ])
+    .expect("run should succeed");
+    let body = serde_json::from_str(&out).expect("output should be json");
+    assert_eq!(
+        body,
+        serde_json::json!({"logs": {}, "tables": {"out": ["us"]}})
+    );
+}
+
+#[test]
+fn check_reports_ok_and_parse_errors() {
+    let good = temp_file("check_good.dsl", r#"input.json("xs") |> json;"#);
+    let out = cli(&["check", good.to_str().unwrap()]).expect("check should pass");
+    assert!(out.ends_with(": ok\n"));
+
+    let bad = temp_file("check_bad.dsl", "x :=");
+    let err = cli(&["check", bad.to_str().unwrap()]).expect_err("check should fail");
+    assert!(err.contains("expected expression"));
+}
+
+#[test]
+fn fmt_prints_writes_and_checks() {
+    let path = temp_file("fmt.dsl", "xs:=input.json(\"xs\")|>json;");
+    let path_str = path.to_str().unwrap();
+
+    assert_eq!(
+        cli(&["fmt", path_str]).expect("fmt should succeed"),
+        "xs := input.json(\"xs\") |> json;\n"
+    );
+    assert!(cli(&["fmt", path_str, "--check"])
+        .expect_err("unformatted file should fail check")
+        .contains("not formatted"));
+
+    cli(&["fmt", path_str, "--write"]).expect("fmt --write should succeed");
+    cli(&["fmt", path_str, "--check"]).expect("formatted file should pass check");
+}
+
+#[test]
+fn unknown_commands_and_flags_show_usage() {
+    assert!(cli(&["nope"]).expect_err("should fail").contains("usage:"));
+    assert!(cli(&["run", "--bogus"])
+        .expect_err("should fail")
+        .contains("unknown flag"));
+    assert!(cli(&[]).expect_err("should fail").contains("usage:"));
+}
+
+#[test]
+fn repl_runs_statements_and_meta_commands() {
+    let mut session =
+        dsl_runtime::Session::new(serde_json::json!({"xs": [1, 2]})).expect("fixtures load");
+    let input = r#"xs := input.json("xs")
+  |> json;
+:bindings
+xs |> map(_ + 1) |> ui.table("out");
+:explain
+nope;
+:reset
+:bindings
+:quit
+"#;
+
+    let mut out = Vec::new();
+    dsl_cli::run_repl(&mut session, &mut input.as_bytes(), &mut out).expect("repl should run");
+    let out = String::from_utf8(out).expect("utf8 output");
+
+    assert!(out.contains("...> "));
+    assert!(out.contains("xs: stream (2 items)\n"));
+    assert!(out.contains("== table out ==\nvalue\n-----\n2\n3\n"));
+    assert!(out.contains("  [sink] ui.table(out)\n"));
+    assert!(out.contains("error: unknown ident nope at 1:1\n"));
+    assert!(out.contains("session reset\n"));
+    assert_eq!(out.matches("xs: stream").count(), 1);
+}
+
+#[test]
+fn gen_prints_fixtures_from_spec() {
+    let spec = temp_file(
+        "gen_spec.json",
+        r#"{"seed": 3, "fixtures": {"xs": {"count": 2, "value": {"type": "seq", "start": 5}}}}"#,
+    );
+
+    let out = cli(&["gen", spec.to_str().unwrap()]).expect("gen should succeed");
+    assert_eq!(out, "{\n  \"xs\": [\n    5,\n    6\n  ]\n}\n");
+}
+
+#[test]
+fn run_text_tables_follow_requested_column_order() {
+    let program = temp_file(
+        "run_columns.dsl",
+        r#"input.json("rows") |> json |> ui.table("people", columns=["name", "id"], format={id: "no.%d"});"#,
+    );
+    let fixtures = temp_file(
+        "run_columns.json",
+        r#"{"rows": [{"id": 1, "name": "Ada", "age": 36}]}"#,
+    );
+
+    let out = cli(&[
+        "run",
+        program.to_str().unwrap(),
+        "--fixtures",
+        fixtures.to_str().unwrap(),
+    ])
+    .expect("run should succeed");
+
+    assert_eq!(
+        out,
+        "== table people ==\nname  | id\n------+-------\n\"Ada\" | \"no.1\"\n"
+    );
+}
+
+#[test]
+fn run_prints_deprecation_warnings_first() {
+    let program = temp_file(
+        "run_warnings.dsl",
+        r#"input.json("xs") |> json |> topk(k=1, by=_, order="desc") |> ui.table("top");"#,
+    );
+    let fixtures = temp_file("run_warnings.json", r#"{"xs": [1, 2]}"#);
+
+    let out = cli(&[
+        "run",
+        program.to_str().unwrap(),
+        "--fixtures",
+        fixtures.to_str().unwrap(),
+    ])
+    .expect("run should succeed");
+    assert_eq!(
+        out,
+        "warning: topk is deprecated; use rank.topk at 1:29\n== table top ==\nvalue\n-----\n2\n"
+    );
+}
+
+#[test]
+fn run_strict_rejects_auto_codec_directions() {
+    let program = temp_file(
+        "run_strict.dsl",
+        r#"input.json("xs") |> json |> ui.table("xs");"#,
+    );
+    let fixtures = temp_file("run_strict.json", r#"{"xs": [1]}"#);
+    let (program, fixtures) = (program.to_str().unwrap(), fixtures.to_str().unwrap());
+
+    cli(&["run", program, "--fixtures", fixtures]).expect("auto mode decodes");
+    let err = cli(&["run", program, "--fixtures", fixtures, "--strict"])
+        .expect_err("strict mode needs ~json");
+    assert_eq!(
+        err,
+        "json only encodes here; decode with ~json or json.decode at 1:21"
+    );
+}
+
+#[test]
+fn run_coverage_reports_items_per_stage() {
+    let program = temp_file(
+        "run_coverage.dsl",
+        r#"input.json("xs") |> json |> filter(_ > 5) |> ui.table("big");"#,
+    );
+    let fixtures = temp_file("run_coverage.json", r#"{"xs": [1, 2]}"#);
+    let out = cli(&[
+        "run",
+        program.to_str().unwrap(),
+        "--fixtures",
+        fixtures.to_str().unwrap(),
+        "--coverage",
+    ])
+    .expect("run should succeed");
+    assert_eq!(
+        out,
+        "== table big ==
+value
+-----
+== coverage ==
+1:1 statement pipeline: 2 items
+1:1 source input.json(\"xs\"): 2 items
+1:21 stage json: 2 items
+1:29 stage filter(_ > 5): 2 items
+1:46 stage ui.table(\"big\"): 0 items
+"
+    );
+}
@@ -0,0 +1,93 @@
+//! Baseline timings for representative pipelines over generated fixtures.
+//!
+//! `cargo bench -p dsl_runtime` prints a summary per case plus a per-stage
+//! breakdown of one run. Set `DSL_BENCH_ROWS` to change the fixture size.
+
+use dsl_runtime::bench::{measure, run_with_timing};
+use dsl_runtime::generate_fixtures;
+use serde_json::{json, Value};
+
+const ITERATIONS: usize = 10;
+
+const CASES: &[(&str, &str)] = &[
+    (
+        "map_filter",
+        r#"input.json("events") |> json |> filter(_.ms > 250) |> map(_.ms + _.id) |> ui.table("out");"#,
+    ),
+    (
+        "group_count_topk",
+        r#"input.json("events") |> json |> group.count(by_key=_.tag) |> rank.topk(k=3, by=_.count, order="desc") |> ui.table("out");"#,
+    ),
+    (
+        "group_collect_all",
+        r#"input.json("events") |> json |> group.collect_all(by_key=_.user, within_ms=1000, limit=10) |> ui.table("out");"#,
+    ),
+    (
+        "kv_lookup",
+        r#"input.json("users") |> json |> kv.load(store="users");
+input.json("events") |> json |> lookup.kv(store="users", key=_.user) |> ui.table("out");"#,
+    ),
+    (
+        "codec_round_trip",
+        r#"input.json("events") |> json |> json >> base64 |> ~(json >> base64) |> ui.table("out");"#,
+    ),
+];
+
+fn fixtures(rows: i64) -> Value {
+    let mut spec = json!({
+        "seed": 1,
+        "fixtures": {
+            "events": {
+                "count": 0,
+                "fields": {
+                    "id": {"type": "seq"},
+                    "ms": {"type": "int", "min": 0, "max": 500},
+                    "tag": {"type": "choice", "values": ["rust", "ui", "db", "ops", "web"]},
+                    "user": {"type": "string", "prefix": "u", "min": 0, "max": 99}
+                }
+            },
+            "users": {
+                "count": 100,
+                "fields": {
+                    "key": {"type": "string", "prefix": "u", "min": 0, "max": 99},
+                    "value": {"type": "int", "min": 0, "max": 9}
+                }
+            }
+        }
+    });
+    if let Value::Object(root) = &mut spec {
+        if let Some(Value::Object(all)) = root.get_mut("fixtures") {
+            if let Some(Value::Object(events)) = all.get_mut("events") {
+                events.insert("count".to_string(), Value::Number(rows.into()));
+            }
+        }
+    }
+    generate_fixtures(&spec).expect("bench spec should generate")
+}
+
+fn main() {
+    let rows = std::env::var("DSL_BENCH_ROWS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5_000);
+    let fixtures = fixtures(rows);
+    println!("rows={rows} iterations={ITERATIONS}");
+
+    for (name, program) in CASES {
+        let summary = measure(ITERATIONS, || {
+            dsl_runtime::run(program, fixtures.clone()).expect("bench program should run")
+        });
+        println!(
+            "{name:<20} min {:>10.3?}  median {:>10.3?}  mean {:>10.3?}",
+            summary.min, summary.median, summary.mean
+        );
+
+        let timed = run_with_timing(program, fixtures.clone()).expect("bench program should run");
+        for stage in &timed.stages {
+            println!(
+                "    {:>10.3?}  {:>6} -> {:<6} {:>9}B  {}",
+                stage.elapsed, stage.items_in, stage.items_out, stage.bytes_out, stage.stage
+            );
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Async facade over the synchronous interpreter.
+//!
+//! [`Runtime::run_async`] runs the program, collects every async host call and
+//! async source it could not answer yet, awaits those futures together, and
+//! runs again with the results cached — until nothing is pending. A round stops
+//! before the next statement or stage once something is pending, so no stage
+//! ever sees a placeholder; stages before that point run again each round.
+//! Futures need not be `Send`. [`block_on`] drives them on native targets; wasm
+//! hosts await `run_async` on their own executor (e.g. `spawn_local`).
+
+use crate::errors::{Error, LIMIT_EXCEEDED};
+use crate::runtime::Runtime;
+use crate::{
+    CancelToken, Outputs, RuntimeError, RuntimeState, Source, SourceContext, StageArgs, Value,
+};
+use serde_json::Value as JsonValue;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+use std::task::Poll;
+#[cfg(not(target_arch = "wasm32"))]
+use std::task::{Context, Wake, Waker};
+
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+pub type AsyncHostFn = Rc<dyn Fn(Value) -> BoxFuture<Result<Value, String>>>;
+
+pub type AsyncSource = Rc<dyn Fn(&StageArgs) -> BoxFuture<Result<Vec<Value>, String>>>;
+
+/// Resolve-and-rerun rounds before giving up. Each round gets at least one
+/// stage further, so this bounds the stages that wait on async calls.
+const MAX_ROUNDS: usize = 64;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum CallKey {
+    Host(String, Value),
+    Source(String, String),
+}
+
+enum Resolved {
+    Host(Result<Value, String>),
+    Source(Result<Vec<Value>, String>),
+}
+
+#[derive(Default)]
+struct Cache {
+    resolved: HashMap<CallKey, Resolved>,
+    pending: Vec<(CallKey, BoxFuture<Resolved>)>,
+    /// Cancelled on the round's first miss, which stops the run at the next stage.
+    round: CancelToken,
+}
+
+impl Cache {
+    fn is_waiting(&self, key: &CallKey) -> bool {
+        self.pending.iter().any(|(pending, _)| pending == key)
+    }
+
+    /// Records `fut` for `key` unless it is already awaited, and ends the round.
+    fn wait(&mut self, key: CallKey, fut: impl FnOnce() -> BoxFuture<Resolved>) {
+        if !self.is_waiting(&key) {
+            self.pending.push((key, fut()));
+        }
+        self.round.cancel();
+    }
+}
+
+type SharedCache = Rc<RefCell<Cache>>;
+
+impl Runtime {
+    /// Registers an async function for `host.call("name", arg)`.
+    pub fn register_async_host_fn<F, Fut>(&mut self, name: &str, func: F) -> &mut Self
+    where
+        F: Fn(Value) -> Fut + 'static,
+        Fut: Future<Output = Result<Value, String>> + 'static,
+    {
+        self.async_host_fns
+            .insert(name.to_string(), Rc::new(move |arg| Box::pin(func(arg))));
+        self
+    }
+
+    /// Registers an async input provider (same naming rules as
+    /// [`Runtime::register_source`]). Calls are cached by their arguments.
+    pub fn register_async_source<F, Fut>(
+        &mut self,
+        name: &str,
+        read: F,
+    ) -> Result<&mut Self, String>
+    where
+        F: Fn(&StageArgs) -> Fut + 'static,
+        Fut: Future<Output = Result<Vec<Value>, String>> + 'static,
+    {
+        // The placeholder runs the sync registration checks and explains the
+        // error if the program is run synchronously.
+        self.register_source(name, Pending(name.to_string()))?;
+        self.async_sources
+            .insert(name.to_string(), Rc::new(move |args| Box::pin(read(args))));
+        Ok(self)
+    }
+
+    /// Like [`Runtime::run`], awaiting async host functions and sources.
+    ///
+    /// Each round reruns the program from the start and stops before the first
+    /// statement or stage that would consume an unanswered call, so downstream
+    /// stages only ever see resolved values. Sync host functions and custom
+    /// stages upstream of that point are called again in every round; async
+    /// calls are made once per distinct argument.
+    pub async fn run_async(
+        &self,
+        program: &str,
+        fixtures: JsonValue,
+    ) -> Result<Outputs, RuntimeError> {
+        let program = crate::compile(program)?;
+        let cache = SharedCache::default();
+        let round_runtime = self.with_cached_async(&cache);
+        for _ in 0..MAX_ROUNDS {
+            let round = CancelToken::new();
+            cache.borrow_mut().round = round.clone();
+            let state = RuntimeState {
+                runtime: Some(&round_runtime),
+                round: Some(round),
+                ..RuntimeState::default()
+            };
+            let result = crate::run_program(&program, None, fixtures.clone(), state);
+            let pending = std::mem::take(&mut cache.borrow_mut().pending);
+            if pending.is_empty() {
+                return result;
+            }
+            for (key, resolved) in join_all(pending).await {
+                cache.borrow_mut().resolved.insert(key, resolved);
+            }
+        }
+        Err(Error::new(
+            LIMIT_EXCEEDED,
+            format!("async calls did not settle after {MAX_ROUNDS} rounds"),
+        )
+        .into())
+    }
+
+    /// A copy of `self` whose async extensions answer from `cache`, recording a
+    /// pending future and ending the round on a miss. The stage still finishes
+    /// (with a placeholder) so it collects all of its misses in one round.
+    fn with_cached_async(&self, cache: &SharedCache) -> Runtime {
+        let mut runtime = self.clone();
+        for (name, func) in &self.async_host_fns {
+            let (cache, func, name) = (Rc::clone(cache), Rc::clone(func), name.clone());
+            runtime.register_host_fn(&name.clone(), move |arg| {
+                let key = CallKey::Host(name.clone(), arg.clone());
+                let mut cache = cache.borrow_mut();
+                match cache.resolved.get(&key) {
+                    Some(Resolved::Host(result)) => result.clone(),
+                    _ => {
+                        cache.wait(key, || {
+                            let fut = func(arg);
+                            Box::pin(async move { Resolved::Host(fut.await) })
+                        });
+                        Ok(Value::Null)
+                    }
+                }
+            });
+        }
+        for (name, read) in &self.async_sources {
+            runtime.sources.insert(
+                name.clone(),
+                Rc::new(CachedSource {
+                    name: name.clone(),
+                    read: Rc::clone(read),
+                    cache: Rc::clone(cache),
+                }),
+            );
+        }
+        runtime
+    }
+}
+
+/// Stand-in for an async source outside `run_async`.
+struct Pending(String);
+
+impl Source for Pending {
+    fn read(&self, _args: &StageArgs, _ctx: &SourceContext<'_>) -> Result<Vec<Value>, String> {
+        Err(format!("{} is async; use Runtime::run_async", self.0))
+    }
+}
+
+struct CachedSource {
+    name: String,
+    read: AsyncSource,
+    cache: SharedCache,
+}
+
+impl Source for CachedSource {
+    fn read(&self, args: &StageArgs, _ctx: &SourceContext<'_>) -> Result<Vec<Value>, String> {
+        let key = CallKey::Source(self.name.clone(), args.text());
+        let mut cache = self.cache.borrow_mut();
+        match cache.resolved.get(&key) {
+            Some(Resolved::Source(result)) => result.clone(),
+            _ => {
+                cache.wait(key, || {
+                    let fut = (self.read)(args);
+                    Box::pin(async move { Resolved::Source(fut.await) })
+                });
+                Ok(Vec::new())
+            }
+        }
+    }
+}
+
+/// Polls every future until all have completed, preserving order.
+async fn join_all<K>(futures: Vec<(K, BoxFuture<Resolved>)>) -> Vec<(K, Resolved)> {
+    let mut slots: Vec<(K, Option<BoxFuture<Resolved>>, Option<Resolved>)> = futures
+        .into_iter()
+        .map(|(key, fut)| (key, Some(fut), None))
+        .collect();
+    std::future::poll_fn(|cx| {
+        let mut done = true;
+        for (_, fut, out) in slots.iter_mut() {
+            if let Some(f) = fut {
+                match f.as_mut().poll(cx) {
+                    Poll::Ready(value) => {
+                        *out = Some(value);
+                        *fut = None;
+                    }
+                    Poll::Pending => done = false,
+                }
+            }
+        }
+        if done {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+    slots
+        .into_iter()
+        .map(|(key, _, out)| (key, out.expect("future completed")))
+        .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct FlagWaker {
+    woken: AtomicBool,
+    thread: std::thread::Thread,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Wake for FlagWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+        self.thread.unpark();
+    }
+}
+
+/// Minimal single-threaded executor: drives `fut` to completion on the current
+/// thread, parking between wake-ups. Not available on wasm, where blocking the
+/// only thread would keep JS promises from ever resolving.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = std::pin::pin!(fut);
+    let flag = Arc::new(FlagWaker {
+        woken: AtomicBool::new(true),
+        thread: std::thread::current(),
+    });
+    let waker = Waker::from(Arc::clone(&flag));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if flag.woken.swap(false, Ordering::SeqCst) {
+            if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+                return out;
+            }
+            continue;
+        }
+        std::thread::park();
+    }
+}
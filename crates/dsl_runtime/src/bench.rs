@@ -0,0 +1,73 @@
+//! Timing helpers for performance baselines (`cargo bench -p dsl_runtime`).
+//!
+//! Uses `std::time::Instant`, so these are for native hosts only.
+
+use crate::{Outputs, RuntimeError, RuntimeState};
+use serde_json::Value as JsonValue;
+use std::time::{Duration, Instant};
+
+/// One pipeline stage as it ran: `stage` is its source text.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StageTiming {
+    pub stage: String,
+    pub items_in: usize,
+    pub items_out: usize,
+    /// [`crate::Value::approx_size`] of the items the stage emitted.
+    pub bytes_out: usize,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedRun {
+    pub outputs: Outputs,
+    /// Wall time for the whole run, including parsing.
+    pub total: Duration,
+    /// Top-level pipeline stages in execution order; composed stages count as one.
+    pub stages: Vec<StageTiming>,
+}
+
+/// Like [`crate::run`], recording how long each pipeline stage took.
+pub fn run_with_timing(program: &str, fixtures: JsonValue) -> Result<TimedRun, RuntimeError> {
+    let start = Instant::now();
+    let mut stages = Vec::new();
+    let state = RuntimeState {
+        timings: Some(&mut stages),
+        ..RuntimeState::default()
+    };
+    let outputs = crate::run_with_state(program, fixtures, state)?;
+    Ok(TimedRun {
+        outputs,
+        total: start.elapsed(),
+        stages,
+    })
+}
+
+/// Min / median / mean over a set of samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub iterations: usize,
+    pub min: Duration,
+    pub median: Duration,
+    pub mean: Duration,
+}
+
+/// Calls `f` once to warm up, then `iterations` more times, timing each call.
+pub fn measure<T>(iterations: usize, mut f: impl FnMut() -> T) -> Summary {
+    let iterations = iterations.max(1);
+    std::hint::black_box(f());
+    let mut samples: Vec<Duration> = (0..iterations)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(f());
+            start.elapsed()
+        })
+        .collect();
+    samples.sort();
+    Summary {
+        iterations,
+        min: samples[0],
+        median: samples[iterations / 2],
+        mean: samples.iter().sum::<Duration>() / iterations as u32,
+    }
+}
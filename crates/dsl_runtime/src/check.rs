@@ -0,0 +1,230 @@
+//! Checks over a parsed program that run before any data flows.
+
+use crate::diagram::callee_name;
+use crate::errors::{Error, DUPLICATE_BINDING, RESERVED_NAME, USE_BEFORE_BIND};
+use crate::messages::Message;
+use crate::params::{walk, walk_stmt};
+use crate::runtime::is_reserved_namespace;
+use dsl_syntax::{CallArg, Expr, Program, Span, Stmt};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What binding a name that is already bound does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rebinding {
+    /// An error unless the statement is written `override name := ...;`.
+    #[default]
+    Error,
+    /// Replaces the earlier binding.
+    Allow,
+}
+
+/// Checks `program`, parsed from `source`; errors end with `at line:column`.
+pub(crate) fn check(program: &Program, source: &str, rebinding: Rebinding) -> Result<(), Error> {
+    reserved_bindings(program, source)?;
+    if rebinding == Rebinding::Error {
+        duplicate_bindings(program, source)?;
+    }
+    forward_references(program, source)?;
+    stage_arguments(program, source)
+}
+
+/// `ui := ...;` would hide `ui.table` and every other stage in the namespace.
+fn reserved_bindings(program: &Program, source: &str) -> Result<(), Error> {
+    for stmt in &program.statements {
+        if matches!(stmt, Stmt::Const { .. }) {
+            continue;
+        }
+        if let Some(name) = binds(stmt).iter().find(|name| is_reserved_namespace(name)) {
+            return Err(Error::templated(
+                RESERVED_NAME,
+                Message::new("reserved_namespace").arg("name", name),
+            )
+            .at(stmt.span().resolve(source).0));
+        }
+    }
+    Ok(())
+}
+
+fn duplicate_bindings(program: &Program, source: &str) -> Result<(), Error> {
+    let mut bound: BTreeMap<&str, Span> = BTreeMap::new();
+    for stmt in &program.statements {
+        let (names, overrides) = match stmt {
+            Stmt::Binding {
+                name, overrides, ..
+            } => (std::slice::from_ref(name), *overrides),
+            Stmt::Destructure {
+                names, overrides, ..
+            } => (names.as_slice(), *overrides),
+            Stmt::Pipeline { .. } | Stmt::Const { .. } => continue,
+        };
+        for name in names {
+            if let Some(first) = bound.insert(name, stmt.span()) {
+                if !overrides {
+                    return Err(Error::templated(
+                        DUPLICATE_BINDING,
+                        Message::new("duplicate_binding")
+                            .arg("name", name)
+                            .arg("first", first.resolve(source).0),
+                    )
+                    .at(stmt.span().resolve(source).0));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Names a statement binds.
+fn binds(stmt: &Stmt) -> &[String] {
+    match stmt {
+        Stmt::Binding { name, .. } | Stmt::Const { name, .. } => std::slice::from_ref(name),
+        Stmt::Destructure { names, .. } => names,
+        Stmt::Pipeline { .. } => &[],
+    }
+}
+
+/// Identifiers a statement reads, in source order (stage and function names and
+/// `let` and `match` names excluded).
+fn uses(stmt: &Stmt) -> Vec<(String, Span)> {
+    let (mut out, mut locals) = (Vec::new(), BTreeSet::new());
+    let _ = walk_stmt(&mut stmt.clone(), &mut |expr| {
+        match expr {
+            Expr::Ident { name, span } => out.push((name.clone(), *span)),
+            Expr::Let { name, .. } => {
+                locals.insert(name.clone());
+            }
+            Expr::Match { arms, .. } => {
+                let bound = arms.iter().flat_map(|arm| arm.pattern.bindings());
+                locals.extend(bound.map(str::to_string));
+            }
+            _ => {}
+        }
+        Ok(())
+    });
+    out.retain(|(name, _)| !locals.contains(name));
+    out.sort_by_key(|(_, span)| span.start);
+    out
+}
+
+/// For each statement, the names it reads exactly once and no later statement
+/// reads, which the interpreter moves out of the environment instead of sharing.
+pub(crate) fn last_reads(program: &Program) -> Vec<BTreeSet<String>> {
+    let mut read_later = BTreeSet::new();
+    let mut out: Vec<_> = program
+        .statements
+        .iter()
+        .rev()
+        .map(|stmt| {
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            for (name, _) in uses(stmt) {
+                *counts.entry(name).or_default() += 1;
+            }
+            let last = counts
+                .iter()
+                .filter(|(name, count)| **count == 1 && !read_later.contains(*name))
+                .map(|(name, _)| name.clone())
+                .collect();
+            read_later.extend(counts.into_keys());
+            last
+        })
+        .collect();
+    out.reverse();
+    out
+}
+
+/// Rejects reading a name that only a later statement binds, and names the cycle
+/// when that later statement in turn depends on this one.
+fn forward_references(program: &Program, source: &str) -> Result<(), Error> {
+    let statements = &program.statements;
+    let uses: Vec<_> = statements.iter().map(uses).collect();
+    let mut first_def: BTreeMap<&str, usize> = BTreeMap::new();
+    for (idx, stmt) in statements.iter().enumerate() {
+        for name in binds(stmt) {
+            first_def.entry(name).or_insert(idx);
+        }
+    }
+    for (idx, stmt) in statements.iter().enumerate() {
+        for (name, span) in &uses[idx] {
+            let Some(&def) = first_def.get(name.as_str()).filter(|def| **def >= idx) else {
+                continue;
+            };
+            let cycle = if def == idx {
+                Some(format!("{name} -> {name}"))
+            } else {
+                binds(stmt).iter().find_map(|own| {
+                    let path = path(name, own, &first_def, &uses, &mut BTreeSet::new())?;
+                    Some(format!("{own} -> {}", path.join(" -> ")))
+                })
+            };
+            let message = match cycle {
+                Some(cycle) => Message::new("used_in_cycle").arg("cycle", cycle),
+                None => Message::new("used_before_bound"),
+            };
+            return Err(Error::templated(
+                USE_BEFORE_BIND,
+                message
+                    .arg("name", name)
+                    .arg("bound", statements[def].span().resolve(source).0),
+            )
+            .at(span.resolve(source).0));
+        }
+    }
+    Ok(())
+}
+
+/// `[from, ..., to]` following each name to the names its first definition reads.
+fn path(
+    from: &str,
+    to: &str,
+    first_def: &BTreeMap<&str, usize>,
+    uses: &[Vec<(String, Span)>],
+    seen: &mut BTreeSet<String>,
+) -> Option<Vec<String>> {
+    if !seen.insert(from.to_string()) {
+        return None;
+    }
+    let def = *first_def.get(from)?;
+    uses[def].iter().find_map(|(next, _)| {
+        let rest = if next == to {
+            vec![to.to_string()]
+        } else {
+            path(next, to, first_def, uses, seen)?
+        };
+        Some([vec![from.to_string()], rest].concat())
+    })
+}
+
+/// Builds every built-in stage call whose arguments are all literals, so a bad `k`,
+/// `order`, or missing named argument fails here rather than mid-run. Calls that
+/// read a param or a binding wait for the run.
+fn stage_arguments(program: &Program, source: &str) -> Result<(), Error> {
+    for stmt in &program.statements {
+        walk_stmt(&mut stmt.clone(), &mut |expr| {
+            let Expr::Call { args, span, .. } = &*expr else {
+                return Ok(());
+            };
+            let Some(name) = callee_name(expr) else {
+                return Ok(());
+            };
+            let name = dsl_syntax::migrate::current_stage_name(&name).unwrap_or(&name);
+            if name.starts_with("input.") || name == "host.call" || !literal_args(args) {
+                return Ok(());
+            }
+            crate::builtin_stage(name, args, false)
+                .map(drop)
+                .map_err(|e| e.at(span.resolve(source).0))
+        })?;
+    }
+    Ok(())
+}
+
+fn literal_args(args: &[CallArg]) -> bool {
+    args.iter().all(|arg| {
+        let (CallArg::Positional(value) | CallArg::Named { value, .. }) = arg;
+        walk(&mut value.clone(), &mut |expr| match expr {
+            Expr::Ident { .. } | Expr::Param { .. } => Err(Error::from(String::new())),
+            _ => Ok(()),
+        })
+        .is_ok()
+    })
+}
@@ -0,0 +1,421 @@
+//! Reversible codec stages (`json`, `utf8`, `base64`, `ndjson`) and a
+//! round-trip harness: every codec must satisfy `inverse(forward(x)) == [x]` for
+//! each `x` it accepts.
+
+use crate::errors::{Error, DECODE, NOT_REVERSIBLE, STRICT};
+use crate::intern::Interner;
+use crate::type_expr::TypeExpr;
+use crate::{Direction, Stream, Value};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Json,
+    Utf8,
+    Base64,
+    Ndjson,
+}
+
+impl Codec {
+    /// Every codec; the round-trip tests iterate this, so new codecs are covered.
+    pub const ALL: [Codec; 4] = [Codec::Json, Codec::Utf8, Codec::Base64, Codec::Ndjson];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Json => "json",
+            Codec::Utf8 => "utf8",
+            Codec::Base64 => "base64",
+            Codec::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Codec> {
+        Codec::ALL.into_iter().find(|codec| codec.name() == name)
+    }
+}
+
+/// Encodes one value.
+pub fn forward(codec: Codec, value: Value) -> Result<Value, String> {
+    encode(codec, value).map_err(|e| e.message)
+}
+
+fn encode(codec: Codec, value: Value) -> Result<Value, Error> {
+    match codec {
+        Codec::Json => json_forward(value),
+        #[cfg(feature = "codecs")]
+        Codec::Utf8 => utf8_forward(value),
+        #[cfg(feature = "codecs")]
+        Codec::Base64 => base64_forward(value),
+        #[cfg(feature = "codecs")]
+        Codec::Ndjson => {
+            let mut bytes =
+                serde_json::to_vec(&crate::value_to_json(value)).map_err(|e| e.to_string())?;
+            bytes.push(b'\n');
+            Ok(Value::Bytes(bytes))
+        }
+        #[cfg(not(feature = "codecs"))]
+        _ => Err(crate::features::unavailable(codec.name(), "codecs").into()),
+    }
+}
+
+/// Decodes one value. `ndjson` may yield any number of values; the others yield one.
+pub fn inverse(codec: Codec, value: Value) -> Result<Vec<Value>, String> {
+    decode(codec, value, &mut Interner::default()).map_err(|e| e.message)
+}
+
+fn decode(codec: Codec, value: Value, interner: &mut Interner) -> Result<Vec<Value>, Error> {
+    match codec {
+        Codec::Json => json_inverse(value, interner).map(|v| vec![v]),
+        #[cfg(feature = "codecs")]
+        Codec::Utf8 => utf8_inverse(value).map(|v| vec![v]),
+        #[cfg(feature = "codecs")]
+        Codec::Base64 => base64_inverse(value).map(|v| vec![v]),
+        #[cfg(feature = "codecs")]
+        Codec::Ndjson => match value {
+            Value::Bytes(bytes) => {
+                let text =
+                    String::from_utf8(bytes).map_err(|e| Error::new(DECODE, e.to_string()))?;
+                parse_ndjson(&text, interner)
+            }
+            other => {
+                Err(Error::mismatch("Bytes", "ndjson inverse expects Bytes").got(other.kind()))
+            }
+        },
+        #[cfg(not(feature = "codecs"))]
+        _ => Err(crate::features::unavailable(codec.name(), "codecs").into()),
+    }
+}
+
+/// Whether `value` is in the codec's forward domain (auto mode encodes it).
+pub fn accepts(codec: Codec, value: &Value) -> bool {
+    match codec {
+        Codec::Json => accepts_json_forward(value),
+        #[cfg(feature = "codecs")]
+        Codec::Ndjson => accepts_json_forward(value),
+        #[cfg(feature = "codecs")]
+        Codec::Utf8 => accepts_utf8_forward(value),
+        #[cfg(feature = "codecs")]
+        Codec::Base64 => accepts_base64_forward(value),
+        #[cfg(not(feature = "codecs"))]
+        _ => false,
+    }
+}
+
+/// Whether auto mode decodes `value` (checked only when [`accepts`] is false).
+pub fn accepts_inverse(codec: Codec, value: &Value) -> bool {
+    match codec {
+        Codec::Json => accepts_json_inverse(value),
+        #[cfg(feature = "codecs")]
+        Codec::Ndjson => accepts_json_inverse(value),
+        #[cfg(feature = "codecs")]
+        Codec::Utf8 => accepts_utf8_inverse(value),
+        #[cfg(feature = "codecs")]
+        Codec::Base64 => accepts_base64_inverse(value),
+        #[cfg(not(feature = "codecs"))]
+        _ => false,
+    }
+}
+
+/// Checks `inverse(forward(value)) == [value]`. Values outside the forward domain
+/// are an error, as is `json` on records or arrays containing `Bytes` (they encode
+/// as integer arrays).
+pub fn roundtrip_check(value: &Value, codec: Codec) -> Result<(), String> {
+    let name = codec.name();
+    if !accepts(codec, value) {
+        return Err(format!("{name} does not accept {value:?}"));
+    }
+    let encoded = forward(codec, value.clone()).map_err(|e| format!("{name} forward: {e}"))?;
+    let decoded = inverse(codec, encoded.clone()).map_err(|e| format!("{name} inverse: {e}"))?;
+    if decoded.as_slice() != std::slice::from_ref(value) {
+        return Err(format!(
+            "{name} round trip mismatch: {value:?} -> {encoded:?} -> {decoded:?}"
+        ));
+    }
+    Ok(())
+}
+
+/// Deterministic mix of values for property tests: scalars, tricky strings,
+/// top-level `Bytes`, and nested arrays/records of JSON-representable values.
+pub fn sample_values(seed: u64, count: usize) -> Vec<Value> {
+    let mut rng = crate::generate::SplitMix64(seed);
+    let mut out = vec![
+        Value::Null,
+        Value::I64(0),
+        Value::I64(i64::MIN),
+        Value::I64(i64::MAX),
+        Value::String(String::new()),
+        Value::String("quote \" slash \\ tab \t line \n é 🦀".to_string()),
+        Value::Bytes(Vec::new()),
+        Value::Bytes((0..=255).collect()),
+        Value::Array(Vec::new()),
+        Value::Record(BTreeMap::new()),
+    ];
+    while out.len() < count {
+        let value = match rng.next() % 3 {
+            0 => Value::Bytes((0..rng.next() % 8).map(|_| rng.next() as u8).collect()),
+            _ => sample_json_value(&mut rng, 2),
+        };
+        out.push(value);
+    }
+    out.truncate(count);
+    out
+}
+
+fn sample_json_value(rng: &mut crate::generate::SplitMix64, depth: u32) -> Value {
+    let kinds = if depth == 0 { 4 } else { 6 };
+    match rng.next() % kinds {
+        0 => Value::Null,
+        1 => Value::Bool(rng.next() & 1 == 1),
+        2 => Value::I64(rng.next() as i64),
+        3 => {
+            const CHARS: &[char] = &[
+                'a', 'Z', '0', ' ', '"', '\\', '\n', '\t', '\u{1}', 'é', '✓', '🦀',
+            ];
+            let len = rng.next() % 6;
+            Value::String(
+                (0..len)
+                    .map(|_| CHARS[(rng.next() % CHARS.len() as u64) as usize])
+                    .collect(),
+            )
+        }
+        4 => Value::Array(
+            (0..rng.next() % 4)
+                .map(|_| sample_json_value(rng, depth - 1))
+                .collect(),
+        ),
+        _ => Value::Record(
+            (0..rng.next() % 4)
+                .map(|i| (format!("k{i}").into(), sample_json_value(rng, depth - 1)))
+                .collect(),
+        ),
+    }
+}
+
+/// Applies `codec` to every item: auto mode encodes accepted values and decodes
+/// the rest; forward mode only encodes. With `expect`, each value encoded and each
+/// value decoded must have that type. Also returns how many items were decoded.
+pub(crate) fn apply(
+    codec: Codec,
+    direction: Direction,
+    expect: Option<&TypeExpr>,
+    stream: Stream,
+    interner: &mut Interner,
+) -> Result<(Stream, usize), Error> {
+    let check = |idx: usize, value: &Value| match expect {
+        Some(ty) => ty.check(value).map_err(|e| {
+            e.map_message(|e| format!("{} expect={ty}: item {idx}: {e}", codec.name()))
+        }),
+        None => Ok(()),
+    };
+    let mut out = Vec::new();
+    let mut decoded_items = 0;
+    for (idx, value) in stream.into_iter().enumerate() {
+        let inverse_mode = match direction {
+            Direction::Inverse => true,
+            Direction::Auto if accepts(codec, &value) => false,
+            Direction::Auto if accepts_inverse(codec, &value) => true,
+            Direction::Auto => {
+                return Err(Error::new(
+                    NOT_REVERSIBLE,
+                    "no matching direction for stage",
+                ))
+            }
+            Direction::Forward if accepts(codec, &value) || !accepts_inverse(codec, &value) => {
+                false
+            }
+            Direction::Forward => {
+                let name = codec.name();
+                return Err(Error::new(
+                    STRICT,
+                    format!("{name} only encodes here; decode with ~{name} or {name}.decode"),
+                ));
+            }
+        };
+        if inverse_mode {
+            decoded_items += 1;
+            let decoded = decode(codec, value, interner)?;
+            decoded.iter().try_for_each(|value| check(idx, value))?;
+            out.extend(decoded);
+        } else {
+            check(idx, &value)?;
+            out.push(encode(codec, value)?);
+        }
+    }
+    Ok((Stream::new(out), decoded_items))
+}
+
+fn json_forward(value: Value) -> Result<Value, Error> {
+    let json = crate::value_to_json(value);
+    serde_json::to_vec(&json)
+        .map(Value::Bytes)
+        .map_err(|e| e.to_string().into())
+}
+
+fn json_inverse(value: Value, interner: &mut Interner) -> Result<Value, Error> {
+    match value {
+        Value::Bytes(bytes) => serde_json::from_slice(&bytes)
+            .map(|json| crate::json_to_value(json, interner))
+            .map_err(|e| Error::new(DECODE, e.to_string())),
+        other => Err(Error::mismatch("Bytes", "json inverse expects Bytes").got(other.kind())),
+    }
+}
+
+#[cfg(feature = "codecs")]
+fn utf8_forward(value: Value) -> Result<Value, Error> {
+    match value {
+        Value::String(s) => Ok(Value::Bytes(s.into_bytes())),
+        other => Err(Error::mismatch("String", "utf8 forward expects String").got(other.kind())),
+    }
+}
+
+#[cfg(feature = "codecs")]
+fn utf8_inverse(value: Value) -> Result<Value, Error> {
+    match value {
+        Value::Bytes(bytes) => String::from_utf8(bytes)
+            .map(Value::String)
+            .map_err(|e| Error::new(DECODE, e.to_string())),
+        other => Err(Error::mismatch("Bytes", "utf8 inverse expects Bytes").got(other.kind())),
+    }
+}
+
+#[cfg(feature = "codecs")]
+fn base64_forward(value: Value) -> Result<Value, Error> {
+    match value {
+        Value::Bytes(bytes) => Ok(Value::String(base64_encode(&bytes))),
+        other => Err(Error::mismatch("Bytes", "base64 forward expects Bytes").got(other.kind())),
+    }
+}
+
+#[cfg(feature = "codecs")]
+fn base64_inverse(value: Value) -> Result<Value, Error> {
+    match value {
+        Value::String(s) => Ok(Value::Bytes(base64_decode(&s)?)),
+        other => Err(Error::mismatch("String", "base64 inverse expects String").got(other.kind())),
+    }
+}
+
+#[cfg(feature = "codecs")]
+/// Parses one JSON value per non-blank line.
+pub(crate) fn parse_ndjson(text: &str, interner: &mut Interner) -> Result<Vec<Value>, Error> {
+    let mut out = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let json = serde_json::from_str(line)
+            .map_err(|e| Error::new(DECODE, format!("ndjson line {}: {e}", idx + 1)))?;
+        out.push(crate::json_to_value(json, interner));
+    }
+    Ok(out)
+}
+
+fn accepts_json_forward(value: &Value) -> bool {
+    !matches!(value, Value::Bytes(_) | Value::Unit)
+}
+
+fn accepts_json_inverse(value: &Value) -> bool {
+    matches!(value, Value::Bytes(_))
+}
+
+#[cfg(feature = "codecs")]
+fn accepts_utf8_forward(value: &Value) -> bool {
+    matches!(value, Value::String(_))
+}
+
+#[cfg(feature = "codecs")]
+fn accepts_utf8_inverse(value: &Value) -> bool {
+    matches!(value, Value::Bytes(_))
+}
+
+#[cfg(feature = "codecs")]
+fn accepts_base64_forward(value: &Value) -> bool {
+    matches!(value, Value::Bytes(_))
+}
+
+#[cfg(feature = "codecs")]
+fn accepts_base64_inverse(value: &Value) -> bool {
+    matches!(value, Value::String(_))
+}
+
+#[cfg(feature = "codecs")]
+fn base64_encode(bytes: &[u8]) -> String {
+    const T: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut o = String::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let b0 = bytes[i] as u32;
+        let b1 = if i + 1 < bytes.len() {
+            bytes[i + 1] as u32
+        } else {
+            0
+        };
+        let b2 = if i + 2 < bytes.len() {
+            bytes[i + 2] as u32
+        } else {
+            0
+        };
+        let n = (b0 << 16) | (b1 << 8) | b2;
+        o.push(T[((n >> 18) & 63) as usize] as char);
+        o.push(T[((n >> 12) & 63) as usize] as char);
+        o.push(if i + 1 < bytes.len() {
+            T[((n >> 6) & 63) as usize] as char
+        } else {
+            '='
+        });
+        o.push(if i + 2 < bytes.len() {
+            T[(n & 63) as usize] as char
+        } else {
+            '='
+        });
+        i += 3;
+    }
+    o
+}
+
+#[cfg(feature = "codecs")]
+pub(crate) fn base64_decode(s: &str) -> Result<Vec<u8>, Error> {
+    fn v(c: u8) -> Option<u8> {
+        match c {
+            b'A'..=b'Z' => Some(c - b'A'),
+            b'a'..=b'z' => Some(c - b'a' + 26),
+            b'0'..=b'9' => Some(c - b'0' + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let invalid = || Error::new(DECODE, "invalid base64");
+    let bytes = s.as_bytes();
+    if !bytes.len().is_multiple_of(4) {
+        return Err(Error::new(DECODE, "invalid base64 length"));
+    }
+
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c0 = v(bytes[i]).ok_or_else(invalid)? as u32;
+        let c1 = v(bytes[i + 1]).ok_or_else(invalid)? as u32;
+        let c2 = if bytes[i + 2] == b'=' {
+            64
+        } else {
+            v(bytes[i + 2]).ok_or_else(invalid)? as u32
+        };
+        let c3 = if bytes[i + 3] == b'=' {
+            64
+        } else {
+            v(bytes[i + 3]).ok_or_else(invalid)? as u32
+        };
+
+        let n = (c0 << 18) | (c1 << 12) | ((c2 & 63) << 6) | (c3 & 63);
+        out.push(((n >> 16) & 255) as u8);
+        if c2 != 64 {
+            out.push(((n >> 8) & 255) as u8);
+        }
+        if c3 != 64 {
+            out.push((n & 255) as u8);
+        }
+        i += 4;
+    }
+    Ok(out)
+}
@@ -0,0 +1,229 @@
+//! Table columns: the schema inferred for every `ui.table` ([`crate::Outputs::schemas`])
+//! and column-wise output ([`crate::RunOptions::columnar`]). Record rows get one
+//! column per key (sorted, like the CLI and playground tables); other rows become a
+//! single `value` column.
+
+use serde_json::{Map, Value as JsonValue};
+
+/// What a column holds. Nulls don't count, so a column of numbers and nulls is `Number`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColumnType {
+    /// Every cell is null.
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+    /// More than one non-null kind.
+    Mixed,
+}
+
+impl ColumnType {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColumnType::Null => "null",
+            ColumnType::Bool => "bool",
+            ColumnType::Number => "number",
+            ColumnType::String => "string",
+            ColumnType::Array => "array",
+            ColumnType::Object => "object",
+            ColumnType::Mixed => "mixed",
+        }
+    }
+
+    fn of(value: &JsonValue) -> Self {
+        match value {
+            JsonValue::Null => ColumnType::Null,
+            JsonValue::Bool(_) => ColumnType::Bool,
+            JsonValue::Number(_) => ColumnType::Number,
+            JsonValue::String(_) => ColumnType::String,
+            JsonValue::Array(_) => ColumnType::Array,
+            JsonValue::Object(_) => ColumnType::Object,
+        }
+    }
+
+    fn merge(self, other: ColumnType) -> Self {
+        match (self, other) {
+            (ColumnType::Null, kind) | (kind, ColumnType::Null) => kind,
+            (a, b) if a == b => a,
+            _ => ColumnType::Mixed,
+        }
+    }
+}
+
+/// One column of [`crate::Outputs::schemas`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColumnSchema {
+    pub name: String,
+    pub kind: ColumnType,
+    /// Some row has `null` here or lacks the field.
+    pub nullable: bool,
+}
+
+impl ColumnSchema {
+    /// `{"name", "type", "nullable"}`.
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(Map::from_iter([
+            ("name".to_string(), JsonValue::String(self.name.clone())),
+            (
+                "type".to_string(),
+                JsonValue::String(self.kind.name().to_string()),
+            ),
+            ("nullable".to_string(), JsonValue::Bool(self.nullable)),
+        ]))
+    }
+}
+
+/// Infers the columns of `rows` without copying them.
+pub fn infer_schema(rows: &[JsonValue]) -> Vec<ColumnSchema> {
+    infer_schema_ordered(rows, None)
+}
+
+/// [`infer_schema`] with the columns in `order` (from `ui.table(columns=...)`).
+pub(crate) fn infer_schema_ordered(
+    rows: &[JsonValue],
+    order: Option<&[String]>,
+) -> Vec<ColumnSchema> {
+    let Some(names) = record_columns(rows, order) else {
+        let (kind, nullable) = column_kind(rows.iter().map(Some));
+        return vec![ColumnSchema {
+            name: "value".to_string(),
+            kind,
+            nullable,
+        }];
+    };
+    names
+        .into_iter()
+        .map(|name| {
+            let (kind, nullable) = column_kind(rows.iter().map(|row| match row {
+                JsonValue::Object(map) => map.get(&name),
+                _ => None,
+            }));
+            ColumnSchema {
+                name,
+                kind,
+                nullable,
+            }
+        })
+        .collect()
+}
+
+/// `order`, or else the sorted union of the keys, when every row is a record (and
+/// there is at least one).
+fn record_columns(rows: &[JsonValue], order: Option<&[String]>) -> Option<Vec<String>> {
+    let all_records = rows.iter().all(|row| matches!(row, JsonValue::Object(_)));
+    if rows.is_empty() || !all_records {
+        return None;
+    }
+    if let Some(order) = order {
+        return Some(order.to_vec());
+    }
+    let mut names: Vec<String> = Vec::new();
+    for row in rows {
+        if let JsonValue::Object(map) = row {
+            for key in map.keys() {
+                if let Err(at) = names.binary_search(key) {
+                    names.insert(at, key.clone());
+                }
+            }
+        }
+    }
+    Some(names)
+}
+
+fn column_kind<'a>(cells: impl Iterator<Item = Option<&'a JsonValue>>) -> (ColumnType, bool) {
+    let mut kind = ColumnType::Null;
+    let mut nullable = false;
+    for cell in cells {
+        match cell {
+            None | Some(JsonValue::Null) => nullable = true,
+            Some(value) => kind = kind.merge(ColumnType::of(value)),
+        }
+    }
+    (kind, nullable)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Column {
+    pub name: String,
+    pub kind: ColumnType,
+    /// One cell per row; missing record fields are null.
+    pub values: Vec<JsonValue>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColumnarTable {
+    pub row_count: usize,
+    pub columns: Vec<Column>,
+}
+
+impl ColumnarTable {
+    pub fn from_rows(rows: Vec<JsonValue>) -> Self {
+        Self::from_rows_ordered(rows, None)
+    }
+
+    pub(crate) fn from_rows_ordered(rows: Vec<JsonValue>, order: Option<&[String]>) -> Self {
+        let row_count = rows.len();
+        let Some(names) = record_columns(&rows, order) else {
+            return Self {
+                row_count,
+                columns: vec![column("value".to_string(), rows)],
+            };
+        };
+        let mut cells: Vec<Vec<JsonValue>> = vec![Vec::with_capacity(row_count); names.len()];
+        for row in rows {
+            let JsonValue::Object(mut map) = row else {
+                unreachable!("checked above");
+            };
+            for (name, cells) in names.iter().zip(&mut cells) {
+                cells.push(map.remove(name).unwrap_or(JsonValue::Null));
+            }
+        }
+        Self {
+            row_count,
+            columns: names
+                .into_iter()
+                .zip(cells)
+                .map(|(n, c)| column(n, c))
+                .collect(),
+        }
+    }
+
+    /// `{"row_count": n, "columns": [{"name", "type", "values"}]}`.
+    pub fn to_json(&self) -> JsonValue {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| {
+                JsonValue::Object(Map::from_iter([
+                    ("name".to_string(), JsonValue::String(column.name.clone())),
+                    (
+                        "type".to_string(),
+                        JsonValue::String(column.kind.name().to_string()),
+                    ),
+                    (
+                        "values".to_string(),
+                        JsonValue::Array(column.values.clone()),
+                    ),
+                ]))
+            })
+            .collect();
+        JsonValue::Object(Map::from_iter([
+            (
+                "row_count".to_string(),
+                JsonValue::Number((self.row_count as i64).into()),
+            ),
+            ("columns".to_string(), JsonValue::Array(columns)),
+        ]))
+    }
+}
+
+fn column(name: String, values: Vec<JsonValue>) -> Column {
+    let (kind, _) = column_kind(values.iter().map(Some));
+    Column { name, kind, values }
+}
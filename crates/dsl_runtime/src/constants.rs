@@ -0,0 +1,131 @@
+//! `const NAME := expr;` statements, lowered into literals before a program runs.
+
+use crate::errors::{Error, CONSTANTS, RESERVED_NAME};
+use crate::params::{literal, walk_stmt};
+use crate::runtime::is_reserved_namespace;
+use dsl_syntax::{Expr, Program, Stmt};
+use serde_json::{Map, Value as JsonValue};
+use std::collections::BTreeSet;
+
+/// Removes `const` statements from `program`, substituting each constant into the
+/// statements after it. A same-named entry in `host` replaces the declared value
+/// (it must have the same JSON type); host entries the program never declares are
+/// ignored. `defined` carries constants between calls (see `Session`).
+pub(crate) fn lower(program: &Program, defined: &mut Map, host: &Map) -> Result<Program, Error> {
+    let mut bound: BTreeSet<String> = BTreeSet::new();
+    let mut statements = Vec::new();
+    for stmt in &program.statements {
+        let mut stmt = stmt.clone();
+        walk_stmt(&mut stmt, &mut |expr| substitute(expr, defined))?;
+        match stmt {
+            Stmt::Const { name, expr, .. } => {
+                check_name(&name, defined, &bound)?;
+                let declared = crate::eval_value_expr(&expr, None)
+                    .map_err(|e| e.map_message(|e| format!("const {name}: {e}")))?
+                    .to_json();
+                let value = match host.get(&name) {
+                    Some(value) if kind(value) != kind(&declared) => {
+                        return Err(Error::new(
+                            CONSTANTS,
+                            format!(
+                                "const {name}: host value is {}, but the program declares {}",
+                                kind(value),
+                                kind(&declared)
+                            ),
+                        ))
+                    }
+                    Some(value) => value.clone(),
+                    None => declared,
+                };
+                defined.insert(name, value);
+            }
+            Stmt::Binding { ref name, .. } if defined.contains_key(name) => {
+                return Err(Error::new(
+                    CONSTANTS,
+                    format!("{name} is already a constant"),
+                ));
+            }
+            Stmt::Destructure { ref names, .. } => {
+                if let Some(name) = names.iter().find(|name| defined.contains_key(*name)) {
+                    return Err(Error::new(
+                        CONSTANTS,
+                        format!("{name} is already a constant"),
+                    ));
+                }
+                bound.extend(names.iter().cloned());
+                statements.push(stmt);
+            }
+            stmt => {
+                if let Stmt::Binding { name, .. } = &stmt {
+                    bound.insert(name.clone());
+                }
+                statements.push(stmt);
+            }
+        }
+    }
+    Ok(Program {
+        statements,
+        comments: program.comments.clone(),
+        span: program.span,
+    })
+}
+
+fn check_name(name: &str, defined: &Map, bound: &BTreeSet<String>) -> Result<(), Error> {
+    if matches!(name, "true" | "false" | "null") || is_reserved_namespace(name) {
+        return Err(Error::new(
+            RESERVED_NAME,
+            format!("const {name}: name is reserved"),
+        ));
+    }
+    if defined.contains_key(name) {
+        return Err(Error::new(
+            CONSTANTS,
+            format!("const {name} is already defined"),
+        ));
+    }
+    if bound.contains(name) {
+        return Err(Error::new(
+            CONSTANTS,
+            format!("const {name}: name is already bound"),
+        ));
+    }
+    Ok(())
+}
+
+fn kind(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "a boolean",
+        JsonValue::Number(_) => "a number",
+        JsonValue::String(_) => "a string",
+        JsonValue::Array(_) => "an array",
+        JsonValue::Object(_) => "an object",
+    }
+}
+
+fn substitute(expr: &mut Expr, defined: &Map) -> Result<(), Error> {
+    match expr {
+        Expr::Ident { name, span } => {
+            if let Some(value) = defined.get(name.as_str()) {
+                *expr = literal(value, *span);
+            }
+        }
+        Expr::Let { name, .. } if defined.contains_key(name.as_str()) => {
+            return Err(Error::new(
+                CONSTANTS,
+                format!("let {name}: name is already a constant"),
+            ));
+        }
+        Expr::Match { arms, .. } => {
+            let mut bound = arms.iter().flat_map(|arm| arm.pattern.bindings());
+            if let Some(name) = bound.find(|name| defined.contains_key(*name)) {
+                return Err(Error::new(
+                    CONSTANTS,
+                    format!("match pattern {name}: name is already a constant"),
+                ));
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
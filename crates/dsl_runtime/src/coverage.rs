@@ -0,0 +1,199 @@
+//! Coverage mode ([`crate::RunOptions::coverage`]): how many items reached each
+//! statement, source, stage, and `match` arm, so fixtures can be checked to exercise
+//! a whole program.
+
+use crate::params::walk_stmt;
+use dsl_syntax::{format_expr, format_pattern, Expr, LineCol, Program, Span, Stmt};
+use serde_json::{Map, Value as JsonValue};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+
+thread_local! {
+    /// Items seen per `(start, end)` span while a [`Recording`] is live. `match` arms
+    /// are evaluated without the run's state, so hits are collected here.
+    static HITS: RefCell<Option<BTreeMap<(usize, usize), usize>>> = const { RefCell::new(None) };
+}
+
+/// Counts `items` reaching the node at `span`; a no-op outside a coverage run.
+pub(crate) fn hit(span: Span, items: usize) {
+    HITS.with(|hits| {
+        if let Some(hits) = hits.borrow_mut().as_mut() {
+            *hits.entry((span.start, span.end)).or_default() += items;
+        }
+    });
+}
+
+/// Collects [`hit`]s from creation until [`Recording::finish`] or drop.
+pub(crate) struct Recording;
+
+impl Recording {
+    pub(crate) fn start() -> Self {
+        HITS.with(|hits| *hits.borrow_mut() = Some(BTreeMap::new()));
+        Recording
+    }
+
+    /// One entry per pipeline statement, source, stage, and `match` arm of
+    /// `program`, in source order.
+    pub(crate) fn finish(self, program: &Program, source: Option<&str>) -> Vec<CoverageEntry> {
+        let hits = HITS
+            .with(|hits| hits.borrow_mut().take())
+            .unwrap_or_default();
+        let items = |span: Span| hits.get(&(span.start, span.end)).copied().unwrap_or(0);
+        let mut nodes: Vec<(Span, CoverageKind, String, usize)> = Vec::new();
+        for stmt in &program.statements {
+            let (Stmt::Binding { expr, .. }
+            | Stmt::Destructure { expr, .. }
+            | Stmt::Pipeline { expr, .. }) = stmt
+            else {
+                continue;
+            };
+            if let Expr::Pipeline { input, .. } = expr {
+                let text = match stmt {
+                    Stmt::Binding { name, .. } => name.clone(),
+                    Stmt::Destructure { names, .. } => names.join(", "),
+                    _ => "pipeline".to_string(),
+                };
+                nodes.push((
+                    stmt.span(),
+                    CoverageKind::Statement,
+                    text,
+                    items(input.span()),
+                ));
+            }
+            let _ = walk_stmt(&mut stmt.clone(), &mut |expr| {
+                match expr {
+                    Expr::Pipeline { input, stages, .. } => {
+                        let text = format_expr(input);
+                        nodes.push((
+                            input.span(),
+                            CoverageKind::Source,
+                            text,
+                            items(input.span()),
+                        ));
+                        for stage in stages.iter() {
+                            let text = format_expr(stage);
+                            nodes.push((
+                                stage.span(),
+                                CoverageKind::Stage,
+                                text,
+                                items(stage.span()),
+                            ));
+                        }
+                    }
+                    Expr::Match { arms, .. } => {
+                        for arm in arms.iter() {
+                            let text = format_pattern(&arm.pattern);
+                            nodes.push((arm.span, CoverageKind::Arm, text, items(arm.span)));
+                        }
+                    }
+                    _ => {}
+                }
+                Ok(())
+            });
+        }
+        nodes.sort_by_key(|(span, ..)| (span.start, std::cmp::Reverse(span.end)));
+        nodes
+            .into_iter()
+            .map(|(span, kind, text, items)| CoverageEntry {
+                kind,
+                text,
+                at: source.map(|source| span.resolve(source).0),
+                items,
+            })
+            .collect()
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        HITS.with(|hits| hits.borrow_mut().take());
+    }
+}
+
+/// What a [`CoverageEntry`] counts items for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CoverageKind {
+    /// A pipeline statement; counts the items its source produced.
+    Statement,
+    /// A pipeline's input; counts the items it produced.
+    Source,
+    /// A stage; counts the items it read.
+    Stage,
+    /// A `match` arm; counts the values it fit.
+    Arm,
+}
+
+impl CoverageKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            CoverageKind::Statement => "statement",
+            CoverageKind::Source => "source",
+            CoverageKind::Stage => "stage",
+            CoverageKind::Arm => "arm",
+        }
+    }
+}
+
+/// One node of a coverage report. Displays as `3:6 stage map(_.id): 2 items`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoverageEntry {
+    pub kind: CoverageKind,
+    /// The statement's bound names (`pipeline` when it binds none), the source or
+    /// stage as formatted code, or the arm's pattern.
+    pub text: String,
+    /// Where the node starts, when the run had the program source.
+    pub at: Option<LineCol>,
+    pub items: usize,
+}
+
+impl CoverageEntry {
+    /// Whether at least one item reached the node.
+    pub fn covered(&self) -> bool {
+        self.items > 0
+    }
+
+    /// `{"kind", "text", "items"}`, plus `"line"` and `"column"` when it has a position.
+    pub fn to_json(&self) -> JsonValue {
+        let mut out = Map::from_iter([
+            (
+                "kind".to_string(),
+                JsonValue::String(self.kind.name().to_string()),
+            ),
+            ("text".to_string(), JsonValue::String(self.text.clone())),
+            (
+                "items".to_string(),
+                JsonValue::Number((self.items as i64).into()),
+            ),
+        ]);
+        if let Some(at) = self.at {
+            out.insert(
+                "line".to_string(),
+                JsonValue::Number((at.line as i64).into()),
+            );
+            out.insert(
+                "column".to_string(),
+                JsonValue::Number((at.column as i64).into()),
+            );
+        }
+        JsonValue::Object(out)
+    }
+}
+
+impl fmt::Display for CoverageEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(at) = self.at {
+            write!(f, "{at} ")?;
+        }
+        let noun = if self.items == 1 { "item" } else { "items" };
+        write!(
+            f,
+            "{} {}: {} {noun}",
+            self.kind.name(),
+            self.text,
+            self.items
+        )
+    }
+}
@@ -0,0 +1,264 @@
+//! Static dataflow diagrams (Graphviz DOT and Mermaid) built from the AST; nothing
+//! is executed.
+
+use dsl_syntax::{format_expr, Expr, Stmt};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NodeKind {
+    Source,
+    Stage,
+    Binding,
+    Sink,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EdgeKind {
+    Flow,
+    /// A stage writing named run state feeding one that reads it (see [`state_access`]).
+    Store,
+}
+
+struct Node {
+    label: String,
+    kind: NodeKind,
+    /// Comments of the statement that added this node first, written above it.
+    comments: Vec<String>,
+}
+
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<(usize, usize, EdgeKind)>,
+    /// Stream bindings resolve to the node that holds them.
+    streams: BTreeMap<String, usize>,
+    /// Stage bindings are inlined at their use sites, labelled by name.
+    stages: BTreeSet<String>,
+    writers: BTreeMap<String, Vec<usize>>,
+    readers: Vec<(String, usize)>,
+}
+
+/// Renders `program` as a Graphviz `digraph`.
+pub fn to_dot(program: &str) -> Result<String, String> {
+    let graph = build_graph(program)?;
+    let mut out = String::from("digraph program {\n  rankdir=LR;\n");
+    for (idx, node) in graph.nodes.iter().enumerate() {
+        let shape = match node.kind {
+            NodeKind::Source => "invhouse",
+            NodeKind::Stage => "box",
+            NodeKind::Binding => "ellipse",
+            NodeKind::Sink => "house",
+        };
+        for comment in &node.comments {
+            out.push_str(&format!("  // {comment}\n"));
+        }
+        out.push_str(&format!(
+            "  n{idx} [label=\"{}\", shape={shape}];\n",
+            node.label.replace('\\', "\\\\").replace('"', "\\\"")
+        ));
+    }
+    for (from, to, kind) in &graph.edges {
+        match kind {
+            EdgeKind::Flow => out.push_str(&format!("  n{from} -> n{to};\n")),
+            EdgeKind::Store => out.push_str(&format!("  n{from} -> n{to} [style=dashed];\n")),
+        }
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+/// Renders `program` as a Mermaid `flowchart`.
+pub fn to_mermaid(program: &str) -> Result<String, String> {
+    let graph = build_graph(program)?;
+    let mut out = String::from("flowchart LR\n");
+    for (idx, node) in graph.nodes.iter().enumerate() {
+        let label = node.label.replace('"', "#quot;");
+        let shape = match node.kind {
+            NodeKind::Source => format!("[/\"{label}\"/]"),
+            NodeKind::Stage => format!("[\"{label}\"]"),
+            NodeKind::Binding => format!("([\"{label}\"])"),
+            NodeKind::Sink => format!("[[\"{label}\"]]"),
+        };
+        for comment in &node.comments {
+            out.push_str(&format!("  %% {comment}\n"));
+        }
+        out.push_str(&format!("  n{idx}{shape}\n"));
+    }
+    for (from, to, kind) in &graph.edges {
+        match kind {
+            EdgeKind::Flow => out.push_str(&format!("  n{from} --> n{to}\n")),
+            EdgeKind::Store => out.push_str(&format!("  n{from} -.-> n{to}\n")),
+        }
+    }
+    Ok(out)
+}
+
+fn build_graph(program: &str) -> Result<Graph, String> {
+    let program = crate::compile(program)?;
+    let mut graph = Graph::default();
+    for stmt in &program.statements {
+        let first = graph.nodes.len();
+        match stmt {
+            Stmt::Binding { name, expr, .. } => {
+                if is_stage_expr(expr, &graph) {
+                    graph.stages.insert(name.clone());
+                    continue;
+                }
+                let node = graph.add(name.clone(), NodeKind::Binding);
+                if let Some(tail) = graph.add_stream(expr) {
+                    graph.edges.push((tail, node, EdgeKind::Flow));
+                }
+                graph.streams.insert(name.clone(), node);
+            }
+            Stmt::Destructure { names, expr, .. } => {
+                let tail = graph.add_stream(expr);
+                for name in names {
+                    let node = graph.add(name.clone(), NodeKind::Binding);
+                    if let Some(tail) = tail {
+                        graph.edges.push((tail, node, EdgeKind::Flow));
+                    }
+                    graph.streams.insert(name.clone(), node);
+                }
+            }
+            Stmt::Pipeline { expr, .. } => {
+                graph.add_stream(expr);
+            }
+            Stmt::Const { .. } => {}
+        }
+        if let Some(node) = graph.nodes.get_mut(first) {
+            node.comments = stmt.comments().iter().map(|c| c.text.clone()).collect();
+        }
+    }
+
+    for (store, reader) in std::mem::take(&mut graph.readers) {
+        for writer in graph.writers.get(&store).cloned().unwrap_or_default() {
+            graph.edges.push((writer, reader, EdgeKind::Store));
+        }
+    }
+    Ok(graph)
+}
+
+impl Graph {
+    fn add(&mut self, label: String, kind: NodeKind) -> usize {
+        self.nodes.push(Node {
+            label,
+            kind,
+            comments: Vec::new(),
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Adds nodes for a stream-producing expression; returns the last node.
+    fn add_stream(&mut self, expr: &Expr) -> Option<usize> {
+        match expr {
+            Expr::Pipeline { input, stages, .. } => {
+                let mut tail = self.add_stream(input);
+                for stage in stages {
+                    let node = self.add_stage(stage);
+                    if let Some(prev) = tail {
+                        self.edges.push((prev, node, EdgeKind::Flow));
+                    }
+                    tail = Some(node);
+                }
+                tail
+            }
+            Expr::Ident { name, .. } if self.streams.contains_key(name) => {
+                self.streams.get(name).copied()
+            }
+            _ => Some(self.add(format_expr(expr), NodeKind::Source)),
+        }
+    }
+
+    fn add_stage(&mut self, expr: &Expr) -> usize {
+        let name = callee_name(expr);
+        let kind = match name.as_deref() {
+            Some(n) if n.starts_with("ui.") || n == "kv.load" || n == "rbac.load" => NodeKind::Sink,
+            _ => NodeKind::Stage,
+        };
+        let node = self.add(format_expr(expr), kind);
+        for access in state_access(expr) {
+            match access {
+                StateAccess::Writes(store) => self.writers.entry(store).or_default().push(node),
+                StateAccess::Reads(store) => self.readers.push((store, node)),
+            }
+        }
+        node
+    }
+}
+
+fn is_stage_expr(expr: &Expr, graph: &Graph) -> bool {
+    match expr {
+        Expr::Compose { .. } | Expr::Inverse { .. } => true,
+        Expr::Ident { name, .. } => {
+            graph.stages.contains(name) || crate::Codec::from_name(name).is_some()
+        }
+        Expr::Call { .. } => !matches!(callee_name(expr), Some(n) if n.starts_with("input.")),
+        Expr::FieldAccess { .. } => {
+            matches!(callee_name(expr), Some(n) if crate::explicit_codec(&n).is_some())
+        }
+        _ => false,
+    }
+}
+
+pub(crate) fn callee_name(expr: &Expr) -> Option<String> {
+    fn path(expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Ident { name, .. } => Some(name.clone()),
+            Expr::FieldAccess { expr, field, .. } => {
+                path(expr).map(|base| format!("{base}.{field}"))
+            }
+            _ => None,
+        }
+    }
+    match expr {
+        Expr::Call { callee, .. } => path(callee),
+        _ => path(expr),
+    }
+}
+
+/// Named run state a stage call writes or reads: `kv.load(store=..)` writes and
+/// `lookup.*(store=..)` reads `kv:<store>`; `rbac.load(name=..)` writes and
+/// `rbac.evaluate(policy=..)` and `rbac.diff(before=.., after=..)` read `rbac:<name>`.
+pub(crate) enum StateAccess {
+    Writes(String),
+    Reads(String),
+}
+
+pub(crate) fn state_access(expr: &Expr) -> Vec<StateAccess> {
+    let Some(name) = callee_name(expr) else {
+        return Vec::new();
+    };
+    let named = |param: &str, prefix: &str| {
+        string_arg(expr, param).map(|value| format!("{prefix}:{value}"))
+    };
+    let access = match name.as_str() {
+        "kv.load" => vec![named("store", "kv").map(StateAccess::Writes)],
+        _ if name.starts_with("lookup.") => vec![named("store", "kv").map(StateAccess::Reads)],
+        "rbac.load" => vec![named("name", "rbac").map(StateAccess::Writes)],
+        "rbac.evaluate" => vec![named("policy", "rbac").map(StateAccess::Reads)],
+        "rbac.diff" => vec![
+            named("before", "rbac").map(StateAccess::Reads),
+            named("after", "rbac").map(StateAccess::Reads),
+        ],
+        _ => Vec::new(),
+    };
+    access.into_iter().flatten().collect()
+}
+
+/// The string literal passed as `param` of stage call `expr`, defaults included.
+fn string_arg(expr: &Expr, param: &str) -> Option<String> {
+    let (Expr::Call { args, .. }, Some(name)) = (expr, callee_name(expr)) else {
+        return None;
+    };
+    let value = match crate::stage_args::params(&name) {
+        Some(params) => crate::stage_args::Args::bind(&name, params, args)
+            .ok()?
+            .opt(param)?
+            .clone(),
+        None => crate::named_arg(args, param).ok()?.clone(),
+    };
+    match value {
+        Expr::String { value, .. } => Some(value),
+        _ => None,
+    }
+}
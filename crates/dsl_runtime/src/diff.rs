@@ -0,0 +1,147 @@
+//! `ui.diff("name", expected="fixture", key=_.id)`: compares a stream with a
+//! fixture and records one row per added, removed, or changed entry.
+//! [`diff_outputs`] does the same for the tables and logs of two runs.
+
+use crate::{LogRecord, Outputs};
+use serde_json::{Map, Value as JsonValue};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Per-table and per-log differences between two runs ([`diff_outputs`]).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutputsDiff {
+    /// `ui.diff` rows, keyed by row index, for each table that differs; a table only
+    /// one run produced is compared with no rows.
+    pub tables: BTreeMap<String, Vec<JsonValue>>,
+    /// The same for logs, comparing `{level, message, item}` records.
+    pub logs: BTreeMap<String, Vec<JsonValue>>,
+}
+
+impl OutputsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty() && self.logs.is_empty()
+    }
+
+    /// `{"tables": {name: [row]}, "logs": {name: [row]}}`.
+    pub fn to_json(&self) -> JsonValue {
+        let section = |diffs: &BTreeMap<String, Vec<JsonValue>>| {
+            JsonValue::Object(
+                diffs
+                    .iter()
+                    .map(|(name, rows)| (name.clone(), JsonValue::Array(rows.clone())))
+                    .collect(),
+            )
+        };
+        JsonValue::Object(Map::from_iter([
+            ("tables".to_string(), section(&self.tables)),
+            ("logs".to_string(), section(&self.logs)),
+        ]))
+    }
+}
+
+/// Compares the tables and logs of run `a` (expected) with those of run `b` (actual).
+pub fn diff_outputs(a: &Outputs, b: &Outputs) -> OutputsDiff {
+    OutputsDiff {
+        tables: diff_named(&a.tables, &b.tables, JsonValue::clone),
+        logs: diff_named(&a.logs, &b.logs, LogRecord::to_json),
+    }
+}
+
+fn diff_named<T>(
+    a: &BTreeMap<String, Vec<T>>,
+    b: &BTreeMap<String, Vec<T>>,
+    to_json: impl Fn(&T) -> JsonValue,
+) -> BTreeMap<String, Vec<JsonValue>> {
+    let indexed = |rows: Option<&Vec<T>>| -> Vec<(JsonValue, JsonValue)> {
+        rows.into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(idx, row)| (JsonValue::Number((idx as i64).into()), to_json(row)))
+            .collect()
+    };
+    let names: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let rows = diff_unique(indexed(a.get(name)), indexed(b.get(name)));
+            (!rows.is_empty()).then(|| (name.clone(), rows))
+        })
+        .collect()
+}
+
+/// Diff rows between `expected` and `actual`, each paired with its match key (the
+/// row index when `ui.diff` has no `key=`). Removed and changed rows come in
+/// expected order, then added rows in stream order; equal rows are left out.
+pub(crate) fn diff_rows(
+    expected: Vec<(JsonValue, JsonValue)>,
+    actual: Vec<(JsonValue, JsonValue)>,
+) -> Result<Vec<JsonValue>, String> {
+    check_unique(&expected, "expected")?;
+    check_unique(&actual, "stream")?;
+    Ok(diff_unique(expected, actual))
+}
+
+fn diff_unique(
+    expected: Vec<(JsonValue, JsonValue)>,
+    actual: Vec<(JsonValue, JsonValue)>,
+) -> Vec<JsonValue> {
+    let mut actual: Vec<Option<(JsonValue, JsonValue)>> = actual.into_iter().map(Some).collect();
+    let mut out = Vec::new();
+    for (key, expected_row) in expected {
+        let found = actual
+            .iter_mut()
+            .find(|slot| matches!(slot, Some((k, _)) if *k == key))
+            .and_then(Option::take);
+        match found {
+            Some((_, actual_row)) if actual_row == expected_row => {}
+            Some((_, actual_row)) => out.push(changed(key, expected_row, actual_row)),
+            None => out.push(entry("removed", key, [("expected", expected_row)])),
+        }
+    }
+    for (key, actual_row) in actual.into_iter().flatten() {
+        out.push(entry("added", key, [("actual", actual_row)]));
+    }
+    out
+}
+
+fn check_unique(rows: &[(JsonValue, JsonValue)], side: &str) -> Result<(), String> {
+    for (idx, (key, _)) in rows.iter().enumerate() {
+        if rows[..idx].iter().any(|(earlier, _)| earlier == key) {
+            let key = serde_json::to_string(key).unwrap_or_default();
+            return Err(format!("ui.diff: duplicate key {key} in {side} rows"));
+        }
+    }
+    Ok(())
+}
+
+fn changed(key: JsonValue, expected: JsonValue, actual: JsonValue) -> JsonValue {
+    let fields = match (&expected, &actual) {
+        (JsonValue::Object(a), JsonValue::Object(b)) => {
+            let mut names: Vec<&String> = a.keys().chain(b.keys()).collect();
+            names.sort();
+            names.dedup();
+            let changed = names
+                .into_iter()
+                .filter(|name| a.get(*name) != b.get(*name))
+                .map(|name| JsonValue::String(name.clone()))
+                .collect();
+            Some(JsonValue::Array(changed))
+        }
+        _ => None,
+    };
+    let mut row = entry("changed", key, [("expected", expected), ("actual", actual)]);
+    if let (Some(fields), JsonValue::Object(map)) = (fields, &mut row) {
+        map.insert("fields".to_string(), fields);
+    }
+    row
+}
+
+fn entry<const N: usize>(op: &str, key: JsonValue, values: [(&str, JsonValue); N]) -> JsonValue {
+    let mut map = Map::new();
+    map.insert("op".to_string(), JsonValue::String(op.to_string()));
+    map.insert("key".to_string(), key);
+    for (name, value) in values {
+        map.insert(name.to_string(), value);
+    }
+    JsonValue::Object(map)
+}
@@ -0,0 +1,543 @@
+//! The error catalog: a stable code (`E0005`) and kind (`unknown_ident`) for every
+//! compile and runtime error, attached where the error is raised so hosts can link to
+//! help pages and tests can assert on codes instead of wording; and [`RuntimeError`],
+//! the error type of the compile and run entry points.
+
+use crate::messages::Message;
+use dsl_syntax::{ParseError, Span};
+use std::fmt;
+
+/// One entry of the error catalog. Codes are never reused once published.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ErrorCode {
+    /// `E0001`-style code.
+    pub code: &'static str,
+    /// Machine-readable name, such as `unknown_stage`.
+    pub kind: &'static str,
+    /// One line for help pages.
+    pub summary: &'static str,
+}
+
+const fn entry(code: &'static str, kind: &'static str, summary: &'static str) -> ErrorCode {
+    ErrorCode {
+        code,
+        kind,
+        summary,
+    }
+}
+
+/// Errors the catalog doesn't know yet, including host errors.
+pub(crate) const OTHER: &ErrorCode = &entry("E0000", "other", "An error without a catalog entry.");
+pub(crate) const SYNTAX: &ErrorCode = &entry("E0001", "syntax", "The program doesn't parse.");
+pub(crate) const DUPLICATE_BINDING: &ErrorCode = &entry(
+    "E0002",
+    "duplicate_binding",
+    "A name is bound twice without `override`.",
+);
+pub(crate) const USE_BEFORE_BIND: &ErrorCode = &entry(
+    "E0003",
+    "use_before_bind",
+    "A statement reads a name that a later statement binds.",
+);
+pub(crate) const RESERVED_NAME: &ErrorCode = &entry(
+    "E0004",
+    "reserved_name",
+    "A binding, constant, or custom stage uses a reserved name.",
+);
+pub(crate) const UNKNOWN_IDENT: &ErrorCode =
+    &entry("E0005", "unknown_ident", "A name that nothing binds.");
+pub(crate) const UNKNOWN_STAGE: &ErrorCode = &entry(
+    "E0006",
+    "unknown_stage",
+    "A call to a stage, source, or host function that doesn't exist.",
+);
+pub(crate) const PARAMS: &ErrorCode = &entry(
+    "E0007",
+    "params",
+    "Params are missing, unknown, or unbound.",
+);
+pub(crate) const CONSTANTS: &ErrorCode = &entry(
+    "E0008",
+    "constants",
+    "A constant is redefined, clashes with a binding, or has the wrong host type.",
+);
+pub(crate) const STAGE_ARGUMENTS: &ErrorCode = &entry(
+    "E0009",
+    "stage_arguments",
+    "A stage call's arguments don't fit its parameters.",
+);
+pub(crate) const STRICT: &ErrorCode = &entry(
+    "E0010",
+    "strict",
+    "Strict mode rejects a guess the runtime would make.",
+);
+const MISSING_FIXTURE: &ErrorCode = &entry(
+    "E0011",
+    "missing_fixture",
+    "The program reads a fixture that isn't supplied.",
+);
+pub(crate) const NOT_REVERSIBLE: &ErrorCode = &entry(
+    "E0012",
+    "not_reversible",
+    "A stage is inverted or round-tripped but can't be.",
+);
+pub(crate) const NO_MATCH: &ErrorCode =
+    &entry("E0013", "no_match", "No `match` arm fits the value.");
+pub(crate) const DECODE: &ErrorCode = &entry("E0014", "decode", "A codec can't decode its input.");
+pub(crate) const OVERFLOW: &ErrorCode =
+    &entry("E0015", "overflow", "Integer arithmetic overflowed.");
+pub(crate) const LIMIT_EXCEEDED: &ErrorCode = &entry(
+    "E0016",
+    "limit_exceeded",
+    "A memory, group, depth, or iteration limit was exceeded.",
+);
+const TYPE_MISMATCH: &ErrorCode = &entry(
+    "E0017",
+    "type_mismatch",
+    "A value has the wrong type for the stage or operator.",
+);
+pub(crate) const MISSING_FIELD: &ErrorCode = &entry(
+    "E0018",
+    "missing_field",
+    "A record has no field of that name.",
+);
+pub(crate) const CANCELLED: &ErrorCode = &entry(
+    "E0019",
+    "cancelled",
+    "The host cancelled the run or stopped it at a yield point.",
+);
+pub(crate) const BINDING_KIND: &ErrorCode = &entry(
+    "E0020",
+    "binding_kind",
+    "A name holds a stream where a stage is expected, or the reverse.",
+);
+pub(crate) const UNSUPPORTED_EXPRESSION: &ErrorCode = &entry(
+    "E0021",
+    "unsupported_expression",
+    "An expression form can't be evaluated where it appears.",
+);
+pub(crate) const INVALID_FIXTURES: &ErrorCode = &entry(
+    "E0022",
+    "invalid_fixtures",
+    "The fixtures aren't an object of arrays or strings.",
+);
+pub(crate) const UNKNOWN_POLICY: &ErrorCode = &entry(
+    "E0023",
+    "unknown_policy",
+    "An rbac stage names a policy that `rbac.load` hasn't loaded.",
+);
+pub(crate) const DUPLICATE_TRANSITION: &ErrorCode = &entry(
+    "E0024",
+    "duplicate_transition",
+    "A state machine has two transitions from one state on one event.",
+);
+
+const CATALOG: &[&ErrorCode] = &[
+    OTHER,
+    SYNTAX,
+    DUPLICATE_BINDING,
+    USE_BEFORE_BIND,
+    RESERVED_NAME,
+    UNKNOWN_IDENT,
+    UNKNOWN_STAGE,
+    PARAMS,
+    CONSTANTS,
+    STAGE_ARGUMENTS,
+    STRICT,
+    MISSING_FIXTURE,
+    NOT_REVERSIBLE,
+    NO_MATCH,
+    DECODE,
+    OVERFLOW,
+    LIMIT_EXCEEDED,
+    TYPE_MISMATCH,
+    MISSING_FIELD,
+    CANCELLED,
+    BINDING_KIND,
+    UNSUPPORTED_EXPRESSION,
+    INVALID_FIXTURES,
+    UNKNOWN_POLICY,
+    DUPLICATE_TRANSITION,
+];
+
+/// Reads the serialized object back as the catalog entry with its `code`.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for &'static ErrorCode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        struct Serialized {
+            code: String,
+        }
+        let code = Serialized::deserialize(deserializer)?.code;
+        CATALOG
+            .iter()
+            .find(|entry| entry.code == code)
+            .copied()
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown error code {code}")))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ErrorCode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <&'static ErrorCode>::deserialize(deserializer).copied()
+    }
+}
+
+/// Every catalog entry in code order, starting with `E0000` (`other`).
+pub fn error_codes() -> impl Iterator<Item = ErrorCode> {
+    CATALOG.iter().map(|entry| **entry)
+}
+
+/// An error inside the runtime: its message and the catalog entry the code that
+/// raised it chose. `String` errors (host errors among them) convert as [`OTHER`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Error {
+    pub(crate) code: &'static ErrorCode,
+    pub(crate) message: String,
+    detail: Detail,
+    /// The message-catalog message `message` was rendered from, if any.
+    text: Option<Box<Message>>,
+}
+
+/// What the typed [`RuntimeError`] variants carry beyond the message, recorded by
+/// the code that raised the error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Detail {
+    None,
+    MissingFixture {
+        name: String,
+    },
+    Type {
+        expected: String,
+        got: Option<String>,
+    },
+}
+
+impl Error {
+    /// An error with catalog entry `code`; type mismatches and missing fixtures use
+    /// [`Error::mismatch`] and [`Error::missing_fixture`], and messages with a
+    /// template [`Error::templated`].
+    pub(crate) fn new(code: &'static ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            detail: Detail::None,
+            text: None,
+        }
+    }
+
+    /// An error with catalog entry `code` whose message is `text`, in English.
+    pub(crate) fn templated(code: &'static ErrorCode, text: Message) -> Self {
+        let message = text.render("en");
+        Self {
+            text: Some(Box::new(text)),
+            ..Error::new(code, message)
+        }
+    }
+
+    /// `missing fixture: {name}`.
+    pub(crate) fn missing_fixture(name: &str) -> Self {
+        Self {
+            detail: Detail::MissingFixture {
+                name: name.to_string(),
+            },
+            ..Error::templated(
+                MISSING_FIXTURE,
+                Message::new("missing_fixture").arg("name", name),
+            )
+        }
+    }
+
+    /// A value that isn't `expected`, such as `I64 or String`.
+    pub(crate) fn mismatch(expected: impl Into<String>, message: impl Into<String>) -> Self {
+        Error::new(TYPE_MISMATCH, message).expecting(expected)
+    }
+
+    /// [`Error::mismatch`] with a message that has a template.
+    pub(crate) fn templated_mismatch(expected: impl Into<String>, text: Message) -> Self {
+        Error::templated(TYPE_MISMATCH, text).expecting(expected)
+    }
+
+    fn expecting(mut self, expected: impl Into<String>) -> Self {
+        self.detail = Detail::Type {
+            expected: expected.into(),
+            got: None,
+        };
+        self
+    }
+
+    /// The same type mismatch naming the type it `got`.
+    pub(crate) fn got(mut self, got: impl Into<String>) -> Self {
+        if let Detail::Type { got: slot, .. } = &mut self.detail {
+            *slot = Some(got.into());
+        }
+        self
+    }
+
+    /// The same error with `f` applied to its message, such as to name the stage.
+    /// The new message has no template.
+    pub(crate) fn map_message(mut self, f: impl FnOnce(String) -> String) -> Self {
+        self.message = f(self.message);
+        self.text = None;
+        self
+    }
+
+    /// The same error ending in `at {position}`.
+    pub(crate) fn at(mut self, position: impl fmt::Display) -> Self {
+        let mut text = self.text.take().map_or_else(
+            || Message::plain(self.code.kind, &self.message),
+            |text| *text,
+        );
+        text.position = Some(position.to_string());
+        self.message = format!("{} at {position}", self.message);
+        self.text = Some(Box::new(text));
+        self
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::new(OTHER, message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::new(OTHER, message)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::templated(SYNTAX, Message::from(&error))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Why a program failed to compile or run. `Display` is the full message, position
+/// included, so `err.to_string()` reads as the `String` errors of earlier versions.
+/// Every variant also carries `localized`, the message as a catalog id and arguments
+/// for [`RuntimeError::localize`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum RuntimeError {
+    /// The program doesn't parse (`E0001`).
+    ParseError {
+        message: String,
+        localized: Box<Message>,
+        span: Span,
+    },
+    /// A value has the wrong type for a stage or operator (`E0017`). `expected` is
+    /// what the stage or operator asks for, such as `I64 or String`; `got` is the
+    /// actual type, such as `string`, when the failing value is at hand. `stage` and
+    /// `item_index` are as for [`RuntimeError::StageError`], when known.
+    TypeError {
+        message: String,
+        localized: Box<Message>,
+        span: Option<Span>,
+        expected: String,
+        got: Option<String>,
+        stage: Option<String>,
+        item_index: Option<usize>,
+    },
+    /// The program reads fixture `name`, which isn't supplied (`E0011`).
+    MissingFixture {
+        message: String,
+        localized: Box<Message>,
+        name: String,
+    },
+    /// A source or stage failed with catalog entry `code`. `stage` is its source
+    /// text (or its formatted expression for compiled programs); `item_index` is the
+    /// position of the failing item in the stage's input for per-item stages (`map`,
+    /// `filter`, `flat_map`).
+    StageError {
+        message: String,
+        localized: Box<Message>,
+        code: &'static ErrorCode,
+        stage: String,
+        span: Span,
+        item_index: Option<usize>,
+    },
+    /// Any other compile-time check, params, or host error, with its catalog entry.
+    Other {
+        message: String,
+        localized: Box<Message>,
+        code: &'static ErrorCode,
+    },
+}
+
+impl RuntimeError {
+    /// The full message, as `Display` shows it.
+    pub fn message(&self) -> &str {
+        match self {
+            RuntimeError::ParseError { message, .. }
+            | RuntimeError::TypeError { message, .. }
+            | RuntimeError::MissingFixture { message, .. }
+            | RuntimeError::StageError { message, .. }
+            | RuntimeError::Other { message, .. } => message,
+        }
+    }
+
+    /// The message as a catalog id and arguments.
+    pub fn localized(&self) -> &Message {
+        match self {
+            RuntimeError::ParseError { localized, .. }
+            | RuntimeError::TypeError { localized, .. }
+            | RuntimeError::MissingFixture { localized, .. }
+            | RuntimeError::StageError { localized, .. }
+            | RuntimeError::Other { localized, .. } => localized,
+        }
+    }
+
+    /// The message in `locale`, one of [`crate::LOCALES`]; see [`Message::render`].
+    pub fn localize(&self, locale: &str) -> String {
+        self.localized().render(locale)
+    }
+
+    /// The catalog entry for this error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            RuntimeError::ParseError { .. } => *SYNTAX,
+            RuntimeError::TypeError { .. } => *TYPE_MISMATCH,
+            RuntimeError::MissingFixture { .. } => *MISSING_FIXTURE,
+            RuntimeError::StageError { code, .. } | RuntimeError::Other { code, .. } => **code,
+        }
+    }
+
+    /// Where in the program the error arose, when known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            RuntimeError::ParseError { span, .. } | RuntimeError::StageError { span, .. } => {
+                Some(*span)
+            }
+            RuntimeError::TypeError { span, .. } => *span,
+            RuntimeError::MissingFixture { .. } | RuntimeError::Other { .. } => None,
+        }
+    }
+
+    /// The same error with `{program}: ` in front of its message, for
+    /// [`crate::run_workspace`]. The localized message keeps its id and gains a
+    /// `program` argument.
+    pub(crate) fn in_program(mut self, program: &str) -> Self {
+        let (message, localized) = match &mut self {
+            RuntimeError::ParseError {
+                message, localized, ..
+            }
+            | RuntimeError::TypeError {
+                message, localized, ..
+            }
+            | RuntimeError::MissingFixture {
+                message, localized, ..
+            }
+            | RuntimeError::StageError {
+                message, localized, ..
+            }
+            | RuntimeError::Other {
+                message, localized, ..
+            } => (message, localized),
+        };
+        message.insert_str(0, &format!("{program}: "));
+        localized
+            .args
+            .push(("program".to_string(), program.to_string()));
+        self
+    }
+
+    /// `error`, raised at `span` by `stage` (the failing source or stage, when one
+    /// was recorded) on input item `item_index`.
+    pub(crate) fn located(
+        error: Error,
+        span: Option<Span>,
+        stage: Option<String>,
+        item_index: Option<usize>,
+    ) -> Self {
+        let Error {
+            code,
+            message,
+            detail,
+            text,
+        } = error;
+        let localized = text.unwrap_or_else(|| Box::new(Message::plain(code.kind, &message)));
+        match detail {
+            Detail::MissingFixture { name } => RuntimeError::MissingFixture {
+                message,
+                localized,
+                name,
+            },
+            Detail::Type { expected, got } => RuntimeError::TypeError {
+                message,
+                localized,
+                span,
+                expected,
+                got,
+                stage,
+                item_index,
+            },
+            Detail::None => match span.zip(stage) {
+                Some((span, stage)) => RuntimeError::StageError {
+                    message,
+                    localized,
+                    code,
+                    stage,
+                    span,
+                    item_index,
+                },
+                None => RuntimeError::Other {
+                    message,
+                    localized,
+                    code,
+                },
+            },
+        }
+    }
+}
+
+impl From<Error> for RuntimeError {
+    fn from(error: Error) -> Self {
+        RuntimeError::located(error, None, None, None)
+    }
+}
+
+impl From<String> for RuntimeError {
+    fn from(message: String) -> Self {
+        Error::from(message).into()
+    }
+}
+
+impl From<ParseError> for RuntimeError {
+    fn from(error: ParseError) -> Self {
+        RuntimeError::ParseError {
+            message: error.to_string(),
+            localized: Box::new(Message::from(&error)),
+            span: error.span,
+        }
+    }
+}
+
+impl From<RuntimeError> for String {
+    fn from(error: RuntimeError) -> Self {
+        match error {
+            RuntimeError::ParseError { message, .. }
+            | RuntimeError::TypeError { message, .. }
+            | RuntimeError::MissingFixture { message, .. }
+            | RuntimeError::StageError { message, .. }
+            | RuntimeError::Other { message, .. } => message,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
@@ -0,0 +1,90 @@
+//! Optional subsystems behind cargo features (`codecs`, `graph`, `kv`, `rbac`,
+//! `stats`): which ones this build has, and the stages and sources each one adds.
+//! Names from a left-out feature fail with an error naming the feature instead of
+//! an unknown-stage error.
+
+/// Every optional feature and whether this build has it.
+pub(crate) const FEATURES: &[(&str, bool)] = &[
+    ("codecs", cfg!(feature = "codecs")),
+    ("graph", cfg!(feature = "graph")),
+    ("kv", cfg!(feature = "kv")),
+    ("rbac", cfg!(feature = "rbac")),
+    ("stats", cfg!(feature = "stats")),
+];
+
+/// The feature the built-in stage, codec, or source `name` comes from; `None` for
+/// the core and for names that are not built in.
+pub(crate) fn feature_of(name: &str) -> Option<&'static str> {
+    if matches!(name, "input.bytes" | "input.ndjson") {
+        return Some("codecs");
+    }
+    if let Some((codec, _)) = crate::codec_stage(name, false) {
+        return (codec != crate::Codec::Json).then_some("codecs");
+    }
+    crate::stage_args::params(name)?;
+    match name.split('.').next() {
+        Some("graph") => Some("graph"),
+        Some("kv" | "lookup") => Some("kv"),
+        Some("rbac") => Some("rbac"),
+        Some("stats") => Some("stats"),
+        _ => None,
+    }
+}
+
+/// Whether this build has `feature`.
+pub(crate) fn enabled(feature: &str) -> bool {
+    FEATURES.iter().any(|(name, on)| *name == feature && *on)
+}
+
+/// An error for `name` when the feature it comes from is left out of this build.
+pub(crate) fn require(name: &str) -> Result<(), String> {
+    match feature_of(name) {
+        Some(feature) if !enabled(feature) => Err(unavailable(name, feature)),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn unavailable(name: &str, feature: &str) -> String {
+    format!("{name} is not available: dsl_runtime was built without the `{feature}` feature")
+}
+
+/// What a build of the runtime offers: the optional features compiled in, and the
+/// stages and sources programs can call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Capabilities {
+    /// Enabled optional features, e.g. `["codecs", "kv"]`.
+    pub features: Vec<String>,
+    /// Stage and codec names, sorted; registered custom stages included.
+    pub stages: Vec<String>,
+    /// `input.*` source names, sorted; registered sources included.
+    pub sources: Vec<String>,
+}
+
+/// The capabilities of this build with no custom stages or sources registered;
+/// see [`crate::Runtime::capabilities`].
+pub fn capabilities() -> Capabilities {
+    let codecs = crate::Codec::ALL.map(crate::Codec::name);
+    let mut stages: Vec<String> = crate::stage_args::STAGES
+        .iter()
+        .map(|(stage, _)| *stage)
+        .chain(["select", "host.call"])
+        .chain(codecs)
+        .filter(|name| require(name).is_ok())
+        .map(str::to_string)
+        .collect();
+    stages.sort();
+    Capabilities {
+        features: FEATURES
+            .iter()
+            .filter(|(_, on)| *on)
+            .map(|(name, _)| name.to_string())
+            .collect(),
+        stages,
+        sources: crate::source::BUILTIN_SOURCES
+            .iter()
+            .filter(|name| require(name).is_ok())
+            .map(|name| name.to_string())
+            .collect(),
+    }
+}
@@ -0,0 +1,72 @@
+//! `fsm.evaluate(transitions="transitions", initial="start", event=_.event)`: runs
+//! events through a state machine read from `{from, event, to}` fixture rows.
+
+use crate::errors::{Error, DUPLICATE_TRANSITION};
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+
+pub(crate) struct Machine {
+    transitions: BTreeMap<(String, String), String>,
+}
+
+/// Where a run of events ended.
+pub(crate) struct Run {
+    pub(crate) state: String,
+    /// Every state visited, starting with the initial one.
+    pub(crate) path: Vec<String>,
+    /// False when an event had no transition; the run stops at that event.
+    pub(crate) accepted: bool,
+}
+
+impl Machine {
+    pub(crate) fn from_rows(rows: &[JsonValue]) -> Result<Self, Error> {
+        let mut transitions = BTreeMap::new();
+        for row in rows {
+            let (from, event, to) = (field(row, "from")?, field(row, "event")?, field(row, "to")?);
+            if transitions.contains_key(&(from.clone(), event.clone())) {
+                return Err(Error::new(
+                    DUPLICATE_TRANSITION,
+                    format!("fsm.evaluate: two transitions from {from} on {event}"),
+                ));
+            }
+            transitions.insert((from, event), to);
+        }
+        Ok(Self { transitions })
+    }
+
+    pub(crate) fn run<'a>(&self, initial: &str, events: impl IntoIterator<Item = &'a str>) -> Run {
+        let mut run = Run {
+            state: initial.to_string(),
+            path: vec![initial.to_string()],
+            accepted: true,
+        };
+        for event in events {
+            let key = (run.state.clone(), event.to_string());
+            let Some(next) = self.transitions.get(&key) else {
+                run.accepted = false;
+                break;
+            };
+            run.state = next.clone();
+            run.path.push(next.clone());
+        }
+        run
+    }
+}
+
+fn field(row: &JsonValue, name: &str) -> Result<String, Error> {
+    let JsonValue::Object(map) = row else {
+        return Err(Error::mismatch(
+            "records",
+            "fsm.evaluate transitions rows must be records",
+        ));
+    };
+    match map.get(name) {
+        Some(JsonValue::String(value)) => Ok(value.clone()),
+        _ => Err(Error::mismatch(
+            "string fields from, event, and to",
+            format!(
+                "fsm.evaluate transitions rows need string fields from, event, and to (missing {name})"
+            ),
+        )),
+    }
+}
@@ -0,0 +1,184 @@
+//! Synthetic fixture generation from a small JSON spec.
+//!
+//! ```json
+//! {
+//!   "seed": 7,
+//!   "fixtures": {
+//!     "events": {
+//!       "count": 3,
+//!       "fields": {
+//!         "id": {"type": "seq", "start": 1},
+//!         "ms": {"type": "int", "min": 0, "max": 500},
+//!         "user": {"type": "string", "prefix": "u", "min": 1, "max": 9},
+//!         "kind": {"type": "choice", "values": ["click", "view"]}
+//!       }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! Output is fully determined by the spec (including `seed`, default 0).
+
+use serde_json::{Map, Value as JsonValue};
+
+/// Builds a fixtures object (`{name: [rows...]}`) from `spec`.
+pub fn generate_fixtures(spec: &JsonValue) -> Result<JsonValue, String> {
+    let spec = expect_object(spec, "fixture spec")?;
+    let seed = match spec.get("seed") {
+        Some(value) => expect_int(value, "seed")? as u64,
+        None => 0,
+    };
+    let fixtures = expect_object(
+        spec.get("fixtures")
+            .ok_or_else(|| "fixture spec must contain 'fixtures'".to_string())?,
+        "fixtures",
+    )?;
+
+    let mut rng = SplitMix64(seed);
+    let mut out = Map::new();
+    for (name, fixture) in fixtures {
+        let fixture = expect_object(fixture, &format!("fixture '{name}'"))?;
+        let count = expect_int(
+            fixture
+                .get("count")
+                .ok_or_else(|| format!("fixture '{name}' must contain 'count'"))?,
+            "count",
+        )?;
+        if count < 0 {
+            return Err(format!("fixture '{name}' count must be >= 0"));
+        }
+        let row_spec = match (fixture.get("fields"), fixture.get("value")) {
+            (Some(fields), None) => {
+                let mut record = Map::new();
+                record.insert("type".to_string(), JsonValue::String("record".to_string()));
+                record.insert("fields".to_string(), fields.clone());
+                JsonValue::Object(record)
+            }
+            (None, Some(value)) => value.clone(),
+            _ => {
+                return Err(format!(
+                    "fixture '{name}' must contain exactly one of 'fields' or 'value'"
+                ))
+            }
+        };
+
+        let mut rows = Vec::new();
+        for index in 0..count {
+            rows.push(generate_value(&row_spec, index, &mut rng)?);
+        }
+        out.insert(name.clone(), JsonValue::Array(rows));
+    }
+    Ok(JsonValue::Object(out))
+}
+
+fn generate_value(spec: &JsonValue, index: i64, rng: &mut SplitMix64) -> Result<JsonValue, String> {
+    let spec = expect_object(spec, "field spec")?;
+    let kind = match spec.get("type") {
+        Some(JsonValue::String(kind)) => kind.as_str(),
+        _ => return Err("field spec must contain string 'type'".to_string()),
+    };
+    let int_field = |name: &str, default: i64| match spec.get(name) {
+        Some(value) => expect_int(value, name),
+        None => Ok(default),
+    };
+
+    match kind {
+        "seq" => {
+            let start = int_field("start", 0)?;
+            let step = int_field("step", 1)?;
+            Ok(JsonValue::Number((start + index * step).into()))
+        }
+        "int" => {
+            let min = int_field("min", 0)?;
+            let max = int_field("max", 100)?;
+            Ok(JsonValue::Number(rng.range(min, max)?.into()))
+        }
+        "bool" => Ok(JsonValue::Bool(rng.next() & 1 == 1)),
+        "string" => {
+            let prefix = match spec.get("prefix") {
+                Some(JsonValue::String(prefix)) => prefix.clone(),
+                None => String::new(),
+                _ => return Err("string prefix must be a string".to_string()),
+            };
+            let min = int_field("min", 0)?;
+            let max = int_field("max", 100)?;
+            Ok(JsonValue::String(format!(
+                "{prefix}{}",
+                rng.range(min, max)?
+            )))
+        }
+        "choice" => match spec.get("values") {
+            Some(JsonValue::Array(values)) if !values.is_empty() => {
+                let idx = rng.range(0, values.len() as i64 - 1)? as usize;
+                Ok(values[idx].clone())
+            }
+            _ => Err("choice values must be a non-empty array".to_string()),
+        },
+        "const" => spec
+            .get("value")
+            .cloned()
+            .ok_or_else(|| "const field spec must contain 'value'".to_string()),
+        "array" => {
+            let items = spec
+                .get("items")
+                .ok_or_else(|| "array field spec must contain 'items'".to_string())?;
+            let len = rng.range(int_field("min_len", 0)?, int_field("max_len", 3)?)?;
+            let mut out = Vec::new();
+            for item_index in 0..len {
+                out.push(generate_value(items, item_index, rng)?);
+            }
+            Ok(JsonValue::Array(out))
+        }
+        "record" => {
+            let fields = expect_object(
+                spec.get("fields")
+                    .ok_or_else(|| "record field spec must contain 'fields'".to_string())?,
+                "fields",
+            )?;
+            let mut out = Map::new();
+            for (name, field) in fields {
+                out.insert(name.clone(), generate_value(field, index, rng)?);
+            }
+            Ok(JsonValue::Object(out))
+        }
+        _ => Err(format!("unknown field type: {kind}")),
+    }
+}
+
+fn expect_object<'a>(value: &'a JsonValue, what: &str) -> Result<&'a Map, String> {
+    match value {
+        JsonValue::Object(map) => Ok(map),
+        _ => Err(format!("{what} must be an object")),
+    }
+}
+
+fn expect_int(value: &JsonValue, what: &str) -> Result<i64, String> {
+    match value {
+        JsonValue::Number(n) => n
+            .as_i64()
+            .ok_or_else(|| format!("{what} must be an integer")),
+        _ => Err(format!("{what} must be an integer")),
+    }
+}
+
+/// Small, dependency-free PRNG so generated fixtures are reproducible everywhere.
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform-ish integer in `min..=max`.
+    fn range(&mut self, min: i64, max: i64) -> Result<i64, String> {
+        if min > max {
+            return Err(format!("range min {min} must be <= max {max}"));
+        }
+        let span = (max as i128 - min as i128 + 1) as u128;
+        Ok((min as i128 + (self.next() as u128 % span) as i128) as i64)
+    }
+}
@@ -0,0 +1,233 @@
+//! Directed graphs over string node ids, read from fixture edge rows: the walks behind
+//! `graph.reachable(edges="edges", from=_.node, direction="down")`,
+//! `graph.shortest_path`, `graph.components`, and the resource ancestors of
+//! `rbac.evaluate`.
+
+use crate::errors::{Error, STAGE_ARGUMENTS};
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Which way `graph.*` stages follow `{from, to}` edge rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    /// `from` to `to`.
+    Down,
+    /// `to` to `from`.
+    Up,
+    /// Either way.
+    Both,
+}
+
+impl Direction {
+    pub(crate) fn parse(direction: &str) -> Result<Self, Error> {
+        match direction {
+            "down" => Ok(Direction::Down),
+            "up" => Ok(Direction::Up),
+            "both" => Ok(Direction::Both),
+            other => Err(Error::new(
+                STAGE_ARGUMENTS,
+                format!("unknown direction: {other} (expected down, up, or both)"),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Graph {
+    /// Neighbours of each node in edge row order; every node has an entry.
+    next: BTreeMap<String, Vec<String>>,
+    /// Nodes in the order edges first mention them.
+    nodes: Vec<String>,
+}
+
+impl Graph {
+    pub(crate) fn add_edge(&mut self, from: String, to: String) {
+        self.add_node(&from);
+        self.add_node(&to);
+        self.next.entry(from).or_default().push(to);
+    }
+
+    fn add_node(&mut self, node: &str) {
+        if !self.next.contains_key(node) {
+            self.next.insert(node.to_string(), Vec::new());
+            self.nodes.push(node.to_string());
+        }
+    }
+
+    /// The graph of `{from, to}` rows, followed in `direction`.
+    pub(crate) fn from_rows(
+        rows: &[JsonValue],
+        direction: Direction,
+        stage: &str,
+    ) -> Result<Self, Error> {
+        let mut graph = Graph::default();
+        for row in rows {
+            let (from, to) = (node(row, "from", stage)?, node(row, "to", stage)?);
+            if direction != Direction::Up {
+                graph.add_edge(from.clone(), to.clone());
+            }
+            if direction != Direction::Down {
+                graph.add_edge(to, from);
+            }
+        }
+        Ok(graph)
+    }
+
+    /// `start` and every node reachable from it, nearest first (ties in edge order).
+    /// A node more than `max_depth` edges away fails with the path to it.
+    pub(crate) fn reachable(
+        &self,
+        start: &str,
+        max_depth: usize,
+    ) -> Result<Vec<String>, Vec<String>> {
+        // Each reachable node with the index of the one that led to it.
+        let mut out: Vec<(String, Option<usize>)> = vec![(start.to_string(), None)];
+        let mut seen = BTreeSet::from([start.to_string()]);
+        let mut level = 0..1;
+        let mut depth = 0;
+        while !level.is_empty() {
+            let next = out.len();
+            for idx in level {
+                for neighbour in self.next.get(&out[idx].0).into_iter().flatten() {
+                    if seen.contains(neighbour) {
+                        continue;
+                    }
+                    if depth == max_depth {
+                        let mut path = path_to(&out, idx);
+                        path.push(neighbour.clone());
+                        return Err(path);
+                    }
+                    seen.insert(neighbour.clone());
+                    out.push((neighbour.clone(), Some(idx)));
+                }
+            }
+            level = next..out.len();
+            depth += 1;
+        }
+        Ok(out.into_iter().map(|(node, _)| node).collect())
+    }
+
+    /// The nodes on a path from `from` to `to` with the fewest edges (the first found
+    /// in edge order), or `None` when `to` is unreachable.
+    pub(crate) fn shortest_path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let mut out: Vec<(String, Option<usize>)> = vec![(from.to_string(), None)];
+        let mut seen = BTreeSet::from([from.to_string()]);
+        let mut idx = 0;
+        while idx < out.len() {
+            if out[idx].0 == to {
+                return Some(path_to(&out, idx));
+            }
+            for neighbour in self.next.get(&out[idx].0).into_iter().flatten() {
+                if seen.insert(neighbour.clone()) {
+                    out.push((neighbour.clone(), Some(idx)));
+                }
+            }
+            idx += 1;
+        }
+        None
+    }
+
+    /// Every node with the id of its weakly connected component, in the order edges
+    /// first mention them; ids count up from 0 in that order too.
+    pub(crate) fn components(&self) -> Vec<(String, usize)> {
+        let mut undirected = Graph::default();
+        for node in &self.nodes {
+            undirected.add_node(node);
+        }
+        for (from, tos) in &self.next {
+            for to in tos {
+                undirected.add_edge(from.clone(), to.clone());
+                undirected.add_edge(to.clone(), from.clone());
+            }
+        }
+
+        let mut component_of: BTreeMap<String, usize> = BTreeMap::new();
+        let mut count = 0;
+        for node in &self.nodes {
+            if component_of.contains_key(node) {
+                continue;
+            }
+            // No walk reaches usize::MAX levels, so this never fails.
+            for member in undirected.reachable(node, usize::MAX).unwrap_or_default() {
+                component_of.insert(member, count);
+            }
+            count += 1;
+        }
+        self.nodes
+            .iter()
+            .map(|node| (node.clone(), component_of[node]))
+            .collect()
+    }
+
+    /// Every cycle, each as the path from its first node back to it (`["a", "b", "a"]`).
+    #[cfg(feature = "rbac")]
+    pub(crate) fn cycles(&self) -> Vec<Vec<String>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            Open,
+            Done,
+        }
+        fn visit<'a>(
+            node: &'a str,
+            next: &'a BTreeMap<String, Vec<String>>,
+            marks: &mut BTreeMap<&'a str, Mark>,
+            path: &mut Vec<&'a str>,
+            cycles: &mut Vec<Vec<String>>,
+        ) {
+            marks.insert(node, Mark::Open);
+            path.push(node);
+            for neighbour in next.get(node).into_iter().flatten() {
+                match marks.get(neighbour.as_str()) {
+                    Some(Mark::Open) => {
+                        let start = path.iter().position(|n| n == neighbour).unwrap_or(0);
+                        let mut cycle: Vec<String> =
+                            path[start..].iter().map(|n| n.to_string()).collect();
+                        cycle.push(neighbour.clone());
+                        cycles.push(cycle);
+                    }
+                    Some(Mark::Done) => {}
+                    None => visit(neighbour, next, marks, path, cycles),
+                }
+            }
+            path.pop();
+            marks.insert(node, Mark::Done);
+        }
+
+        let mut marks = BTreeMap::new();
+        let mut cycles = Vec::new();
+        for node in self.next.keys() {
+            if !marks.contains_key(node.as_str()) {
+                visit(node, &self.next, &mut marks, &mut Vec::new(), &mut cycles);
+            }
+        }
+        cycles
+    }
+}
+
+/// The nodes from the walk's start to `out[idx]`, following each node's predecessor.
+fn path_to(out: &[(String, Option<usize>)], idx: usize) -> Vec<String> {
+    let mut path = Vec::new();
+    let mut at = Some(idx);
+    while let Some(idx) = at {
+        path.push(out[idx].0.clone());
+        at = out[idx].1;
+    }
+    path.reverse();
+    path
+}
+
+fn node(row: &JsonValue, name: &str, stage: &str) -> Result<String, Error> {
+    match row {
+        JsonValue::Object(map) => match map.get(name) {
+            Some(JsonValue::String(value)) => Ok(value.clone()),
+            _ => Err(Error::mismatch(
+                format!("a string field '{name}'"),
+                format!("{stage} edges rows need a string field '{name}'"),
+            )),
+        },
+        _ => Err(Error::mismatch(
+            "records",
+            format!("{stage} edges rows must be records"),
+        )),
+    }
+}
@@ -0,0 +1,122 @@
+//! `ui.graph("deps", node=_.id, edges=_.depends_on)`: node/edge output for
+//! hierarchy data.
+
+use crate::errors::Error;
+use serde_json::{Map, Value as JsonValue};
+
+/// One `ui.graph` output ([`crate::Outputs::graphs`]).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphSpec {
+    /// In first-seen order. Edge targets that no item declared are added with a
+    /// `null` item.
+    pub nodes: Vec<GraphNode>,
+    /// `(from, to)` node ids, deduplicated, in first-seen order.
+    pub edges: Vec<(JsonValue, JsonValue)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphNode {
+    pub id: JsonValue,
+    /// The item that declared the node.
+    pub item: JsonValue,
+}
+
+impl GraphSpec {
+    /// Adds the node `id` for `item` with edges to `targets`. A node seen again
+    /// keeps its first item and gains the new edges.
+    pub(crate) fn add(
+        &mut self,
+        id: JsonValue,
+        item: JsonValue,
+        targets: Vec<JsonValue>,
+    ) -> Result<(), Error> {
+        check_id(&id, "node")?;
+        match self.nodes.iter_mut().find(|node| node.id == id) {
+            Some(node) if matches!(node.item, JsonValue::Null) => node.item = item,
+            Some(_) => {}
+            None => self.nodes.push(GraphNode {
+                id: id.clone(),
+                item,
+            }),
+        }
+        for target in targets {
+            check_id(&target, "edges")?;
+            let edge = (id.clone(), target);
+            if !self.edges.contains(&edge) {
+                self.edges.push(edge);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds nodes for edge targets no item declared.
+    pub(crate) fn close(&mut self) {
+        for (_, to) in &self.edges {
+            if !self.nodes.iter().any(|node| node.id == *to) {
+                self.nodes.push(GraphNode {
+                    id: to.clone(),
+                    item: JsonValue::Null,
+                });
+            }
+        }
+    }
+
+    /// `{"nodes": [{"id", "item"}], "edges": [{"from", "to"}]}`.
+    pub fn to_json(&self) -> JsonValue {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                JsonValue::Object(Map::from_iter([
+                    ("id".to_string(), node.id.clone()),
+                    ("item".to_string(), node.item.clone()),
+                ]))
+            })
+            .collect();
+        let edges = self
+            .edges
+            .iter()
+            .map(|(from, to)| {
+                JsonValue::Object(Map::from_iter([
+                    ("from".to_string(), from.clone()),
+                    ("to".to_string(), to.clone()),
+                ]))
+            })
+            .collect();
+        JsonValue::Object(Map::from_iter([
+            ("nodes".to_string(), JsonValue::Array(nodes)),
+            ("edges".to_string(), JsonValue::Array(edges)),
+        ]))
+    }
+
+    /// Mermaid flowchart (top-down) labelled with the node ids.
+    pub fn to_mermaid(&self) -> String {
+        let index = |id: &JsonValue| self.nodes.iter().position(|node| node.id == *id);
+        let mut out = String::from("flowchart TD\n");
+        for (idx, node) in self.nodes.iter().enumerate() {
+            let label = match &node.id {
+                JsonValue::String(s) => s.clone(),
+                other => serde_json::to_string(other).unwrap_or_default(),
+            };
+            out.push_str(&format!("  g{idx}[\"{}\"]\n", label.replace('"', "#quot;")));
+        }
+        for (from, to) in &self.edges {
+            if let (Some(from), Some(to)) = (index(from), index(to)) {
+                out.push_str(&format!("  g{from} --> g{to}\n"));
+            }
+        }
+        out
+    }
+}
+
+fn check_id(id: &JsonValue, arg: &str) -> Result<(), Error> {
+    match id {
+        JsonValue::String(_) | JsonValue::Number(_) => Ok(()),
+        _ => Err(Error::mismatch(
+            "strings or numbers",
+            format!("ui.graph {arg} must evaluate to strings or numbers"),
+        )),
+    }
+}
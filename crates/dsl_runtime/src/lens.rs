@@ -0,0 +1,13 @@
+//! `lens(forward=expr, inverse=expr)`: a reversible stage from two expressions.
+//! Each run checks that `inverse` undoes `forward` on a sample of the items.
+
+/// Items checked per run.
+pub(crate) const SAMPLE: usize = 16;
+
+/// Up to [`SAMPLE`] indices spread evenly over `len` items, first and last included.
+pub(crate) fn sample(len: usize) -> Vec<usize> {
+    if len <= SAMPLE {
+        return (0..len).collect();
+    }
+    (0..SAMPLE).map(|i| i * (len - 1) / (SAMPLE - 1)).collect()
+}
@@ -1,8 +1,92 @@
-use dsl_syntax::{parse_program, CallArg, Expr, Program, Stmt};
+use dsl_syntax::{
+    format_expr, parse_program_with_limits, BinaryOp, CallArg, Expr, Pattern, Stmt, StringPart,
+};
+use intern::Interner;
 use serde_json::{Map, Value as JsonValue};
-use std::collections::{BTreeMap, HashMap};
-
-#[derive(Debug, Clone, PartialEq)]
+use stage_args::Args;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use table_spec::TableSpec;
+
+mod async_runtime;
+pub mod bench;
+mod check;
+pub mod codecs;
+mod columnar;
+mod constants;
+mod coverage;
+mod diagram;
+mod diff;
+mod errors;
+mod features;
+mod fsm;
+mod generate;
+#[cfg(any(feature = "graph", feature = "rbac"))]
+#[cfg_attr(not(feature = "graph"), allow(dead_code))]
+mod graph;
+mod graph_sink;
+mod intern;
+mod lens;
+mod logs;
+mod messages;
+mod options;
+mod params;
+mod partial;
+#[cfg(feature = "rbac")]
+mod rbac;
+mod runtime;
+mod sample;
+mod sink;
+mod snapshot;
+mod source;
+mod stage_args;
+#[cfg(feature = "stats")]
+mod stats;
+mod suggest;
+mod table_spec;
+pub mod testing;
+mod type_expr;
+mod warnings;
+mod writer;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use async_runtime::block_on;
+pub use async_runtime::{AsyncHostFn, AsyncSource, BoxFuture};
+pub use check::Rebinding;
+pub use codecs::Codec;
+pub use columnar::{infer_schema, Column, ColumnSchema, ColumnType, ColumnarTable};
+pub use coverage::{CoverageEntry, CoverageKind};
+pub use diagram::{to_dot, to_mermaid};
+pub use diff::{diff_outputs, OutputsDiff};
+pub use dsl_syntax::{ParseLimits, Program};
+pub use errors::{error_codes, ErrorCode, RuntimeError};
+use errors::{
+    Error, BINDING_KIND, CANCELLED, INVALID_FIXTURES, LIMIT_EXCEEDED, MISSING_FIELD,
+    NOT_REVERSIBLE, NO_MATCH, OVERFLOW, PARAMS, STAGE_ARGUMENTS, STRICT, UNKNOWN_IDENT,
+    UNKNOWN_STAGE, UNSUPPORTED_EXPRESSION,
+};
+pub use features::{capabilities, Capabilities};
+pub use generate::generate_fixtures;
+pub use graph_sink::{GraphNode, GraphSpec};
+pub use intern::Key;
+pub use logs::{LogLevel, LogRecord};
+pub use messages::{Message, LOCALES};
+pub use options::{ExplainLevel, RunOptions};
+pub use params::{bind_params, param_names};
+pub use partial::run_statement;
+pub use runtime::{HostFn, Runtime, StageArgs, StageImpl};
+pub use sample::{Sample, SampleReport};
+pub use sink::Sink;
+pub use source::{Source, SourceContext};
+pub use suggest::suggestions;
+pub use warnings::{Warning, WarningKind};
+pub use writer::{CallbackWriter, JsonlWriter, MemoryWriter, OutputEvent, OutputWriter};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     Null,
     Bool(bool),
@@ -10,10 +94,61 @@ pub enum Value {
     String(String),
     Bytes(Vec<u8>),
     Array(Vec<Value>),
-    Record(BTreeMap<String, Value>),
+    Record(BTreeMap<Key, Value>),
+    /// `tag(value, "name")`: a value marked as variant `name`. Shows as
+    /// `{"tag": name, "value": value}` in outputs.
+    Tagged(String, Box<Value>),
     Unit,
 }
 
+/// Per-value overhead used by [`Value::approx_size`]; fixed so accounting is
+/// the same on every target.
+const VALUE_OVERHEAD_BYTES: usize = 16;
+
+impl Value {
+    /// Rough footprint in bytes (a fixed overhead per value plus string, byte,
+    /// and record-key lengths), used for memory accounting.
+    pub fn approx_size(&self) -> usize {
+        VALUE_OVERHEAD_BYTES
+            + match self {
+                Value::String(s) => s.len(),
+                Value::Bytes(bytes) => bytes.len(),
+                Value::Array(items) => items.iter().map(Value::approx_size).sum(),
+                Value::Record(fields) => fields
+                    .iter()
+                    .map(|(key, value)| key.len() + value.approx_size())
+                    .sum(),
+                Value::Tagged(tag, value) => tag.len() + value.approx_size(),
+                Value::Null | Value::Bool(_) | Value::I64(_) | Value::Unit => 0,
+            }
+    }
+
+    /// JSON form as seen in `Outputs::tables` (`Bytes` become integer arrays, `Unit` is null).
+    pub fn to_json(&self) -> JsonValue {
+        value_to_json(self.clone())
+    }
+
+    pub fn from_json(json: JsonValue) -> Value {
+        json_to_value(json, &mut Interner::default())
+    }
+
+    /// The value's type as type expressions spell it (`i64`, `record`, ...); what a
+    /// [`RuntimeError::TypeError`] `got`.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::I64(_) => "i64",
+            Value::String(_) => "string",
+            Value::Bytes(_) => "bytes",
+            Value::Array(_) => "array",
+            Value::Record(_) => "record",
+            Value::Tagged(..) => "tagged",
+            Value::Unit => "unit",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, PartialEq)]
 pub struct Stream {
     values: Vec<Value>,
@@ -23,6 +158,10 @@ impl Stream {
     fn new(values: Vec<Value>) -> Self {
         Self { values }
     }
+
+    fn approx_size(&self) -> usize {
+        self.values.iter().map(Value::approx_size).sum()
+    }
 }
 
 impl IntoIterator for Stream {
@@ -35,20 +174,231 @@ impl IntoIterator for Stream {
 }
 
 #[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Outputs {
     pub tables: BTreeMap<String, Vec<JsonValue>>,
-    pub logs: BTreeMap<String, Vec<String>>,
+    pub logs: BTreeMap<String, Vec<LogRecord>>,
     pub explain: Vec<String>,
+    /// Set when the run stopped early because its [`CancelToken`] fired; the other
+    /// fields hold whatever was produced up to that point.
+    pub cancelled: bool,
+    /// Audit mode ([`RunOptions::audit`]): [`Outputs::execution_fingerprint`] of
+    /// this run.
+    pub fingerprint: Option<String>,
+    /// With memory tracking ([`RunOptions::track_memory`] or a memory limit): the
+    /// most stream bytes ([`Value::approx_size`]) held at once, counting stream
+    /// bindings and the stream flowing through the current pipeline.
+    pub peak_stream_bytes: Option<usize>,
+    /// With [`RunOptions::columnar`]: every table, column-wise. `tables` is left
+    /// empty so large outputs are held only once.
+    pub tables_columnar: Option<BTreeMap<String, ColumnarTable>>,
+    /// Inferred columns of every table ([`infer_schema`]), for typed rendering; in
+    /// `column_order` when the table has one.
+    pub schemas: BTreeMap<String, Vec<ColumnSchema>>,
+    /// Column order requested with `ui.table(name, columns=[...])`, by table.
+    pub column_order: BTreeMap<String, Vec<String>>,
+    /// `ui.graph` outputs, by name.
+    pub graphs: BTreeMap<String, GraphSpec>,
+    /// Non-fatal diagnostics, such as deprecated stage names, mixed codec
+    /// directions, and items dropped over a limit.
+    pub warnings: Vec<Warning>,
+    /// Coverage mode ([`RunOptions::coverage`]): items that reached each pipeline
+    /// statement, source, stage, and `match` arm, in source order.
+    pub coverage: Option<Vec<CoverageEntry>>,
+    /// With [`RunOptions::sample_input`]: the sampled run's stage stats and their
+    /// full-input estimates.
+    pub sample: Option<SampleReport>,
+}
+
+impl Outputs {
+    /// FNV-1a hash (16 hex digits) of the tables, logs, and explain lines in their
+    /// canonical snapshot form. Identical runs have identical fingerprints.
+    pub fn execution_fingerprint(&self) -> String {
+        let canonical = serde_json::to_string(&testing::outputs_snapshot(self)).unwrap_or_default();
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in canonical.bytes() {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        format!("{hash:016x}")
+    }
 }
 
+/// Shared flag checked by [`run_cancellable`] between statements, stages, and items.
 #[derive(Debug, Clone, Default)]
-struct RuntimeState {
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Called by [`run_with_yield`] at regular yield points so a host can keep its UI
+/// responsive or enforce a soft timeout.
+pub trait YieldHook {
+    /// `points` is the number of yield points reached so far in the run. Returning
+    /// `false` stops the run as [`CancelToken::cancel`] would.
+    fn on_yield(&mut self, points: u64) -> bool;
+}
+
+impl<F: FnMut(u64) -> bool> YieldHook for F {
+    fn on_yield(&mut self, points: u64) -> bool {
+        self(points)
+    }
+}
+
+/// A [`YieldHook`] and how often to call it.
+struct YieldPoints<'a> {
+    hook: &'a mut dyn YieldHook,
+    interval: u64,
+    reached: u64,
+    stopped: bool,
+}
+
+impl YieldPoints<'_> {
+    fn reach(&mut self) {
+        self.reached += 1;
+        if !self.stopped && self.reached.is_multiple_of(self.interval) {
+            self.stopped = !self.hook.on_yield(self.reached);
+        }
+    }
+}
+
+#[derive(Default)]
+struct RuntimeState<'a> {
+    #[cfg(feature = "kv")]
     kv_stores: HashMap<String, HashMap<String, Value>>,
+    /// Policies compiled by `rbac.load`, by name.
+    #[cfg(feature = "rbac")]
+    rbac_policies: HashMap<String, rbac::Policy>,
+    /// When set, sink stages offer each item here before buffering it in `Outputs`.
+    sink: Option<&'a mut dyn Sink>,
+    cancel: Option<CancelToken>,
+    /// Set by [`Runtime::run_async`]: once cancelled, the run stops before the
+    /// next statement or stage, so the current one still finishes its items.
+    round: Option<CancelToken>,
+    /// When set, every cancellation check is also a yield point.
+    yields: Option<YieldPoints<'a>>,
+    /// When set, each top-level pipeline stage appends its timing here.
+    timings: Option<&'a mut Vec<bench::StageTiming>>,
+    /// Host extensions; `None` for the free `run*` functions.
+    runtime: Option<&'a Runtime>,
+    /// Host values that replace same-named `const` statements.
+    constants: Option<&'a Map>,
+    explain: ExplainLevel,
+    audit: bool,
+    memory: Option<MemoryTracker>,
+    columnar: bool,
+    strict: bool,
+    coverage: bool,
+    /// Record keys decoded or built during this run.
+    interner: Interner,
+    /// The source or stage whose failure is being returned, for its line:column.
+    error_span: Option<dsl_syntax::Span>,
+    /// The failing source or stage as formatted source, for [`RuntimeError::StageError`]
+    /// when the program text isn't at hand.
+    error_stage: Option<String>,
+    /// Position of the failing item in a per-item stage's input.
+    error_item: Option<usize>,
+    /// Bindings the current statement reads for the last time in the run; reading
+    /// one moves it out of the environment, so consuming it copies nothing.
+    last_reads: BTreeSet<String>,
+    /// Warnings and where they arose, until [`take_warnings`] moves them to the outputs.
+    /// A stage's own warnings get its span once it finishes.
+    warnings: Vec<(WarningKind, String, Option<dsl_syntax::Span>)>,
+    /// For each stage binding, the first component `~` can't invert (`None` when
+    /// the whole binding inverts).
+    irreversible: BTreeMap<String, Option<String>>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryTracker {
+    limit: Option<usize>,
+    peak: usize,
+    /// Bytes held by stream bindings, by name.
+    bound: BTreeMap<String, usize>,
+}
+
+impl MemoryTracker {
+    /// Records `stream` flowing out of `at` (a source or stage) and enforces the limit.
+    fn observe(&mut self, at: &Expr, stream: &Stream) -> Result<(), Error> {
+        let held = stream.approx_size() + self.bound.values().sum::<usize>();
+        self.peak = self.peak.max(held);
+        match self.limit {
+            Some(limit) if held > limit => Err(Error::new(
+                LIMIT_EXCEEDED,
+                format!(
+                    "memory limit exceeded at stage {} ({held} bytes > {limit})",
+                    dsl_syntax::format_expr(at)
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl RuntimeState<'_> {
+    /// Under verbose explain, the number of items to trace after each stage.
+    fn trace_items(&self) -> Option<usize> {
+        match self.explain {
+            ExplainLevel::Verbose { items } => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Fails with [`CANCELLED`] once the run's token is cancelled or its yield hook
+    /// has stopped it; also counts as a yield point.
+    fn check_cancelled(&mut self) -> Result<(), Error> {
+        check_cancelled(self.cancel.as_ref(), &mut self.yields)
+    }
+
+    /// [`RuntimeState::check_cancelled`] between statements and stages, which also
+    /// ends an async round.
+    fn check_boundary(&mut self) -> Result<(), Error> {
+        if self.round.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Err(Error::new(CANCELLED, "cancelled"));
+        }
+        self.check_cancelled()
+    }
+
+    fn stop_requested(&self) -> bool {
+        stop_requested(self.cancel.as_ref(), self.yields.as_ref())
+            || self.round.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+}
+
+/// [`RuntimeState::check_cancelled`] for loops that hold another borrow of the state.
+fn check_cancelled(
+    cancel: Option<&CancelToken>,
+    yields: &mut Option<YieldPoints>,
+) -> Result<(), Error> {
+    if let Some(yields) = yields {
+        yields.reach();
+    }
+    if stop_requested(cancel, yields.as_ref()) {
+        return Err(Error::new(CANCELLED, "cancelled"));
+    }
+    Ok(())
+}
+
+fn stop_requested(cancel: Option<&CancelToken>, yields: Option<&YieldPoints>) -> bool {
+    cancel.is_some_and(CancelToken::is_cancelled) || yields.is_some_and(|yields| yields.stopped)
 }
 
+/// A bound name. Streams are shared: a stage copies the items only when it
+/// consumes a binding that a later read still needs.
 #[derive(Debug, Clone)]
 enum Binding {
-    Stream(Stream),
+    Stream(Rc<Stream>),
     Stage(Stage),
 }
 
@@ -57,238 +407,1086 @@ enum Stage {
     Map(Expr),
     Filter(Expr),
     FlatMap(Expr),
+    /// `select(col=expr, ...)`: map each item to a record of the named columns.
+    Select(Vec<(String, Expr)>),
+    /// `having(expr)`: a filter over `{key, ...}` group records.
+    Having(Expr),
+    /// `lens(forward=..., inverse=...)`; `~` swaps the two.
+    Lens {
+        forward: Expr,
+        inverse: Expr,
+    },
     GroupCollectAll {
         by_key: Expr,
         within_ms: i64,
         limit: i64,
+        overflow: Overflow,
+        /// Sorts each record's `items`; ties keep stream order.
+        order_items_by: Option<SortBy>,
     },
     GroupCount {
         by_key: Expr,
     },
+    Sessionize {
+        by_key: Expr,
+        gap_ms: i64,
+        ts: Expr,
+    },
+    DedupeWithin {
+        by: Expr,
+        within_ms: i64,
+        ts: Expr,
+    },
+    #[cfg(feature = "stats")]
+    StatsOutliers {
+        field: Expr,
+        method: stats::OutlierMethod,
+        drop: bool,
+    },
+    Throttle {
+        by_key: Expr,
+        limit: i64,
+        per_ms: i64,
+        /// `None`: the simulated clock advances 1 ms per item.
+        ts: Option<Expr>,
+        mark: bool,
+    },
     RankTopK {
         k: i64,
-        by: Expr,
-        order: SortOrder,
+        by: SortBy,
     },
+    /// `sort(by, order)`: a stable sort of the whole stream.
+    Sort(SortBy),
     RankKMergeArrays {
-        by: Expr,
-        order: SortOrder,
+        by: SortBy,
         limit: i64,
     },
     GroupTopNItems {
         by_key: Expr,
         n: i64,
-        order_by: Expr,
-        order: SortOrder,
+        order_by: SortBy,
     },
+    #[cfg(feature = "kv")]
     KvLoad {
         store: String,
+        /// The record fields holding each entry's key and value.
+        key_field: String,
+        value_field: String,
     },
+    #[cfg(feature = "kv")]
     LookupKv {
         store: String,
         key: Expr,
     },
+    #[cfg(feature = "kv")]
     LookupBatchKv {
         store: String,
         key: Expr,
         batch_size: i64,
         within_ms: i64,
     },
+    #[cfg(feature = "rbac")]
+    RbacLoad {
+        name: String,
+        fixtures: rbac::PolicyFixtures,
+    },
+    #[cfg(feature = "rbac")]
     RbacEvaluate {
-        principal_bindings: String,
-        role_perms: String,
-        resource_ancestors: String,
+        policy: rbac::PolicyRef,
+        max_depth: i64,
+        effective_at: Option<Expr>,
+    },
+    /// Requests whose decision differs between two loaded policies.
+    #[cfg(feature = "rbac")]
+    RbacDiff {
+        before: String,
+        after: String,
+        /// Fixture of requests; the stream's items when absent.
+        requests: Option<String>,
+        max_depth: i64,
+        effective_at: Option<Expr>,
+    },
+    #[cfg(feature = "graph")]
+    GraphReachable {
+        edges: String,
+        from: Expr,
+        direction: graph::Direction,
+    },
+    #[cfg(feature = "graph")]
+    GraphShortestPath {
+        edges: String,
+        from: Expr,
+        to: Expr,
+        direction: graph::Direction,
+    },
+    /// Ignores its input items and emits `{node, component}` per node of `edges`.
+    #[cfg(feature = "graph")]
+    GraphComponents {
+        edges: String,
+    },
+    FsmEvaluate {
+        transitions: String,
+        initial: String,
+        event: Expr,
+        by_key: Option<Expr>,
+    },
+    /// With `json(expect="...")`, the type every decoded-side value must have.
+    Codec(Codec, Direction, Option<type_expr::TypeExpr>),
+    UiTable(TableSpec),
+    UiLog(String, LogLevel),
+    UiGraph {
+        name: String,
+        node: Expr,
+        edges: Expr,
+    },
+    UiDiff {
+        name: String,
+        expected: String,
+        key: Option<Expr>,
+    },
+    SinkCustom(String),
+    /// `iterate(step=..., until=..., max_iters=100)`: feeds each round's new items back
+    /// through `step` until none are new, a new item fits `until`, or `max_iters`.
+    Iterate {
+        step: Box<Stage>,
+        until: Option<Expr>,
+        max_iters: i64,
     },
-    Json(Direction),
-    Utf8(Direction),
-    Base64(Direction),
-    UiTable(String),
-    UiLog(String),
     Compose(Vec<Stage>),
+    Custom(runtime::CustomStage),
+    HostCall(runtime::HostCall),
 }
 
 #[derive(Debug, Clone, Copy)]
 enum Direction {
+    /// Encode what the codec accepts, decode the rest.
     Auto,
+    /// Encode only: `json.encode`, and bare codecs in strict mode.
+    Forward,
     Inverse,
 }
 
+/// What `group.collect_all` does with a group's items past `limit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Overflow {
+    /// Stop collecting the group's items.
+    Drop,
+    /// Fail, naming the group's key.
+    Error,
+    /// Start another `{key, items}` record for the group.
+    Spill,
+}
+
+impl Overflow {
+    fn parse(expr: &Expr) -> Result<Self, Error> {
+        match expect_string(expr)?.as_str() {
+            "drop" => Ok(Overflow::Drop),
+            "error" => Ok(Overflow::Error),
+            "spill" => Ok(Overflow::Spill),
+            _ => Err(Error::new(
+                STAGE_ARGUMENTS,
+                "group.collect_all overflow must be \"drop\", \"error\", or \"spill\"",
+            )),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Overflow::Drop => "drop",
+            Overflow::Error => "error",
+            Overflow::Spill => "spill",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum SortOrder {
     Asc,
     Desc,
 }
 
+/// Where `Null` sort keys go, whatever the order.
+#[derive(Debug, Clone, Copy)]
+enum Nulls {
+    First,
+    Last,
+}
+
 #[derive(Debug, Clone)]
 enum SortKey {
+    Null,
     I64(i64),
     String(String),
 }
 
+/// `by=expr` or `by=[expr, ...]`, each key with its own order; later keys break
+/// ties.
+#[derive(Debug, Clone)]
+struct SortBy {
+    keys: Vec<(Expr, SortOrder)>,
+    nulls: Nulls,
+}
+
+impl SortBy {
+    /// `order` is one order for every key, or an array with one order per key.
+    fn parse(by: &Expr, order: &Expr, nulls: &Expr) -> Result<Self, Error> {
+        let by = match by {
+            Expr::Array { items, .. } if items.is_empty() => {
+                return Err(Error::new(
+                    STAGE_ARGUMENTS,
+                    "by needs at least one sort key",
+                ))
+            }
+            Expr::Array { items, .. } => items.clone(),
+            _ => vec![by.clone()],
+        };
+        let orders = match order {
+            Expr::Array { items, .. } => items
+                .iter()
+                .map(parse_sort_order)
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => vec![parse_sort_order(order)?; by.len()],
+        };
+        if orders.len() != by.len() {
+            return Err(Error::new(
+                STAGE_ARGUMENTS,
+                format!(
+                    "order has {} entries for {} sort keys",
+                    orders.len(),
+                    by.len()
+                ),
+            ));
+        }
+        let nulls = match expect_string(nulls)?.as_str() {
+            "first" => Nulls::First,
+            "last" => Nulls::Last,
+            _ => {
+                return Err(Error::new(
+                    STAGE_ARGUMENTS,
+                    "nulls must be \"first\" or \"last\"",
+                ))
+            }
+        };
+        Ok(Self {
+            keys: by.into_iter().zip(orders).collect(),
+            nulls,
+        })
+    }
+
+    /// `item`'s sort keys; `err` when one isn't `I64`, `String`, or `Null`.
+    fn key(&self, item: &Value, err: &str) -> Result<Vec<SortKey>, Error> {
+        self.keys
+            .iter()
+            .map(|(by, _)| expect_sort_key(eval_value_expr(by, Some(item))?, err))
+            .collect()
+    }
+
+    fn compare(&self, a: &[SortKey], b: &[SortKey]) -> std::cmp::Ordering {
+        self.keys
+            .iter()
+            .zip(a.iter().zip(b))
+            .map(|((_, order), (a, b))| compare_keys(a, b, *order, self.nulls))
+            .find(|cmp| cmp.is_ne())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    }
+}
+
 #[derive(Debug, Clone)]
 struct GroupTopNItem {
     source_index: usize,
-    order_key: SortKey,
+    order_key: Vec<SortKey>,
     value: Value,
 }
 
-#[derive(Debug, Clone)]
-struct GroupTopNBucket {
-    key: Value,
-    items: Vec<GroupTopNItem>,
+/// Groups in first-seen key order, with hashed key lookup.
+struct OrderedGroups<T> {
+    index: HashMap<Value, usize>,
+    groups: Vec<(Value, T)>,
+}
+
+impl<T> OrderedGroups<T> {
+    fn new() -> Self {
+        Self {
+            index: HashMap::new(),
+            groups: Vec::new(),
+        }
+    }
+
+    fn entry(&mut self, key: Value, init: impl FnOnce() -> T) -> &mut T {
+        self.keyed_entry(key, init).1
+    }
+
+    /// Like [`Self::entry`], also handing back the group's key.
+    fn keyed_entry(&mut self, key: Value, init: impl FnOnce() -> T) -> (&Value, &mut T) {
+        let idx = *self.index.entry(key).or_insert_with_key(|key| {
+            self.groups.push((key.clone(), init()));
+            self.groups.len() - 1
+        });
+        let (key, group) = &mut self.groups[idx];
+        (key, group)
+    }
+
+    fn into_groups(self) -> Vec<(Value, T)> {
+        self.groups
+    }
+}
+
+/// Parses and checks `program`; binding a name twice needs `override`.
+pub fn compile(program: &str) -> Result<Program, RuntimeError> {
+    compile_with(program, Rebinding::default(), &ParseLimits::default())
+}
+
+fn compile_with(
+    program: &str,
+    rebinding: Rebinding,
+    limits: &ParseLimits,
+) -> Result<Program, RuntimeError> {
+    let parsed = parse_program_with_limits(program, limits)?;
+    check::check(&parsed, program, rebinding)?;
+    Ok(parsed)
+}
+
+pub fn run(program: &str, fixtures: JsonValue) -> Result<Outputs, RuntimeError> {
+    run_with_state(program, fixtures, RuntimeState::default())
+}
+
+/// Like [`run`], with params, host constants, and explain level from `options`.
+pub fn run_with_options(
+    program: &str,
+    fixtures: JsonValue,
+    options: &RunOptions,
+) -> Result<Outputs, RuntimeError> {
+    let compiled = options.bind(&compile_with(
+        program,
+        options.rebinding,
+        &options.parse_limits,
+    )?)?;
+    match options.sample {
+        Some(sample) => sample::run(&compiled, program, fixtures, options, None, sample),
+        None => run_program(&compiled, Some(program), fixtures, options.state()),
+    }
+}
+
+/// Like [`run`], with `$name` placeholders filled from the `params` object first.
+pub fn run_with_params(
+    program: &str,
+    fixtures: JsonValue,
+    params: &JsonValue,
+) -> Result<Outputs, RuntimeError> {
+    let compiled = params::bind(&compile(program)?, params)?;
+    run_program(&compiled, Some(program), fixtures, RuntimeState::default())
+}
+
+/// Like [`run`], but every `ui.table` row is passed to `on_row(table, row)` as the
+/// sink produces it. Returned `Outputs::tables` stays empty; logs and explain are
+/// still buffered.
+pub fn run_streaming(
+    program: &str,
+    fixtures: JsonValue,
+    on_row: impl FnMut(&str, JsonValue),
+) -> Result<Outputs, RuntimeError> {
+    let mut sink = sink::TableRowCallback(on_row);
+    let state = RuntimeState {
+        sink: Some(&mut sink),
+        ..RuntimeState::default()
+    };
+    run_with_state(program, fixtures, state)
+}
+
+/// Like [`run`], but every `ui.table` row and `ui.log` record goes to `writer` as
+/// its sink produces it, and `writer.finish()` runs after the last one. Returned
+/// `Outputs::tables` and `Outputs::logs` stay empty.
+pub fn run_with_writer(
+    program: &str,
+    fixtures: JsonValue,
+    writer: &mut dyn OutputWriter,
+) -> Result<Outputs, RuntimeError> {
+    let mut sink = writer::WriterSink(writer);
+    let state = RuntimeState {
+        sink: Some(&mut sink),
+        ..RuntimeState::default()
+    };
+    let mut outputs = run_with_state(program, fixtures, state)?;
+    sink.0.finish()?;
+    outputs.logs.retain(|_, records| !records.is_empty());
+    Ok(outputs)
+}
+
+/// Like [`run`], but stops as soon as `token` is cancelled and returns the partial
+/// outputs with `cancelled` set instead of an error.
+pub fn run_cancellable(
+    program: &str,
+    fixtures: JsonValue,
+    token: &CancelToken,
+) -> Result<Outputs, RuntimeError> {
+    let state = RuntimeState {
+        cancel: Some(token.clone()),
+        ..RuntimeState::default()
+    };
+    run_with_state(program, fixtures, state)
 }
 
-pub fn compile(program: &str) -> Result<Program, String> {
-    parse_program(program).map_err(|e| e.to_string())
+/// Like [`run`], calling `hook` at every `interval`-th yield point (0 counts as 1).
+/// Every item a stage processes is a yield point, as is the start of each statement
+/// and stage. When the hook returns `false` the run stops and returns its partial
+/// outputs with `cancelled` set, as [`run_cancellable`] does.
+pub fn run_with_yield(
+    program: &str,
+    fixtures: JsonValue,
+    interval: u64,
+    hook: &mut dyn YieldHook,
+) -> Result<Outputs, RuntimeError> {
+    let state = RuntimeState {
+        yields: Some(YieldPoints {
+            hook,
+            interval: interval.max(1),
+            reached: 0,
+            stopped: false,
+        }),
+        ..RuntimeState::default()
+    };
+    run_with_state(program, fixtures, state)
+}
+
+/// Runs an already compiled program, so hosts can parse once and run many times.
+pub fn run_compiled(program: &Program, fixtures: JsonValue) -> Result<Outputs, RuntimeError> {
+    run_program(program, None, fixtures, RuntimeState::default())
+}
+
+/// Runs each of `programs` against the same `fixtures`, in name order, and returns
+/// their outputs by name. `kv.load` stores and `rbac.load` policies carry over from
+/// one program to the next; bindings do not. The first failing program's error is
+/// returned, its message prefixed with the program's name.
+pub fn run_workspace(
+    programs: BTreeMap<String, &str>,
+    fixtures: JsonValue,
+) -> Result<BTreeMap<String, Outputs>, RuntimeError> {
+    run_workspace_with(programs, fixtures, RuntimeState::default)
 }
 
-pub fn run(program: &str, fixtures: JsonValue) -> Result<Outputs, String> {
-    let program = compile(program)?;
-    let fixture_map = parse_fixtures(fixtures)?;
+fn run_workspace_with<'a>(
+    programs: BTreeMap<String, &str>,
+    fixtures: JsonValue,
+    fresh: impl Fn() -> RuntimeState<'a>,
+) -> Result<BTreeMap<String, Outputs>, RuntimeError> {
+    let fixture_map = parse_fixtures(fixtures, false)?;
+    let mut state = fresh();
+    let mut outputs = BTreeMap::new();
+    for (name, program) in programs {
+        let result = compile(program)
+            .and_then(|compiled| run_parsed(&compiled, Some(program), &fixture_map, &mut state));
+        let program_outputs = result.map_err(|e| e.in_program(&name))?;
+        outputs.insert(name, program_outputs);
+        state = RuntimeState {
+            #[cfg(feature = "kv")]
+            kv_stores: std::mem::take(&mut state.kv_stores),
+            #[cfg(feature = "rbac")]
+            rbac_policies: std::mem::take(&mut state.rbac_policies),
+            ..fresh()
+        };
+    }
+    Ok(outputs)
+}
+
+fn run_with_state(
+    program: &str,
+    fixtures: JsonValue,
+    state: RuntimeState,
+) -> Result<Outputs, RuntimeError> {
+    run_program(&compile(program)?, Some(program), fixtures, state)
+}
+
+/// Runs `program`; with its `source`, runtime errors end with the failing source
+/// or stage's `at line:column`.
+fn run_program(
+    program: &Program,
+    source: Option<&str>,
+    fixtures: JsonValue,
+    mut state: RuntimeState,
+) -> Result<Outputs, RuntimeError> {
+    let fixture_map = parse_fixtures(fixtures, state.strict)?;
+    run_parsed(program, source, &fixture_map, &mut state)
+}
+
+fn run_parsed(
+    program: &Program,
+    source: Option<&str>,
+    fixture_map: &BTreeMap<String, Vec<JsonValue>>,
+    state: &mut RuntimeState,
+) -> Result<Outputs, RuntimeError> {
     let mut env: BTreeMap<String, Binding> = BTreeMap::new();
-    let mut state = RuntimeState::default();
     let mut outputs = Outputs::default();
+    let lowered;
+    let program = if program
+        .statements
+        .iter()
+        .any(|stmt| matches!(stmt, Stmt::Const { .. }))
+    {
+        let no_host = Map::new();
+        let host = state.constants.unwrap_or(&no_host);
+        lowered = constants::lower(program, &mut Map::new(), host)?;
+        &lowered
+    } else {
+        program
+    };
 
-    for stmt in &program.statements {
-        match stmt {
-            Stmt::Binding { name, expr, .. } => {
-                outputs.explain.push(format!("binding {name}"));
-                let val = eval_expr(expr, &env, &fixture_map, &mut state, &mut outputs)?;
-                env.insert(name.clone(), val);
-            }
-            Stmt::Pipeline { expr, .. } => {
-                outputs.explain.push("pipeline".to_string());
-                let _ = expect_stream(eval_expr(
-                    expr,
-                    &env,
-                    &fixture_map,
-                    &mut state,
-                    &mut outputs,
-                )?)?;
+    let recording = state.coverage.then(coverage::Recording::start);
+    let mut last_reads = check::last_reads(program);
+    for (stmt, last_reads) in program.statements.iter().zip(&mut last_reads) {
+        state.last_reads = std::mem::take(last_reads);
+        if let Err(e) = run_stmt(stmt, &mut env, fixture_map, state, &mut outputs) {
+            if e.code == CANCELLED && state.stop_requested() {
+                outputs.cancelled = true;
+                break;
             }
+            return Err(locate_error(e, state, stmt, source));
         }
     }
+    outputs.warnings = take_warnings(state, source);
+    outputs.coverage = recording.map(|recording| recording.finish(program, source));
 
+    if state.explain == ExplainLevel::Quiet {
+        outputs.explain.clear();
+    }
+    if state.audit {
+        outputs.fingerprint = Some(outputs.execution_fingerprint());
+    }
+    outputs.peak_stream_bytes = state.memory.as_ref().map(|memory| memory.peak);
+    outputs.schemas = outputs
+        .tables
+        .iter()
+        .map(|(name, rows)| {
+            let order = outputs.column_order.get(name).map(Vec::as_slice);
+            (name.clone(), columnar::infer_schema_ordered(rows, order))
+        })
+        .collect();
+    if state.columnar {
+        let tables = std::mem::take(&mut outputs.tables);
+        outputs.tables_columnar = Some(
+            tables
+                .into_iter()
+                .map(|(name, rows)| {
+                    let order = outputs.column_order.get(&name).map(Vec::as_slice);
+                    (name, ColumnarTable::from_rows_ordered(rows, order))
+                })
+                .collect(),
+        );
+    }
     Ok(outputs)
 }
 
+fn locate_error(
+    e: Error,
+    state: &mut RuntimeState,
+    stmt: &Stmt,
+    source: Option<&str>,
+) -> RuntimeError {
+    let failed = state.error_span.take();
+    let formatted = state.error_stage.take();
+    let item_index = state.error_item.take();
+    let span = failed.unwrap_or_else(|| stmt.span());
+    let (message, stage) = match source {
+        Some(source) => (
+            e.at(span.resolve(source).0),
+            failed.map(|span| source[span.start..span.end].to_string()),
+        ),
+        None => (e, failed.and(formatted)),
+    };
+    RuntimeError::located(message, Some(span), stage, item_index)
+}
+
+/// Records `index` as the failing item of a per-item stage unless an inner stage
+/// already did.
+fn fail_at_item<T>(
+    state: &mut RuntimeState,
+    index: usize,
+    result: Result<T, Error>,
+) -> Result<T, Error> {
+    if result.is_err() && state.error_item.is_none() {
+        state.error_item = Some(index);
+    }
+    result
+}
+
+fn take_warnings(state: &mut RuntimeState, source: Option<&str>) -> Vec<Warning> {
+    std::mem::take(&mut state.warnings)
+        .into_iter()
+        .map(|(kind, message, span)| Warning {
+            kind,
+            message,
+            at: span
+                .zip(source)
+                .map(|(span, source)| span.resolve(source).0),
+        })
+        .collect()
+}
+
+/// Records a warning from the stage being applied; [`attribute_warnings`] places it.
+fn warn(state: &mut RuntimeState, kind: WarningKind, message: String) {
+    state.warnings.push((kind, message, None));
+}
+
+/// Places the warnings a stage just recorded at `stage`.
+fn attribute_warnings(state: &mut RuntimeState, stage: &Expr) {
+    for (_, _, span) in state.warnings.iter_mut().rev() {
+        if span.is_some() {
+            break;
+        }
+        *span = Some(stage.span());
+    }
+}
+
+/// Records `expr` as the failing source or stage unless an inner one already was.
+fn fail_at<T>(state: &mut RuntimeState, expr: &Expr, result: Result<T, Error>) -> Result<T, Error> {
+    if result.is_err() && state.error_span.is_none() {
+        state.error_span = Some(expr.span());
+        state.error_stage = Some(format_expr(expr));
+    }
+    result
+}
+
+/// Incremental evaluation for interactive use: bindings, `kv.load` stores, and
+/// `rbac.load` policies persist across [`Session::eval`] calls until
+/// [`Session::reset`].
+pub struct Session {
+    fixtures: BTreeMap<String, Vec<JsonValue>>,
+    env: BTreeMap<String, Binding>,
+    constants: Map,
+    state: RuntimeState<'static>,
+    last_explain: Vec<String>,
+    /// Which version of its statement made each binding and `kv.load` store (see
+    /// [`run_statement`]); cleared by [`Session::eval`].
+    versions: BTreeMap<String, u64>,
+    /// Source of each stage binding, in binding order, for [`Session::serialize`].
+    stage_sources: Vec<(String, String)>,
+}
+
+impl Session {
+    pub fn new(fixtures: JsonValue) -> Result<Self, RuntimeError> {
+        Ok(Self {
+            fixtures: parse_fixtures(fixtures, false)?,
+            env: BTreeMap::new(),
+            constants: Map::new(),
+            state: RuntimeState::default(),
+            last_explain: Vec::new(),
+            versions: BTreeMap::new(),
+            stage_sources: Vec::new(),
+        })
+    }
+
+    /// Runs every statement in `src` and returns only the outputs they produced.
+    /// Statements before a failing one keep their effects.
+    pub fn eval(&mut self, src: &str) -> Result<Outputs, RuntimeError> {
+        let program = constants::lower(&compile(src)?, &mut self.constants, &Map::new())?;
+        self.versions.clear();
+        let mut outputs = Outputs::default();
+        let mut result = Ok(());
+        for stmt in &program.statements {
+            if let Err(e) = run_stmt(
+                stmt,
+                &mut self.env,
+                &self.fixtures,
+                &mut self.state,
+                &mut outputs,
+            ) {
+                result = Err(locate_error(e, &mut self.state, stmt, Some(src)));
+                break;
+            }
+            self.note_binding(stmt);
+        }
+        outputs.warnings = take_warnings(&mut self.state, Some(src));
+        self.last_explain = outputs.explain.clone();
+        result.map(|()| outputs)
+    }
+
+    /// Explain lines from the most recent [`Session::eval`].
+    pub fn last_explain(&self) -> &[String] {
+        &self.last_explain
+    }
+
+    /// Bound names with a short description (`stream (N items)`, `stage`,
+    /// `stage (reversible)`, or `const <json>`).
+    pub fn bindings(&self) -> Vec<(String, String)> {
+        let mut out: Vec<(String, String)> = self
+            .env
+            .iter()
+            .map(|(name, binding)| {
+                let kind = match binding {
+                    Binding::Stream(stream) => format!("stream ({} items)", stream.values.len()),
+                    Binding::Stage(_) if self.state.irreversible.get(name) == Some(&None) => {
+                        "stage (reversible)".to_string()
+                    }
+                    Binding::Stage(_) => "stage".to_string(),
+                };
+                (name.clone(), kind)
+            })
+            .collect();
+        out.extend(self.constants.iter().map(|(name, value)| {
+            let json = serde_json::to_string(value).unwrap_or_default();
+            (name.clone(), format!("const {json}"))
+        }));
+        out.sort();
+        out
+    }
+
+    /// Drops all bindings and runtime state; fixtures are kept.
+    pub fn reset(&mut self) {
+        self.env.clear();
+        self.constants.clear();
+        self.state = RuntimeState::default();
+        self.last_explain.clear();
+        self.versions.clear();
+        self.stage_sources.clear();
+    }
+}
+
+fn run_stmt(
+    stmt: &Stmt,
+    env: &mut BTreeMap<String, Binding>,
+    fixture_map: &BTreeMap<String, Vec<JsonValue>>,
+    state: &mut RuntimeState,
+    outputs: &mut Outputs,
+) -> Result<(), Error> {
+    state.check_boundary()?;
+    match stmt {
+        Stmt::Binding { name, expr, .. } => {
+            outputs.explain.push(format!("binding {name}"));
+            let val = eval_expr(expr, env, fixture_map, state, outputs)?;
+            if let (Some(memory), Binding::Stream(stream)) = (state.memory.as_mut(), &val) {
+                memory.bound.insert(name.clone(), stream.approx_size());
+            }
+            match val {
+                Binding::Stage(_) => {
+                    let part = irreversible_part(expr, state).map(|(part, _)| part);
+                    state.irreversible.insert(name.clone(), part);
+                }
+                Binding::Stream(_) => {
+                    state.irreversible.remove(name);
+                }
+            }
+            env.insert(name.clone(), val);
+        }
+        Stmt::Destructure { names, expr, .. } => {
+            outputs
+                .explain
+                .push(format!("binding {{{}}}", names.join(", ")));
+            let stream = expect_stream(eval_expr(expr, env, fixture_map, state, outputs)?)?;
+            for name in names {
+                let values = stream
+                    .values
+                    .iter()
+                    .map(|item| {
+                        take_field(Cow::Borrowed(item), name)
+                            .map(Cow::into_owned)
+                            .map_err(|e| e.map_message(|e| format!("destructuring {name}: {e}")))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let part = Stream::new(values);
+                state.irreversible.remove(name);
+                if let Some(memory) = state.memory.as_mut() {
+                    memory.bound.insert(name.clone(), part.approx_size());
+                }
+                env.insert(name.clone(), Binding::Stream(Rc::new(part)));
+            }
+        }
+        Stmt::Pipeline { expr, .. } => {
+            outputs.explain.push("pipeline".to_string());
+            let _ = expect_stream(eval_expr(expr, env, fixture_map, state, outputs)?)?;
+        }
+        Stmt::Const { name, .. } => {
+            return Err(format!("const {name} was not lowered before running").into());
+        }
+    }
+    Ok(())
+}
+
 fn eval_expr(
     expr: &Expr,
-    env: &BTreeMap<String, Binding>,
+    env: &mut BTreeMap<String, Binding>,
     fixtures: &BTreeMap<String, Vec<JsonValue>>,
     state: &mut RuntimeState,
     outputs: &mut Outputs,
-) -> Result<Binding, String> {
+) -> Result<Binding, Error> {
     match expr {
         Expr::Pipeline { input, stages, .. } => {
             let mut stream = expect_stream(eval_expr(input, env, fixtures, state, outputs)?)?;
+            coverage::hit(input.span(), stream.values.len());
             for stage_expr in stages {
-                let stage = expect_stage(eval_expr(stage_expr, env, fixtures, state, outputs)?)?;
-                stream = apply_stage(&stage, stream, fixtures, state, outputs)?;
+                let stage =
+                    eval_expr(stage_expr, env, fixtures, state, outputs).and_then(expect_stage);
+                let stage = fail_at(state, stage_expr, stage)?;
+                coverage::hit(stage_expr.span(), stream.values.len());
+                let type_in = state.trace_items().map(|_| stream_type(&stream));
+                if state.timings.is_none() {
+                    let result = apply_stage(&stage, stream, fixtures, state, outputs);
+                    stream = fail_at(state, stage_expr, result)?;
+                    attribute_warnings(state, stage_expr);
+                    observe_memory(state, stage_expr, &stream)?;
+                    explain_type(type_in, &stream, state, outputs);
+                    trace(&stream, state, outputs);
+                    continue;
+                }
+                let items_in = stream.values.len();
+                let start = std::time::Instant::now();
+                let result = apply_stage(&stage, stream, fixtures, state, outputs);
+                stream = fail_at(state, stage_expr, result)?;
+                attribute_warnings(state, stage_expr);
+                let timing = bench::StageTiming {
+                    stage: dsl_syntax::format_expr(stage_expr),
+                    items_in,
+                    items_out: stream.values.len(),
+                    bytes_out: stream.approx_size(),
+                    elapsed: start.elapsed(),
+                };
+                if let Some(timings) = state.timings.as_mut() {
+                    timings.push(timing);
+                }
+                observe_memory(state, stage_expr, &stream)?;
+                explain_type(type_in, &stream, state, outputs);
+                trace(&stream, state, outputs);
             }
-            Ok(Binding::Stream(stream))
+            Ok(Binding::Stream(Rc::new(stream)))
         }
         Expr::Call { callee, args, .. } => {
-            let name = callee_name(callee).ok_or_else(|| "unsupported callee".to_string())?;
+            let name = callee_name(callee)
+                .ok_or_else(|| Error::new(UNKNOWN_STAGE, "unsupported callee"))?;
+            let name = resolve_alias(name, callee, state, outputs);
             match name.as_str() {
-                "input.json" => {
-                    let fixture_name = expect_string(positional_arg(args, 0)?)?;
-                    outputs
-                        .explain
-                        .push(format!("  [source] input.json({fixture_name})"));
-                    let items = fixtures
-                        .get(&fixture_name)
-                        .ok_or_else(|| format!("missing fixture: {fixture_name}"))?;
-                    let values = items
-                        .iter()
-                        .map(|item| {
-                            serde_json::to_vec(item)
-                                .map(Value::Bytes)
-                                .map_err(|e| e.to_string())
-                        })
-                        .collect::<Result<Vec<_>, _>>()?;
-                    Ok(Binding::Stream(Stream::new(values)))
+                _ if name.starts_with("input.") => {
+                    features::require(&name)?;
+                    let runtime_source = state.runtime.and_then(|runtime| runtime.source(&name));
+                    let source = source::builtin(&name)
+                        .or(runtime_source.as_deref())
+                        .ok_or_else(|| unknown_source(&name, state.runtime))?;
+                    let args = StageArgs::new(args);
+                    outputs.explain.push(format!(
+                        "  [source] {}",
+                        source.explain(&dsl_syntax::format_expr(expr), &args)
+                    ));
+                    let ctx = source::SourceContext::new(fixtures, &mut state.interner);
+                    let values = source.read(&args, &ctx).map_err(|e| ctx.error(e));
+                    let values = Stream::new(fail_at(state, expr, values)?);
+                    observe_memory(state, expr, &values)?;
+                    explain_type(None, &values, state, outputs);
+                    trace(&values, state, outputs);
+                    Ok(Binding::Stream(Rc::new(values)))
+                }
+                "host.call" => {
+                    let fn_name = expect_string(positional_arg(args, 0)?)?;
+                    let arg = positional_arg(args, 1)?;
+                    let runtime = state.runtime.ok_or_else(|| {
+                        Error::new(UNKNOWN_STAGE, format!("unknown host function: {fn_name}"))
+                    })?;
+                    Ok(Binding::Stage(Stage::HostCall(
+                        runtime.host_call(&fn_name, arg)?,
+                    )))
                 }
-                "map" => Ok(Binding::Stage(Stage::Map(positional_arg(args, 0)?.clone()))),
-                "filter" => Ok(Binding::Stage(Stage::Filter(
-                    positional_arg(args, 0)?.clone(),
-                ))),
-                "flat_map" => Ok(Binding::Stage(Stage::FlatMap(
-                    positional_arg(args, 0)?.clone(),
-                ))),
-                "group.collect_all" => Ok(Binding::Stage(Stage::GroupCollectAll {
-                    by_key: named_arg(args, "by_key")?.clone(),
-                    within_ms: expect_i64_literal(named_arg(args, "within_ms")?)?,
-                    limit: expect_i64_literal(named_arg(args, "limit")?)?,
-                })),
-                "group.count" => Ok(Binding::Stage(Stage::GroupCount {
-                    by_key: named_arg(args, "by_key")?.clone(),
-                })),
-                "rank.topk" => Ok(Binding::Stage(Stage::RankTopK {
-                    k: expect_i64_literal(named_arg(args, "k")?)?,
-                    by: named_arg(args, "by")?.clone(),
-                    order: parse_sort_order(named_arg(args, "order")?)?,
-                })),
-                "rank.kmerge_arrays" => Ok(Binding::Stage(Stage::RankKMergeArrays {
-                    by: named_arg(args, "by")?.clone(),
-                    order: parse_sort_order(named_arg(args, "order")?)?,
-                    limit: expect_i64_literal(named_arg(args, "limit")?)?,
-                })),
-                "group.topn_items" => Ok(Binding::Stage(Stage::GroupTopNItems {
-                    by_key: named_arg(args, "by_key")?.clone(),
-                    n: expect_i64_literal(named_arg(args, "n")?)?,
-                    order_by: named_arg(args, "order_by")?.clone(),
-                    order: parse_sort_order(named_arg(args, "order")?)?,
-                })),
-                "kv.load" => Ok(Binding::Stage(Stage::KvLoad {
-                    store: expect_string(named_arg(args, "store")?)?,
-                })),
-                "lookup.kv" => Ok(Binding::Stage(Stage::LookupKv {
-                    store: expect_string(named_arg(args, "store")?)?,
-                    key: named_arg(args, "key")?.clone(),
-                })),
-                "lookup.batch_kv" => Ok(Binding::Stage(Stage::LookupBatchKv {
-                    store: expect_string(named_arg(args, "store")?)?,
-                    key: named_arg(args, "key")?.clone(),
-                    batch_size: expect_i64_literal(named_arg(args, "batch_size")?)?,
-                    within_ms: expect_i64_literal(named_arg(args, "within_ms")?)?,
-                })),
-                "rbac.evaluate" => Ok(Binding::Stage(Stage::RbacEvaluate {
-                    principal_bindings: expect_string(named_arg(args, "principal_bindings")?)?,
-                    role_perms: expect_string(named_arg(args, "role_perms")?)?,
-                    resource_ancestors: expect_string(named_arg(args, "resource_ancestors")?)?,
-                })),
-                "ui.table" => Ok(Binding::Stage(Stage::UiTable(expect_string(
-                    positional_arg(args, 0)?,
-                )?))),
-                "ui.log" => Ok(Binding::Stage(Stage::UiLog(expect_string(
-                    positional_arg(args, 0)?,
-                )?))),
-                _ => Err(format!("unsupported call: {name}")),
+                _ => match builtin_stage(&name, args, state.strict)? {
+                    Some(Stage::Iterate {
+                        step,
+                        until,
+                        max_iters,
+                    }) => {
+                        let step = iterate_step(args, *step, env, fixtures, state, outputs)?;
+                        Ok(Binding::Stage(Stage::Iterate {
+                            step: Box::new(step),
+                            until,
+                            max_iters,
+                        }))
+                    }
+                    Some(stage) => Ok(Binding::Stage(stage)),
+                    // Registered names are namespaced outside the reserved namespaces,
+                    // so a custom stage never shadows a built-in or is shadowed by one.
+                    None => state
+                        .runtime
+                        .and_then(|runtime| runtime.stage(&name, args))
+                        .map(|stage| Binding::Stage(Stage::Custom(stage)))
+                        .ok_or_else(|| unsupported_call(&name, state.runtime)),
+                },
             }
         }
-        Expr::Ident { name, .. } if name == "json" => {
-            Ok(Binding::Stage(Stage::Json(Direction::Auto)))
-        }
-        Expr::Ident { name, .. } if name == "utf8" => {
-            Ok(Binding::Stage(Stage::Utf8(Direction::Auto)))
+        Expr::Ident { name, .. } if Codec::from_name(name).is_some() => {
+            features::require(name)?;
+            let (codec, direction) = codec_stage(name, state.strict).expect("checked");
+            Ok(Binding::Stage(Stage::Codec(codec, direction, None)))
         }
-        Expr::Ident { name, .. } if name == "base64" => {
-            Ok(Binding::Stage(Stage::Base64(Direction::Auto)))
+        Expr::Ident { name, .. } => {
+            let binding = if state.last_reads.remove(name) {
+                env.remove(name)
+            } else {
+                env.get(name).cloned()
+            };
+            binding.ok_or_else(|| {
+                let codecs = Codec::ALL
+                    .map(Codec::name)
+                    .into_iter()
+                    .filter(|codec| features::require(codec).is_ok());
+                let known = env.keys().map(String::as_str).chain(codecs);
+                Error::templated(
+                    UNKNOWN_IDENT,
+                    Message::new("unknown_ident")
+                        .arg("name", name)
+                        .suggest(suggest::nearest(name, known)),
+                )
+            })
         }
-        Expr::Ident { name, .. } => env
-            .get(name)
-            .cloned()
-            .ok_or_else(|| format!("unknown ident {name}")),
         Expr::Compose { left, right, .. } => Ok(Binding::Stage(Stage::Compose(vec![
             expect_stage(eval_expr(left, env, fixtures, state, outputs)?)?,
             expect_stage(eval_expr(right, env, fixtures, state, outputs)?)?,
         ]))),
-        Expr::Inverse { expr, .. } => Ok(Binding::Stage(invert_stage(expect_stage(eval_expr(
-            expr, env, fixtures, state, outputs,
-        )?)?)?)),
-        _ => Err("unsupported expression for stream/stage evaluation".to_string()),
+        Expr::Inverse { expr: inner, .. } => {
+            let stage = expect_stage(eval_expr(inner, env, fixtures, state, outputs)?)?;
+            invert_stage(stage).map(Binding::Stage).map_err(|e| {
+                let Some((part, span)) = irreversible_part(inner, state) else {
+                    return e;
+                };
+                if state.error_span.is_none() {
+                    state.error_span = span.or(Some(expr.span()));
+                    state.error_stage = Some(format_expr(expr));
+                }
+                let message = match &**inner {
+                    Expr::Ident { name, .. } => {
+                        format!("cannot invert {name}: its component {part} is not reversible")
+                    }
+                    _ => format!(
+                        "cannot invert {}: {part} is not reversible",
+                        format_expr(inner)
+                    ),
+                };
+                Error::new(NOT_REVERSIBLE, message)
+            })
+        }
+        Expr::FieldAccess { .. } => match callee_name(expr).as_deref().and_then(explicit_codec) {
+            Some((codec, direction)) => {
+                features::require(codec.name())?;
+                Ok(Binding::Stage(Stage::Codec(codec, direction, None)))
+            }
+            None => Err(Error::new(
+                UNSUPPORTED_EXPRESSION,
+                "unsupported expression for stream/stage evaluation",
+            )),
+        },
+        _ => Err(Error::new(
+            UNSUPPORTED_EXPRESSION,
+            "unsupported expression for stream/stage evaluation",
+        )),
+    }
+}
+
+/// Runs a stage renamed by a grammar migration ([`dsl_syntax::migrate`]) under its
+/// current name, with a deprecation warning.
+fn resolve_alias(
+    name: String,
+    at: &Expr,
+    state: &mut RuntimeState,
+    outputs: &mut Outputs,
+) -> String {
+    let Some(current) = dsl_syntax::migrate::current_stage_name(&name) else {
+        return name;
+    };
+    outputs
+        .explain
+        .push(format!("  [deprecated] {name} -> {current}"));
+    let warning = (
+        WarningKind::Deprecated,
+        format!("{name} is deprecated; use {current}"),
+        Some(at.span()),
+    );
+    if !state.warnings.contains(&warning) {
+        state.warnings.push(warning);
+    }
+    current.to_string()
+}
+
+fn observe_memory(state: &mut RuntimeState, at: &Expr, stream: &Stream) -> Result<(), Error> {
+    let result = match state.memory.as_mut() {
+        Some(memory) => memory.observe(at, stream),
+        None => Ok(()),
+    };
+    fail_at(state, at, result)
+}
+
+/// `stream<T>` for the narrowest `T` fitting a stream's items, or `empty`.
+fn stream_type(stream: &Stream) -> String {
+    match type_expr::TypeExpr::infer(&stream.values) {
+        Some(ty) => format!("stream<{ty}>"),
+        None => "empty".to_string(),
+    }
+}
+
+/// Verbose explain: the stream types a source writes or a stage reads and writes,
+/// inferred from the items that flowed.
+fn explain_type(
+    type_in: Option<String>,
+    stream: &Stream,
+    state: &RuntimeState,
+    outputs: &mut Outputs,
+) {
+    if state.trace_items().is_none() {
+        return;
     }
+    let type_out = stream_type(stream);
+    outputs.explain.push(match type_in {
+        Some(type_in) => format!("    type: {type_in} -> {type_out}"),
+        None => format!("    type: {type_out}"),
+    });
+}
+
+/// Verbose explain: the first items of `stream` after a source or stage.
+fn trace(stream: &Stream, state: &RuntimeState, outputs: &mut Outputs) {
+    let Some(limit) = state.trace_items() else {
+        return;
+    };
+    for (idx, item) in stream.values.iter().take(limit).enumerate() {
+        let json = serde_json::to_string(&item.to_json()).unwrap_or_default();
+        outputs.explain.push(format!("    #{idx} {json}"));
+    }
+    if stream.values.len() > limit {
+        outputs
+            .explain
+            .push(format!("    ... {} more", stream.values.len() - limit));
+    }
+}
+
+/// `unsupported call: grup.count (did you mean `group.count`?)`, suggesting built-in
+/// and registered custom stages.
+fn unsupported_call(name: &str, runtime: Option<&Runtime>) -> Error {
+    let mut known: Vec<String> = stage_args::STAGES
+        .iter()
+        .map(|(stage, _)| *stage)
+        .chain(["select", "json", "ndjson", "host.call"])
+        .filter(|stage| features::require(stage).is_ok())
+        .map(str::to_string)
+        .collect();
+    if let Some(runtime) = runtime {
+        known.extend(runtime.stage_names());
+    }
+    let suggestions = suggest::nearest(name, known.iter().map(String::as_str));
+    Error::templated(
+        UNKNOWN_STAGE,
+        Message::new("unsupported_call")
+            .arg("name", name)
+            .suggest(suggestions),
+    )
+}
+
+fn unknown_source(name: &str, runtime: Option<&Runtime>) -> Error {
+    let mut available: Vec<String> = source::BUILTIN_SOURCES
+        .iter()
+        .filter(|name| features::require(name).is_ok())
+        .map(|name| name.to_string())
+        .collect();
+    if let Some(runtime) = runtime {
+        available.extend(runtime.source_names());
+    }
+    available.sort();
+    Error::new(
+        UNKNOWN_STAGE,
+        format!(
+            "unknown source provider: {name} (available: {})",
+            available.join(", ")
+        ),
+    )
 }
 
 fn apply_stage(
@@ -297,21 +1495,28 @@ fn apply_stage(
     fixtures: &BTreeMap<String, Vec<JsonValue>>,
     state: &mut RuntimeState,
     outputs: &mut Outputs,
-) -> Result<Stream, String> {
+) -> Result<Stream, Error> {
+    state.check_boundary()?;
     match stage {
         Stage::Map(expr) => {
             outputs.explain.push("  [pure] map".to_string());
             let out = stream
                 .into_iter()
-                .map(|item| eval_value_expr(expr, Some(&item)))
+                .enumerate()
+                .map(|(index, item)| {
+                    state.check_cancelled()?;
+                    fail_at_item(state, index, eval_value_expr(expr, Some(&item)))
+                })
                 .collect::<Result<Vec<_>, _>>()?;
             Ok(Stream::new(out))
         }
         Stage::Filter(expr) => {
             outputs.explain.push("  [pure] filter".to_string());
             let mut out = Vec::new();
-            for item in stream {
-                if truthy(&eval_value_expr(expr, Some(&item))?)? {
+            for (index, item) in stream.into_iter().enumerate() {
+                state.check_cancelled()?;
+                let keep = eval_value_expr(expr, Some(&item)).and_then(|value| truthy(&value));
+                if fail_at_item(state, index, keep)? {
                     out.push(item);
                 }
             }
@@ -320,122 +1525,392 @@ fn apply_stage(
         Stage::FlatMap(expr) => {
             outputs.explain.push("  [pure] flat_map".to_string());
             let mut out = Vec::new();
+            for (index, item) in stream.into_iter().enumerate() {
+                state.check_cancelled()?;
+                let values = eval_value_expr(expr, Some(&item)).and_then(|value| match value {
+                    Value::Array(values) => Ok(values),
+                    other => Err(
+                        Error::mismatch("Array", "flat_map expression must return Array")
+                            .got(other.kind()),
+                    ),
+                });
+                out.extend(fail_at_item(state, index, values)?);
+            }
+            Ok(Stream::new(out))
+        }
+        Stage::Lens { forward, inverse } => {
+            outputs.explain.push("  [reversible] lens".to_string());
+            let items = stream.values;
+            let out = items
+                .iter()
+                .map(|item| {
+                    state.check_cancelled()?;
+                    eval_value_expr(forward, Some(item))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            for idx in lens::sample(items.len()) {
+                let back = eval_value_expr(inverse, Some(&out[idx]))
+                    .map_err(|e| e.map_message(|e| format!("lens inverse: {e}")))?;
+                if back != items[idx] {
+                    let json =
+                        |value: &Value| serde_json::to_string(&value.to_json()).unwrap_or_default();
+                    return Err(Error::new(
+                        NOT_REVERSIBLE,
+                        format!(
+                            "lens does not round-trip item {idx}: {} came back as {}",
+                            json(&items[idx]),
+                            json(&back)
+                        ),
+                    ));
+                }
+            }
+            Ok(Stream::new(out))
+        }
+        Stage::Having(expr) => {
+            outputs.explain.push("  [pure] having".to_string());
+            let mut out = Vec::new();
             for item in stream {
-                match eval_value_expr(expr, Some(&item))? {
-                    Value::Array(values) => out.extend(values),
-                    _ => return Err("flat_map expression must return Array".to_string()),
+                state.check_cancelled()?;
+                if !matches!(&item, Value::Record(rec) if rec.contains_key("key")) {
+                    return Err(Error::mismatch(
+                        "{key, items} group records",
+                        "having expects {key, items} group records; use it after a group.* stage",
+                    ));
+                }
+                if truthy(&eval_value_expr(expr, Some(&item))?)? {
+                    out.push(item);
                 }
             }
             Ok(Stream::new(out))
         }
+        Stage::Select(columns) => {
+            let names: Vec<&str> = columns.iter().map(|(name, _)| name.as_str()).collect();
+            outputs
+                .explain
+                .push(format!("  [projection] select({})", names.join(", ")));
+            let out = stream
+                .into_iter()
+                .map(|item| {
+                    state.check_cancelled()?;
+                    columns
+                        .iter()
+                        .map(|(name, expr)| {
+                            Ok((
+                                state.interner.intern(name),
+                                eval_value_expr(expr, Some(&item))?,
+                            ))
+                        })
+                        .collect::<Result<BTreeMap<_, _>, Error>>()
+                        .map(Value::Record)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Stream::new(out))
+        }
         Stage::GroupCollectAll {
             by_key,
             within_ms,
             limit,
+            overflow,
+            order_items_by,
         } => {
-            if *within_ms < 0 {
-                return Err("group.collect_all within_ms must be >= 0".to_string());
-            }
-            if *limit < 0 {
-                return Err("group.collect_all limit must be >= 0".to_string());
-            }
             outputs
                 .explain
                 .push("  [pure] group.collect_all".to_string());
+            if state.trace_items().is_some() {
+                outputs.explain.push(format!(
+                    "    window: one window over {} items (simulated within_ms={within_ms}), limit={limit} per group, overflow={}",
+                    stream.values.len(),
+                    overflow.name()
+                ));
+            }
 
-            let mut groups: Vec<(Value, Vec<Value>)> = Vec::new();
+            // Each group's items in chunks of at most `limit`; only spilling starts
+            // a second chunk.
+            let max_items = *limit as usize;
+            let mut dropped = 0usize;
+            let mut groups = OrderedGroups::new();
             for item in stream {
+                state.check_cancelled()?;
                 let key = eval_value_expr(by_key, Some(&item))?;
-                if let Some((_, items)) = groups.iter_mut().find(|(k, _)| *k == key) {
-                    items.push(item);
-                } else {
-                    groups.push((key, vec![item]));
+                let (key, chunks) = groups.keyed_entry(key, || vec![Vec::new()]);
+                let chunk = chunks.last_mut().expect("a group has a chunk");
+                if chunk.len() < max_items {
+                    chunk.push(item);
+                    continue;
+                }
+                match overflow {
+                    Overflow::Drop => dropped += 1,
+                    Overflow::Error => {
+                        let shown =
+                            serde_json::to_string(&key.to_json()).map_err(|e| e.to_string())?;
+                        return Err(Error::new(
+                            LIMIT_EXCEEDED,
+                            format!("group.collect_all: group {shown} has more than {limit} items"),
+                        ));
+                    }
+                    Overflow::Spill => chunks.push(vec![item]),
                 }
             }
 
-            let max_items = *limit as usize;
-            let out = groups
-                .into_iter()
-                .map(|(key, mut items)| {
-                    if items.len() > max_items {
-                        items.truncate(max_items);
+            if dropped > 0 {
+                warn(
+                    state,
+                    WarningKind::Truncated,
+                    format!("group.collect_all dropped {dropped} items over limit={limit}"),
+                );
+            }
+
+            let mut out = Vec::new();
+            for (key, chunks) in groups.into_groups() {
+                for mut items in chunks {
+                    if let Some(order_by) = order_items_by {
+                        items = sort_items(
+                            state,
+                            items,
+                            order_by,
+                            "group.collect_all order_items_by must evaluate to I64, String, or Null",
+                        )?;
                     }
-                    Value::Record(BTreeMap::from([
-                        ("key".to_string(), key),
-                        ("items".to_string(), Value::Array(items)),
-                    ]))
-                })
-                .collect();
+                    out.push(Value::Record(BTreeMap::from([
+                        (state.interner.intern("key"), key.clone()),
+                        (state.interner.intern("items"), Value::Array(items)),
+                    ])));
+                }
+            }
             Ok(Stream::new(out))
         }
         Stage::GroupCount { by_key } => {
             outputs.explain.push("  [pure] group.count".to_string());
 
-            let mut groups: Vec<(Value, i64)> = Vec::new();
+            let mut groups = OrderedGroups::new();
             for item in stream {
+                state.check_cancelled()?;
                 let key = eval_value_expr(by_key, Some(&item))?;
                 expect_group_key(&key, "group.count by_key must evaluate to I64 or String")?;
-
-                if let Some((_, count)) = groups.iter_mut().find(|(k, _)| *k == key) {
-                    *count += 1;
-                } else {
-                    groups.push((key, 1));
-                }
+                *groups.entry(key, || 0) += 1;
             }
 
             let out = groups
+                .into_groups()
                 .into_iter()
                 .map(|(key, count)| {
                     Value::Record(BTreeMap::from([
-                        ("key".to_string(), key),
-                        ("count".to_string(), Value::I64(count)),
+                        (state.interner.intern("key"), key),
+                        (state.interner.intern("count"), Value::I64(count)),
                     ]))
                 })
                 .collect();
             Ok(Stream::new(out))
         }
-        Stage::RankTopK { k, by, order } => {
-            if *k < 0 {
-                return Err("rank.topk k must be >= 0".to_string());
+        Stage::Sessionize { by_key, gap_ms, ts } => {
+            outputs.explain.push("  [pure] sessionize".to_string());
+
+            let mut groups = OrderedGroups::new();
+            for item in stream {
+                state.check_cancelled()?;
+                let key = eval_value_expr(by_key, Some(&item))?;
+                expect_group_key(&key, "sessionize by_key must evaluate to I64 or String")?;
+                let at = expect_timestamp(ts, &item, "sessionize")?;
+                groups.entry(key, Vec::new).push((at, item));
             }
-            outputs.explain.push("  [pure] rank.topk".to_string());
 
-            let mut rows: Vec<(usize, SortKey, Value)> = Vec::new();
-            for (idx, item) in stream.into_iter().enumerate() {
-                let key = expect_sort_key(
-                    eval_value_expr(by, Some(&item))?,
-                    "rank.topk by expression must evaluate to I64 or String",
-                )?;
-                rows.push((idx, key, item));
+            let mut out = Vec::new();
+            for (key, mut items) in groups.into_groups() {
+                items.sort_by_key(|(at, _)| *at);
+                let mut sessions: Vec<Vec<Value>> = Vec::new();
+                let mut last = None;
+                for (at, item) in items {
+                    match (sessions.last_mut(), last) {
+                        (Some(session), Some(last)) if at.abs_diff(last) <= *gap_ms as u64 => {
+                            session.push(item)
+                        }
+                        _ => sessions.push(vec![item]),
+                    }
+                    last = Some(at);
+                }
+                for (session_id, items) in sessions.into_iter().enumerate() {
+                    out.push(Value::Record(BTreeMap::from([
+                        (state.interner.intern("key"), key.clone()),
+                        (
+                            state.interner.intern("session_id"),
+                            Value::I64(session_id as i64),
+                        ),
+                        (state.interner.intern("items"), Value::Array(items)),
+                    ])));
+                }
             }
+            Ok(Stream::new(out))
+        }
+        Stage::DedupeWithin { by, within_ms, ts } => {
+            outputs.explain.push("  [pure] dedupe.within".to_string());
 
-            rows.sort_by(|(idx_a, key_a, _), (idx_b, key_b, _)| {
-                compare_keys(key_a, key_b, *order).then_with(|| idx_a.cmp(idx_b))
-            });
+            // Key -> timestamp of the last kept item; duplicates don't extend the window.
+            let mut kept_at: HashMap<Value, i64> = HashMap::new();
+            let mut out = Vec::new();
+            let mut dropped = 0usize;
+            for item in stream {
+                state.check_cancelled()?;
+                let key = eval_value_expr(by, Some(&item))?;
+                let at = expect_timestamp(ts, &item, "dedupe.within")?;
+                match kept_at.get(&key) {
+                    Some(kept) if at.abs_diff(*kept) <= *within_ms as u64 => dropped += 1,
+                    _ => {
+                        kept_at.insert(key, at);
+                        out.push(item);
+                    }
+                }
+            }
+            if state.trace_items().is_some() {
+                outputs.explain.push(format!(
+                    "    dropped: {dropped} duplicates (within_ms={within_ms})"
+                ));
+            }
+            Ok(Stream::new(out))
+        }
+        #[cfg(feature = "stats")]
+        Stage::StatsOutliers {
+            field,
+            method,
+            drop,
+        } => {
+            outputs
+                .explain
+                .push(format!("  [pure] stats.outliers({})", method.name()));
+            let items: Vec<Value> = stream.into_iter().collect();
+            let values = items
+                .iter()
+                .map(|item| match eval_value_expr(field, Some(item))? {
+                    Value::I64(v) => Ok(v),
+                    other => Err(Error::mismatch(
+                        "I64",
+                        "stats.outliers field must evaluate to I64",
+                    )
+                    .got(other.kind())),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let Some((low, high)) = method.fences(&values) else {
+                return Ok(Stream::new(items));
+            };
+            if state.trace_items().is_some() {
+                outputs.explain.push(format!("    fences: {low} .. {high}"));
+            }
+            let mut out = Vec::new();
+            for (item, value) in items.into_iter().zip(values) {
+                let is_outlier = (value as f64) < low || (value as f64) > high;
+                match (drop, item) {
+                    (true, _) if is_outlier => {}
+                    (true, item) => out.push(item),
+                    (false, Value::Record(mut record)) => {
+                        record.insert(state.interner.intern("is_outlier"), Value::Bool(is_outlier));
+                        out.push(Value::Record(record));
+                    }
+                    (false, _) => {
+                        return Err(Error::mismatch(
+                            "records",
+                            "stats.outliers items must be records (or use mode=\"drop\")",
+                        ))
+                    }
+                }
+            }
+            Ok(Stream::new(out))
+        }
+        Stage::Throttle {
+            by_key,
+            limit,
+            per_ms,
+            ts,
+            mark,
+        } => {
+            outputs
+                .explain
+                .push(format!("  [pure] throttle(limit={limit}, per_ms={per_ms})"));
 
-            let top_k = *k as usize;
-            let out = rows
+            // Token buckets in units of 1/per_ms token, so refill (`limit` units per
+            // ms) stays integral.
+            let capacity = limit.saturating_mul(*per_ms);
+            let mut buckets: OrderedGroups<(i64, i64, usize)> = OrderedGroups::new();
+            let mut out = Vec::new();
+            for (idx, item) in stream.into_iter().enumerate() {
+                state.check_cancelled()?;
+                let key = eval_value_expr(by_key, Some(&item))?;
+                expect_group_key(&key, "throttle by_key must evaluate to I64 or String")?;
+                let at = match ts {
+                    Some(ts) => expect_timestamp(ts, &item, "throttle")?,
+                    None => idx as i64,
+                };
+                let (tokens, last, dropped) = buckets.entry(key, || (capacity, at, 0));
+                let refill = at.saturating_sub(*last).max(0).saturating_mul(*limit);
+                *tokens = tokens.saturating_add(refill).min(capacity);
+                *last = (*last).max(at);
+                let allowed = *tokens >= *per_ms;
+                if allowed {
+                    *tokens -= per_ms;
+                } else {
+                    *dropped += 1;
+                }
+                if *mark {
+                    out.push(Value::Record(BTreeMap::from([
+                        (state.interner.intern("item"), item),
+                        (state.interner.intern("allowed"), Value::Bool(allowed)),
+                    ])));
+                } else if allowed {
+                    out.push(item);
+                }
+            }
+            let drops: Vec<String> = buckets
+                .into_groups()
                 .into_iter()
-                .take(top_k)
-                .map(|(_, _, item)| item)
+                .filter(|(_, (_, _, dropped))| *dropped > 0)
+                .map(|(key, (_, _, dropped))| match key {
+                    Value::String(key) => format!("{key}={dropped}"),
+                    Value::I64(key) => format!("{key}={dropped}"),
+                    _ => unreachable!("checked by expect_group_key"),
+                })
                 .collect();
+            if !drops.is_empty() {
+                let verb = if *mark { "over limit" } else { "dropped" };
+                outputs
+                    .explain
+                    .push(format!("    {verb}: {}", drops.join(", ")));
+            }
             Ok(Stream::new(out))
         }
-        Stage::RankKMergeArrays { by, order, limit } => {
-            if *limit < 0 {
-                return Err("rank.kmerge_arrays limit must be >= 0".to_string());
-            }
+        Stage::RankTopK { k, by } => {
+            outputs.explain.push("  [pure] rank.topk".to_string());
+
+            let rows = sort_items(
+                state,
+                stream.values,
+                by,
+                "rank.topk by expression must evaluate to I64, String, or Null",
+            )?;
+            let top_k = *k as usize;
+            Ok(Stream::new(rows.into_iter().take(top_k).collect()))
+        }
+        Stage::Sort(by) => {
+            outputs.explain.push("  [pure] sort".to_string());
+            Ok(Stream::new(sort_items(
+                state,
+                stream.values,
+                by,
+                "sort by expression must evaluate to I64, String, or Null",
+            )?))
+        }
+        Stage::RankKMergeArrays { by, limit } => {
             outputs
                 .explain
                 .push("  [pure] rank.kmerge_arrays".to_string());
 
             let mut out = Vec::new();
             for item in stream {
+                state.check_cancelled()?;
                 let lists = match item {
                     Value::Array(values) => values,
                     _ => {
-                        return Err("rank.kmerge_arrays input value must be Array[Array[Value]]"
-                            .to_string())
+                        return Err(Error::mismatch(
+                            "Array[Array[Value]]",
+                            "rank.kmerge_arrays input value must be Array[Array[Value]]",
+                        ))
                     }
                 };
 
@@ -444,10 +1919,10 @@ fn apply_stage(
                     match value {
                         Value::Array(values) => list_values.push(values),
                         _ => {
-                            return Err(
-                                "rank.kmerge_arrays input value must be Array[Array[Value]]"
-                                    .to_string(),
-                            )
+                            return Err(Error::mismatch(
+                                "Array[Array[Value]]",
+                                "rank.kmerge_arrays input value must be Array[Array[Value]]",
+                            ))
                         }
                     }
                 }
@@ -456,25 +1931,23 @@ fn apply_stage(
                 let mut emitted = 0usize;
                 let max_items = *limit as usize;
                 while emitted < max_items {
-                    let mut best: Option<(usize, usize, SortKey)> = None;
+                    let mut best: Option<(usize, usize, Vec<SortKey>)> = None;
                     for (list_idx, list) in list_values.iter().enumerate() {
                         let elem_idx = idxs[list_idx];
                         if elem_idx >= list.len() {
                             continue;
                         }
-                        let candidate = list[elem_idx].clone();
-                        let key = expect_sort_key(
-                            eval_value_expr(by, Some(&candidate))?,
-                            "rank.kmerge_arrays by expression must evaluate to I64 or String",
+                        let key = by.key(
+                            &list[elem_idx],
+                            "rank.kmerge_arrays by expression must evaluate to I64, String, or Null",
                         )?;
 
                         let should_take = match &best {
                             None => true,
-                            Some((best_list_idx, _, best_key)) => {
-                                compare_keys(&key, best_key, *order)
-                                    .then_with(|| list_idx.cmp(best_list_idx))
-                                    .is_lt()
-                            }
+                            Some((best_list_idx, _, best_key)) => by
+                                .compare(&key, best_key)
+                                .then_with(|| list_idx.cmp(best_list_idx))
+                                .is_lt(),
                         };
 
                         if should_take {
@@ -497,91 +1970,90 @@ fn apply_stage(
             by_key,
             n,
             order_by,
-            order,
         } => {
-            if *n < 0 {
-                return Err("group.topn_items n must be >= 0".to_string());
-            }
             outputs
                 .explain
                 .push("  [pure] group.topn_items".to_string());
 
-            let mut groups: Vec<GroupTopNBucket> = Vec::new();
+            let mut groups = OrderedGroups::new();
             for (idx, item) in stream.into_iter().enumerate() {
+                state.check_cancelled()?;
                 let key = eval_value_expr(by_key, Some(&item))?;
                 expect_group_key(
                     &key,
                     "group.topn_items by_key must evaluate to I64 or String",
                 )?;
-                let order_key = expect_sort_key(
-                    eval_value_expr(order_by, Some(&item))?,
-                    "group.topn_items order_by must evaluate to I64 or String",
+                let order_key = order_by.key(
+                    &item,
+                    "group.topn_items order_by must evaluate to I64, String, or Null",
                 )?;
 
-                if let Some(bucket) = groups.iter_mut().find(|bucket| bucket.key == key) {
-                    bucket.items.push(GroupTopNItem {
-                        source_index: idx,
-                        order_key,
-                        value: item,
-                    });
-                } else {
-                    groups.push(GroupTopNBucket {
-                        key,
-                        items: vec![GroupTopNItem {
-                            source_index: idx,
-                            order_key,
-                            value: item,
-                        }],
-                    });
-                }
+                groups.entry(key, Vec::new).push(GroupTopNItem {
+                    source_index: idx,
+                    order_key,
+                    value: item,
+                });
             }
 
             let max_items = *n as usize;
             let out = groups
+                .into_groups()
                 .into_iter()
-                .map(|mut bucket| {
-                    bucket.items.sort_by(|a, b| {
-                        compare_keys(&a.order_key, &b.order_key, *order)
+                .map(|(key, mut items)| {
+                    items.sort_by(|a, b| {
+                        order_by
+                            .compare(&a.order_key, &b.order_key)
                             .then_with(|| a.source_index.cmp(&b.source_index))
                     });
-                    if bucket.items.len() > max_items {
-                        bucket.items.truncate(max_items);
+                    if items.len() > max_items {
+                        items.truncate(max_items);
                     }
                     Value::Record(BTreeMap::from([
-                        ("key".to_string(), bucket.key),
+                        (state.interner.intern("key"), key),
                         (
-                            "items".to_string(),
-                            Value::Array(
-                                bucket.items.into_iter().map(|entry| entry.value).collect(),
-                            ),
+                            state.interner.intern("items"),
+                            Value::Array(items.into_iter().map(|entry| entry.value).collect()),
                         ),
                     ]))
                 })
                 .collect();
             Ok(Stream::new(out))
         }
-        Stage::KvLoad { store } => {
+        #[cfg(feature = "kv")]
+        Stage::KvLoad {
+            store,
+            key_field,
+            value_field,
+        } => {
             outputs.explain.push(format!("  [sink] kv.load({store})"));
             let kv = state.kv_stores.entry(store.clone()).or_default();
             for item in stream {
+                check_cancelled(state.cancel.as_ref(), &mut state.yields)?;
                 let record = expect_record(item, "kv.load input must be Record")?;
                 let key = expect_string_value(
-                    record.get("key").cloned().unwrap_or(Value::Null),
-                    "kv.load input.key must be String",
+                    record
+                        .get(key_field.as_str())
+                        .cloned()
+                        .unwrap_or(Value::Null),
+                    &format!("kv.load input.{key_field} must be String"),
                 )?;
-                let value = record
-                    .get("value")
-                    .cloned()
-                    .ok_or_else(|| "kv.load input must contain field 'value'".to_string())?;
+                let value = record.get(value_field.as_str()).cloned().ok_or_else(|| {
+                    Error::mismatch(
+                        format!("a record with field '{value_field}'"),
+                        format!("kv.load input must contain field '{value_field}'"),
+                    )
+                })?;
                 kv.insert(key, value);
             }
             Ok(Stream::new(vec![Value::Unit]))
         }
+        #[cfg(feature = "kv")]
         Stage::LookupKv { store, key } => {
             outputs.explain.push(format!("  [pure] lookup.kv({store})"));
             let kv = state.kv_stores.get(store);
             let mut out = Vec::new();
             for item in stream {
+                check_cancelled(state.cancel.as_ref(), &mut state.yields)?;
                 let lookup_key = expect_string_value(
                     eval_value_expr(key, Some(&item))?,
                     "lookup.kv key must evaluate to String",
@@ -590,28 +2062,34 @@ fn apply_stage(
                     .and_then(|s| s.get(&lookup_key).cloned())
                     .unwrap_or(Value::Null);
                 out.push(Value::Record(BTreeMap::from([
-                    ("left".to_string(), item),
-                    ("right".to_string(), right),
+                    (state.interner.intern("left"), item),
+                    (state.interner.intern("right"), right),
                 ])));
             }
             Ok(Stream::new(out))
         }
+        #[cfg(feature = "kv")]
         Stage::LookupBatchKv {
             store,
             key,
             batch_size,
             within_ms,
         } => {
-            if *batch_size < 0 || *within_ms < 0 {
-                return Err("lookup.batch_kv batch_size/within_ms must be >= 0".to_string());
-            }
             outputs
                 .explain
                 .push(format!("  [pure] lookup.batch_kv({store})"));
+            if state.trace_items().is_some() {
+                let size = (*batch_size).max(1) as usize;
+                outputs.explain.push(format!(
+                    "    batches: {} of up to {size} keys (simulated within_ms={within_ms})",
+                    stream.values.len().div_ceil(size)
+                ));
+            }
             let kv = state.kv_stores.get(store);
             let items: Vec<Value> = stream.into_iter().collect();
             let mut out = Vec::new();
             for item in items {
+                check_cancelled(state.cancel.as_ref(), &mut state.yields)?;
                 let lookup_key = expect_string_value(
                     eval_value_expr(key, Some(&item))?,
                     "lookup.batch_kv key must evaluate to String",
@@ -620,78 +2098,438 @@ fn apply_stage(
                     .and_then(|s| s.get(&lookup_key).cloned())
                     .unwrap_or(Value::Null);
                 out.push(Value::Record(BTreeMap::from([
-                    ("left".to_string(), item),
-                    ("right".to_string(), right),
+                    (state.interner.intern("left"), item),
+                    (state.interner.intern("right"), right),
                 ])));
             }
             Ok(Stream::new(out))
         }
+        #[cfg(feature = "rbac")]
+        Stage::RbacLoad {
+            name,
+            fixtures: rows,
+        } => {
+            outputs.explain.push(format!("  [sink] rbac.load({name})"));
+            let policy = rows.compile(fixtures)?;
+            warn_ancestor_cycles(state, "rbac.load", &policy);
+            state.rbac_policies.insert(name.clone(), policy);
+            Ok(Stream::new(vec![Value::Unit]))
+        }
+        #[cfg(feature = "rbac")]
         Stage::RbacEvaluate {
-            principal_bindings,
-            role_perms,
-            resource_ancestors,
+            policy,
+            max_depth,
+            effective_at,
         } => {
-            outputs.explain.push("  [pure] rbac.evaluate".to_string());
-            let bindings = fixtures
-                .get(principal_bindings)
-                .ok_or_else(|| format!("missing fixture: {principal_bindings}"))?;
-            let perms = fixtures
-                .get(role_perms)
-                .ok_or_else(|| format!("missing fixture: {role_perms}"))?;
-            let ancestors = fixtures
-                .get(resource_ancestors)
-                .ok_or_else(|| format!("missing fixture: {resource_ancestors}"))?;
-            eval_rbac(stream, bindings, perms, ancestors)
-        }
-        Stage::Json(direction) => {
-            outputs.explain.push("  [reversible] json".to_string());
-            apply_reversible(
-                stream,
-                *direction,
-                json_forward,
-                json_inverse,
-                accepts_json_forward,
-                accepts_json_inverse,
-            )
-        }
-        Stage::Utf8(direction) => {
-            outputs.explain.push("  [reversible] utf8".to_string());
-            apply_reversible(
-                stream,
+            let compiled;
+            let policy = match policy {
+                rbac::PolicyRef::Fixtures(rows) => {
+                    outputs.explain.push("  [pure] rbac.evaluate".to_string());
+                    compiled = rows.compile(fixtures)?;
+                    warn_ancestor_cycles(state, "rbac.evaluate", &compiled);
+                    &compiled
+                }
+                rbac::PolicyRef::Loaded(name) => {
+                    outputs
+                        .explain
+                        .push(format!("  [pure] rbac.evaluate({name})"));
+                    loaded_policy(&state.rbac_policies, "rbac.evaluate", name)?
+                }
+            };
+            let mut cache = rbac::Cache::default();
+            let mut out = Vec::new();
+            for request in stream {
+                check_cancelled(state.cancel.as_ref(), &mut state.yields)?;
+                let at = effective_time(effective_at.as_ref(), &request, "rbac.evaluate")?;
+                let decision =
+                    policy.evaluate(value_to_json(request), at, *max_depth as usize, &mut cache)?;
+                out.push(json_to_value(decision, &mut state.interner));
+            }
+            if state.trace_items().is_some() {
+                outputs
+                    .explain
+                    .push(format!("    cache: {}", cache.hit_rates()));
+            }
+            Ok(Stream::new(out))
+        }
+        #[cfg(feature = "rbac")]
+        Stage::RbacDiff {
+            before,
+            after,
+            requests,
+            max_depth,
+            effective_at,
+        } => {
+            outputs
+                .explain
+                .push(format!("  [pure] rbac.diff({before} -> {after})"));
+            let requests: Vec<Value> = match requests {
+                Some(name) => fixtures
+                    .get(name)
+                    .ok_or_else(|| Error::missing_fixture(name))?
+                    .iter()
+                    .map(|row| json_to_value(row.clone(), &mut state.interner))
+                    .collect(),
+                None => stream.into_iter().collect(),
+            };
+            let policies = (
+                loaded_policy(&state.rbac_policies, "rbac.diff", before)?,
+                loaded_policy(&state.rbac_policies, "rbac.diff", after)?,
+            );
+            let mut caches = Default::default();
+            let mut changed = Vec::new();
+            for request in requests {
+                check_cancelled(state.cancel.as_ref(), &mut state.yields)?;
+                let at = effective_time(effective_at.as_ref(), &request, "rbac.diff")?;
+                let request = value_to_json(request);
+                if let Some(row) =
+                    rbac::changed_decision(policies, request, at, *max_depth as usize, &mut caches)?
+                {
+                    changed.push(row);
+                }
+            }
+            let out = changed
+                .into_iter()
+                .map(|row| json_to_value(row, &mut state.interner))
+                .collect();
+            Ok(Stream::new(out))
+        }
+        #[cfg(feature = "graph")]
+        Stage::GraphReachable {
+            edges,
+            from,
+            direction,
+        } => {
+            outputs
+                .explain
+                .push(format!("  [pure] graph.reachable({edges})"));
+            let rows = fixtures
+                .get(edges)
+                .ok_or_else(|| Error::missing_fixture(edges))?;
+            let graph = graph::Graph::from_rows(rows, *direction, "graph.reachable")?;
+            let mut out = Vec::new();
+            for item in stream {
+                state.check_cancelled()?;
+                let start = expect_string_value(
+                    eval_value_expr(from, Some(&item))?,
+                    "graph.reachable from must evaluate to String",
+                )?;
+                // No walk reaches usize::MAX levels, so this never fails.
+                let reachable = graph.reachable(&start, usize::MAX).unwrap_or_default();
+                let reachable = reachable.into_iter().skip(1).map(Value::String).collect();
+                out.push(Value::Record(BTreeMap::from([
+                    (state.interner.intern("item"), item),
+                    (state.interner.intern("reachable"), Value::Array(reachable)),
+                ])));
+            }
+            Ok(Stream::new(out))
+        }
+        #[cfg(feature = "graph")]
+        Stage::GraphShortestPath {
+            edges,
+            from,
+            to,
+            direction,
+        } => {
+            outputs
+                .explain
+                .push(format!("  [pure] graph.shortest_path({edges})"));
+            let rows = fixtures
+                .get(edges)
+                .ok_or_else(|| Error::missing_fixture(edges))?;
+            let graph = graph::Graph::from_rows(rows, *direction, "graph.shortest_path")?;
+            let mut out = Vec::new();
+            for item in stream {
+                state.check_cancelled()?;
+                let start = expect_string_value(
+                    eval_value_expr(from, Some(&item))?,
+                    "graph.shortest_path from must evaluate to String",
+                )?;
+                let end = expect_string_value(
+                    eval_value_expr(to, Some(&item))?,
+                    "graph.shortest_path to must evaluate to String",
+                )?;
+                let path = match graph.shortest_path(&start, &end) {
+                    Some(path) => Value::Array(path.into_iter().map(Value::String).collect()),
+                    None => Value::Null,
+                };
+                out.push(Value::Record(BTreeMap::from([
+                    (state.interner.intern("item"), item),
+                    (state.interner.intern("path"), path),
+                ])));
+            }
+            Ok(Stream::new(out))
+        }
+        #[cfg(feature = "graph")]
+        Stage::GraphComponents { edges } => {
+            outputs
+                .explain
+                .push(format!("  [pure] graph.components({edges})"));
+            let rows = fixtures
+                .get(edges)
+                .ok_or_else(|| Error::missing_fixture(edges))?;
+            let graph = graph::Graph::from_rows(rows, graph::Direction::Down, "graph.components")?;
+            let out = graph
+                .components()
+                .into_iter()
+                .map(|(node, component)| {
+                    Value::Record(BTreeMap::from([
+                        (state.interner.intern("node"), Value::String(node)),
+                        (
+                            state.interner.intern("component"),
+                            Value::I64(component as i64),
+                        ),
+                    ]))
+                })
+                .collect();
+            Ok(Stream::new(out))
+        }
+        Stage::FsmEvaluate {
+            transitions,
+            initial,
+            event,
+            by_key,
+        } => {
+            outputs.explain.push("  [pure] fsm.evaluate".to_string());
+            let rows = fixtures
+                .get(transitions)
+                .ok_or_else(|| Error::missing_fixture(transitions))?;
+            let machine = fsm::Machine::from_rows(rows)?;
+            let label = if by_key.is_some() { "key" } else { "item" };
+            let mut runs = Vec::new();
+            match by_key {
+                Some(by_key) => {
+                    let mut groups = OrderedGroups::new();
+                    for item in stream {
+                        state.check_cancelled()?;
+                        let key = eval_value_expr(by_key, Some(&item))?;
+                        expect_group_key(
+                            &key,
+                            "fsm.evaluate by_key must evaluate to I64 or String",
+                        )?;
+                        let events = fsm_events(eval_value_expr(event, Some(&item))?)?;
+                        groups.entry(key, Vec::new).extend(events);
+                    }
+                    for (key, events) in groups.into_groups() {
+                        state.check_cancelled()?;
+                        runs.push((key, machine.run(initial, events.iter().map(String::as_str))));
+                    }
+                }
+                None => {
+                    for item in stream {
+                        state.check_cancelled()?;
+                        let events = fsm_events(eval_value_expr(event, Some(&item))?)?;
+                        let run = machine.run(initial, events.iter().map(String::as_str));
+                        runs.push((item, run));
+                    }
+                }
+            }
+            let out = runs
+                .into_iter()
+                .map(|(subject, run)| {
+                    let path = run.path.into_iter().map(Value::String).collect();
+                    Value::Record(BTreeMap::from([
+                        (state.interner.intern(label), subject),
+                        (state.interner.intern("state"), Value::String(run.state)),
+                        (state.interner.intern("path"), Value::Array(path)),
+                        (state.interner.intern("accepted"), Value::Bool(run.accepted)),
+                    ]))
+                })
+                .collect();
+            Ok(Stream::new(out))
+        }
+        Stage::Codec(codec, direction, expect) => {
+            outputs.explain.push(match expect {
+                Some(ty) => format!("  [reversible] {} expect={ty}", codec.name()),
+                None => format!("  [reversible] {}", codec.name()),
+            });
+            let items_in = stream.values.len();
+            let (out, decoded) = codecs::apply(
+                *codec,
                 *direction,
-                utf8_forward,
-                utf8_inverse,
-                accepts_utf8_forward,
-                accepts_utf8_inverse,
-            )
-        }
-        Stage::Base64(direction) => {
-            outputs.explain.push("  [reversible] base64".to_string());
-            apply_reversible(
+                expect.as_ref(),
                 stream,
-                *direction,
-                base64_forward,
-                base64_inverse,
-                accepts_base64_forward,
-                accepts_base64_inverse,
-            )
+                &mut state.interner,
+            )?;
+            if matches!(direction, Direction::Auto) && decoded > 0 && decoded < items_in {
+                let name = codec.name();
+                warn(
+                    state,
+                    WarningKind::MixedDirection,
+                    format!(
+                        "{name} encoded {} items and decoded {decoded} by auto direction; write {name}.encode or {name}.decode to pick one",
+                        items_in - decoded
+                    ),
+                );
+            }
+            Ok(out)
         }
-        Stage::UiTable(name) => {
+        Stage::UiTable(spec) => {
+            let name = &spec.name;
             outputs.explain.push(format!("  [sink] ui.table({name})"));
+            if let Some(columns) = &spec.columns {
+                outputs.column_order.insert(name.clone(), columns.clone());
+            }
+            let table = outputs.tables.entry(name.clone()).or_default();
+            for item in stream {
+                state.check_cancelled()?;
+                let row = spec.shape(value_to_json(item))?;
+                if let Some(sink) = state.sink.as_mut() {
+                    if sink.table_row(name, &row)? {
+                        continue;
+                    }
+                }
+                table.push(row);
+            }
+            if table.is_empty() && state.sink.is_some() {
+                outputs.tables.remove(name);
+            }
+            Ok(Stream::new(vec![Value::Unit]))
+        }
+        Stage::UiLog(name, level) => {
+            outputs.explain.push(format!("  [sink] ui.log({name})"));
+            let log = outputs.logs.entry(name.clone()).or_default();
+            for item in stream {
+                state.check_cancelled()?;
+                let item = value_to_json(item);
+                let record = LogRecord {
+                    level: *level,
+                    message: serde_json::to_string(&item).map_err(|e| e.to_string())?,
+                    item,
+                };
+                if let Some(sink) = state.sink.as_mut() {
+                    if sink.log_record(name, &record)? {
+                        continue;
+                    }
+                }
+                log.push(record);
+            }
+            Ok(Stream::new(vec![Value::Unit]))
+        }
+        Stage::UiGraph { name, node, edges } => {
+            outputs.explain.push(format!("  [sink] ui.graph({name})"));
+            let graph = outputs.graphs.entry(name.clone()).or_default();
+            for item in stream {
+                state.check_cancelled()?;
+                let id = value_to_json(eval_value_expr(node, Some(&item))?);
+                let targets = match value_to_json(eval_value_expr(edges, Some(&item))?) {
+                    JsonValue::Array(targets) => targets,
+                    JsonValue::Null => Vec::new(),
+                    target => vec![target],
+                };
+                graph.add(id, value_to_json(item), targets)?;
+            }
+            graph.close();
+            Ok(Stream::new(vec![Value::Unit]))
+        }
+        Stage::UiDiff {
+            name,
+            expected,
+            key,
+        } => {
+            outputs.explain.push(format!("  [sink] ui.diff({name})"));
+            let expected_rows = fixtures
+                .get(expected)
+                .ok_or_else(|| Error::missing_fixture(expected))?;
+            let keyed = |idx: usize, row: Value| -> Result<(JsonValue, JsonValue), Error> {
+                let row_key = match key {
+                    Some(key) => value_to_json(eval_value_expr(key, Some(&row))?),
+                    None => JsonValue::Number((idx as i64).into()),
+                };
+                Ok((row_key, value_to_json(row)))
+            };
+            let expected_rows = expected_rows
+                .iter()
+                .enumerate()
+                .map(|(idx, row)| keyed(idx, json_to_value(row.clone(), &mut state.interner)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let actual_rows = stream
+                .into_iter()
+                .enumerate()
+                .map(|(idx, row)| keyed(idx, row))
+                .collect::<Result<Vec<_>, _>>()?;
             let table = outputs.tables.entry(name.clone()).or_default();
+            for row in diff::diff_rows(expected_rows, actual_rows)? {
+                state.check_cancelled()?;
+                if let Some(sink) = state.sink.as_mut() {
+                    if sink.table_row(name, &row)? {
+                        continue;
+                    }
+                }
+                table.push(row);
+            }
+            if table.is_empty() && state.sink.is_some() {
+                outputs.tables.remove(name);
+            }
+            Ok(Stream::new(vec![Value::Unit]))
+        }
+        Stage::SinkCustom(name) => {
+            outputs
+                .explain
+                .push(format!("  [sink] sink.custom({name})"));
             for item in stream {
-                table.push(value_to_json(item));
+                let taken = match state.sink.as_mut() {
+                    Some(sink) => sink.custom(name, &item)?,
+                    None => false,
+                };
+                if !taken {
+                    return Err(
+                        format!("sink.custom({name}): no embedder sink accepted the item").into(),
+                    );
+                }
+            }
+            Ok(Stream::new(vec![Value::Unit]))
+        }
+        Stage::Iterate {
+            step,
+            until,
+            max_iters,
+        } => {
+            outputs
+                .explain
+                .push(format!("  [pure] iterate(max_iters={max_iters})"));
+            let mut step_explain = None;
+            let mut seen: std::collections::HashSet<Value> =
+                stream.values.iter().cloned().collect();
+            let mut frontier = stream.values.clone();
+            let mut out = stream.values;
+            let mut iterations = 0;
+            while !frontier.is_empty() {
+                if iterations == *max_iters {
+                    return Err(Error::templated(
+                        LIMIT_EXCEEDED,
+                        Message::new("no_fixed_point").arg("max_iters", max_iters),
+                    ));
+                }
+                state.check_cancelled()?;
+                let produced = apply_stage(step, Stream::new(frontier), fixtures, state, outputs)?;
+                // The step explains itself once, not once per round.
+                match step_explain {
+                    Some(len) => outputs.explain.truncate(len),
+                    None => step_explain = Some(outputs.explain.len()),
+                }
+                iterations += 1;
+                frontier = produced
+                    .into_iter()
+                    .filter(|item| seen.insert(item.clone()))
+                    .collect();
+                out.extend(frontier.iter().cloned());
+                if let Some(until) = until {
+                    let mut done = false;
+                    for item in &frontier {
+                        done = done || truthy(&eval_value_expr(until, Some(item))?)?;
+                    }
+                    if done {
+                        break;
+                    }
+                }
             }
-            Ok(Stream::new(vec![Value::Unit]))
-        }
-        Stage::UiLog(name) => {
-            outputs.explain.push(format!("  [sink] ui.log({name})"));
-            let log = outputs.logs.entry(name.clone()).or_default();
-            for item in stream {
-                let json = value_to_json(item);
-                log.push(serde_json::to_string(&json).map_err(|e| e.to_string())?);
+            if state.trace_items().is_some() {
+                outputs
+                    .explain
+                    .push(format!("    iterations: {iterations}"));
             }
-            Ok(Stream::new(vec![Value::Unit]))
+            Ok(Stream::new(out))
         }
         Stage::Compose(stages) => {
             let mut current = stream;
@@ -700,142 +2538,463 @@ fn apply_stage(
             }
             Ok(current)
         }
+        Stage::HostCall(call) => {
+            outputs
+                .explain
+                .push(format!("  [host] host.call({})", call.name));
+            let mut out = Vec::new();
+            for item in stream {
+                state.check_cancelled()?;
+                let arg = eval_value_expr(&call.arg, Some(&item))?;
+                out.push(
+                    (call.func)(arg)
+                        .map_err(|e| Error::from(format!("host.call({}): {e}", call.name)))?,
+                );
+            }
+            Ok(Stream::new(out))
+        }
+        Stage::Custom(custom) => {
+            outputs.explain.push(format!("  [custom] {}", custom.name));
+            custom
+                .imp
+                .apply(&custom.args, stream.values)
+                .map(Stream::new)
+                .map_err(|e| format!("{}: {e}", custom.name).into())
+        }
     }
 }
 
-fn eval_rbac(
-    stream: Stream,
-    principal_bindings: &[JsonValue],
-    role_perms: &[JsonValue],
-    resource_ancestors: &[JsonValue],
-) -> Result<Stream, String> {
-    let mut roles_by_principal: BTreeMap<String, Vec<String>> = BTreeMap::new();
-    for row in principal_bindings {
-        let principal = expect_json_string_field(row, "principal")?;
-        let role = expect_json_string_field(row, "role")?;
-        roles_by_principal.entry(principal).or_default().push(role);
-    }
-
-    let mut perms_by_role_action: BTreeMap<(String, String), Vec<JsonValue>> = BTreeMap::new();
-    for row in role_perms {
-        let role = expect_json_string_field(row, "role")?;
-        let action = expect_json_string_field(row, "action")?;
-        perms_by_role_action
-            .entry((role, action))
-            .or_default()
-            .push(row.clone());
-    }
-
-    let mut ancestor_map: BTreeMap<String, Vec<String>> = BTreeMap::new();
-    for row in resource_ancestors {
-        let resource = expect_json_string_field(row, "resource")?;
-        let ancestor = expect_json_string_field(row, "ancestor")?;
-        ancestor_map.entry(resource).or_default().push(ancestor);
-    }
-
-    let mut out = Vec::new();
-    for request in stream {
-        let request_json = value_to_json(request.clone());
-        let principal = expect_json_string_field(&request_json, "principal")?;
-        let action = expect_json_string_field(&request_json, "action")?;
-        let resource = expect_json_string_field(&request_json, "resource")?;
-
-        let roles = roles_by_principal
-            .get(&principal)
-            .cloned()
-            .unwrap_or_default();
-        let reachable_resources = collect_resource_ancestors(&resource, &ancestor_map);
-
-        let mut matches = Vec::new();
-        for role in &roles {
-            if let Some(candidates) = perms_by_role_action.get(&(role.clone(), action.clone())) {
-                for perm in candidates {
-                    let perm_resource = expect_json_string_field(perm, "resource")?;
-                    if reachable_resources.iter().any(|r| r == &perm_resource) {
-                        matches.push(perm.clone());
-                    }
-                }
-            }
-        }
+#[cfg(feature = "rbac")]
+fn policy_fixtures(args: &Args) -> Result<rbac::PolicyFixtures, Error> {
+    Ok(rbac::PolicyFixtures {
+        principal_bindings: expect_string(args.get("principal_bindings")?)?,
+        role_perms: expect_string(args.get("role_perms")?)?,
+        resource_ancestors: expect_string(args.get("resource_ancestors")?)?,
+    })
+}
 
-        out.push(json_to_value(JsonValue::Object(Map::from_iter([
-            ("request".to_string(), request_json),
-            (
-                "decision".to_string(),
-                JsonValue::String(if matches.is_empty() {
-                    "deny".to_string()
-                } else {
-                    "allow".to_string()
-                }),
-            ),
-            ("matches".to_string(), JsonValue::Array(matches)),
-        ]))));
+#[cfg(feature = "rbac")]
+fn loaded_policy<'s>(
+    policies: &'s HashMap<String, rbac::Policy>,
+    stage: &str,
+    name: &str,
+) -> Result<&'s rbac::Policy, Error> {
+    policies.get(name).ok_or_else(|| {
+        Error::new(
+            errors::UNKNOWN_POLICY,
+            format!("{stage}: no policy named \"{name}\"; load it first with rbac.load(name=\"{name}\", ...)"),
+        )
+    })
+}
+
+/// The `effective_at` of `request`, when the stage has one.
+#[cfg(feature = "rbac")]
+fn effective_time(
+    effective_at: Option<&Expr>,
+    request: &Value,
+    stage: &str,
+) -> Result<Option<rbac::Timestamp>, Error> {
+    let Some(expr) = effective_at else {
+        return Ok(None);
+    };
+    match eval_value_expr(expr, Some(request))? {
+        Value::I64(ms) => Ok(Some(rbac::Timestamp::Millis(ms))),
+        Value::String(text) => Ok(Some(rbac::Timestamp::Text(text))),
+        other => Err(Error::mismatch(
+            "I64 or String",
+            format!("{stage} effective_at must evaluate to I64 or String"),
+        )
+        .got(other.kind())),
+    }
+}
+
+#[cfg(feature = "rbac")]
+fn warn_ancestor_cycles(state: &mut RuntimeState, stage: &str, policy: &rbac::Policy) {
+    for cycle in policy.ancestor_cycles() {
+        let message = format!(
+            "{stage}: resource ancestors form a cycle: {}",
+            cycle.join(" -> ")
+        );
+        warn(state, WarningKind::AncestorCycle, message);
     }
+}
 
-    Ok(Stream::new(out))
+/// An `fsm.evaluate` event: one string, or an array of them run in order.
+fn fsm_events(value: Value) -> Result<Vec<String>, Error> {
+    let err = "fsm.evaluate event must evaluate to a String or an Array of Strings";
+    match value {
+        Value::Array(events) => events
+            .into_iter()
+            .map(|event| expect_string_value(event, err))
+            .collect(),
+        event => Ok(vec![expect_string_value(event, err)?]),
+    }
 }
 
-fn collect_resource_ancestors(
-    resource: &str,
-    ancestor_map: &BTreeMap<String, Vec<String>>,
-) -> Vec<String> {
-    let mut out = vec![resource.to_string()];
-    let mut idx = 0usize;
-    while idx < out.len() {
-        if let Some(ancestors) = ancestor_map.get(&out[idx]) {
-            for ancestor in ancestors {
-                if !out.iter().any(|existing| existing == ancestor) {
-                    out.push(ancestor.clone());
-                }
-            }
+/// The stage `iterate`'s `step` argument names (a stage call, binding, codec, or
+/// composition), or `item_step`, the `flat_map` of it as an item expression.
+fn iterate_step(
+    args: &[CallArg],
+    item_step: Stage,
+    env: &mut BTreeMap<String, Binding>,
+    fixtures: &BTreeMap<String, Vec<JsonValue>>,
+    state: &mut RuntimeState,
+    outputs: &mut Outputs,
+) -> Result<Stage, Error> {
+    let params = stage_args::params("iterate").unwrap_or_default();
+    let args = Args::bind("iterate", params, args)?;
+    let step = args.get("step")?;
+    let is_stage = match step {
+        Expr::Compose { .. } | Expr::Inverse { .. } => true,
+        Expr::Ident { name, .. } => {
+            matches!(env.get(name), Some(Binding::Stage(_))) || Codec::from_name(name).is_some()
         }
-        idx += 1;
+        Expr::Call { .. } | Expr::FieldAccess { .. } => {
+            let callee = match step {
+                Expr::Call { callee, .. } => callee,
+                _ => step,
+            };
+            callee_name(callee).is_some_and(|name| {
+                name == "select"
+                    || name == "host.call"
+                    || stage_args::params(&name).is_some()
+                    || codec_stage(&name, false).is_some()
+                    || state
+                        .runtime
+                        .is_some_and(|runtime| runtime.stage_names().contains(&name))
+            })
+        }
+        _ => false,
+    };
+    if !is_stage {
+        return Ok(item_step);
     }
-    out
+    expect_stage(eval_expr(step, env, fixtures, state, outputs)?)
 }
 
-fn expect_json_string_field(value: &JsonValue, name: &str) -> Result<String, String> {
-    match value {
-        JsonValue::Object(map) => match map.get(name) {
-            Some(JsonValue::String(value)) => Ok(value.clone()),
-            _ => Err(format!("expected string field '{name}'")),
-        },
-        _ => Err("expected object".to_string()),
+/// The first component of stage expression `expr` that `~` can't invert, and its
+/// span when it is written inline rather than inside an earlier binding.
+fn irreversible_part(
+    expr: &Expr,
+    state: &RuntimeState,
+) -> Option<(String, Option<dsl_syntax::Span>)> {
+    match expr {
+        Expr::Compose { left, right, .. } => {
+            irreversible_part(left, state).or_else(|| irreversible_part(right, state))
+        }
+        Expr::Inverse { expr, .. } => irreversible_part(expr, state),
+        Expr::Ident { name, .. } if Codec::from_name(name).is_some() => None,
+        Expr::Call { .. }
+            if callee_name(expr)
+                .is_some_and(|name| name == "lens" || codec_stage(&name, false).is_some()) =>
+        {
+            None
+        }
+        Expr::Ident { name, .. } if state.irreversible.contains_key(name) => {
+            state.irreversible[name].clone().map(|part| (part, None))
+        }
+        Expr::FieldAccess { .. }
+            if callee_name(expr)
+                .as_deref()
+                .and_then(explicit_codec)
+                .is_some() =>
+        {
+            None
+        }
+        _ => Some((format_expr(expr), Some(expr.span()))),
     }
 }
 
-fn apply_reversible(
-    stream: Stream,
-    direction: Direction,
-    forward: fn(Value) -> Result<Value, String>,
-    inverse: fn(Value) -> Result<Value, String>,
-    forward_accepts: fn(&Value) -> bool,
-    inverse_accepts: fn(&Value) -> bool,
-) -> Result<Stream, String> {
-    let mut out = Vec::new();
-    for value in stream {
-        let next = match direction {
-            Direction::Inverse => inverse(value)?,
-            Direction::Auto => {
-                if forward_accepts(&value) {
-                    forward(value)?
-                } else if inverse_accepts(&value) {
-                    inverse(value)?
-                } else {
-                    return Err("no matching direction for stage".to_string());
+/// The built-in stage `name(args)`, or `None` when `name` isn't one. Needs only the
+/// arguments, so the compile-time [`check`] can run it on literal ones.
+pub(crate) fn builtin_stage(
+    name: &str,
+    args: &[CallArg],
+    strict: bool,
+) -> Result<Option<Stage>, Error> {
+    if name == "select" {
+        return select_columns(args).map(|columns| Some(Stage::Select(columns)));
+    }
+    features::require(name)?;
+    let params = match stage_args::params(name) {
+        Some(params) => params,
+        None if matches!(
+            codec_stage(name, strict),
+            Some((Codec::Json | Codec::Ndjson, _))
+        ) =>
+        {
+            &[stage_args::Param::Required("expect")]
+        }
+        None => return Ok(None),
+    };
+    let args = Args::bind(name, params, args)?;
+    let stage = match name {
+        "map" => Stage::Map(args.get("f")?.clone()),
+        "lens" => Stage::Lens {
+            forward: args.get("forward")?.clone(),
+            inverse: args.get("inverse")?.clone(),
+        },
+        "filter" => Stage::Filter(args.get("pred")?.clone()),
+        "flat_map" => Stage::FlatMap(args.get("f")?.clone()),
+        "where" => conjunction(&args, Stage::Filter),
+        "having" => conjunction(&args, Stage::Having),
+        "group.collect_all" => Stage::GroupCollectAll {
+            by_key: args.get("by_key")?.clone(),
+            within_ms: expect_i64_literal(args.get("within_ms")?)?,
+            limit: expect_i64_literal(args.get("limit")?)?,
+            overflow: Overflow::parse(args.get("overflow")?)?,
+            order_items_by: args
+                .opt("order_items_by")
+                .map(|by| SortBy::parse(by, args.get("order")?, args.get("nulls")?))
+                .transpose()?,
+        },
+        "group.count" => Stage::GroupCount {
+            by_key: args.get("by_key")?.clone(),
+        },
+        "sessionize" => Stage::Sessionize {
+            by_key: args.get("by_key")?.clone(),
+            gap_ms: expect_i64_literal(args.get("gap_ms")?)?,
+            ts: args.get("ts")?.clone(),
+        },
+        "dedupe.within" => Stage::DedupeWithin {
+            by: args.get("by")?.clone(),
+            within_ms: expect_i64_literal(args.get("within_ms")?)?,
+            ts: args.get("ts")?.clone(),
+        },
+        #[cfg(feature = "stats")]
+        "stats.outliers" => Stage::StatsOutliers {
+            field: args.get("field")?.clone(),
+            method: stats::OutlierMethod::parse(&expect_string(args.get("method")?)?)?,
+            drop: match expect_string(args.get("mode")?)?.as_str() {
+                "flag" => false,
+                "drop" => true,
+                _ => {
+                    return Err(Error::new(
+                        STAGE_ARGUMENTS,
+                        "stats.outliers mode must be \"flag\" or \"drop\"",
+                    ))
                 }
-            }
-        };
-        out.push(next);
+            },
+        },
+        "throttle" => Stage::Throttle {
+            by_key: args.get("by_key")?.clone(),
+            limit: expect_i64_literal(args.get("limit")?)?,
+            per_ms: expect_i64_literal(args.get("per_ms")?)?,
+            ts: args.opt("ts").cloned(),
+            mark: match expect_string(args.get("mode")?)?.as_str() {
+                "drop" => false,
+                "mark" => true,
+                _ => {
+                    return Err(Error::new(
+                        STAGE_ARGUMENTS,
+                        "throttle mode must be \"drop\" or \"mark\"",
+                    ))
+                }
+            },
+        },
+        "rank.topk" => Stage::RankTopK {
+            k: expect_i64_literal(args.get("k")?)?,
+            by: SortBy::parse(args.get("by")?, args.get("order")?, args.get("nulls")?)?,
+        },
+        "sort" => Stage::Sort(SortBy::parse(
+            args.get("by")?,
+            args.get("order")?,
+            args.get("nulls")?,
+        )?),
+        "rank.kmerge_arrays" => Stage::RankKMergeArrays {
+            by: SortBy::parse(args.get("by")?, args.get("order")?, args.get("nulls")?)?,
+            limit: expect_i64_literal(args.get("limit")?)?,
+        },
+        "group.topn_items" => Stage::GroupTopNItems {
+            by_key: args.get("by_key")?.clone(),
+            n: expect_i64_literal(args.get("n")?)?,
+            order_by: SortBy::parse(
+                args.get("order_by")?,
+                args.get("order")?,
+                args.get("nulls")?,
+            )?,
+        },
+        #[cfg(feature = "kv")]
+        "kv.load" => Stage::KvLoad {
+            store: expect_string(args.get("store")?)?,
+            key_field: expect_string(args.get("key_field")?)?,
+            value_field: expect_string(args.get("value_field")?)?,
+        },
+        #[cfg(feature = "kv")]
+        "lookup.kv" => Stage::LookupKv {
+            store: expect_string(args.get("store")?)?,
+            key: args.get("key")?.clone(),
+        },
+        #[cfg(feature = "kv")]
+        "lookup.batch_kv" => Stage::LookupBatchKv {
+            store: expect_string(args.get("store")?)?,
+            key: args.get("key")?.clone(),
+            batch_size: expect_i64_literal(args.get("batch_size")?)?,
+            within_ms: expect_i64_literal(args.get("within_ms")?)?,
+        },
+        #[cfg(feature = "rbac")]
+        "rbac.load" => Stage::RbacLoad {
+            name: expect_string(args.get("name")?)?,
+            fixtures: policy_fixtures(&args)?,
+        },
+        #[cfg(feature = "rbac")]
+        "rbac.evaluate" => Stage::RbacEvaluate {
+            policy: match args.opt("policy") {
+                Some(_) if args.opt("principal_bindings").is_some() => {
+                    return Err(Error::new(
+                        STAGE_ARGUMENTS,
+                        "rbac.evaluate takes policy or fixture names, not both",
+                    ))
+                }
+                Some(policy) => rbac::PolicyRef::Loaded(expect_string(policy)?),
+                None => rbac::PolicyRef::Fixtures(policy_fixtures(&args)?),
+            },
+            max_depth: expect_i64_literal(args.get("max_depth")?)?,
+            effective_at: args.opt("effective_at").cloned(),
+        },
+        #[cfg(feature = "rbac")]
+        "rbac.diff" => Stage::RbacDiff {
+            before: expect_string(args.get("before")?)?,
+            after: expect_string(args.get("after")?)?,
+            requests: args.opt("requests").map(expect_string).transpose()?,
+            max_depth: expect_i64_literal(args.get("max_depth")?)?,
+            effective_at: args.opt("effective_at").cloned(),
+        },
+        #[cfg(feature = "graph")]
+        "graph.reachable" => Stage::GraphReachable {
+            edges: expect_string(args.get("edges")?)?,
+            from: args.get("from")?.clone(),
+            direction: graph::Direction::parse(&expect_string(args.get("direction")?)?)?,
+        },
+        #[cfg(feature = "graph")]
+        "graph.shortest_path" => Stage::GraphShortestPath {
+            edges: expect_string(args.get("edges")?)?,
+            from: args.get("from")?.clone(),
+            to: args.get("to")?.clone(),
+            direction: graph::Direction::parse(&expect_string(args.get("direction")?)?)?,
+        },
+        #[cfg(feature = "graph")]
+        "graph.components" => Stage::GraphComponents {
+            edges: expect_string(args.get("edges")?)?,
+        },
+        "iterate" => Stage::Iterate {
+            // An item expression; `iterate_step` swaps in the stage when `step` is one.
+            step: Box::new(Stage::FlatMap(args.get("step")?.clone())),
+            until: args.opt("until").cloned(),
+            max_iters: expect_i64_literal(args.get("max_iters")?)?,
+        },
+        "fsm.evaluate" => Stage::FsmEvaluate {
+            transitions: expect_string(args.get("transitions")?)?,
+            initial: expect_string(args.get("initial")?)?,
+            event: args.get("event")?.clone(),
+            by_key: args.opt("by_key").cloned(),
+        },
+        "ui.table" => Stage::UiTable(TableSpec::parse(&args)?),
+        "ui.log" => Stage::UiLog(
+            expect_string(args.get("name")?)?,
+            LogLevel::parse(&expect_string(args.get("level")?)?)?,
+        ),
+        "ui.graph" => Stage::UiGraph {
+            name: expect_string(args.get("name")?)?,
+            node: args.get("node")?.clone(),
+            edges: args.get("edges")?.clone(),
+        },
+        "ui.diff" => Stage::UiDiff {
+            name: expect_string(args.get("name")?)?,
+            expected: expect_string(args.get("expected")?)?,
+            key: args.opt("key").cloned(),
+        },
+        "sink.custom" => Stage::SinkCustom(expect_string(args.get("name")?)?),
+        _ => {
+            let (codec, direction) = codec_stage(name, strict).expect("checked");
+            let expect = expect_string(args.get("expect")?)?;
+            let expect = type_expr::TypeExpr::parse(&expect, strict)
+                .map_err(|e| Error::new(STAGE_ARGUMENTS, format!("{name} expect: {e}")))?;
+            Stage::Codec(codec, direction, Some(expect))
+        }
+    };
+    check_stage_literals(&stage)?;
+    Ok(Some(stage))
+}
+
+/// Range checks on a built-in stage's literal arguments.
+fn check_stage_literals(stage: &Stage) -> Result<(), Error> {
+    let problem = match stage {
+        Stage::GroupCollectAll { within_ms, .. } if *within_ms < 0 => {
+            "group.collect_all within_ms must be >= 0"
+        }
+        Stage::GroupCollectAll { limit, .. } if *limit < 0 => {
+            "group.collect_all limit must be >= 0"
+        }
+        Stage::GroupCollectAll {
+            limit: 0,
+            overflow: Overflow::Spill,
+            ..
+        } => "group.collect_all limit must be > 0 with overflow=\"spill\"",
+        Stage::Sessionize { gap_ms, .. } if *gap_ms < 0 => "sessionize gap_ms must be >= 0",
+        Stage::DedupeWithin { within_ms, .. } if *within_ms < 0 => {
+            "dedupe.within within_ms must be >= 0"
+        }
+        Stage::Throttle { limit, per_ms, .. } if *limit < 0 || *per_ms <= 0 => {
+            "throttle limit must be >= 0 and per_ms > 0"
+        }
+        Stage::RankTopK { k, .. } if *k < 0 => "rank.topk k must be >= 0",
+        Stage::RankKMergeArrays { limit, .. } if *limit < 0 => {
+            "rank.kmerge_arrays limit must be >= 0"
+        }
+        Stage::GroupTopNItems { n, .. } if *n < 0 => "group.topn_items n must be >= 0",
+        Stage::Iterate { max_iters, .. } if *max_iters < 0 => "iterate max_iters must be >= 0",
+        #[cfg(feature = "rbac")]
+        Stage::RbacEvaluate { max_depth, .. } if *max_depth < 0 => {
+            "rbac.evaluate max_depth must be >= 0"
+        }
+        #[cfg(feature = "rbac")]
+        Stage::RbacDiff { max_depth, .. } if *max_depth < 0 => "rbac.diff max_depth must be >= 0",
+        #[cfg(feature = "kv")]
+        Stage::LookupBatchKv {
+            batch_size,
+            within_ms,
+            ..
+        } if *batch_size < 0 || *within_ms < 0 => {
+            "lookup.batch_kv batch_size/within_ms must be >= 0"
+        }
+        _ => return Ok(()),
+    };
+    Err(Error::new(STAGE_ARGUMENTS, problem))
+}
+
+/// A codec stage name: a bare codec (encoding only in `strict` mode) or an
+/// [`explicit_codec`].
+fn codec_stage(name: &str, strict: bool) -> Option<(Codec, Direction)> {
+    match Codec::from_name(name) {
+        Some(codec) if strict => Some((codec, Direction::Forward)),
+        Some(codec) => Some((codec, Direction::Auto)),
+        None => explicit_codec(name),
     }
-    Ok(Stream::new(out))
 }
 
-fn invert_stage(stage: Stage) -> Result<Stage, String> {
+/// `json.encode` / `json.decode` (likewise for the other codecs): a codec with its
+/// direction spelled out.
+pub(crate) fn explicit_codec(name: &str) -> Option<(Codec, Direction)> {
+    let (codec, direction) = name.split_once('.')?;
+    let direction = match direction {
+        "encode" => Direction::Forward,
+        "decode" => Direction::Inverse,
+        _ => return None,
+    };
+    Some((Codec::from_name(codec)?, direction))
+}
+
+fn invert_stage(stage: Stage) -> Result<Stage, Error> {
     Ok(match stage {
-        Stage::Json(_) => Stage::Json(Direction::Inverse),
-        Stage::Utf8(_) => Stage::Utf8(Direction::Inverse),
-        Stage::Base64(_) => Stage::Base64(Direction::Inverse),
+        Stage::Codec(codec, Direction::Inverse, expect) => {
+            Stage::Codec(codec, Direction::Forward, expect)
+        }
+        Stage::Lens { forward, inverse } => Stage::Lens {
+            forward: inverse,
+            inverse: forward,
+        },
+        Stage::Codec(codec, _, expect) => Stage::Codec(codec, Direction::Inverse, expect),
         Stage::Compose(stages) => Stage::Compose(
             stages
                 .into_iter()
@@ -843,335 +3002,501 @@ fn invert_stage(stage: Stage) -> Result<Stage, String> {
                 .map(invert_stage)
                 .collect::<Result<Vec<_>, _>>()?,
         ),
-        _ => return Err("stage is not reversible".to_string()),
+        _ => return Err(Error::new(NOT_REVERSIBLE, "stage is not reversible")),
     })
 }
 
-fn eval_value_expr(expr: &Expr, current: Option<&Value>) -> Result<Value, String> {
-    let mut env = BTreeMap::new();
-    if let Some(v) = current {
-        env.insert("_".to_string(), v.clone());
-    }
-    eval_value_expr_with_env(expr, &env)
+fn eval_value_expr(expr: &Expr, current: Option<&Value>) -> Result<Value, Error> {
+    eval_value_ref(expr, current).map(Cow::into_owned)
+}
+
+/// Evaluates `expr` with `_` bound to `current`. Results borrow from `current`
+/// where possible so field access does not copy whole records.
+fn eval_value_ref<'v>(expr: &Expr, current: Option<&'v Value>) -> Result<Cow<'v, Value>, Error> {
+    eval_in(expr, current, &[])
+}
+
+/// `let` bindings in scope, innermost last.
+type Locals = [(String, Value)];
+
+fn local<'l>(locals: &'l Locals, name: &str) -> Option<&'l Value> {
+    locals
+        .iter()
+        .rev()
+        .find(|(local, _)| local == name)
+        .map(|(_, value)| value)
 }
 
-fn eval_value_expr_with_env(expr: &Expr, env: &BTreeMap<String, Value>) -> Result<Value, String> {
+fn eval_in<'v>(
+    expr: &Expr,
+    current: Option<&'v Value>,
+    locals: &Locals,
+) -> Result<Cow<'v, Value>, Error> {
     match expr {
-        Expr::Placeholder { .. } => env
-            .get("_")
-            .cloned()
-            .ok_or_else(|| "placeholder _ is not bound".to_string()),
-        Expr::Ident { name, .. } => env
-            .get(name)
-            .cloned()
-            .ok_or_else(|| format!("unknown identifier {name}")),
-        Expr::Number { value, .. } => Ok(Value::I64(*value)),
-        Expr::String { value, .. } => Ok(Value::String(value.clone())),
+        Expr::Placeholder { .. } => current
+            .map(Cow::Borrowed)
+            .ok_or_else(|| Error::new(UNKNOWN_IDENT, "placeholder _ is not bound")),
+        Expr::Ident { name, .. } => match current {
+            Some(value) if name == "_" => Ok(Cow::Borrowed(value)),
+            _ => local(locals, name)
+                .map(|value| Cow::Owned(value.clone()))
+                .ok_or_else(|| {
+                    let known = locals.iter().map(|(local, _)| local.as_str());
+                    Error::templated(
+                        UNKNOWN_IDENT,
+                        Message::new("unknown_local")
+                            .arg("name", name)
+                            .suggest(suggest::nearest(name, known)),
+                    )
+                }),
+        },
+        Expr::Let {
+            name, value, body, ..
+        } => {
+            let value = eval_in(value, current, locals)?.into_owned();
+            let mut inner = locals.to_vec();
+            inner.push((name.clone(), value));
+            eval_in(body, current, &inner)
+        }
+        Expr::Match { expr, arms, .. } => {
+            let value = eval_in(expr, current, locals)?;
+            for arm in arms {
+                let mut inner = locals.to_vec();
+                if fits(&arm.pattern, &value, &mut inner) {
+                    coverage::hit(arm.span, 1);
+                    return eval_in(&arm.body, current, &inner);
+                }
+            }
+            let shown = serde_json::to_string(&value.to_json()).map_err(|e| e.to_string())?;
+            Err(Error::templated(
+                NO_MATCH,
+                Message::new("no_arm_fits").arg("value", shown),
+            ))
+        }
+        Expr::Param { name, .. } => Err(unbound_param(name)),
+        Expr::Number { value, .. } => Ok(Cow::Owned(Value::I64(*value))),
+        Expr::Bool { value, .. } => Ok(Cow::Owned(Value::Bool(*value))),
+        Expr::Null { .. } => Ok(Cow::Owned(Value::Null)),
+        Expr::Negate { expr, .. } => negate(&*eval_in(expr, current, locals)?),
+        Expr::String { value, .. } => Ok(Cow::Owned(Value::String(value.clone()))),
+        Expr::Interpolated { parts, .. } => {
+            let mut out = String::new();
+            for part in parts {
+                match part {
+                    StringPart::Text(text) => out.push_str(text),
+                    StringPart::Expr(expr) => match &*eval_in(expr, current, locals)? {
+                        Value::String(s) => out.push_str(s),
+                        value => out.push_str(
+                            &serde_json::to_string(&value.to_json()).map_err(|e| e.to_string())?,
+                        ),
+                    },
+                }
+            }
+            Ok(Cow::Owned(Value::String(out)))
+        }
         Expr::Array { items, .. } => {
             let mut out = Vec::new();
             for item in items {
-                out.push(eval_value_expr_with_env(item, env)?);
+                out.push(eval_in(item, current, locals)?.into_owned());
             }
-            Ok(Value::Array(out))
+            Ok(Cow::Owned(Value::Array(out)))
         }
         Expr::Record { fields, .. } => {
             let mut out = BTreeMap::new();
             for field in fields {
                 out.insert(
-                    field.name.clone(),
-                    eval_value_expr_with_env(&field.value, env)?,
+                    Key::from(field.name.as_str()),
+                    eval_in(&field.value, current, locals)?.into_owned(),
                 );
             }
-            Ok(Value::Record(out))
+            Ok(Cow::Owned(Value::Record(out)))
+        }
+        Expr::FieldAccess { expr, field, .. } => take_field(eval_in(expr, current, locals)?, field),
+        Expr::Binary {
+            op, left, right, ..
+        } => {
+            let left = eval_in(left, current, locals)?;
+            match op {
+                BinaryOp::Coalesce if matches!(*left, Value::Null) => {
+                    eval_in(right, current, locals)
+                }
+                BinaryOp::Coalesce => Ok(left),
+                _ => binary(*op, &left, &*eval_in(right, current, locals)?).map(Cow::Owned),
+            }
         }
-        Expr::FieldAccess { expr, field, .. } => match eval_value_expr_with_env(expr, env)? {
-            Value::Record(mut rec) => rec
-                .remove(field)
-                .ok_or_else(|| format!("field not found: {field}")),
-            _ => Err("field access requires a record".to_string()),
-        },
-        Expr::Raw { text, .. } => eval_raw(text, env),
         Expr::Call { callee, args, .. } => {
-            let name = callee_name(callee).ok_or_else(|| "unsupported callee".to_string())?;
+            let name = callee_name(callee)
+                .ok_or_else(|| Error::new(UNKNOWN_STAGE, "unsupported callee"))?;
+            let arg = |index| eval_in(positional_arg(args, index)?, current, locals);
             match name.as_str() {
                 "array.map" => {
-                    let arr = eval_value_expr_with_env(positional_arg(args, 0)?, env)?;
+                    let arr = arg(0)?;
                     let func = positional_arg(args, 1)?;
-                    let items = expect_array(arr)?;
                     let mut out = Vec::new();
-                    for item in items {
-                        out.push(eval_with_current(func, env, item)?);
+                    for item in expect_array_ref(&arr)? {
+                        out.push(eval_in(func, Some(item), locals)?.into_owned());
                     }
-                    Ok(Value::Array(out))
+                    Ok(Cow::Owned(Value::Array(out)))
                 }
                 "array.filter" => {
-                    let arr = eval_value_expr_with_env(positional_arg(args, 0)?, env)?;
+                    let arr = arg(0)?;
                     let func = positional_arg(args, 1)?;
-                    let items = expect_array(arr)?;
                     let mut out = Vec::new();
-                    for item in items {
-                        if truthy(&eval_with_current(func, env, item.clone())?)? {
-                            out.push(item);
+                    for item in expect_array_ref(&arr)? {
+                        if eval_in(func, Some(item), locals).and_then(|v| truthy(&v))? {
+                            out.push(item.clone());
                         }
                     }
-                    Ok(Value::Array(out))
+                    Ok(Cow::Owned(Value::Array(out)))
                 }
                 "array.any" => {
-                    let arr = eval_value_expr_with_env(positional_arg(args, 0)?, env)?;
+                    let arr = arg(0)?;
                     let func = positional_arg(args, 1)?;
-                    let items = expect_array(arr)?;
-                    for item in items {
-                        if truthy(&eval_with_current(func, env, item)?)? {
-                            return Ok(Value::Bool(true));
+                    for item in expect_array_ref(&arr)? {
+                        if eval_in(func, Some(item), locals).and_then(|v| truthy(&v))? {
+                            return Ok(Cow::Owned(Value::Bool(true)));
                         }
                     }
-                    Ok(Value::Bool(false))
+                    Ok(Cow::Owned(Value::Bool(false)))
                 }
                 "array.flat_map" => {
-                    let arr = eval_value_expr_with_env(positional_arg(args, 0)?, env)?;
+                    let arr = arg(0)?;
                     let func = positional_arg(args, 1)?;
-                    let items = expect_array(arr)?;
                     let mut out = Vec::new();
-                    for item in items {
-                        let mapped = eval_with_current(func, env, item)?;
+                    for item in expect_array_ref(&arr)? {
+                        let mapped = eval_in(func, Some(item), locals)?.into_owned();
                         out.extend(expect_array(mapped)?);
                     }
-                    Ok(Value::Array(out))
+                    Ok(Cow::Owned(Value::Array(out)))
                 }
                 "array.contains" => {
-                    let arr = eval_value_expr_with_env(positional_arg(args, 0)?, env)?;
-                    let needle = eval_value_expr_with_env(positional_arg(args, 1)?, env)?;
-                    let items = expect_array(arr)?;
-                    Ok(Value::Bool(items.into_iter().any(|item| item == needle)))
+                    let arr = arg(0)?;
+                    let needle = arg(1)?;
+                    let found = expect_array_ref(&arr)?.contains(&needle);
+                    Ok(Cow::Owned(Value::Bool(found)))
                 }
                 "default" => {
-                    let value = eval_value_expr_with_env(positional_arg(args, 0)?, env)?;
-                    if matches!(value, Value::Null) {
-                        eval_value_expr_with_env(positional_arg(args, 1)?, env)
+                    let value = arg(0)?;
+                    if matches!(*value, Value::Null) {
+                        arg(1)
                     } else {
                         Ok(value)
                     }
                 }
-                _ => Err(format!("unsupported expression call: {name}")),
+                "int" | "str" | "bool" | "int?" | "str?" | "bool?" => {
+                    let value = arg(0)?;
+                    let base = name.trim_end_matches('?');
+                    match convert(base, &value) {
+                        Some(converted) => Ok(Cow::Owned(converted)),
+                        None if name.ends_with('?') => Ok(Cow::Owned(Value::Null)),
+                        None => {
+                            let shown = serde_json::to_string(&value.to_json())
+                                .map_err(|e| e.to_string())?;
+                            let expected = match base {
+                                "int" => "I64",
+                                "str" => "String",
+                                _ => "Bool",
+                            };
+                            Err(Error::mismatch(
+                                expected,
+                                format!("{base}: cannot convert {shown}"),
+                            )
+                            .got(value.kind()))
+                        }
+                    }
+                }
+                "tag" => {
+                    let tag = expect_string_value(
+                        arg(1)?.into_owned(),
+                        "tag name must evaluate to String",
+                    )?;
+                    let value = match arg(0)?.into_owned() {
+                        Value::Tagged(_, value) => *value,
+                        value => value,
+                    };
+                    Ok(Cow::Owned(Value::Tagged(tag, Box::new(value))))
+                }
+                "untag" => Ok(match arg(0)? {
+                    Cow::Borrowed(Value::Tagged(_, value)) => Cow::Borrowed(&**value),
+                    Cow::Owned(Value::Tagged(_, value)) => Cow::Owned(*value),
+                    value => value,
+                }),
+                "json.pointer" => {
+                    let value = arg(0)?;
+                    let pointer = expect_string_value(
+                        arg(1)?.into_owned(),
+                        "json.pointer pointer must evaluate to String",
+                    )?;
+                    json_pointer(&value, &pointer).map(|v| Cow::Owned(v.clone()))
+                }
+                "json.path" => {
+                    let value = arg(0)?;
+                    let path = expect_string_value(
+                        arg(1)?.into_owned(),
+                        "json.path path must evaluate to String",
+                    )?;
+                    let matches = json_path(&value, &path)?;
+                    Ok(Cow::Owned(Value::Array(
+                        matches.into_iter().cloned().collect(),
+                    )))
+                }
+                _ => Err(Error::new(
+                    UNKNOWN_STAGE,
+                    format!("unsupported expression call: {name}"),
+                )),
             }
         }
-        _ => Err("unsupported expression form".to_string()),
+        _ => Err(Error::new(
+            UNSUPPORTED_EXPRESSION,
+            "unsupported expression form",
+        )),
     }
 }
 
-fn eval_with_current(
-    expr: &Expr,
-    env: &BTreeMap<String, Value>,
-    current: Value,
-) -> Result<Value, String> {
-    let mut scoped = env.clone();
-    scoped.insert("_".to_string(), current);
-    eval_value_expr_with_env(expr, &scoped)
-}
-
-fn expect_array(value: Value) -> Result<Vec<Value>, String> {
-    match value {
-        Value::Array(items) => Ok(items),
-        _ => Err("expected array".to_string()),
+/// `int`, `str`, or `bool` of `value`; `None` when it has no such reading.
+fn convert(to: &str, value: &Value) -> Option<Value> {
+    match (to, value) {
+        ("int", Value::I64(n)) => Some(Value::I64(*n)),
+        ("int", Value::String(s)) => s.trim().parse().ok().map(Value::I64),
+        ("int", Value::Bool(b)) => Some(Value::I64(i64::from(*b))),
+        ("str", Value::String(s)) => Some(Value::String(s.clone())),
+        ("str", Value::I64(n)) => Some(Value::String(n.to_string())),
+        ("str", Value::Bool(b)) => Some(Value::String(b.to_string())),
+        ("bool", Value::Bool(b)) => Some(Value::Bool(*b)),
+        ("bool", Value::String(s)) => match s.trim() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        ("bool", Value::I64(0)) => Some(Value::Bool(false)),
+        ("bool", Value::I64(1)) => Some(Value::Bool(true)),
+        _ => None,
     }
 }
 
-fn expect_record(value: Value, err: &str) -> Result<BTreeMap<String, Value>, String> {
-    match value {
-        Value::Record(record) => Ok(record),
-        _ => Err(err.to_string()),
+/// Whether `value` fits `pattern`, pushing the names it binds onto `bound`.
+fn fits(pattern: &Pattern, value: &Value, bound: &mut Vec<(String, Value)>) -> bool {
+    match (pattern, value) {
+        (Pattern::Wildcard { .. }, _) => true,
+        (Pattern::Literal(literal), _) => eval_value_expr(literal, None).is_ok_and(|l| l == *value),
+        (Pattern::Bind { name, .. }, _) => {
+            bound.push((name.clone(), value.clone()));
+            true
+        }
+        (Pattern::Record { fields, rest, .. }, Value::Record(record)) => {
+            (*rest || record.len() == fields.len())
+                && fields.iter().all(|(name, pattern)| {
+                    record
+                        .get(name.as_str())
+                        .is_some_and(|field| fits(pattern, field, bound))
+                })
+        }
+        (Pattern::Tagged { tag, pattern, .. }, Value::Tagged(name, inner)) => {
+            tag == name && fits(pattern, inner, bound)
+        }
+        (Pattern::Record { .. } | Pattern::Tagged { .. }, _) => false,
     }
 }
 
-fn expect_string_value(value: Value, err: &str) -> Result<String, String> {
+/// Field `field` of a record, borrowed when the record itself is borrowed.
+fn take_field<'v>(value: Cow<'v, Value>, field: &str) -> Result<Cow<'v, Value>, Error> {
+    let missing = || Error::new(MISSING_FIELD, format!("field not found: {field}"));
     match value {
-        Value::String(s) => Ok(s),
-        _ => Err(err.to_string()),
+        Cow::Borrowed(Value::Record(rec)) => rec.get(field).map(Cow::Borrowed).ok_or_else(missing),
+        Cow::Owned(Value::Record(mut rec)) => rec.remove(field).map(Cow::Owned).ok_or_else(missing),
+        other => Err(Error::mismatch("Record", "field access requires a record").got(other.kind())),
     }
 }
 
-fn eval_raw(text: &str, env: &BTreeMap<String, Value>) -> Result<Value, String> {
-    let raw = text.trim();
-    if let Some((l, r)) = split_top_level(raw, '>') {
-        let lhs = eval_raw(l, env)?;
-        let rhs = eval_raw(r, env)?;
-        let (x, y) = match (lhs, rhs) {
-            (Value::I64(x), Value::I64(y)) => (x, y),
-            _ => return Err("operator > expects i64 operands".to_string()),
-        };
-        return Ok(Value::Bool(x > y));
-    }
-    if let Some((l, r)) = split_top_level(raw, '+') {
-        let lhs = eval_raw(l, env)?;
-        let rhs = eval_raw(r, env)?;
-        return match (lhs, rhs) {
-            (Value::I64(x), Value::I64(y)) => Ok(Value::I64(x + y)),
-            (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{a}{b}"))),
-            _ => Err("operator + expects i64 or string operands".to_string()),
-        };
-    }
-
-    if raw == "_" {
-        return env
-            .get("_")
-            .cloned()
-            .ok_or_else(|| "placeholder _ is not bound".to_string());
-    }
-
-    if let Ok(n) = raw.parse::<i64>() {
-        return Ok(Value::I64(n));
-    }
-
-    if raw.starts_with('"') {
-        return match serde_json::from_str(raw).map_err(|e| e.to_string())? {
-            JsonValue::String(s) => Ok(Value::String(s)),
-            _ => Err("invalid string literal".to_string()),
-        };
-    }
-
-    if raw == "true" {
-        return Ok(Value::Bool(true));
-    }
-    if raw == "false" {
-        return Ok(Value::Bool(false));
-    }
-    if raw == "null" {
-        return Ok(Value::Null);
+/// Resolves an RFC 6901 pointer; missing segments yield `Null`.
+fn json_pointer<'v>(value: &'v Value, pointer: &str) -> Result<&'v Value, Error> {
+    if pointer.is_empty() {
+        return Ok(value);
     }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(Error::mismatch(
+            "an empty pointer or one starting with '/'",
+            "json.pointer pointer must be empty or start with '/'",
+        ));
+    };
 
-    if let Some((root, field)) = raw.rsplit_once('.') {
-        let root_val = eval_raw(root, env)?;
-        return match root_val {
-            Value::Record(mut rec) => rec
-                .remove(field)
-                .ok_or_else(|| format!("field not found: {field}")),
-            _ => Err("field access requires a record".to_string()),
+    let mut current = value;
+    for token in rest.split('/') {
+        let token = token.replace("~1", "/").replace("~0", "~");
+        current = match current {
+            Value::Record(rec) => rec.get(token.as_str()).unwrap_or(&Value::Null),
+            Value::Array(items) => token
+                .parse::<usize>()
+                .ok()
+                .and_then(|idx| items.get(idx))
+                .unwrap_or(&Value::Null),
+            _ => &Value::Null,
         };
     }
-
-    env.get(raw)
-        .cloned()
-        .ok_or_else(|| format!("unknown expression: {raw}"))
+    Ok(current)
 }
 
-fn split_top_level(input: &str, needle: char) -> Option<(&str, &str)> {
-    let mut depth_paren = 0usize;
-    let mut depth_brack = 0usize;
-    let mut depth_brace = 0usize;
-    let mut in_string = false;
-    let mut escaped = false;
+#[derive(Debug, Clone)]
+enum JsonPathSegment {
+    Field(String),
+    Index(usize),
+    Wildcard,
+}
 
-    for (idx, c) in input.char_indices() {
-        if in_string {
-            if escaped {
-                escaped = false;
-            } else if c == '\\' {
-                escaped = true;
-            } else if c == '"' {
-                in_string = false;
-            }
-            continue;
-        }
-
-        match c {
-            '"' => in_string = true,
-            '(' => depth_paren += 1,
-            ')' => depth_paren = depth_paren.saturating_sub(1),
-            '[' => depth_brack += 1,
-            ']' => depth_brack = depth_brack.saturating_sub(1),
-            '{' => depth_brace += 1,
-            '}' => depth_brace = depth_brace.saturating_sub(1),
-            _ if c == needle && depth_paren == 0 && depth_brack == 0 && depth_brace == 0 => {
-                let left = input[..idx].trim();
-                let right = input[idx + c.len_utf8()..].trim();
-                if !left.is_empty() && !right.is_empty() {
-                    return Some((left, right));
+/// Evaluates a small JSONPath subset (`$`, `.name`, `['name']`, `[n]`, `[*]`, `.*`)
+/// and returns every match in document order.
+fn json_path<'v>(value: &'v Value, path: &str) -> Result<Vec<&'v Value>, Error> {
+    let segments = parse_json_path(path)?;
+    let mut current = vec![value];
+    for segment in &segments {
+        let mut next = Vec::new();
+        for item in current {
+            match (segment, item) {
+                (JsonPathSegment::Field(name), Value::Record(rec)) => {
+                    next.extend(rec.get(name.as_str()))
                 }
+                (JsonPathSegment::Index(idx), Value::Array(items)) => next.extend(items.get(*idx)),
+                (JsonPathSegment::Wildcard, Value::Record(rec)) => next.extend(rec.values()),
+                (JsonPathSegment::Wildcard, Value::Array(items)) => next.extend(items),
+                _ => {}
             }
-            _ => {}
         }
+        current = next;
     }
-    None
+    Ok(current)
 }
 
-fn truthy(value: &Value) -> Result<bool, String> {
-    match value {
-        Value::Bool(v) => Ok(*v),
-        _ => Err("filter expression must evaluate to bool".to_string()),
+fn parse_json_path(path: &str) -> Result<Vec<JsonPathSegment>, Error> {
+    let invalid = || Error::from(format!("invalid json.path: {path}"));
+    let mut rest = path.trim().strip_prefix('$').ok_or_else(invalid)?;
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if rest.starts_with("..") {
+            return Err(format!("json.path recursive descent is not supported: {path}").into());
+        }
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+            let name = &after_dot[..end];
+            if name.is_empty() {
+                return Err(invalid());
+            }
+            segments.push(if name == "*" {
+                JsonPathSegment::Wildcard
+            } else {
+                JsonPathSegment::Field(name.to_string())
+            });
+            rest = &after_dot[end..];
+        } else if let Some(after_brack) = rest.strip_prefix('[') {
+            let end = after_brack.find(']').ok_or_else(invalid)?;
+            let inner = after_brack[..end].trim();
+            segments.push(if inner == "*" {
+                JsonPathSegment::Wildcard
+            } else if let Some(quoted) = inner.strip_prefix('\'').and_then(|s| s.strip_suffix('\''))
+            {
+                JsonPathSegment::Field(quoted.to_string())
+            } else {
+                JsonPathSegment::Index(inner.parse::<usize>().map_err(|_| invalid())?)
+            });
+            rest = &after_brack[end + 1..];
+        } else {
+            return Err(invalid());
+        }
     }
+    Ok(segments)
 }
 
-fn json_forward(value: Value) -> Result<Value, String> {
-    let json = value_to_json(value);
-    serde_json::to_vec(&json)
-        .map(Value::Bytes)
-        .map_err(|e| e.to_string())
-}
-
-fn json_inverse(value: Value) -> Result<Value, String> {
+fn expect_array(value: Value) -> Result<Vec<Value>, Error> {
     match value {
-        Value::Bytes(bytes) => serde_json::from_slice(&bytes)
-            .map(json_to_value)
-            .map_err(|e| e.to_string()),
-        _ => Err("json inverse expects Bytes".to_string()),
+        Value::Array(items) => Ok(items),
+        other => Err(Error::mismatch("Array", "expected array").got(other.kind())),
     }
 }
 
-fn utf8_forward(value: Value) -> Result<Value, String> {
+fn expect_array_ref(value: &Value) -> Result<&[Value], Error> {
     match value {
-        Value::String(s) => Ok(Value::Bytes(s.into_bytes())),
-        _ => Err("utf8 forward expects String".to_string()),
+        Value::Array(items) => Ok(items),
+        other => Err(Error::mismatch("Array", "expected array").got(other.kind())),
     }
 }
 
-fn utf8_inverse(value: Value) -> Result<Value, String> {
+#[cfg(feature = "kv")]
+fn expect_record(value: Value, err: &str) -> Result<BTreeMap<Key, Value>, Error> {
     match value {
-        Value::Bytes(bytes) => String::from_utf8(bytes)
-            .map(Value::String)
-            .map_err(|e| e.to_string()),
-        _ => Err("utf8 inverse expects Bytes".to_string()),
+        Value::Record(record) => Ok(record),
+        other => Err(Error::mismatch("Record", err).got(other.kind())),
     }
 }
 
-fn base64_forward(value: Value) -> Result<Value, String> {
+fn expect_string_value(value: Value, err: &str) -> Result<String, Error> {
     match value {
-        Value::Bytes(bytes) => Ok(Value::String(base64_encode(&bytes))),
-        _ => Err("base64 forward expects Bytes".to_string()),
+        Value::String(s) => Ok(s),
+        other => Err(Error::mismatch("String", err).got(other.kind())),
     }
 }
 
-fn base64_inverse(value: Value) -> Result<Value, String> {
-    match value {
-        Value::String(s) => Ok(Value::Bytes(base64_decode(&s)?)),
-        _ => Err("base64 inverse expects String".to_string()),
+/// `left op right` for the operators that evaluate both sides.
+fn binary(op: BinaryOp, left: &Value, right: &Value) -> Result<Value, Error> {
+    match (op, left, right) {
+        (BinaryOp::Eq, _, _) => Ok(Value::Bool(left == right)),
+        (BinaryOp::Gt, Value::I64(x), Value::I64(y)) => Ok(Value::Bool(x > y)),
+        (BinaryOp::Add, Value::I64(x), Value::I64(y)) => x
+            .checked_add(*y)
+            .map(Value::I64)
+            .ok_or_else(|| Error::new(OVERFLOW, "operator + overflowed")),
+        (BinaryOp::Add, Value::String(a), Value::String(b)) => Ok(Value::String(format!("{a}{b}"))),
+        (BinaryOp::Gt, _, _) => Err(Error::templated_mismatch(
+            "i64 operands",
+            Message::new("gt_operands"),
+        )
+        .got(operand_kinds(left, right))),
+        (BinaryOp::Add, _, _) => Err(Error::templated_mismatch(
+            "i64 or string operands",
+            Message::new("add_operands"),
+        )
+        .got(operand_kinds(left, right))),
+        (BinaryOp::Coalesce, _, _) => Ok(if matches!(left, Value::Null) {
+            right.clone()
+        } else {
+            left.clone()
+        }),
     }
 }
 
-fn accepts_json_forward(value: &Value) -> bool {
-    !matches!(value, Value::Bytes(_) | Value::Unit)
-}
-
-fn accepts_json_inverse(value: &Value) -> bool {
-    matches!(value, Value::Bytes(_))
-}
-
-fn accepts_utf8_forward(value: &Value) -> bool {
-    matches!(value, Value::String(_))
+/// `got` for a binary operator: both operand types, `i64 and string`.
+fn operand_kinds(left: &Value, right: &Value) -> String {
+    format!("{} and {}", left.kind(), right.kind())
 }
 
-fn accepts_utf8_inverse(value: &Value) -> bool {
-    matches!(value, Value::Bytes(_))
+fn negate(value: &Value) -> Result<Cow<'static, Value>, Error> {
+    match value {
+        Value::I64(n) => n
+            .checked_neg()
+            .map(|n| Cow::Owned(Value::I64(n)))
+            .ok_or_else(|| Error::new(OVERFLOW, "unary - overflowed")),
+        other => Err(
+            Error::mismatch("an i64 operand", "unary - expects an i64 operand").got(other.kind()),
+        ),
+    }
 }
 
-fn accepts_base64_forward(value: &Value) -> bool {
-    matches!(value, Value::Bytes(_))
+fn unbound_param(name: &str) -> Error {
+    Error::templated(PARAMS, Message::new("unbound_param").arg("name", name))
 }
 
-fn accepts_base64_inverse(value: &Value) -> bool {
-    matches!(value, Value::String(_))
+fn truthy(value: &Value) -> Result<bool, Error> {
+    match value {
+        Value::Bool(v) => Ok(*v),
+        other => Err(
+            Error::mismatch("bool", "filter expression must evaluate to bool").got(other.kind()),
+        ),
+    }
 }
 
-fn parse_fixtures(fixtures: JsonValue) -> Result<BTreeMap<String, Vec<JsonValue>>, String> {
+/// In `strict` mode fixture values must be arrays.
+fn parse_fixtures(
+    fixtures: JsonValue,
+    strict: bool,
+) -> Result<BTreeMap<String, Vec<JsonValue>>, Error> {
     match fixtures {
         JsonValue::Object(map) => {
             let mut out = BTreeMap::new();
@@ -1180,12 +3505,29 @@ fn parse_fixtures(fixtures: JsonValue) -> Result<BTreeMap<String, Vec<JsonValue>
                     JsonValue::Array(items) => {
                         out.insert(name, items);
                     }
-                    _ => return Err("fixture values must be arrays".to_string()),
+                    // A bare string fixture (e.g. an NDJSON document) is a one-item array.
+                    JsonValue::String(text) if !strict => {
+                        out.insert(name, vec![JsonValue::String(text)]);
+                    }
+                    JsonValue::String(_) => {
+                        return Err(Error::new(
+                            STRICT,
+                            format!(
+                                "fixture {name} must be an array in strict mode (wrap the string in [...])"
+                            ),
+                        ))
+                    }
+                    _ => {
+                        return Err(Error::new(
+                            INVALID_FIXTURES,
+                            "fixture values must be arrays or strings",
+                        ))
+                    }
                 }
             }
             Ok(out)
         }
-        _ => Err("fixtures must be an object".to_string()),
+        _ => Err(Error::new(INVALID_FIXTURES, "fixtures must be an object")),
     }
 }
 
@@ -1199,16 +3541,55 @@ fn callee_name(expr: &Expr) -> Option<String> {
     }
 }
 
-fn positional_arg(args: &[CallArg], index: usize) -> Result<&Expr, String> {
+fn positional_arg(args: &[CallArg], index: usize) -> Result<&Expr, Error> {
     args.get(index)
-        .ok_or_else(|| "missing positional arg".to_string())
+        .ok_or_else(|| Error::new(STAGE_ARGUMENTS, "missing positional arg"))
         .and_then(|arg| match arg {
             CallArg::Positional(expr) => Ok(expr),
-            CallArg::Named { .. } => Err("named arguments are not supported in v0".to_string()),
+            CallArg::Named { .. } => Err(Error::new(
+                STAGE_ARGUMENTS,
+                "named arguments are not supported in v0",
+            )),
         })
 }
 
-fn named_arg<'a>(args: &'a [CallArg], name: &str) -> Result<&'a Expr, String> {
+/// `where(a, b)` / `having(a, b)`: one predicate stage per argument, all of
+/// which must hold.
+fn conjunction(args: &Args, make: fn(Expr) -> Stage) -> Stage {
+    let mut parts: Vec<Stage> = args.all("pred").into_iter().cloned().map(make).collect();
+    match parts.len() {
+        1 => parts.remove(0),
+        _ => Stage::Compose(parts),
+    }
+}
+
+fn select_columns(args: &[CallArg]) -> Result<Vec<(String, Expr)>, Error> {
+    let mut columns: Vec<(String, Expr)> = Vec::new();
+    for arg in args {
+        let CallArg::Named { name, value, .. } = arg else {
+            return Err(Error::new(
+                STAGE_ARGUMENTS,
+                "select expects named columns, e.g. select(id=_.id)",
+            ));
+        };
+        if columns.iter().any(|(existing, _)| existing == name) {
+            return Err(Error::new(
+                STAGE_ARGUMENTS,
+                format!("select: duplicate column {name}"),
+            ));
+        }
+        columns.push((name.clone(), value.clone()));
+    }
+    if columns.is_empty() {
+        return Err(Error::new(
+            STAGE_ARGUMENTS,
+            "select expects at least one column",
+        ));
+    }
+    Ok(columns)
+}
+
+fn named_arg<'a>(args: &'a [CallArg], name: &str) -> Result<&'a Expr, Error> {
     args.iter()
         .find_map(|arg| match arg {
             CallArg::Named {
@@ -1218,48 +3599,90 @@ fn named_arg<'a>(args: &'a [CallArg], name: &str) -> Result<&'a Expr, String> {
             } if arg_name == name => Some(value),
             _ => None,
         })
-        .ok_or_else(|| format!("missing named arg: {name}"))
+        .ok_or_else(|| Error::new(STAGE_ARGUMENTS, format!("missing named arg: {name}")))
 }
 
-fn expect_string(expr: &Expr) -> Result<String, String> {
+fn expect_string(expr: &Expr) -> Result<String, Error> {
     match expr {
         Expr::String { value, .. } => Ok(value.clone()),
-        _ => Err("expected string literal".to_string()),
+        _ => Err(Error::mismatch(
+            "a string literal",
+            "expected string literal",
+        )),
     }
 }
 
-fn expect_i64_literal(expr: &Expr) -> Result<i64, String> {
+fn expect_i64_literal(expr: &Expr) -> Result<i64, Error> {
     match expr {
         Expr::Number { value, .. } => Ok(*value),
-        _ => Err("expected i64 literal".to_string()),
+        _ => Err(Error::mismatch("an i64 literal", "expected i64 literal")),
     }
 }
 
-fn parse_sort_order(expr: &Expr) -> Result<SortOrder, String> {
+fn parse_sort_order(expr: &Expr) -> Result<SortOrder, Error> {
     match expect_string(expr)?.as_str() {
         "asc" => Ok(SortOrder::Asc),
         "desc" => Ok(SortOrder::Desc),
-        _ => Err("order must be \"asc\" or \"desc\"".to_string()),
+        _ => Err(Error::new(
+            STAGE_ARGUMENTS,
+            "order must be \"asc\" or \"desc\"",
+        )),
     }
 }
 
-fn expect_sort_key(value: Value, err: &str) -> Result<SortKey, String> {
+fn expect_sort_key(value: Value, err: &str) -> Result<SortKey, Error> {
     match value {
+        Value::Null => Ok(SortKey::Null),
         Value::I64(v) => Ok(SortKey::I64(v)),
         Value::String(v) => Ok(SortKey::String(v)),
-        _ => Err(err.to_string()),
+        other => Err(Error::mismatch("I64, String, or Null", err).got(other.kind())),
+    }
+}
+
+/// An item's `ts=` timestamp (ms on the simulated clock).
+fn expect_timestamp(ts: &Expr, item: &Value, stage: &str) -> Result<i64, Error> {
+    match eval_value_expr(ts, Some(item))? {
+        Value::I64(at) => Ok(at),
+        other => Err(
+            Error::mismatch("I64", format!("{stage} ts must evaluate to I64")).got(other.kind()),
+        ),
     }
 }
 
-fn expect_group_key(value: &Value, err: &str) -> Result<(), String> {
+fn expect_group_key(value: &Value, err: &str) -> Result<(), Error> {
     match value {
         Value::I64(_) | Value::String(_) => Ok(()),
-        _ => Err(err.to_string()),
+        other => Err(Error::mismatch("I64 or String", err).got(other.kind())),
     }
 }
 
-fn compare_keys(a: &SortKey, b: &SortKey, order: SortOrder) -> std::cmp::Ordering {
+/// `items` stably sorted by `by`, so equal keys keep their order.
+fn sort_items(
+    state: &mut RuntimeState,
+    items: Vec<Value>,
+    by: &SortBy,
+    err: &str,
+) -> Result<Vec<Value>, Error> {
+    let mut keyed = items
+        .into_iter()
+        .map(|item| {
+            state.check_cancelled()?;
+            Ok((by.key(&item, err)?, item))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    keyed.sort_by(|(a, _), (b, _)| by.compare(a, b));
+    Ok(keyed.into_iter().map(|(_, item)| item).collect())
+}
+
+fn compare_keys(a: &SortKey, b: &SortKey, order: SortOrder, nulls: Nulls) -> std::cmp::Ordering {
+    let null_first = match nulls {
+        Nulls::First => std::cmp::Ordering::Less,
+        Nulls::Last => std::cmp::Ordering::Greater,
+    };
     let cmp = match (a, b) {
+        (SortKey::Null, SortKey::Null) => return std::cmp::Ordering::Equal,
+        (SortKey::Null, _) => return null_first,
+        (_, SortKey::Null) => return null_first.reverse(),
         (SortKey::I64(x), SortKey::I64(y)) => x.cmp(y),
         (SortKey::String(x), SortKey::String(y)) => x.cmp(y),
         (SortKey::I64(_), SortKey::String(_)) => std::cmp::Ordering::Less,
@@ -1272,17 +3695,18 @@ fn compare_keys(a: &SortKey, b: &SortKey, order: SortOrder) -> std::cmp::Orderin
     }
 }
 
-fn expect_stage(binding: Binding) -> Result<Stage, String> {
+fn expect_stage(binding: Binding) -> Result<Stage, Error> {
     match binding {
         Binding::Stage(stage) => Ok(stage),
-        _ => Err("expected stage".to_string()),
+        _ => Err(Error::new(BINDING_KIND, "expected stage")),
     }
 }
 
-fn expect_stream(binding: Binding) -> Result<Stream, String> {
+/// The stream `binding` holds, copied only if the environment still shares it.
+fn expect_stream(binding: Binding) -> Result<Stream, Error> {
     match binding {
-        Binding::Stream(stream) => Ok(stream),
-        _ => Err("expected stream".to_string()),
+        Binding::Stream(stream) => Ok(Rc::unwrap_or_clone(stream)),
+        _ => Err(Error::new(BINDING_KIND, "expected stream")),
     }
 }
 
@@ -1301,105 +3725,36 @@ fn value_to_json(value: Value) -> JsonValue {
         Value::Record(record) => {
             let mut out = Map::new();
             for (k, v) in record {
-                out.insert(k, value_to_json(v));
+                out.insert(k.into(), value_to_json(v));
             }
             JsonValue::Object(out)
         }
+        Value::Tagged(tag, value) => {
+            let mut out = Map::new();
+            out.insert("tag".to_string(), JsonValue::String(tag));
+            out.insert("value".to_string(), value_to_json(*value));
+            JsonValue::Object(out)
+        }
         Value::Unit => JsonValue::Null,
     }
 }
 
-fn json_to_value(value: JsonValue) -> Value {
+fn json_to_value(value: JsonValue, interner: &mut Interner) -> Value {
     match value {
         JsonValue::Null => Value::Null,
         JsonValue::Bool(v) => Value::Bool(v),
         JsonValue::Number(v) => Value::I64(v.as_i64().unwrap_or_default()),
         JsonValue::String(v) => Value::String(v),
-        JsonValue::Array(items) => Value::Array(items.into_iter().map(json_to_value).collect()),
+        JsonValue::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| json_to_value(item, interner))
+                .collect(),
+        ),
         JsonValue::Object(map) => Value::Record(
             map.into_iter()
-                .map(|(k, v)| (k, json_to_value(v)))
+                .map(|(k, v)| (interner.intern(&k), json_to_value(v, interner)))
                 .collect(),
         ),
     }
 }
-
-fn base64_encode(bytes: &[u8]) -> String {
-    const T: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
-    let mut o = String::new();
-    let mut i = 0;
-    while i < bytes.len() {
-        let b0 = bytes[i] as u32;
-        let b1 = if i + 1 < bytes.len() {
-            bytes[i + 1] as u32
-        } else {
-            0
-        };
-        let b2 = if i + 2 < bytes.len() {
-            bytes[i + 2] as u32
-        } else {
-            0
-        };
-        let n = (b0 << 16) | (b1 << 8) | b2;
-        o.push(T[((n >> 18) & 63) as usize] as char);
-        o.push(T[((n >> 12) & 63) as usize] as char);
-        o.push(if i + 1 < bytes.len() {
-            T[((n >> 6) & 63) as usize] as char
-        } else {
-            '='
-        });
-        o.push(if i + 2 < bytes.len() {
-            T[(n & 63) as usize] as char
-        } else {
-            '='
-        });
-        i += 3;
-    }
-    o
-}
-
-fn base64_decode(s: &str) -> Result<Vec<u8>, String> {
-    fn v(c: u8) -> Option<u8> {
-        match c {
-            b'A'..=b'Z' => Some(c - b'A'),
-            b'a'..=b'z' => Some(c - b'a' + 26),
-            b'0'..=b'9' => Some(c - b'0' + 52),
-            b'+' => Some(62),
-            b'/' => Some(63),
-            _ => None,
-        }
-    }
-
-    let bytes = s.as_bytes();
-    if !bytes.len().is_multiple_of(4) {
-        return Err("invalid base64 length".to_string());
-    }
-
-    let mut out = Vec::new();
-    let mut i = 0;
-    while i < bytes.len() {
-        let c0 = v(bytes[i]).ok_or_else(|| "invalid base64".to_string())? as u32;
-        let c1 = v(bytes[i + 1]).ok_or_else(|| "invalid base64".to_string())? as u32;
-        let c2 = if bytes[i + 2] == b'=' {
-            64
-        } else {
-            v(bytes[i + 2]).ok_or_else(|| "invalid base64".to_string())? as u32
-        };
-        let c3 = if bytes[i + 3] == b'=' {
-            64
-        } else {
-            v(bytes[i + 3]).ok_or_else(|| "invalid base64".to_string())? as u32
-        };
-
-        let n = (c0 << 18) | (c1 << 12) | ((c2 & 63) << 6) | (c3 & 63);
-        out.push(((n >> 16) & 255) as u8);
-        if c2 != 64 {
-            out.push(((n >> 8) & 255) as u8);
-        }
-        if c3 != 64 {
-            out.push((n & 255) as u8);
-        }
-        i += 4;
-    }
-    Ok(out)
-}
@@ -0,0 +1,77 @@
+//! Structured `ui.log` output.
+
+use serde_json::{Map, Value as JsonValue};
+
+/// Severity of a `ui.log("name", level="warn")` sink; `info` when omitted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LogLevel {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 4] = [
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.name() == name)
+            .ok_or_else(|| {
+                format!("unknown log level: {name} (expected debug, info, warn, or error)")
+            })
+    }
+}
+
+/// One item written to a `ui.log` sink.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LogRecord {
+    pub level: LogLevel,
+    /// The item as compact JSON (the v0 log line).
+    pub message: String,
+    pub item: JsonValue,
+}
+
+impl LogRecord {
+    /// `message`, prefixed with `[level] ` unless the level is `info`; used where
+    /// logs are shown as plain lines.
+    pub fn line(&self) -> String {
+        match self.level {
+            LogLevel::Info => self.message.clone(),
+            level => format!("[{}] {}", level.name(), self.message),
+        }
+    }
+
+    /// `{"level", "message", "item"}`.
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(Map::from_iter([
+            (
+                "level".to_string(),
+                JsonValue::String(self.level.name().to_string()),
+            ),
+            (
+                "message".to_string(),
+                JsonValue::String(self.message.clone()),
+            ),
+            ("item".to_string(), self.item.clone()),
+        ]))
+    }
+}
@@ -0,0 +1,270 @@
+//! The message catalog behind localized diagnostics: errors record a message id and
+//! named arguments where they are raised, and render from the templates here in any
+//! of [`LOCALES`]. Messages without a template stay in English.
+
+use crate::errors::SYNTAX;
+use crate::suggest;
+use dsl_syntax::ParseError;
+
+/// Locales errors can be rendered in.
+pub const LOCALES: &[&str] = &["en", "es", "fr"];
+
+/// An error message as its catalog id and arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Message {
+    /// Stable id such as `unknown_ident`. A message without a template of its own
+    /// uses its error kind, such as `type_mismatch`, and its English text as the
+    /// `message` argument.
+    pub id: String,
+    /// Interpolated arguments by name, in template order. A `program` argument,
+    /// set for errors from one program of a workspace, prefixes the message.
+    pub args: Vec<(String, String)>,
+    /// Names from a "did you mean" hint.
+    pub suggestions: Vec<String>,
+    /// `line:column` of the error, rendered as a trailing `at line:column`.
+    pub position: Option<String>,
+}
+
+/// One message: its id and its text in each of [`LOCALES`], with `{name}` holes.
+struct Template {
+    id: &'static str,
+    en: &'static str,
+    es: &'static str,
+    fr: &'static str,
+}
+
+const TEMPLATES: &[Template] = &[
+    Template {
+        id: "unexpected_token",
+        en: "unexpected token `{token}`, expected {expected}",
+        es: "token inesperado `{token}`, se esperaba {expected}",
+        fr: "jeton inattendu `{token}`, {expected} attendu",
+    },
+    Template {
+        id: "unexpected_end",
+        en: "unexpected end of input, expected {expected}",
+        es: "fin de entrada inesperado, se esperaba {expected}",
+        fr: "fin d'entrée inattendue, {expected} attendu",
+    },
+    Template {
+        id: "unterminated_string",
+        en: "unterminated string literal",
+        es: "cadena sin terminar",
+        fr: "chaîne non terminée",
+    },
+    Template {
+        id: "duplicate_binding",
+        en: "duplicate binding {name} (first bound at {first}; write `override {name} := ...;` to replace it)",
+        es: "enlace duplicado {name} (enlazado primero en {first}; escribe `override {name} := ...;` para reemplazarlo)",
+        fr: "liaison en double {name} (liée d'abord à {first} ; écrivez `override {name} := ...;` pour la remplacer)",
+    },
+    Template {
+        id: "used_before_bound",
+        en: "{name} is used before it is bound (bound at {bound})",
+        es: "{name} se usa antes de enlazarse (enlazado en {bound})",
+        fr: "{name} est utilisé avant d'être lié (lié à {bound})",
+    },
+    Template {
+        id: "used_in_cycle",
+        en: "cycle {cycle}: {name} is used before it is bound (bound at {bound})",
+        es: "ciclo {cycle}: {name} se usa antes de enlazarse (enlazado en {bound})",
+        fr: "cycle {cycle} : {name} est utilisé avant d'être lié (lié à {bound})",
+    },
+    Template {
+        id: "reserved_namespace",
+        en: "cannot bind reserved namespace '{name}'",
+        es: "no se puede enlazar el espacio de nombres reservado '{name}'",
+        fr: "impossible de lier l'espace de noms réservé '{name}'",
+    },
+    Template {
+        id: "unknown_ident",
+        en: "unknown ident {name}",
+        es: "identificador desconocido {name}",
+        fr: "identifiant inconnu {name}",
+    },
+    Template {
+        id: "unknown_local",
+        en: "unknown local {name}",
+        es: "identificador local desconocido {name}",
+        fr: "identifiant local inconnu {name}",
+    },
+    Template {
+        id: "unsupported_call",
+        en: "unsupported call: {name}",
+        es: "llamada no soportada: {name}",
+        fr: "appel non pris en charge : {name}",
+    },
+    Template {
+        id: "missing_argument",
+        en: "{stage}: missing argument {name} (expected {signature})",
+        es: "{stage}: falta el argumento {name} (se esperaba {signature})",
+        fr: "{stage} : argument {name} manquant ({signature} attendu)",
+    },
+    Template {
+        id: "missing_param",
+        en: "missing param: ${name}",
+        es: "falta el parámetro: ${name}",
+        fr: "paramètre manquant : ${name}",
+    },
+    Template {
+        id: "unbound_param",
+        en: "unbound param ${name} (run it with params)",
+        es: "parámetro sin valor ${name} (ejecútalo con params)",
+        fr: "paramètre non lié ${name} (exécutez-le avec des params)",
+    },
+    Template {
+        id: "missing_fixture",
+        en: "missing fixture: {name}",
+        es: "falta el fixture: {name}",
+        fr: "fixture manquante : {name}",
+    },
+    Template {
+        id: "no_arm_fits",
+        en: "match: no arm fits {value}",
+        es: "match: ningún brazo encaja con {value}",
+        fr: "match : aucune branche ne correspond à {value}",
+    },
+    Template {
+        id: "gt_operands",
+        en: "operator > expects i64 operands",
+        es: "el operador > espera operandos i64",
+        fr: "l'opérateur > attend des opérandes i64",
+    },
+    Template {
+        id: "add_operands",
+        en: "operator + expects i64 or string operands",
+        es: "el operador + espera operandos i64 o string",
+        fr: "l'opérateur + attend des opérandes i64 ou string",
+    },
+    Template {
+        id: "no_fixed_point",
+        en: "iterate: no fixed point after max_iters={max_iters} iterations",
+        es: "iterate: sin punto fijo tras max_iters={max_iters} iteraciones",
+        fr: "iterate : pas de point fixe après max_iters={max_iters} itérations",
+    },
+];
+
+impl Template {
+    fn text(&self, locale: &str) -> &'static str {
+        match locale {
+            "es" => self.es,
+            "fr" => self.fr,
+            _ => self.en,
+        }
+    }
+}
+
+impl Message {
+    /// Catalog message `id`, without arguments yet.
+    pub(crate) fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            args: Vec::new(),
+            suggestions: Vec::new(),
+            position: None,
+        }
+    }
+
+    /// A message without a template: `text` under the id of its error `kind`.
+    pub(crate) fn plain(kind: &str, text: &str) -> Self {
+        Message::new(kind).arg("message", text)
+    }
+
+    /// The same message with argument `name` set to `value`.
+    pub(crate) fn arg(mut self, name: &str, value: impl ToString) -> Self {
+        self.args.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// The same message ending in a "did you mean" hint for `suggestions`.
+    pub(crate) fn suggest(mut self, suggestions: Vec<String>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+
+    /// The message in `locale`; English when the locale isn't one of [`LOCALES`] or
+    /// the message has no template. A `program` argument comes first as `{program}: `.
+    pub fn render(&self, locale: &str) -> String {
+        let template = TEMPLATES.iter().find(|template| template.id == self.id);
+        let locale = match template {
+            Some(_) if LOCALES.contains(&locale) => locale,
+            _ => "en",
+        };
+        let mut out = match self.arg_value("program") {
+            Some(program) => format!("{program}: "),
+            None => String::new(),
+        };
+        out.push_str(&match template {
+            Some(template) => fill(template.text(locale), &self.args),
+            None => self.arg_value("message").unwrap_or_default().to_string(),
+        });
+        out.push_str(&hint(&self.suggestions, locale));
+        if let Some(position) = &self.position {
+            let at = match locale {
+                "es" => "en",
+                "fr" => "à",
+                _ => "at",
+            };
+            out.push_str(&format!(" {at} {position}"));
+        }
+        out
+    }
+
+    fn arg_value(&self, name: &str) -> Option<&str> {
+        self.args
+            .iter()
+            .find(|(arg, _)| arg == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl From<&ParseError> for Message {
+    fn from(error: &ParseError) -> Self {
+        let message = match &error.message_id {
+            Some(id) => Message {
+                args: error.message_args.clone(),
+                ..Message::new(id)
+            },
+            None => Message::plain(SYNTAX.kind, &error.message),
+        };
+        Message {
+            position: Some(error.start.to_string()),
+            ..message
+        }
+    }
+}
+
+/// `template` with each `{name}` hole replaced by its argument.
+fn fill(template: &str, args: &[(String, String)]) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}').map(|close| open + close) else {
+            break;
+        };
+        out.push_str(&rest[..open]);
+        let name = &rest[open + 1..close];
+        match args.iter().find(|(arg, _)| arg == name) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(&rest[open..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn hint(suggestions: &[String], locale: &str) -> String {
+    let (open, close, or) = match locale {
+        "es" => (" (¿quisiste decir ", "?)", "o"),
+        "fr" => (" (vouliez-vous dire ", " ?)", "ou"),
+        _ => return suggest::hint(suggestions),
+    };
+    let quoted: Vec<String> = suggestions.iter().map(|name| format!("`{name}`")).collect();
+    match quoted.as_slice() {
+        [] => String::new(),
+        [one] => format!("{open}{one}{close}"),
+        [rest @ .., last] => format!("{open}{} {or} {last}{close}", rest.join(", ")),
+    }
+}
@@ -0,0 +1,176 @@
+//! Per-run knobs supplied by the host.
+
+use crate::errors::Error;
+use crate::{params, MemoryTracker, ParseLimits, Program, Rebinding, RuntimeState, Sample};
+use serde_json::{Map, Value as JsonValue};
+
+/// How much `Outputs::explain` records.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExplainLevel {
+    /// No explain lines.
+    Quiet,
+    /// One classification line per statement and stage.
+    #[default]
+    Normal,
+    /// Normal lines plus up to `items` traced items after every source and stage,
+    /// and simulated batch/window details.
+    Verbose { items: usize },
+}
+
+impl ExplainLevel {
+    /// Items traced per stage when `verbose` is requested without a count.
+    pub const DEFAULT_TRACE_ITEMS: usize = 3;
+}
+
+/// Host-supplied inputs for [`crate::run_with_options`] beyond the fixtures.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Values for `$name` placeholders (see [`bind_params`](crate::bind_params)).
+    pub params: Map,
+    /// Constants that shadow the program's same-named `const` statements.
+    pub constants: Map,
+    /// What `Outputs::explain` records.
+    pub explain: ExplainLevel,
+    /// Record `Outputs::fingerprint`.
+    pub audit: bool,
+    /// Record `Outputs::peak_stream_bytes`.
+    pub track_memory: bool,
+    /// Fail with `memory limit exceeded at stage X` once the streams held exceed
+    /// this many bytes (implies `track_memory`).
+    pub memory_limit: Option<usize>,
+    /// Return tables as `Outputs::tables_columnar` instead of row lists.
+    pub columnar: bool,
+    /// Strict mode: bare codecs only encode (decoding needs `~codec`) and fixture
+    /// values must be arrays.
+    pub strict: bool,
+    /// Whether binding a name again without `override` is an error.
+    pub rebinding: Rebinding,
+    /// Source length, nesting, and statement caps for parsing the program.
+    pub parse_limits: ParseLimits,
+    /// Record `Outputs::coverage`.
+    pub coverage: bool,
+    /// Run on a sample of each fixture and record `Outputs::sample` (see
+    /// [`RunOptions::sample_input`]).
+    pub sample: Option<Sample>,
+}
+
+impl RunOptions {
+    /// Parses `{ params, constants, explain: "quiet" | "normal" | "verbose",
+    /// explain_items, audit, track_memory, memory_limit, columnar, strict,
+    /// rebinding: "error" | "allow", coverage, max_source_len, max_depth,
+    /// max_statements }`; every key is optional.
+    pub fn from_json(options: &JsonValue) -> Result<Self, String> {
+        let JsonValue::Object(map) = options else {
+            return Err("run options must be a JSON object".to_string());
+        };
+        let mut out = RunOptions::default();
+        let mut items = None;
+        for (key, value) in map {
+            match (key.as_str(), value) {
+                ("params", JsonValue::Object(params)) => out.params = params.clone(),
+                ("constants", JsonValue::Object(constants)) => out.constants = constants.clone(),
+                ("explain", JsonValue::String(level)) => {
+                    out.explain = match level.as_str() {
+                        "quiet" => ExplainLevel::Quiet,
+                        "normal" => ExplainLevel::Normal,
+                        "verbose" => ExplainLevel::Verbose {
+                            items: ExplainLevel::DEFAULT_TRACE_ITEMS,
+                        },
+                        other => {
+                            return Err(format!(
+                            "unknown explain level: {other} (expected quiet, normal, or verbose)"
+                        ))
+                        }
+                    }
+                }
+                ("explain_items", JsonValue::Number(n)) => {
+                    items = Some(
+                        n.as_i64()
+                            .and_then(|n| usize::try_from(n).ok())
+                            .ok_or("explain_items must be >= 0")?,
+                    )
+                }
+                ("audit", JsonValue::Bool(audit)) => out.audit = *audit,
+                ("track_memory", JsonValue::Bool(track)) => out.track_memory = *track,
+                ("columnar", JsonValue::Bool(columnar)) => out.columnar = *columnar,
+                ("strict", JsonValue::Bool(strict)) => out.strict = *strict,
+                ("coverage", JsonValue::Bool(coverage)) => out.coverage = *coverage,
+                ("rebinding", JsonValue::String(rebinding)) => {
+                    out.rebinding = match rebinding.as_str() {
+                        "error" => Rebinding::Error,
+                        "allow" => Rebinding::Allow,
+                        other => {
+                            return Err(format!(
+                                "unknown rebinding: {other} (expected error or allow)"
+                            ))
+                        }
+                    }
+                }
+                ("memory_limit", JsonValue::Number(n)) => {
+                    out.memory_limit = Some(
+                        n.as_i64()
+                            .and_then(|n| usize::try_from(n).ok())
+                            .ok_or("memory_limit must be >= 0")?,
+                    )
+                }
+                ("max_source_len" | "max_depth" | "max_statements", JsonValue::Number(n)) => {
+                    let limit = n
+                        .as_i64()
+                        .and_then(|n| usize::try_from(n).ok())
+                        .ok_or_else(|| format!("{key} must be >= 0"))?;
+                    let limits = &mut out.parse_limits;
+                    *match key.as_str() {
+                        "max_source_len" => &mut limits.max_source_len,
+                        "max_depth" => &mut limits.max_depth,
+                        _ => &mut limits.max_statements,
+                    } = limit;
+                }
+                ("params" | "constants", _) => return Err(format!("{key} must be an object")),
+                ("explain" | "rebinding", _) => return Err(format!("{key} must be a string")),
+                ("explain_items", _) => return Err("explain_items must be a number".to_string()),
+                ("audit" | "track_memory" | "columnar" | "strict" | "coverage", _) => {
+                    return Err(format!("{key} must be a boolean"))
+                }
+                ("memory_limit" | "max_source_len" | "max_depth" | "max_statements", _) => {
+                    return Err(format!("{key} must be a number"))
+                }
+                _ => return Err(format!("unknown run option: {key}")),
+            }
+        }
+        match (&mut out.explain, items) {
+            (ExplainLevel::Verbose { items }, Some(n)) => *items = n,
+            (_, Some(_)) => return Err("explain_items requires explain: \"verbose\"".to_string()),
+            _ => {}
+        }
+        Ok(out)
+    }
+
+    /// Runs on at most `n` items of each fixture array, chosen by `seed` and kept in
+    /// order, and records per-stage stats scaled up to the full fixtures in
+    /// `Outputs::sample`, to profile a pipeline before running it on huge inputs.
+    /// Tables and logs hold only the sample's results.
+    pub fn sample_input(mut self, n: usize, seed: u64) -> Self {
+        self.sample = Some(Sample { items: n, seed });
+        self
+    }
+
+    pub(crate) fn bind(&self, program: &Program) -> Result<Program, Error> {
+        params::bind(program, &JsonValue::Object(self.params.clone()))
+    }
+
+    pub(crate) fn state(&self) -> RuntimeState<'_> {
+        RuntimeState {
+            constants: Some(&self.constants),
+            explain: self.explain,
+            audit: self.audit,
+            columnar: self.columnar,
+            strict: self.strict,
+            coverage: self.coverage,
+            memory: (self.track_memory || self.memory_limit.is_some()).then(|| MemoryTracker {
+                limit: self.memory_limit,
+                ..MemoryTracker::default()
+            }),
+            ..RuntimeState::default()
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! `$name` placeholders, filled from a params object before a program runs.
+
+use crate::errors::{Error, PARAMS};
+use crate::messages::Message;
+use dsl_syntax::{CallArg, Expr, Program, Span, Stmt, StringPart};
+use serde_json::Value as JsonValue;
+use std::collections::BTreeSet;
+
+/// The params `program` references, sorted by name.
+pub fn param_names(program: &Program) -> Vec<String> {
+    let mut names = BTreeSet::new();
+    let mut collect = |expr: &mut Expr| {
+        if let Expr::Param { name, .. } = expr {
+            names.insert(name.clone());
+        }
+        Ok(())
+    };
+    let mut program = program.clone();
+    for stmt in &mut program.statements {
+        // `collect` never fails.
+        let _ = walk_stmt(stmt, &mut collect);
+    }
+    names.into_iter().collect()
+}
+
+/// Substitutes every `$name` in `program` with the literal from `params` (a JSON
+/// object). Every referenced param must be supplied and every supplied param must
+/// be referenced.
+pub fn bind_params(program: &Program, params: &JsonValue) -> Result<Program, String> {
+    bind(program, params).map_err(|e| e.message)
+}
+
+pub(crate) fn bind(program: &Program, params: &JsonValue) -> Result<Program, Error> {
+    let params = match params {
+        JsonValue::Object(map) => map,
+        JsonValue::Null => &serde_json::Map::new(),
+        _ => return Err(Error::new(PARAMS, "params must be a JSON object")),
+    };
+    let used = param_names(program);
+    if let Some(missing) = used.iter().find(|name| !params.contains_key(*name)) {
+        return Err(Error::templated(
+            PARAMS,
+            Message::new("missing_param").arg("name", missing),
+        ));
+    }
+    if let Some(extra) = params.keys().find(|name| !used.contains(name)) {
+        return Err(Error::new(
+            PARAMS,
+            format!(
+                "unknown param: ${extra} (program uses: {})",
+                describe(&used)
+            ),
+        ));
+    }
+
+    let mut program = program.clone();
+    for stmt in &mut program.statements {
+        walk_stmt(stmt, &mut |expr| substitute(expr, params))?;
+    }
+    Ok(program)
+}
+
+fn describe(names: &[String]) -> String {
+    if names.is_empty() {
+        return "none".to_string();
+    }
+    names
+        .iter()
+        .map(|name| format!("${name}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn substitute(expr: &mut Expr, params: &serde_json::Map) -> Result<(), Error> {
+    if let Expr::Param { name, span } = expr {
+        *expr = literal(&params[name.as_str()], *span);
+    }
+    Ok(())
+}
+
+pub(crate) fn literal(value: &JsonValue, span: Span) -> Expr {
+    match value {
+        JsonValue::Number(n) => Expr::Number {
+            value: n.as_i64().unwrap_or_default(),
+            span,
+        },
+        JsonValue::String(value) => Expr::String {
+            value: value.clone(),
+            span,
+        },
+        JsonValue::Array(items) => Expr::Array {
+            items: items.iter().map(|item| literal(item, span)).collect(),
+            span,
+        },
+        JsonValue::Object(map) => Expr::Record {
+            fields: map
+                .iter()
+                .map(|(name, value)| dsl_syntax::RecordField {
+                    name: name.clone(),
+                    value: literal(value, span),
+                    span,
+                })
+                .collect(),
+            span,
+        },
+        JsonValue::Bool(value) => Expr::Bool {
+            value: *value,
+            span,
+        },
+        JsonValue::Null => Expr::Null { span },
+    }
+}
+
+pub(crate) fn walk_stmt(
+    stmt: &mut Stmt,
+    visit: &mut impl FnMut(&mut Expr) -> Result<(), Error>,
+) -> Result<(), Error> {
+    match stmt {
+        Stmt::Binding { expr, .. }
+        | Stmt::Destructure { expr, .. }
+        | Stmt::Pipeline { expr, .. }
+        | Stmt::Const { expr, .. } => walk(expr, visit),
+    }
+}
+
+/// Visits `expr` and its subexpressions bottom-up, except call callees (stage
+/// and function names).
+pub(crate) fn walk(
+    expr: &mut Expr,
+    visit: &mut impl FnMut(&mut Expr) -> Result<(), Error>,
+) -> Result<(), Error> {
+    match expr {
+        Expr::Array { items, .. } => items.iter_mut().try_for_each(|item| walk(item, visit))?,
+        Expr::Record { fields, .. } => fields
+            .iter_mut()
+            .try_for_each(|field| walk(&mut field.value, visit))?,
+        Expr::Interpolated { parts, .. } => {
+            for part in parts {
+                if let StringPart::Expr(expr) = part {
+                    walk(expr, visit)?
+                }
+            }
+        }
+        Expr::FieldAccess { expr, .. } | Expr::Inverse { expr, .. } | Expr::Negate { expr, .. } => {
+            walk(expr, visit)?
+        }
+        Expr::Binary { left, right, .. } => {
+            walk(left, visit)?;
+            walk(right, visit)?;
+        }
+        Expr::Call { args, .. } => {
+            for arg in args {
+                match arg {
+                    CallArg::Positional(value) | CallArg::Named { value, .. } => {
+                        walk(value, visit)?
+                    }
+                }
+            }
+        }
+        Expr::Pipeline { input, stages, .. } => {
+            walk(input, visit)?;
+            stages.iter_mut().try_for_each(|stage| walk(stage, visit))?;
+        }
+        Expr::Compose { left, right, .. } => {
+            walk(left, visit)?;
+            walk(right, visit)?;
+        }
+        Expr::Let { value, body, .. } => {
+            walk(value, visit)?;
+            walk(body, visit)?;
+        }
+        Expr::Match { expr, arms, .. } => {
+            walk(expr, visit)?;
+            arms.iter_mut()
+                .try_for_each(|arm| walk(&mut arm.body, visit))?;
+        }
+        Expr::Ident { .. }
+        | Expr::Placeholder { .. }
+        | Expr::Param { .. }
+        | Expr::Number { .. }
+        | Expr::Bool { .. }
+        | Expr::Null { .. }
+        | Expr::String { .. } => {}
+    }
+    visit(expr)
+}
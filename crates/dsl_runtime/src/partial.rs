@@ -0,0 +1,173 @@
+//! [`run_statement`]: runs one statement of a program in a [`Session`], reusing the
+//! session's bindings and rerunning only the earlier statements it needs.
+
+use crate::diagram::{state_access, StateAccess};
+use crate::errors::Error;
+use crate::params::walk_stmt;
+use crate::{
+    compile, constants, locate_error, parse_fixtures, run_stmt, take_warnings, Outputs,
+    RuntimeError, Session,
+};
+use dsl_syntax::{format_expr, Expr, Stmt};
+use serde_json::{Map, Value as JsonValue};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+
+/// Runs statement `index` (0-based, `const` statements included) of `program` in
+/// `session`. An earlier statement reruns only when this one needs a binding,
+/// `kv.load` store, or `rbac.load` policy from it that the session doesn't hold in its
+/// current version: one
+/// made by the same statement text from the same inputs. The returned tables, logs,
+/// and graphs are the statement's own; explain lines cover every statement that ran.
+/// Fixtures that differ from the session's reset it first.
+pub fn run_statement(
+    program: &str,
+    index: usize,
+    fixtures: JsonValue,
+    session: &mut Session,
+) -> Result<Outputs, RuntimeError> {
+    let parsed = compile(program)?;
+    let Some(selected) = parsed.statements.get(index) else {
+        return Err(format!(
+            "statement {index} is out of range (the program has {} statements)",
+            parsed.statements.len()
+        )
+        .into());
+    };
+    let selected = selected.span();
+    let lowered = constants::lower(&parsed, &mut Map::new(), &Map::new())?;
+    session.use_fixtures(fixtures)?;
+    let Some(target) = lowered.statements.iter().position(|s| s.span() == selected) else {
+        // A `const`: it only has an effect on the statements after it.
+        return Ok(Outputs::default());
+    };
+    let statements = &lowered.statements[..=target];
+    let deps: Vec<Deps> = statements.iter().map(Deps::of).collect();
+    let versions = versions(statements, &deps);
+
+    let mut needed = deps[target].uses.clone();
+    let mut plan = vec![target];
+    for idx in (0..target).rev() {
+        let binds: Vec<&String> = deps[idx]
+            .binds
+            .iter()
+            .filter(|n| needed.contains(*n))
+            .collect();
+        if binds.is_empty() {
+            continue;
+        }
+        for name in &binds {
+            needed.remove(*name);
+        }
+        if binds
+            .iter()
+            .any(|name| session.versions.get(*name) != Some(&versions[idx]))
+        {
+            plan.push(idx);
+            needed.extend(deps[idx].uses.iter().cloned());
+        }
+    }
+    plan.reverse();
+
+    let (mut earlier, mut outputs) = (Outputs::default(), Outputs::default());
+    for idx in plan {
+        let stmt = &statements[idx];
+        let out = if idx == target {
+            &mut outputs
+        } else {
+            &mut earlier
+        };
+        run_stmt(
+            stmt,
+            &mut session.env,
+            &session.fixtures,
+            &mut session.state,
+            out,
+        )
+        .map_err(|e| locate_error(e, &mut session.state, stmt, Some(program)))?;
+        session.note_binding(stmt);
+        for name in &deps[idx].binds {
+            session.versions.insert(name.clone(), versions[idx]);
+        }
+    }
+    earlier.explain.append(&mut outputs.explain);
+    outputs.explain = earlier.explain;
+    outputs.warnings = take_warnings(&mut session.state, Some(program));
+    session.last_explain = outputs.explain.clone();
+    Ok(outputs)
+}
+
+impl Session {
+    fn use_fixtures(&mut self, fixtures: JsonValue) -> Result<(), Error> {
+        let fixtures = parse_fixtures(fixtures, false)?;
+        if fixtures != self.fixtures {
+            self.reset();
+            self.fixtures = fixtures;
+        }
+        Ok(())
+    }
+}
+
+/// Names a statement binds and reads; `kv.load` stores count as `kv:<store>` and
+/// `rbac.load` policies as `rbac:<name>`.
+struct Deps {
+    binds: Vec<String>,
+    uses: BTreeSet<String>,
+}
+
+impl Deps {
+    fn of(stmt: &Stmt) -> Self {
+        let mut binds = match stmt {
+            Stmt::Binding { name, .. } => vec![name.clone()],
+            Stmt::Destructure { names, .. } => names.clone(),
+            Stmt::Pipeline { .. } | Stmt::Const { .. } => Vec::new(),
+        };
+        let mut uses = BTreeSet::new();
+        let _ = walk_stmt(&mut stmt.clone(), &mut |expr| {
+            match expr {
+                Expr::Ident { name, .. } => {
+                    uses.insert(name.clone());
+                }
+                Expr::Call { .. } => {
+                    for access in state_access(expr) {
+                        match access {
+                            StateAccess::Writes(store) => binds.push(store),
+                            StateAccess::Reads(store) => {
+                                uses.insert(store);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+            Ok(())
+        });
+        Self { binds, uses }
+    }
+}
+
+/// A hash per statement of its text and the versions of the names it reads.
+fn versions(statements: &[Stmt], deps: &[Deps]) -> Vec<u64> {
+    let mut current: BTreeMap<&str, u64> = BTreeMap::new();
+    let mut out = Vec::new();
+    for (stmt, deps) in statements.iter().zip(deps) {
+        let mut hasher = DefaultHasher::new();
+        match stmt {
+            Stmt::Binding { name, expr, .. } => (name, format_expr(expr)).hash(&mut hasher),
+            Stmt::Destructure { names, expr, .. } => (names, format_expr(expr)).hash(&mut hasher),
+            Stmt::Pipeline { expr, .. } | Stmt::Const { expr, .. } => {
+                format_expr(expr).hash(&mut hasher)
+            }
+        }
+        for name in &deps.uses {
+            (name, current.get(name.as_str())).hash(&mut hasher);
+        }
+        let version = hasher.finish();
+        for name in &deps.binds {
+            current.insert(name, version);
+        }
+        out.push(version);
+    }
+    out
+}
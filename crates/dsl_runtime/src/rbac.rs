@@ -0,0 +1,368 @@
+//! `rbac.evaluate(principal_bindings=..., role_perms=..., resource_ancestors=...)`:
+//! allows a `{principal, action, resource}` request when one of the principal's roles
+//! has a permission for the action on the resource or one of its ancestors.
+//! `rbac.load(name=..., ...)` compiles the same fixtures once for
+//! `rbac.evaluate(policy=...)`. With `effective_at`, binding and permission rows only
+//! count between their optional `valid_from` (inclusive) and `valid_until` (exclusive).
+
+use crate::errors::{Error, LIMIT_EXCEEDED};
+use crate::graph::Graph;
+use serde_json::{Map, Value as JsonValue};
+use std::collections::{BTreeMap, HashMap};
+
+/// Names of the fixtures a policy is read from.
+#[derive(Debug, Clone)]
+pub(crate) struct PolicyFixtures {
+    pub(crate) principal_bindings: String,
+    pub(crate) role_perms: String,
+    pub(crate) resource_ancestors: String,
+}
+
+impl PolicyFixtures {
+    pub(crate) fn compile(
+        &self,
+        fixtures: &BTreeMap<String, Vec<JsonValue>>,
+    ) -> Result<Policy, Error> {
+        let rows = |name: &String| {
+            fixtures
+                .get(name)
+                .ok_or_else(|| Error::missing_fixture(name))
+        };
+        Policy::from_rows(
+            rows(&self.principal_bindings)?,
+            rows(&self.role_perms)?,
+            rows(&self.resource_ancestors)?,
+        )
+    }
+}
+
+/// The policy an `rbac.evaluate` checks requests against.
+#[derive(Debug, Clone)]
+pub(crate) enum PolicyRef {
+    /// Read from fixtures each time the stage runs.
+    Fixtures(PolicyFixtures),
+    /// Compiled earlier by `rbac.load(name=...)`.
+    Loaded(String),
+}
+
+pub(crate) struct Policy {
+    roles_by_principal: BTreeMap<String, Vec<(String, Validity)>>,
+    perms_by_role_action: BTreeMap<(String, String), Vec<(JsonValue, Validity)>>,
+    ancestors: Graph,
+}
+
+impl Policy {
+    pub(crate) fn from_rows(
+        principal_bindings: &[JsonValue],
+        role_perms: &[JsonValue],
+        resource_ancestors: &[JsonValue],
+    ) -> Result<Self, Error> {
+        let mut roles_by_principal: BTreeMap<String, Vec<(String, Validity)>> = BTreeMap::new();
+        for row in principal_bindings {
+            let principal = field(row, "principal")?;
+            let role = field(row, "role")?;
+            let validity = Validity::of(row, "principal_bindings")?;
+            roles_by_principal
+                .entry(principal)
+                .or_default()
+                .push((role, validity));
+        }
+
+        let mut perms_by_role_action: BTreeMap<(String, String), Vec<(JsonValue, Validity)>> =
+            BTreeMap::new();
+        for row in role_perms {
+            let role = field(row, "role")?;
+            let action = field(row, "action")?;
+            let validity = Validity::of(row, "role_perms")?;
+            perms_by_role_action
+                .entry((role, action))
+                .or_default()
+                .push((row.clone(), validity));
+        }
+
+        let mut ancestors = Graph::default();
+        for row in resource_ancestors {
+            ancestors.add_edge(field(row, "resource")?, field(row, "ancestor")?);
+        }
+
+        Ok(Self {
+            roles_by_principal,
+            perms_by_role_action,
+            ancestors,
+        })
+    }
+
+    /// Every cycle in `resource_ancestors`, each as the path from its first
+    /// resource back to it (`["a", "b", "a"]`).
+    pub(crate) fn ancestor_cycles(&self) -> Vec<Vec<String>> {
+        self.ancestors.cycles()
+    }
+
+    /// `resource` and everything reachable from it through `resource_ancestors`,
+    /// nearest first. Fails when an ancestor lies more than `max_depth` levels up.
+    pub(crate) fn reachable(&self, resource: &str, max_depth: usize) -> Result<Vec<String>, Error> {
+        self.ancestors
+            .reachable(resource, max_depth)
+            .map_err(|path| {
+                Error::new(
+                    LIMIT_EXCEEDED,
+                    format!(
+                        "rbac.evaluate: ancestors of {resource} go deeper than max_depth={max_depth} ({})",
+                        path.join(" -> ")
+                    ),
+                )
+            })
+    }
+
+    /// `{request, decision: "allow" | "deny", matches}` for one request record, as of
+    /// `at` when given, reusing `cache` for repeated requests and resources.
+    pub(crate) fn evaluate(
+        &self,
+        request: JsonValue,
+        at: Option<Timestamp>,
+        max_depth: usize,
+        cache: &mut Cache,
+    ) -> Result<JsonValue, Error> {
+        let principal = field(&request, "principal")?;
+        let action = field(&request, "action")?;
+        let resource = field(&request, "resource")?;
+        let key = (principal, action, resource, at);
+        cache.decisions.lookups += 1;
+        let matches = match cache.decisions.entries.get(&key) {
+            Some(matches) => {
+                cache.decisions.hits += 1;
+                matches.clone()
+            }
+            None => {
+                let matches = self.matches(&key, max_depth, cache)?;
+                cache.decisions.entries.insert(key, matches.clone());
+                matches
+            }
+        };
+
+        let decision = if matches.is_empty() { "deny" } else { "allow" };
+        Ok(JsonValue::Object(Map::from_iter([
+            ("request".to_string(), request),
+            (
+                "decision".to_string(),
+                JsonValue::String(decision.to_string()),
+            ),
+            ("matches".to_string(), JsonValue::Array(matches)),
+        ])))
+    }
+
+    /// The permissions allowing `(principal, action, resource)` at the given time.
+    fn matches(
+        &self,
+        (principal, action, resource, at): &DecisionKey,
+        max_depth: usize,
+        cache: &mut Cache,
+    ) -> Result<Vec<JsonValue>, Error> {
+        cache.ancestors.lookups += 1;
+        let reachable = match cache.ancestors.entries.get(resource) {
+            Some(reachable) => {
+                cache.ancestors.hits += 1;
+                reachable
+            }
+            None => {
+                let reachable = self.reachable(resource, max_depth)?;
+                cache
+                    .ancestors
+                    .entries
+                    .entry(resource.clone())
+                    .or_insert(reachable)
+            }
+        };
+
+        let mut matches = Vec::new();
+        for (role, validity) in self.roles_by_principal.get(principal).into_iter().flatten() {
+            if !validity.contains(at.as_ref())? {
+                continue;
+            }
+            let key = (role.clone(), action.clone());
+            for (perm, validity) in self.perms_by_role_action.get(&key).into_iter().flatten() {
+                if !validity.contains(at.as_ref())? {
+                    continue;
+                }
+                let perm_resource = field(perm, "resource")?;
+                if reachable.contains(&perm_resource) {
+                    matches.push(perm.clone());
+                }
+            }
+        }
+        Ok(matches)
+    }
+}
+
+/// `{request, before, after, matches_before, matches_after}` when `request` is decided
+/// differently by the `(before, after)` policies; each keeps its own cache.
+pub(crate) fn changed_decision(
+    (before, after): (&Policy, &Policy),
+    request: JsonValue,
+    at: Option<Timestamp>,
+    max_depth: usize,
+    caches: &mut (Cache, Cache),
+) -> Result<Option<JsonValue>, Error> {
+    let old = before.evaluate(request.clone(), at.clone(), max_depth, &mut caches.0)?;
+    let new = after.evaluate(request.clone(), at, max_depth, &mut caches.1)?;
+    let (JsonValue::Object(mut old), JsonValue::Object(mut new)) = (old, new) else {
+        return Ok(None);
+    };
+    if old.get("decision") == new.get("decision") {
+        return Ok(None);
+    }
+    let take = |decision: &mut Map, name: &str| decision.remove(name).unwrap_or(JsonValue::Null);
+    Ok(Some(JsonValue::Object(Map::from_iter([
+        ("request".to_string(), request),
+        ("before".to_string(), take(&mut old, "decision")),
+        ("after".to_string(), take(&mut new, "decision")),
+        ("matches_before".to_string(), take(&mut old, "matches")),
+        ("matches_after".to_string(), take(&mut new, "matches")),
+    ]))))
+}
+
+/// A request's principal, action, resource, and `effective_at`.
+type DecisionKey = (String, String, String, Option<Timestamp>);
+
+/// Decisions by [`DecisionKey`] and reachable resources by requested resource, kept
+/// for one `rbac.evaluate` over a stream.
+#[derive(Default)]
+pub(crate) struct Cache {
+    decisions: Memo<DecisionKey, Vec<JsonValue>>,
+    ancestors: Memo<String, Vec<String>>,
+}
+
+impl Cache {
+    /// `decisions: 3/5 cached, ancestors: 1/2 cached`.
+    pub(crate) fn hit_rates(&self) -> String {
+        format!(
+            "decisions: {}/{} cached, ancestors: {}/{} cached",
+            self.decisions.hits,
+            self.decisions.lookups,
+            self.ancestors.hits,
+            self.ancestors.lookups
+        )
+    }
+}
+
+struct Memo<K, V> {
+    entries: HashMap<K, V>,
+    lookups: usize,
+    hits: usize,
+}
+
+impl<K, V> Default for Memo<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            lookups: 0,
+            hits: 0,
+        }
+    }
+}
+
+/// An `effective_at`, `valid_from`, or `valid_until` time: epoch milliseconds or a
+/// string such as an ISO-8601 timestamp, compared as text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Timestamp {
+    Millis(i64),
+    Text(String),
+}
+
+impl Timestamp {
+    fn of_json(value: &JsonValue) -> Option<Self> {
+        match value {
+            JsonValue::Number(n) => n.as_i64().map(Timestamp::Millis),
+            JsonValue::String(text) => Some(Timestamp::Text(text.clone())),
+            _ => None,
+        }
+    }
+
+    /// Fails when one side is a number and the other a string.
+    fn at_or_before(&self, other: &Timestamp) -> Result<bool, Error> {
+        match (self, other) {
+            (Timestamp::Millis(a), Timestamp::Millis(b)) => Ok(a <= b),
+            (Timestamp::Text(a), Timestamp::Text(b)) => Ok(a <= b),
+            _ => Err(Error::mismatch(
+                self.kind(),
+                format!(
+                    "rbac.evaluate: cannot compare {} with {}",
+                    self.describe(),
+                    other.describe()
+                ),
+            )
+            .got(other.kind())),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Timestamp::Millis(_) => "a number",
+            Timestamp::Text(_) => "a string",
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Timestamp::Millis(ms) => format!("number {ms}"),
+            Timestamp::Text(text) => format!("string {text:?}"),
+        }
+    }
+}
+
+/// When a binding or permission row applies; unbounded on a missing or null side.
+#[derive(Debug, Clone, Default)]
+struct Validity {
+    from: Option<Timestamp>,
+    until: Option<Timestamp>,
+}
+
+impl Validity {
+    fn of(row: &JsonValue, table: &str) -> Result<Self, Error> {
+        let bound = |name: &str| match row {
+            JsonValue::Object(map) => match map.get(name) {
+                None | Some(JsonValue::Null) => Ok(None),
+                Some(value) => Timestamp::of_json(value).map(Some).ok_or_else(|| {
+                    Error::mismatch(
+                        "a number, string, or null",
+                        format!("{table} {name} must be a number, string, or null"),
+                    )
+                }),
+            },
+            _ => Ok(None),
+        };
+        Ok(Self {
+            from: bound("valid_from")?,
+            until: bound("valid_until")?,
+        })
+    }
+
+    /// Whether the row applies at `at`; every row applies without a time.
+    fn contains(&self, at: Option<&Timestamp>) -> Result<bool, Error> {
+        let Some(at) = at else {
+            return Ok(true);
+        };
+        if let Some(from) = &self.from {
+            if !from.at_or_before(at)? {
+                return Ok(false);
+            }
+        }
+        match &self.until {
+            Some(until) => Ok(!until.at_or_before(at)?),
+            None => Ok(true),
+        }
+    }
+}
+
+fn field(value: &JsonValue, name: &str) -> Result<String, Error> {
+    match value {
+        JsonValue::Object(map) => match map.get(name) {
+            Some(JsonValue::String(value)) => Ok(value.clone()),
+            _ => Err(Error::mismatch(
+                format!("a string field '{name}'"),
+                format!("expected string field '{name}'"),
+            )),
+        },
+        _ => Err(Error::mismatch("an object", "expected object")),
+    }
+}
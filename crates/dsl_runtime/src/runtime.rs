@@ -0,0 +1,367 @@
+//! Embedder-facing runtime: a [`Runtime`] carries host-registered extensions
+//! (custom stages and host functions) into program runs.
+//!
+//! The stage registry holds host stages only. Built-in stages stay `Stage` variants
+//! built from the stage table, since they need run state a [`StageImpl`] can't see;
+//! they resolve first, and reserved namespaces keep registered names from
+//! colliding with them.
+
+use crate::async_runtime::{AsyncHostFn, AsyncSource};
+use crate::errors::{Error, UNKNOWN_STAGE};
+use crate::writer::WriterSink;
+use crate::{
+    Capabilities, OutputWriter, Outputs, Program, RunOptions, RuntimeError, RuntimeState, Sink,
+    Source, Value,
+};
+use dsl_syntax::{CallArg, Expr};
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Namespaces of built-in calls that aren't in the stage table: sources, `host.call`,
+/// and the `array.*` / `json.*` expression functions.
+const OTHER_NAMESPACES: &[&str] = &["array", "host", "input", "json"];
+
+/// Whether `name` is a namespace owned by the built-ins: that of a dotted stage in
+/// the stage table (`group` for `group.count`), a codec (`utf8` for `utf8.encode`),
+/// or one of [`OTHER_NAMESPACES`]. Custom stages may not use these and programs may
+/// not bind them.
+pub(crate) fn is_reserved_namespace(name: &str) -> bool {
+    crate::stage_args::STAGES
+        .iter()
+        .any(|(stage, _)| stage.split_once('.').is_some_and(|(ns, _)| ns == name))
+        || crate::Codec::from_name(name).is_some()
+        || OTHER_NAMESPACES.contains(&name)
+}
+
+/// A host-provided pipeline stage, e.g. `mycorp.scrub(field="email")`.
+pub trait StageImpl {
+    /// Transforms the whole input stream. `args` are the call's arguments,
+    /// evaluated on demand (as literals or per item).
+    fn apply(&self, args: &StageArgs, input: Vec<Value>) -> Result<Vec<Value>, String>;
+}
+
+impl<F> StageImpl for F
+where
+    F: Fn(&StageArgs, Vec<Value>) -> Result<Vec<Value>, String>,
+{
+    fn apply(&self, args: &StageArgs, input: Vec<Value>) -> Result<Vec<Value>, String> {
+        self(args, input)
+    }
+}
+
+/// Arguments of a custom stage call, kept as expressions so they can reference `_`.
+#[derive(Debug, Clone)]
+pub struct StageArgs {
+    args: Vec<CallArg>,
+}
+
+impl StageArgs {
+    pub(crate) fn new(args: &[CallArg]) -> Self {
+        Self {
+            args: args.to_vec(),
+        }
+    }
+
+    /// The arguments as written, e.g. `"a", limit=3`.
+    pub fn text(&self) -> String {
+        self.args
+            .iter()
+            .map(|arg| match arg {
+                CallArg::Positional(expr) => dsl_syntax::format_expr(expr),
+                CallArg::Named { name, value, .. } => {
+                    format!("{name}={}", dsl_syntax::format_expr(value))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn positional_len(&self) -> usize {
+        self.args
+            .iter()
+            .filter(|arg| matches!(arg, CallArg::Positional(_)))
+            .count()
+    }
+
+    /// Evaluates positional argument `index` without `_` bound.
+    pub fn positional(&self, index: usize) -> Result<Value, String> {
+        crate::eval_value_expr(self.positional_expr(index)?, None).map_err(|e| e.message)
+    }
+
+    /// Evaluates positional argument `index` with `_` bound to `item`.
+    pub fn positional_for(&self, index: usize, item: &Value) -> Result<Value, String> {
+        crate::eval_value_expr(self.positional_expr(index)?, Some(item)).map_err(|e| e.message)
+    }
+
+    pub fn has_named(&self, name: &str) -> bool {
+        self.named_expr(name).is_ok()
+    }
+
+    /// Evaluates named argument `name` without `_` bound.
+    pub fn named(&self, name: &str) -> Result<Value, String> {
+        crate::eval_value_expr(self.named_expr(name)?, None).map_err(|e| e.message)
+    }
+
+    /// Evaluates named argument `name` with `_` bound to `item`.
+    pub fn named_for(&self, name: &str, item: &Value) -> Result<Value, String> {
+        crate::eval_value_expr(self.named_expr(name)?, Some(item)).map_err(|e| e.message)
+    }
+
+    fn positional_expr(&self, index: usize) -> Result<&Expr, String> {
+        self.args
+            .iter()
+            .filter_map(|arg| match arg {
+                CallArg::Positional(expr) => Some(expr),
+                CallArg::Named { .. } => None,
+            })
+            .nth(index)
+            .ok_or_else(|| format!("missing positional arg {index}"))
+    }
+
+    fn named_expr(&self, name: &str) -> Result<&Expr, String> {
+        crate::named_arg(&self.args, name).map_err(|e| e.message)
+    }
+}
+
+/// A registered stage bound to one call site.
+#[derive(Clone)]
+pub(crate) struct CustomStage {
+    pub(crate) name: String,
+    pub(crate) args: StageArgs,
+    pub(crate) imp: Rc<dyn StageImpl>,
+}
+
+impl std::fmt::Debug for CustomStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomStage")
+            .field("name", &self.name)
+            .field("args", &self.args)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A host function callable from `host.call("name", arg)`; one call per item.
+pub type HostFn = Rc<dyn Fn(Value) -> Result<Value, String>>;
+
+/// `host.call` bound to its registered function.
+#[derive(Clone)]
+pub(crate) struct HostCall {
+    pub(crate) name: String,
+    pub(crate) arg: Expr,
+    pub(crate) func: HostFn,
+}
+
+impl std::fmt::Debug for HostCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostCall")
+            .field("name", &self.name)
+            .field("arg", &self.arg)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Runs programs with host extensions. The free functions ([`crate::run`] etc.)
+/// behave like a `Runtime` with nothing registered.
+#[derive(Clone, Default)]
+pub struct Runtime {
+    stages: BTreeMap<String, Rc<dyn StageImpl>>,
+    host_fns: BTreeMap<String, HostFn>,
+    pub(crate) sources: BTreeMap<String, Rc<dyn Source>>,
+    pub(crate) async_host_fns: BTreeMap<String, AsyncHostFn>,
+    pub(crate) async_sources: BTreeMap<String, AsyncSource>,
+}
+
+impl Runtime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `stage` under a namespaced `name` such as `mycorp.scrub`.
+    /// Built-in namespaces are reserved and re-registering a name replaces it.
+    pub fn register_stage(
+        &mut self,
+        name: &str,
+        stage: impl StageImpl + 'static,
+    ) -> Result<&mut Self, String> {
+        let Some((namespace, rest)) = name.split_once('.') else {
+            return Err(format!(
+                "custom stage name must be namespaced (e.g. mycorp.{name})"
+            ));
+        };
+        if namespace.is_empty() || rest.is_empty() {
+            return Err(format!("invalid custom stage name: {name}"));
+        }
+        if is_reserved_namespace(namespace) {
+            return Err(format!(
+                "custom stage {name} uses reserved namespace '{namespace}'"
+            ));
+        }
+        self.stages.insert(name.to_string(), Rc::new(stage));
+        Ok(self)
+    }
+
+    /// Registers `func` for `host.call("name", arg)`, which maps each item to
+    /// `func(arg)`. Re-registering a name replaces it.
+    pub fn register_host_fn(
+        &mut self,
+        name: &str,
+        func: impl Fn(Value) -> Result<Value, String> + 'static,
+    ) -> &mut Self {
+        self.host_fns.insert(name.to_string(), Rc::new(func));
+        self
+    }
+
+    /// Registers an input provider such as `input.sqlite`. Built-in providers
+    /// (`input.json`, `input.ndjson`, `input.bytes`, `input.http`) cannot be replaced.
+    pub fn register_source(
+        &mut self,
+        name: &str,
+        source: impl Source + 'static,
+    ) -> Result<&mut Self, String> {
+        match name.strip_prefix("input.") {
+            Some(provider) if !provider.is_empty() && !provider.contains('.') => {}
+            _ => {
+                return Err(format!(
+                    "source name must look like input.<provider>: {name}"
+                ))
+            }
+        }
+        if crate::source::BUILTIN_SOURCES.contains(&name) {
+            return Err(format!("source {name} is built in"));
+        }
+        self.sources.insert(name.to_string(), Rc::new(source));
+        Ok(self)
+    }
+
+    /// Names of all registered sources (excluding built-ins), sorted.
+    pub fn source_names(&self) -> Vec<String> {
+        self.sources.keys().cloned().collect()
+    }
+
+    /// Names of all registered custom stages, sorted.
+    pub fn stage_names(&self) -> Vec<String> {
+        self.stages.keys().cloned().collect()
+    }
+
+    /// The enabled cargo features plus every stage and source a program run on
+    /// this runtime can call, built-in or registered.
+    pub fn capabilities(&self) -> Capabilities {
+        let mut capabilities = crate::capabilities();
+        capabilities.stages.extend(self.stage_names());
+        capabilities.stages.sort();
+        capabilities.sources.extend(self.source_names());
+        capabilities.sources.sort();
+        capabilities
+    }
+
+    pub fn run(&self, program: &str, fixtures: JsonValue) -> Result<Outputs, RuntimeError> {
+        crate::run_with_state(program, fixtures, self.state())
+    }
+
+    pub fn run_compiled(
+        &self,
+        program: &Program,
+        fixtures: JsonValue,
+    ) -> Result<Outputs, RuntimeError> {
+        crate::run_program(program, None, fixtures, self.state())
+    }
+
+    /// Like [`Runtime::run`], with params, host constants, and explain level from
+    /// `options`.
+    pub fn run_with_options(
+        &self,
+        program: &str,
+        fixtures: JsonValue,
+        options: &RunOptions,
+    ) -> Result<Outputs, RuntimeError> {
+        let compiled = options.bind(&crate::compile_with(
+            program,
+            options.rebinding,
+            &options.parse_limits,
+        )?)?;
+        if let Some(sample) = options.sample {
+            return crate::sample::run(&compiled, program, fixtures, options, Some(self), sample);
+        }
+        let state = RuntimeState {
+            runtime: Some(self),
+            ..options.state()
+        };
+        crate::run_program(&compiled, Some(program), fixtures, state)
+    }
+
+    /// Like [`Runtime::run`], routing sink stages through `sink` (see [`Sink`]).
+    pub fn run_with_sink(
+        &self,
+        program: &str,
+        fixtures: JsonValue,
+        sink: &mut dyn Sink,
+    ) -> Result<Outputs, RuntimeError> {
+        let state = RuntimeState {
+            sink: Some(sink),
+            ..self.state()
+        };
+        crate::run_with_state(program, fixtures, state)
+    }
+
+    /// Like [`Runtime::run`], writing table rows and log records to `writer` (see
+    /// [`crate::run_with_writer`]).
+    pub fn run_with_writer(
+        &self,
+        program: &str,
+        fixtures: JsonValue,
+        writer: &mut dyn OutputWriter,
+    ) -> Result<Outputs, RuntimeError> {
+        let mut sink = WriterSink(writer);
+        let mut outputs = self.run_with_sink(program, fixtures, &mut sink)?;
+        sink.0.finish()?;
+        outputs.logs.retain(|_, records| !records.is_empty());
+        Ok(outputs)
+    }
+
+    /// Like [`crate::run_workspace`], with this runtime's stages, sources, and host
+    /// functions available to every program.
+    pub fn run_workspace(
+        &self,
+        programs: BTreeMap<String, &str>,
+        fixtures: JsonValue,
+    ) -> Result<BTreeMap<String, Outputs>, RuntimeError> {
+        crate::run_workspace_with(programs, fixtures, || self.state())
+    }
+
+    pub(crate) fn stage(&self, name: &str, args: &[CallArg]) -> Option<CustomStage> {
+        self.stages.get(name).map(|imp| CustomStage {
+            name: name.to_string(),
+            args: StageArgs::new(args),
+            imp: Rc::clone(imp),
+        })
+    }
+
+    pub(crate) fn source(&self, name: &str) -> Option<Rc<dyn Source>> {
+        self.sources.get(name).cloned()
+    }
+
+    pub(crate) fn host_call(&self, name: &str, arg: &Expr) -> Result<HostCall, Error> {
+        let func = self.host_fns.get(name).ok_or_else(|| {
+            if self.async_host_fns.contains_key(name) {
+                Error::from(format!(
+                    "host function {name} is async; use Runtime::run_async"
+                ))
+            } else {
+                Error::new(UNKNOWN_STAGE, format!("unknown host function: {name}"))
+            }
+        })?;
+        Ok(HostCall {
+            name: name.to_string(),
+            arg: arg.clone(),
+            func: Rc::clone(func),
+        })
+    }
+
+    fn state(&self) -> RuntimeState<'_> {
+        RuntimeState {
+            runtime: Some(self),
+            ..RuntimeState::default()
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Profiling on a fixture sample ([`RunOptions::sample_input`]): runs the program on
+//! a deterministic subset of every fixture and scales the per-stage stats back up to
+//! the full input.
+//!
+//! Stage stats are timed with `std::time::Instant`, so this is for native hosts only.
+
+use crate::bench::StageTiming;
+use crate::generate::SplitMix64;
+use crate::{run_program, Outputs, Program, RunOptions, Runtime, RuntimeError, RuntimeState};
+use serde_json::Value as JsonValue;
+
+/// How many items to keep per fixture, and the seed choosing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sample {
+    pub items: usize,
+    pub seed: u64,
+}
+
+/// What a sampled run saw and what a full run would likely see.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SampleReport {
+    /// Items across all fixtures.
+    pub fixture_items: usize,
+    /// Items the sampled run read.
+    pub sampled_items: usize,
+    /// Top-level pipeline stages as they ran on the sample.
+    pub sampled: Vec<StageTiming>,
+    /// `sampled` with counts, bytes, and time multiplied by
+    /// `fixture_items / sampled_items`; exact only for stages whose cost is linear
+    /// in their input.
+    pub estimated: Vec<StageTiming>,
+}
+
+impl SampleReport {
+    /// `fixture_items / sampled_items` (1 when nothing was sampled).
+    pub fn scale(&self) -> f64 {
+        if self.sampled_items == 0 {
+            1.0
+        } else {
+            self.fixture_items as f64 / self.sampled_items as f64
+        }
+    }
+}
+
+pub(crate) fn run(
+    program: &Program,
+    source: &str,
+    mut fixtures: JsonValue,
+    options: &RunOptions,
+    runtime: Option<&Runtime>,
+    sample: Sample,
+) -> Result<Outputs, RuntimeError> {
+    let mut rng = SplitMix64(sample.seed);
+    let (mut fixture_items, mut sampled_items) = (0, 0);
+    if let JsonValue::Object(map) = &mut fixtures {
+        for value in map.values_mut() {
+            let JsonValue::Array(items) = value else {
+                fixture_items += 1;
+                sampled_items += 1;
+                continue;
+            };
+            fixture_items += items.len();
+            *items = select(std::mem::take(items), sample.items, &mut rng);
+            sampled_items += items.len();
+        }
+    }
+
+    let mut sampled = Vec::new();
+    let state = RuntimeState {
+        runtime,
+        timings: Some(&mut sampled),
+        ..options.state()
+    };
+    let mut outputs = run_program(program, Some(source), fixtures, state)?;
+    let mut report = SampleReport {
+        fixture_items,
+        sampled_items,
+        estimated: Vec::new(),
+        sampled,
+    };
+    let scale = report.scale();
+    let grow = |n: usize| (n as f64 * scale).round() as usize;
+    report.estimated = report
+        .sampled
+        .iter()
+        .map(|timing| StageTiming {
+            stage: timing.stage.clone(),
+            items_in: grow(timing.items_in),
+            items_out: grow(timing.items_out),
+            bytes_out: grow(timing.bytes_out),
+            elapsed: timing.elapsed.mul_f64(scale),
+        })
+        .collect();
+    outputs.sample = Some(report);
+    Ok(outputs)
+}
+
+/// Keeps `n` of `items`, each equally likely, in their original order.
+fn select(items: Vec<JsonValue>, n: usize, rng: &mut SplitMix64) -> Vec<JsonValue> {
+    let len = items.len();
+    if len <= n {
+        return items;
+    }
+    let mut kept = 0;
+    items
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, _)| {
+            let keep = rng.next() % ((len - idx) as u64) < (n - kept) as u64;
+            kept += usize::from(keep);
+            keep
+        })
+        .map(|(_, item)| item)
+        .collect()
+}
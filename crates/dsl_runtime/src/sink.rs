@@ -0,0 +1,40 @@
+//! Embedder-provided destinations for sink stages.
+
+use crate::{LogRecord, Value};
+use serde_json::Value as JsonValue;
+
+/// Receives items from `ui.table`, `ui.log`, and `sink.custom`. Each method
+/// returns `Ok(true)` once it has taken the item; `Ok(false)` keeps the default
+/// behavior (buffer in `Outputs`, or an error for `sink.custom`).
+pub trait Sink {
+    fn table_row(&mut self, table: &str, row: &JsonValue) -> Result<bool, String> {
+        let _ = (table, row);
+        Ok(false)
+    }
+
+    fn log_line(&mut self, log: &str, line: &str) -> Result<bool, String> {
+        let _ = (log, line);
+        Ok(false)
+    }
+
+    /// Structured form of [`Sink::log_line`]; by default forwards `record.message`
+    /// there.
+    fn log_record(&mut self, log: &str, record: &LogRecord) -> Result<bool, String> {
+        self.log_line(log, &record.message)
+    }
+
+    fn custom(&mut self, name: &str, item: &Value) -> Result<bool, String> {
+        let _ = (name, item);
+        Ok(false)
+    }
+}
+
+/// Adapts a row callback (as used by [`crate::run_streaming`]) to a [`Sink`].
+pub(crate) struct TableRowCallback<F>(pub(crate) F);
+
+impl<F: FnMut(&str, JsonValue)> Sink for TableRowCallback<F> {
+    fn table_row(&mut self, table: &str, row: &JsonValue) -> Result<bool, String> {
+        (self.0)(table, row.clone());
+        Ok(true)
+    }
+}
@@ -0,0 +1,199 @@
+//! [`Session::serialize`] and [`Session::restore`]: a session's fixtures, bindings,
+//! constants, and `kv.load` stores as one JSON value, so a host can keep a session
+//! across page reloads.
+
+use crate::{json_to_value, Binding, RuntimeError, Session, Stream};
+use dsl_syntax::{format_expr, Stmt};
+use serde_json::{Map, Value as JsonValue};
+use std::rc::Rc;
+
+/// Bumped whenever the snapshot layout changes; [`Session::restore`] refuses others.
+const SNAPSHOT_VERSION: i64 = 1;
+
+impl Session {
+    /// The session as JSON:
+    ///
+    /// `{"version": 1, "fixtures": {...}, "streams": {name: [item, ...]},
+    /// "stages": [{"name", "source"}, ...], "constants": {...},
+    /// "kv": {store: {key: value}}, "versions": {name: "hex"}}`
+    ///
+    /// Stream items are in their output JSON form, so `Bytes` come back as integer
+    /// arrays and tagged values as `{tag, value}` records. Stage bindings are kept
+    /// as source and re-evaluated by [`Session::restore`], in the order they were
+    /// bound. `rbac.load` policies are not saved; [`crate::run_statement`] reloads them
+    /// when a statement needs one. Sessions have no clock to save: the simulated
+    /// clock of `throttle` without `ts=` restarts at 0 for every stage it runs in.
+    pub fn serialize(&self) -> JsonValue {
+        let mut out = Map::new();
+        out.insert(
+            "version".to_string(),
+            JsonValue::Number(SNAPSHOT_VERSION.into()),
+        );
+        out.insert(
+            "fixtures".to_string(),
+            JsonValue::Object(
+                self.fixtures
+                    .iter()
+                    .map(|(name, items)| (name.clone(), JsonValue::Array(items.clone())))
+                    .collect(),
+            ),
+        );
+        let streams = self
+            .env
+            .iter()
+            .filter_map(|(name, binding)| match binding {
+                Binding::Stream(stream) => Some((
+                    name.clone(),
+                    JsonValue::Array(stream.values.iter().map(|v| v.to_json()).collect()),
+                )),
+                Binding::Stage(_) => None,
+            })
+            .collect();
+        out.insert("streams".to_string(), JsonValue::Object(streams));
+        let stages = self
+            .stage_sources
+            .iter()
+            .map(|(name, source)| {
+                let mut stage = Map::new();
+                stage.insert("name".to_string(), JsonValue::String(name.clone()));
+                stage.insert("source".to_string(), JsonValue::String(source.clone()));
+                JsonValue::Object(stage)
+            })
+            .collect();
+        out.insert("stages".to_string(), JsonValue::Array(stages));
+        out.insert(
+            "constants".to_string(),
+            JsonValue::Object(self.constants.clone()),
+        );
+        #[cfg(feature = "kv")]
+        out.insert(
+            "kv".to_string(),
+            JsonValue::Object(
+                self.state
+                    .kv_stores
+                    .iter()
+                    .map(|(store, entries)| {
+                        let entries = entries
+                            .iter()
+                            .map(|(key, value)| (key.clone(), value.to_json()))
+                            .collect();
+                        (store.clone(), JsonValue::Object(entries))
+                    })
+                    .collect(),
+            ),
+        );
+        let versions = self
+            .versions
+            .iter()
+            .filter(|(name, _)| !name.starts_with("rbac:"))
+            .map(|(name, version)| (name.clone(), JsonValue::String(format!("{version:016x}"))))
+            .collect();
+        out.insert("versions".to_string(), JsonValue::Object(versions));
+        JsonValue::Object(out)
+    }
+
+    /// A session rebuilt from [`Session::serialize`] output.
+    pub fn restore(snapshot: JsonValue) -> Result<Session, RuntimeError> {
+        let JsonValue::Object(mut snapshot) = snapshot else {
+            return Err(invalid("expected a JSON object"));
+        };
+        match snapshot.get("version") {
+            Some(JsonValue::Number(n)) if n.as_i64() == Some(SNAPSHOT_VERSION) => {}
+            Some(JsonValue::Number(n)) => {
+                return Err(invalid(&format!(
+                    "unsupported version {}",
+                    n.as_i64().unwrap_or_default()
+                )))
+            }
+            _ => return Err(invalid("missing version")),
+        }
+        let fixtures = snapshot.remove("fixtures").unwrap_or(JsonValue::Null);
+        let mut session = Session::new(fixtures)?;
+
+        for (name, items) in object(&mut snapshot, "streams")? {
+            let JsonValue::Array(items) = items else {
+                return Err(invalid(&format!("stream {name} must be an array")));
+            };
+            let values = items
+                .into_iter()
+                .map(|item| json_to_value(item, &mut session.state.interner))
+                .collect();
+            session
+                .env
+                .insert(name, Binding::Stream(Rc::new(Stream::new(values))));
+        }
+        session.constants = object(&mut snapshot, "constants")?;
+        #[cfg(feature = "kv")]
+        for (store, entries) in object(&mut snapshot, "kv")? {
+            let JsonValue::Object(entries) = entries else {
+                return Err(invalid(&format!("kv store {store} must be an object")));
+            };
+            let entries = entries
+                .into_iter()
+                .map(|(key, value)| (key, json_to_value(value, &mut session.state.interner)))
+                .collect();
+            session.state.kv_stores.insert(store, entries);
+        }
+        let stages = match snapshot.remove("stages") {
+            Some(JsonValue::Array(stages)) => stages,
+            None => Vec::new(),
+            Some(_) => return Err(invalid("stages must be an array")),
+        };
+        for stage in stages {
+            let (Some(JsonValue::String(name)), Some(JsonValue::String(source))) =
+                (field(&stage, "name"), field(&stage, "source"))
+            else {
+                return Err(invalid("each stage needs a string name and source"));
+            };
+            session
+                .eval(&format!("{name} := {source};"))
+                .map_err(|e| invalid(&format!("stage {name}: {e}")))?;
+        }
+        for (name, version) in object(&mut snapshot, "versions")? {
+            let version = match &version {
+                JsonValue::String(hex) => u64::from_str_radix(hex, 16).ok(),
+                _ => None,
+            }
+            .ok_or_else(|| invalid(&format!("version of {name} must be a hex string")))?;
+            session.versions.insert(name, version);
+        }
+        session.last_explain.clear();
+        Ok(session)
+    }
+
+    /// Keeps the source of a stage binding `stmt` just made, for [`Session::serialize`].
+    pub(crate) fn note_binding(&mut self, stmt: &Stmt) {
+        let names = match stmt {
+            Stmt::Binding { name, .. } => std::slice::from_ref(name),
+            Stmt::Destructure { names, .. } => names.as_slice(),
+            Stmt::Pipeline { .. } | Stmt::Const { .. } => return,
+        };
+        self.stage_sources
+            .retain(|(bound, _)| !names.contains(bound));
+        if let Stmt::Binding { name, expr, .. } = stmt {
+            if matches!(self.env.get(name), Some(Binding::Stage(_))) {
+                self.stage_sources.push((name.clone(), format_expr(expr)));
+            }
+        }
+    }
+}
+
+fn invalid(reason: &str) -> RuntimeError {
+    format!("invalid session snapshot: {reason}").into()
+}
+
+fn field<'a>(value: &'a JsonValue, name: &str) -> Option<&'a JsonValue> {
+    match value {
+        JsonValue::Object(map) => map.get(name),
+        _ => None,
+    }
+}
+
+/// Removes the object `key` from `snapshot`; a missing key is an empty object.
+fn object(snapshot: &mut Map, key: &str) -> Result<Map, RuntimeError> {
+    match snapshot.remove(key) {
+        Some(JsonValue::Object(map)) => Ok(map),
+        None => Ok(Map::new()),
+        Some(_) => Err(invalid(&format!("{key} must be an object"))),
+    }
+}
@@ -0,0 +1,288 @@
+//! Input providers: `input.json` and friends are built-in [`Source`]s, and
+//! embedders register more (`input.sqlite`, ...) on a `Runtime`.
+
+use crate::errors::Error;
+use crate::intern::Interner;
+use crate::{StageArgs, Value};
+use serde_json::Value as JsonValue;
+use std::cell::{RefCell, RefMut};
+use std::collections::BTreeMap;
+
+/// Produces the initial stream for an `input.<provider>(...)` call.
+pub trait Source {
+    fn read(&self, args: &StageArgs, ctx: &SourceContext<'_>) -> Result<Vec<Value>, String>;
+
+    /// Text after `[source] ` in explain output; `call` is the call as written.
+    fn explain(&self, call: &str, args: &StageArgs) -> String {
+        let _ = args;
+        call.to_string()
+    }
+}
+
+/// What a source can see of the current run.
+pub struct SourceContext<'a> {
+    fixtures: &'a BTreeMap<String, Vec<JsonValue>>,
+    interner: RefCell<&'a mut Interner>,
+    /// The coded error behind the last `String` error handed to the source, so the
+    /// run reports its catalog code when the source passes the message on.
+    raised: RefCell<Option<Error>>,
+}
+
+impl<'a> SourceContext<'a> {
+    pub(crate) fn new(
+        fixtures: &'a BTreeMap<String, Vec<JsonValue>>,
+        interner: &'a mut Interner,
+    ) -> Self {
+        Self {
+            fixtures,
+            interner: RefCell::new(interner),
+            raised: RefCell::new(None),
+        }
+    }
+
+    pub fn fixture(&self, name: &str) -> Result<&'a [JsonValue], String> {
+        self.fixtures
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| self.raise(Error::missing_fixture(name)))
+    }
+
+    /// `error`'s message, remembering its code for [`SourceContext::error`].
+    pub(crate) fn raise(&self, error: Error) -> String {
+        let message = error.message.clone();
+        *self.raised.borrow_mut() = Some(error);
+        message
+    }
+
+    /// The error a source returned as `message`: the coded error it was raised as
+    /// when the source passed it on unchanged, otherwise an uncoded one.
+    pub(crate) fn error(&self, message: String) -> Error {
+        match self.raised.take() {
+            Some(raised) if raised.message == message => raised,
+            _ => message.into(),
+        }
+    }
+
+    /// `json` as a value, with record keys interned by the run like fixture records.
+    pub fn to_value(&self, json: JsonValue) -> Value {
+        crate::json_to_value(json, &mut self.interner())
+    }
+
+    pub(crate) fn interner(&self) -> RefMut<'_, &'a mut Interner> {
+        self.interner.borrow_mut()
+    }
+}
+
+pub(crate) const BUILTIN_SOURCES: &[&str] =
+    &["input.bytes", "input.http", "input.json", "input.ndjson"];
+
+pub(crate) fn builtin(name: &str) -> Option<&'static dyn Source> {
+    match name {
+        "input.json" => Some(&JsonFixture),
+        "input.http" => Some(&HttpMock),
+        #[cfg(feature = "codecs")]
+        "input.ndjson" => Some(&NdjsonFixture),
+        #[cfg(feature = "codecs")]
+        "input.bytes" => Some(&BytesFixture),
+        _ => None,
+    }
+}
+
+fn fixture_name(args: &StageArgs) -> Result<String, String> {
+    match args.positional(0)? {
+        Value::String(name) => Ok(name),
+        _ => Err("expected string literal".to_string()),
+    }
+}
+
+/// A type error from a built-in source.
+fn mismatch(ctx: &SourceContext<'_>, expected: &str, message: impl Into<String>) -> String {
+    ctx.raise(Error::mismatch(expected, message))
+}
+
+/// `input.json("name")`: each fixture entry as `Bytes` of its JSON encoding.
+struct JsonFixture;
+
+impl Source for JsonFixture {
+    fn read(&self, args: &StageArgs, ctx: &SourceContext<'_>) -> Result<Vec<Value>, String> {
+        ctx.fixture(&fixture_name(args)?)?
+            .iter()
+            .map(|item| {
+                serde_json::to_vec(item)
+                    .map(Value::Bytes)
+                    .map_err(|e| e.to_string())
+            })
+            .collect()
+    }
+
+    fn explain(&self, _call: &str, args: &StageArgs) -> String {
+        format!("input.json({})", fixture_name(args).unwrap_or_default())
+    }
+}
+
+#[cfg(feature = "codecs")]
+/// `input.ndjson("name")`: one decoded value per non-blank line of string entries.
+struct NdjsonFixture;
+
+#[cfg(feature = "codecs")]
+impl Source for NdjsonFixture {
+    fn read(&self, args: &StageArgs, ctx: &SourceContext<'_>) -> Result<Vec<Value>, String> {
+        let mut values = Vec::new();
+        for item in ctx.fixture(&fixture_name(args)?)? {
+            match item {
+                JsonValue::String(text) => values.extend(
+                    crate::codecs::parse_ndjson(text, &mut ctx.interner())
+                        .map_err(|e| ctx.raise(e))?,
+                ),
+                _ => {
+                    return Err(mismatch(
+                        ctx,
+                        "strings",
+                        "input.ndjson fixture entries must be strings",
+                    ))
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    fn explain(&self, _call: &str, args: &StageArgs) -> String {
+        format!("input.ndjson({})", fixture_name(args).unwrap_or_default())
+    }
+}
+
+#[cfg(feature = "codecs")]
+/// `input.bytes("name")`: base64 string entries decoded to `Bytes`.
+struct BytesFixture;
+
+#[cfg(feature = "codecs")]
+impl Source for BytesFixture {
+    fn read(&self, args: &StageArgs, ctx: &SourceContext<'_>) -> Result<Vec<Value>, String> {
+        ctx.fixture(&fixture_name(args)?)?
+            .iter()
+            .map(|item| match item {
+                JsonValue::String(text) => crate::codecs::base64_decode(text)
+                    .map(Value::Bytes)
+                    .map_err(|e| ctx.raise(e)),
+                _ => Err(mismatch(
+                    ctx,
+                    "base64 strings",
+                    "input.bytes fixture entries must be base64 strings",
+                )),
+            })
+            .collect()
+    }
+
+    fn explain(&self, _call: &str, args: &StageArgs) -> String {
+        format!(
+            "input.bytes({}) base64 -> Bytes",
+            fixture_name(args).unwrap_or_default()
+        )
+    }
+}
+
+/// Methods `input.http` requests may use.
+const HTTP_METHODS: &[&str] = &["DELETE", "GET", "HEAD", "OPTIONS", "PATCH", "POST", "PUT"];
+
+/// `input.http("GET /users", mock="name")`: a simulated request answered from the
+/// mock fixture `name`, whose entries are `{request?, status?, headers?, body?}`
+/// responses. Each entry whose `request` matches (or that has none) is emitted as
+/// a `{status, headers, body}` record; `status` defaults to 200, `headers` to `{}`,
+/// and `body` to `null`. No request leaves the process.
+struct HttpMock;
+
+impl HttpMock {
+    fn request(args: &StageArgs) -> Result<String, String> {
+        let Value::String(request) = args.positional(0)? else {
+            return Err("input.http request must be a string like \"GET /users\"".to_string());
+        };
+        match request.split_once(' ') {
+            Some((method, path)) if HTTP_METHODS.contains(&method) && path.starts_with('/') => {
+                Ok(request)
+            }
+            _ => Err(format!(
+                "input.http request must look like \"GET /path\" (methods: {}), got \"{request}\"",
+                HTTP_METHODS.join(", ")
+            )),
+        }
+    }
+
+    fn mock(args: &StageArgs) -> Result<String, String> {
+        match args.named("mock")? {
+            Value::String(name) => Ok(name),
+            _ => Err("input.http mock must be a fixture name string".to_string()),
+        }
+    }
+}
+
+impl Source for HttpMock {
+    fn read(&self, args: &StageArgs, ctx: &SourceContext<'_>) -> Result<Vec<Value>, String> {
+        let request = Self::request(args)?;
+        let mock = Self::mock(args)?;
+        let mut responses = Vec::new();
+        for entry in ctx.fixture(&mock)? {
+            let JsonValue::Object(entry) = entry else {
+                return Err(mismatch(ctx, "{request?, status?, headers?, body?} objects", format!(
+                    "input.http mock {mock} entries must be {{request?, status?, headers?, body?}} objects"
+                )));
+            };
+            match entry.get("request") {
+                None => {}
+                Some(JsonValue::String(answers)) if *answers == request => {}
+                Some(JsonValue::String(_)) => continue,
+                Some(_) => {
+                    return Err(mismatch(
+                        ctx,
+                        "a string",
+                        format!("input.http mock {mock} request must be a string"),
+                    ))
+                }
+            }
+            let status = match entry.get("status") {
+                None => 200,
+                Some(JsonValue::Number(n)) => n
+                    .as_i64()
+                    .filter(|status| (100..=599).contains(status))
+                    .ok_or_else(|| format!("input.http mock {mock} status must be 100..=599"))?,
+                Some(_) => {
+                    return Err(mismatch(
+                        ctx,
+                        "I64",
+                        format!("input.http mock {mock} status must be I64"),
+                    ))
+                }
+            };
+            let headers = match entry.get("headers") {
+                None => JsonValue::Object(Default::default()),
+                Some(headers @ JsonValue::Object(_)) => headers.clone(),
+                Some(_) => {
+                    return Err(mismatch(
+                        ctx,
+                        "an object",
+                        format!("input.http mock {mock} headers must be an object"),
+                    ))
+                }
+            };
+            let body = entry.get("body").cloned().unwrap_or(JsonValue::Null);
+            let mut response = serde_json::Map::new();
+            response.insert("status".to_string(), JsonValue::Number(status.into()));
+            response.insert("headers".to_string(), headers);
+            response.insert("body".to_string(), body);
+            responses.push(ctx.to_value(JsonValue::Object(response)));
+        }
+        if responses.is_empty() {
+            return Err(format!(
+                "input.http: no response in mock {mock} matches {request}"
+            ));
+        }
+        Ok(responses)
+    }
+
+    fn explain(&self, _call: &str, args: &StageArgs) -> String {
+        format!(
+            "input.http({} <- mock {}) simulated",
+            Self::request(args).unwrap_or_default(),
+            Self::mock(args).unwrap_or_default()
+        )
+    }
+}
@@ -0,0 +1,362 @@
+//! Binds a built-in stage call's arguments to the stage's parameters: positional
+//! arguments fill them in order and named ones by name, so `map(_ + 1)` and
+//! `map(f=_ + 1)` are the same call.
+
+use crate::errors::{Error, STAGE_ARGUMENTS};
+use crate::messages::Message;
+use dsl_syntax::{CallArg, Expr};
+use std::borrow::Cow;
+
+/// One parameter of a built-in stage.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Param {
+    Required(&'static str),
+    Optional(&'static str),
+    /// Optional, with a default written as DSL source.
+    Default(&'static str, &'static str),
+    /// Takes every remaining positional argument; needs at least one.
+    Variadic(&'static str),
+}
+
+use Param::{Default, Optional, Required, Variadic};
+
+impl Param {
+    fn name(self) -> &'static str {
+        match self {
+            Required(name) | Optional(name) | Default(name, _) | Variadic(name) => name,
+        }
+    }
+}
+
+/// Every built-in stage that binds its arguments, with its parameters in positional
+/// order. `select` takes its columns as named arguments of any name and isn't listed.
+pub(crate) const STAGES: &[(&str, &[Param])] = &[
+    ("map", &[Required("f")]),
+    ("flat_map", &[Required("f")]),
+    ("filter", &[Required("pred")]),
+    ("where", &[Variadic("pred")]),
+    ("having", &[Variadic("pred")]),
+    ("lens", &[Required("forward"), Required("inverse")]),
+    (
+        "group.collect_all",
+        &[
+            Required("by_key"),
+            Default("within_ms", "0"),
+            Default("limit", "1000"),
+            Default("overflow", "\"drop\""),
+            Optional("order_items_by"),
+            Default("order", "\"asc\""),
+            Default("nulls", "\"last\""),
+        ],
+    ),
+    ("group.count", &[Required("by_key")]),
+    (
+        "sessionize",
+        &[Required("by_key"), Required("gap_ms"), Required("ts")],
+    ),
+    (
+        "dedupe.within",
+        &[Required("by"), Required("within_ms"), Required("ts")],
+    ),
+    (
+        "stats.outliers",
+        &[
+            Required("field"),
+            Default("method", "\"iqr\""),
+            Default("mode", "\"flag\""),
+        ],
+    ),
+    (
+        "throttle",
+        &[
+            Required("by_key"),
+            Required("limit"),
+            Required("per_ms"),
+            Optional("ts"),
+            Default("mode", "\"drop\""),
+        ],
+    ),
+    (
+        "rank.topk",
+        &[
+            Required("k"),
+            Required("by"),
+            Required("order"),
+            Default("nulls", "\"last\""),
+        ],
+    ),
+    (
+        "sort",
+        &[
+            Required("by"),
+            Default("order", "\"asc\""),
+            Default("nulls", "\"last\""),
+        ],
+    ),
+    (
+        "rank.kmerge_arrays",
+        &[
+            Required("by"),
+            Required("order"),
+            Required("limit"),
+            Default("nulls", "\"last\""),
+        ],
+    ),
+    (
+        "group.topn_items",
+        &[
+            Required("by_key"),
+            Required("n"),
+            Required("order_by"),
+            Required("order"),
+            Default("nulls", "\"last\""),
+        ],
+    ),
+    (
+        "kv.load",
+        &[
+            Required("store"),
+            Default("key_field", "\"key\""),
+            Default("value_field", "\"value\""),
+        ],
+    ),
+    ("lookup.kv", &[Required("store"), Required("key")]),
+    (
+        "lookup.batch_kv",
+        &[
+            Required("store"),
+            Required("key"),
+            Required("batch_size"),
+            Required("within_ms"),
+        ],
+    ),
+    (
+        "rbac.load",
+        &[
+            Default("name", "\"policy\""),
+            Required("principal_bindings"),
+            Required("role_perms"),
+            Required("resource_ancestors"),
+        ],
+    ),
+    (
+        "rbac.evaluate",
+        &[
+            Optional("principal_bindings"),
+            Optional("role_perms"),
+            Optional("resource_ancestors"),
+            Optional("policy"),
+            Default("max_depth", "32"),
+            Optional("effective_at"),
+        ],
+    ),
+    (
+        "rbac.diff",
+        &[
+            Required("before"),
+            Required("after"),
+            Optional("requests"),
+            Default("max_depth", "32"),
+            Optional("effective_at"),
+        ],
+    ),
+    (
+        "graph.reachable",
+        &[
+            Required("edges"),
+            Required("from"),
+            Default("direction", "\"down\""),
+        ],
+    ),
+    (
+        "graph.shortest_path",
+        &[
+            Required("edges"),
+            Required("from"),
+            Required("to"),
+            Default("direction", "\"down\""),
+        ],
+    ),
+    ("graph.components", &[Required("edges")]),
+    (
+        "iterate",
+        &[
+            Required("step"),
+            Optional("until"),
+            Default("max_iters", "100"),
+        ],
+    ),
+    (
+        "fsm.evaluate",
+        &[
+            Required("transitions"),
+            Required("initial"),
+            Required("event"),
+            Optional("by_key"),
+        ],
+    ),
+    (
+        "ui.table",
+        &[Required("name"), Optional("columns"), Optional("format")],
+    ),
+    ("ui.log", &[Required("name"), Default("level", "\"info\"")]),
+    (
+        "ui.graph",
+        &[Required("name"), Required("node"), Required("edges")],
+    ),
+    (
+        "ui.diff",
+        &[Required("name"), Required("expected"), Optional("key")],
+    ),
+    ("sink.custom", &[Required("name")]),
+];
+
+/// The parameters of built-in stage `name`, in positional order.
+pub(crate) fn params(name: &str) -> Option<&'static [Param]> {
+    STAGES
+        .iter()
+        .find(|(stage, _)| *stage == name)
+        .map(|(_, params)| *params)
+}
+
+/// `stage(a, b?, c=1, d...)`: required, optional, defaulted, and variadic parameters.
+pub(crate) fn signature(stage: &str, params: &[Param]) -> String {
+    let params: Vec<String> = params
+        .iter()
+        .map(|param| match param {
+            Required(name) => name.to_string(),
+            Optional(name) => format!("{name}?"),
+            Default(name, default) => format!("{name}={default}"),
+            Variadic(name) => format!("{name}..."),
+        })
+        .collect();
+    format!("{stage}({})", params.join(", "))
+}
+
+/// A call's arguments bound to parameter names, defaults filled in.
+pub(crate) struct Args<'a> {
+    stage: &'a str,
+    bound: Vec<(&'static str, Cow<'a, Expr>)>,
+}
+
+impl<'a> Args<'a> {
+    /// Binds `args` to `params`. Positional arguments come first; an argument for a
+    /// parameter the stage doesn't have, a second one for the same parameter, or a
+    /// missing required one is an error.
+    pub(crate) fn bind(
+        stage: &'a str,
+        params: &'static [Param],
+        args: &'a [CallArg],
+    ) -> Result<Self, Error> {
+        let mut bound: Vec<(&'static str, Cow<'a, Expr>)> = Vec::new();
+        let mut named = false;
+        for arg in args {
+            let (param, value) = match arg {
+                CallArg::Positional(_) if named => {
+                    return Err(Error::new(
+                        STAGE_ARGUMENTS,
+                        format!("{stage}: positional arguments must come before named ones"),
+                    ))
+                }
+                CallArg::Positional(value) => {
+                    let param = params.get(bound.len()).or_else(|| match params.last() {
+                        Some(last @ Variadic(_)) => Some(last),
+                        _ => None,
+                    });
+                    let Some(param) = param else {
+                        return Err(Error::new(
+                            STAGE_ARGUMENTS,
+                            format!(
+                                "{stage}: too many arguments (expected {})",
+                                signature(stage, params)
+                            ),
+                        ));
+                    };
+                    (param.name(), value)
+                }
+                CallArg::Named { name, value, .. } => {
+                    named = true;
+                    let Some(param) = params.iter().find(|param| param.name() == name) else {
+                        let unbound: Vec<&str> = params
+                            .iter()
+                            .map(|param| param.name())
+                            .filter(|param| !bound.iter().any(|(p, _)| p == param))
+                            .collect();
+                        return Err(Error::new(
+                            STAGE_ARGUMENTS,
+                            format!(
+                                "{stage}: unknown argument {name} (expected {})",
+                                one_of(&unbound)
+                            ),
+                        ));
+                    };
+                    if bound.iter().any(|(p, _)| p == name) {
+                        return Err(Error::new(
+                            STAGE_ARGUMENTS,
+                            format!("{stage}: {name} is given twice"),
+                        ));
+                    }
+                    (param.name(), value)
+                }
+            };
+            bound.push((param, Cow::Borrowed(value)));
+        }
+        for param in params {
+            if bound.iter().any(|(name, _)| *name == param.name()) {
+                continue;
+            }
+            match param {
+                Required(name) | Variadic(name) => {
+                    return Err(Error::templated(
+                        STAGE_ARGUMENTS,
+                        Message::new("missing_argument")
+                            .arg("stage", stage)
+                            .arg("name", name)
+                            .arg("signature", signature(stage, params)),
+                    ))
+                }
+                Default(name, default) => {
+                    let value = dsl_syntax::parse_expr(default).expect("valid default");
+                    bound.push((name, Cow::Owned(value)));
+                }
+                Optional(_) => {}
+            }
+        }
+        Ok(Self { stage, bound })
+    }
+
+    pub(crate) fn get(&self, param: &str) -> Result<&Expr, Error> {
+        self.opt(param).ok_or_else(|| {
+            Error::new(
+                STAGE_ARGUMENTS,
+                format!("{}: missing argument {param}", self.stage),
+            )
+        })
+    }
+
+    pub(crate) fn opt(&self, param: &str) -> Option<&Expr> {
+        self.bound
+            .iter()
+            .find(|(name, _)| *name == param)
+            .map(|(_, value)| value.as_ref())
+    }
+
+    /// Every argument bound to variadic `param`, in order.
+    pub(crate) fn all(&self, param: &str) -> Vec<&Expr> {
+        self.bound
+            .iter()
+            .filter(|(name, _)| *name == param)
+            .map(|(_, value)| value.as_ref())
+            .collect()
+    }
+}
+
+/// `a`, `a or b`, `a, b, or c`.
+fn one_of(names: &[&str]) -> String {
+    match names {
+        [] => "no more arguments".to_string(),
+        [one] => one.to_string(),
+        [a, b] => format!("{a} or {b}"),
+        [rest @ .., last] => format!("{}, or {last}", rest.join(", ")),
+    }
+}
@@ -0,0 +1,66 @@
+//! `stats.outliers(field=_.latency, method="iqr")`: flags values outside fences
+//! computed over the whole stream.
+
+use crate::errors::{Error, STAGE_ARGUMENTS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutlierMethod {
+    /// Outside `[q1 - 1.5 * iqr, q3 + 1.5 * iqr]` (linearly interpolated quartiles).
+    Iqr,
+    /// More than 3 population standard deviations from the mean.
+    ZScore,
+}
+
+impl OutlierMethod {
+    pub(crate) fn parse(name: &str) -> Result<Self, Error> {
+        match name {
+            "iqr" => Ok(OutlierMethod::Iqr),
+            "zscore" => Ok(OutlierMethod::ZScore),
+            _ => Err(Error::new(
+                STAGE_ARGUMENTS,
+                format!("stats.outliers: unknown method {name} (expected iqr or zscore)"),
+            )),
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            OutlierMethod::Iqr => "iqr",
+            OutlierMethod::ZScore => "zscore",
+        }
+    }
+
+    /// `(low, high)`; values outside are outliers. `None` for no values.
+    pub(crate) fn fences(self, values: &[i64]) -> Option<(f64, f64)> {
+        if values.is_empty() {
+            return None;
+        }
+        match self {
+            OutlierMethod::Iqr => {
+                let mut sorted = values.to_vec();
+                sorted.sort_unstable();
+                let (q1, q3) = (quantile(&sorted, 0.25), quantile(&sorted, 0.75));
+                let reach = 1.5 * (q3 - q1);
+                Some((q1 - reach, q3 + reach))
+            }
+            OutlierMethod::ZScore => {
+                let n = values.len() as f64;
+                let mean = values.iter().map(|v| *v as f64).sum::<f64>() / n;
+                let variance = values
+                    .iter()
+                    .map(|v| (*v as f64 - mean).powi(2))
+                    .sum::<f64>()
+                    / n;
+                let reach = 3.0 * variance.sqrt();
+                Some((mean - reach, mean + reach))
+            }
+        }
+    }
+}
+
+fn quantile(sorted: &[i64], p: f64) -> f64 {
+    let pos = p * (sorted.len() - 1) as f64;
+    let (lo, frac) = (pos.floor() as usize, pos.fract());
+    let hi = (lo + 1).min(sorted.len() - 1);
+    sorted[lo] as f64 + frac * (sorted[hi] - sorted[lo]) as f64
+}
@@ -0,0 +1,67 @@
+//! "Did you mean" hints for unknown stage and identifier names: the known names
+//! within a few edits of the misspelled one.
+
+/// At most this many names are suggested.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// The names in `known` closest to `name`, nearest first (ties alphabetically). A name
+/// qualifies when it is at most a third of `name`'s length in edits away (at least one).
+pub(crate) fn nearest<'a>(name: &str, known: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut close: Vec<(usize, &str)> = known
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    close.sort();
+    close.dedup();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// ` (did you mean `a`, `b`, or `c`?)`, or nothing without suggestions.
+pub(crate) fn hint(suggestions: &[String]) -> String {
+    let quoted: Vec<String> = suggestions.iter().map(|name| format!("`{name}`")).collect();
+    match quoted.as_slice() {
+        [] => String::new(),
+        [one] => format!(" (did you mean {one}?)"),
+        [first, second] => format!(" (did you mean {first} or {second}?)"),
+        [rest @ .., last] => format!(" (did you mean {}, or {last}?)", rest.join(", ")),
+    }
+}
+
+/// The names an error message suggests with a [`hint`], for hosts that show them as
+/// quick fixes; empty when the message has none.
+pub fn suggestions(message: &str) -> Vec<String> {
+    let Some((_, rest)) = message.split_once(" (did you mean ") else {
+        return Vec::new();
+    };
+    let Some((names, _)) = rest.split_once("?)") else {
+        return Vec::new();
+    };
+    names
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Levenshtein distance between `a` and `b`, counted in characters.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(ca != *cb);
+            current.push(substitute.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
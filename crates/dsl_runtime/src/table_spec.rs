@@ -0,0 +1,224 @@
+//! `ui.table("out", columns=[...], format={col: "%.2f"})`: column order and
+//! per-column cell formatting.
+
+use crate::errors::{Error, STAGE_ARGUMENTS};
+use crate::stage_args::Args;
+use dsl_syntax::Expr;
+use serde_json::Value as JsonValue;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TableSpec {
+    pub(crate) name: String,
+    /// Requested column order; rows keep only these fields.
+    pub(crate) columns: Option<Vec<String>>,
+    pub(crate) format: Vec<(String, CellFormat)>,
+}
+
+impl TableSpec {
+    pub(crate) fn parse(args: &Args) -> Result<Self, Error> {
+        let Expr::String { value: name, .. } = args.get("name")? else {
+            return Err(Error::mismatch(
+                "a string literal",
+                "expected string literal",
+            ));
+        };
+        Ok(TableSpec {
+            name: name.clone(),
+            columns: args.opt("columns").map(parse_columns).transpose()?,
+            format: match args.opt("format") {
+                Some(format) => parse_formats(format)?,
+                None => Vec::new(),
+            },
+        })
+    }
+
+    /// Shapes one output row: projects it to `columns`, then formats cells. Rows
+    /// that aren't records are formatted as the `value` column.
+    pub(crate) fn shape(&self, row: JsonValue) -> Result<JsonValue, Error> {
+        let mut row = match (&self.columns, row) {
+            (None, row) => row,
+            (Some(columns), JsonValue::Object(mut map)) => JsonValue::Object(
+                columns
+                    .iter()
+                    .map(|column| {
+                        let cell = map.remove(column).unwrap_or(JsonValue::Null);
+                        (column.clone(), cell)
+                    })
+                    .collect(),
+            ),
+            (Some(_), _) => {
+                return Err(Error::mismatch(
+                    "record rows",
+                    format!("ui.table({}) columns= expects record rows", self.name),
+                ))
+            }
+        };
+        for (column, format) in &self.format {
+            let cell = match &mut row {
+                JsonValue::Object(map) => match map.get_mut(column) {
+                    Some(cell) => cell,
+                    None => continue,
+                },
+                other if column == "value" => other,
+                _ => continue,
+            };
+            if !matches!(cell, JsonValue::Null) {
+                *cell = JsonValue::String(format.apply(cell).map_err(|e| {
+                    e.map_message(|e| format!("ui.table({}) format for {column}: {e}", self.name))
+                })?);
+            }
+        }
+        Ok(row)
+    }
+}
+
+fn parse_columns(value: &Expr) -> Result<Vec<String>, Error> {
+    let not_strings = || {
+        Error::new(
+            STAGE_ARGUMENTS,
+            "ui.table columns must be an array of strings",
+        )
+    };
+    let Expr::Array { items, .. } = value else {
+        return Err(not_strings());
+    };
+    let mut columns: Vec<String> = Vec::new();
+    for item in items {
+        let Expr::String { value, .. } = item else {
+            return Err(not_strings());
+        };
+        if columns.contains(value) {
+            return Err(Error::new(
+                STAGE_ARGUMENTS,
+                format!("ui.table columns: {value} is listed twice"),
+            ));
+        }
+        columns.push(value.clone());
+    }
+    Ok(columns)
+}
+
+fn parse_formats(value: &Expr) -> Result<Vec<(String, CellFormat)>, Error> {
+    let Expr::Record { fields, .. } = value else {
+        return Err(Error::new(
+            STAGE_ARGUMENTS,
+            "ui.table format must be a record of format strings",
+        ));
+    };
+    fields
+        .iter()
+        .map(|field| match &field.value {
+            Expr::String { value, .. } => CellFormat::parse(value)
+                .map(|format| (field.name.clone(), format))
+                .map_err(|e| {
+                    Error::new(
+                        STAGE_ARGUMENTS,
+                        format!("ui.table format for {}: {e}", field.name),
+                    )
+                }),
+            _ => Err(Error::new(
+                STAGE_ARGUMENTS,
+                format!(
+                    "ui.table format for {}: expected a format string",
+                    field.name
+                ),
+            )),
+        })
+        .collect()
+}
+
+/// A printf-style template: `%s` (any value), `%d` (integer), `%.Nf` (fixed
+/// decimals), and `%%`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CellFormat(Vec<Piece>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Any,
+    Integer,
+    Fixed(usize),
+}
+
+impl CellFormat {
+    pub(crate) fn parse(template: &str) -> Result<Self, String> {
+        let unsupported = || format!("unsupported format \"{template}\" (use %s, %d, %.Nf, or %%)");
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            let piece = match chars.next() {
+                Some('%') => {
+                    text.push('%');
+                    continue;
+                }
+                Some('s') => Piece::Any,
+                Some('d') => Piece::Integer,
+                Some('.') => {
+                    let mut digits = String::new();
+                    while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(d);
+                    }
+                    match (digits.parse(), chars.next()) {
+                        (Ok(places), Some('f')) => Piece::Fixed(places),
+                        _ => return Err(unsupported()),
+                    }
+                }
+                _ => return Err(unsupported()),
+            };
+            if !text.is_empty() {
+                pieces.push(Piece::Text(std::mem::take(&mut text)));
+            }
+            pieces.push(piece);
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(CellFormat(pieces))
+    }
+
+    pub(crate) fn apply(&self, cell: &JsonValue) -> Result<String, Error> {
+        let mut out = String::new();
+        for piece in &self.0 {
+            match (piece, cell) {
+                (Piece::Text(text), _) => out.push_str(text),
+                (Piece::Any, JsonValue::String(s)) => out.push_str(s),
+                (Piece::Any, other) => {
+                    out.push_str(&serde_json::to_string(other).map_err(|e| e.to_string())?)
+                }
+                (Piece::Integer | Piece::Fixed(0), JsonValue::Number(n)) => {
+                    out.push_str(&n.as_i64().unwrap_or_default().to_string())
+                }
+                // Numbers are integers, so the decimals are always zero.
+                (Piece::Fixed(places), JsonValue::Number(n)) => out.push_str(&format!(
+                    "{}.{}",
+                    n.as_i64().unwrap_or_default(),
+                    "0".repeat(*places)
+                )),
+                (Piece::Integer | Piece::Fixed(_), other) => {
+                    return Err(Error::mismatch(
+                        "a number",
+                        format!("expected a number, got {}", kind(other)),
+                    )
+                    .got(kind(other)))
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn kind(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "a boolean",
+        JsonValue::Number(_) => "a number",
+        JsonValue::String(_) => "a string",
+        JsonValue::Array(_) => "an array",
+        JsonValue::Object(_) => "a record",
+    }
+}
@@ -0,0 +1,202 @@
+//! Golden-file helpers for testing DSL programs.
+//!
+//! ```no_run
+//! use serde_json::json;
+//!
+//! dsl_runtime::testing::assert_program_snapshot(
+//!     r#"input.json("xs") |> ui.table("out");"#,
+//!     json!({"xs": [1]}),
+//!     "tests/snapshots/xs.json",
+//! );
+//! ```
+//!
+//! Run with `DSL_UPDATE_SNAPSHOTS=1` to create or overwrite snapshot files.
+
+use crate::{LogRecord, Outputs, RunOptions};
+use serde_json::{Map, Value as JsonValue};
+use std::path::Path;
+
+/// Environment variable that switches [`assert_program_snapshot`] to update mode.
+pub const UPDATE_ENV: &str = "DSL_UPDATE_SNAPSHOTS";
+
+/// Lines of unchanged context shown around each difference.
+const DIFF_CONTEXT: usize = 3;
+
+/// Runs `program` and panics with a line diff if its outputs differ from the
+/// JSON snapshot at `snapshot_path`. With [`UPDATE_ENV`] set, the snapshot is
+/// written instead.
+pub fn assert_program_snapshot(
+    program: &str,
+    fixtures: JsonValue,
+    snapshot_path: impl AsRef<Path>,
+) {
+    let update = std::env::var_os(UPDATE_ENV).is_some_and(|v| !v.is_empty() && v != "0");
+    if let Err(e) = check_program_snapshot(program, fixtures, snapshot_path.as_ref(), update) {
+        panic!("{e}");
+    }
+}
+
+/// Non-panicking form of [`assert_program_snapshot`] with an explicit update flag.
+pub fn check_program_snapshot(
+    program: &str,
+    fixtures: JsonValue,
+    snapshot_path: &Path,
+    update: bool,
+) -> Result<(), String> {
+    let outputs = crate::run(program, fixtures)?;
+    let actual = render_snapshot(&outputs_snapshot(&outputs))?;
+    let path = snapshot_path.display();
+
+    if update {
+        if let Some(dir) = snapshot_path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{path}: {e}"))?;
+        }
+        return std::fs::write(snapshot_path, actual).map_err(|e| format!("{path}: {e}"));
+    }
+
+    let stored = match std::fs::read_to_string(snapshot_path) {
+        Ok(stored) => stored,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(format!(
+                "snapshot {path} does not exist; rerun with {UPDATE_ENV}=1 to create it"
+            ))
+        }
+        Err(e) => return Err(format!("{path}: {e}")),
+    };
+    let expected = serde_json::from_str(&stored)
+        .map_err(|e| format!("invalid snapshot json in {path}: {e}"))
+        .and_then(|value| render_snapshot(&value))?;
+
+    if expected == actual {
+        return Ok(());
+    }
+    Err(format!(
+        "snapshot {path} does not match (- snapshot, + actual); rerun with {UPDATE_ENV}=1 to accept\n{}",
+        line_diff(&expected, &actual)
+    ))
+}
+
+/// Runs `program` `n` times in audit mode and panics unless every run has the
+/// same [`Outputs::fingerprint`], showing a diff against the first run. Returns
+/// the first run's outputs.
+pub fn run_repeatedly(program: &str, fixtures: JsonValue, n: usize) -> Outputs {
+    assert!(n > 0, "run_repeatedly needs at least one run");
+    let options = RunOptions {
+        audit: true,
+        ..RunOptions::default()
+    };
+    let run = || {
+        crate::run_with_options(program, fixtures.clone(), &options)
+            .unwrap_or_else(|e| panic!("program failed: {e}"))
+    };
+    let first = run();
+    let first_text = render_snapshot(&outputs_snapshot(&first)).expect("snapshot renders");
+    for attempt in 1..n {
+        let next = run();
+        if next.fingerprint != first.fingerprint {
+            let next_text = render_snapshot(&outputs_snapshot(&next)).expect("snapshot renders");
+            panic!(
+                "run {attempt} fingerprint {:?} differs from run 0 {:?} (- run 0, + run {attempt})\n{}",
+                next.fingerprint,
+                first.fingerprint,
+                line_diff(&first_text, &next_text)
+            );
+        }
+    }
+    first
+}
+
+/// The JSON form stored in snapshot files: `{ tables, logs, explain }`.
+pub fn outputs_snapshot(outputs: &Outputs) -> JsonValue {
+    let mut body = Map::new();
+    body.insert(
+        "tables".to_string(),
+        JsonValue::Object(
+            outputs
+                .tables
+                .iter()
+                .map(|(name, rows)| (name.clone(), JsonValue::Array(rows.clone())))
+                .collect(),
+        ),
+    );
+    body.insert(
+        "logs".to_string(),
+        JsonValue::Object(
+            outputs
+                .logs
+                .iter()
+                .map(|(name, records)| {
+                    let lines: Vec<String> = records.iter().map(LogRecord::line).collect();
+                    (name.clone(), string_array(&lines))
+                })
+                .collect(),
+        ),
+    );
+    body.insert("explain".to_string(), string_array(&outputs.explain));
+    JsonValue::Object(body)
+}
+
+fn string_array(lines: &[String]) -> JsonValue {
+    JsonValue::Array(lines.iter().cloned().map(JsonValue::String).collect())
+}
+
+fn render_snapshot(value: &JsonValue) -> Result<String, String> {
+    let mut text = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    text.push('\n');
+    Ok(text)
+}
+
+/// LCS-based line diff, trimmed to the changed regions plus context.
+fn line_diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+
+    // lcs[i][j] = length of the LCS of old[i..] and new[j..].
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<(char, &str)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+
+    let changed: Vec<usize> = (0..lines.len()).filter(|&k| lines[k].0 != ' ').collect();
+    let mut out = String::new();
+    let mut last_shown: Option<usize> = None;
+    for (k, (tag, line)) in lines.iter().enumerate() {
+        let near_change = changed
+            .iter()
+            .any(|&c| k + DIFF_CONTEXT >= c && k <= c + DIFF_CONTEXT);
+        if !near_change {
+            continue;
+        }
+        if last_shown.is_some_and(|prev| prev + 1 != k) || (last_shown.is_none() && k > 0) {
+            out.push_str("  ...\n");
+        }
+        out.push_str(&format!("{tag} {line}\n"));
+        last_shown = Some(k);
+    }
+    if last_shown.is_some_and(|prev| prev + 1 < lines.len()) {
+        out.push_str("  ...\n");
+    }
+    out
+}
@@ -0,0 +1,298 @@
+//! Lightweight type expressions for `json(expect="...")`: `any`, `null`, `bool`,
+//! `i64`, `string`, `bytes`, `unit`, `record`, `array`, `array<T>`, `{name: T, ...}`, and
+//! `T?` (`T` or `null`). Verbose explain also infers them from the values a stage
+//! reads and writes.
+
+use crate::errors::{Error, DECODE, STRICT};
+use crate::Value;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TypeExpr {
+    Any,
+    Null,
+    Bool,
+    I64,
+    String,
+    Bytes,
+    /// What sinks emit.
+    Unit,
+    /// Any record.
+    Record,
+    /// An array, of items of the given type when there is one.
+    Array(Option<Box<TypeExpr>>),
+    /// A record with at least these fields; `closed` (strict mode) rejects others.
+    Fields {
+        fields: Vec<(String, TypeExpr)>,
+        closed: bool,
+    },
+    Nullable(Box<TypeExpr>),
+}
+
+impl TypeExpr {
+    /// Parses `text`; `{...}` record types are closed when `closed` is set.
+    pub(crate) fn parse(text: &str, closed: bool) -> Result<Self, String> {
+        let mut parser = Parser {
+            text,
+            pos: 0,
+            closed,
+        };
+        let ty = parser.parse_type()?;
+        parser.skip_ws();
+        if parser.pos < text.len() {
+            return Err(parser.error("unexpected text"));
+        }
+        Ok(ty)
+    }
+
+    /// The narrowest type fitting every one of `values`; `None` when there are none.
+    pub(crate) fn infer<'v>(values: impl IntoIterator<Item = &'v Value>) -> Option<Self> {
+        values.into_iter().map(Self::of).reduce(Self::join)
+    }
+
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => TypeExpr::Null,
+            Value::Bool(_) => TypeExpr::Bool,
+            Value::I64(_) => TypeExpr::I64,
+            Value::String(_) => TypeExpr::String,
+            Value::Bytes(_) => TypeExpr::Bytes,
+            Value::Unit => TypeExpr::Unit,
+            Value::Array(items) => TypeExpr::Array(Self::infer(items).map(Box::new)),
+            Value::Record(record) => TypeExpr::Fields {
+                fields: record
+                    .iter()
+                    .map(|(name, value)| (name.as_str().to_string(), Self::of(value)))
+                    .collect(),
+                closed: false,
+            },
+            Value::Tagged(..) => TypeExpr::Any,
+        }
+    }
+
+    /// A type fitting both `self` and `other`: `null` makes the other nullable, a field
+    /// only one record type has becomes nullable, and otherwise unlike types are `any`.
+    fn join(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (TypeExpr::Any, _) | (_, TypeExpr::Any) => TypeExpr::Any,
+            (TypeExpr::Null, ty) | (ty, TypeExpr::Null) => ty.nullable(),
+            (TypeExpr::Nullable(a), b) | (b, TypeExpr::Nullable(a)) => a.join(b).nullable(),
+            (TypeExpr::Array(Some(a)), TypeExpr::Array(Some(b))) => {
+                TypeExpr::Array(Some(Box::new(a.join(*b))))
+            }
+            (TypeExpr::Array(item), TypeExpr::Array(None))
+            | (TypeExpr::Array(None), TypeExpr::Array(item)) => TypeExpr::Array(item),
+            (TypeExpr::Fields { fields: a, .. }, TypeExpr::Fields { fields: mut b, .. }) => {
+                let mut fields: Vec<(String, TypeExpr)> = a
+                    .into_iter()
+                    .map(
+                        |(name, ty)| match b.iter().position(|(other, _)| *other == name) {
+                            Some(idx) => {
+                                let (_, other) = b.remove(idx);
+                                (name, ty.join(other))
+                            }
+                            None => (name, ty.nullable()),
+                        },
+                    )
+                    .collect();
+                fields.extend(b.into_iter().map(|(name, ty)| (name, ty.nullable())));
+                TypeExpr::Fields {
+                    fields,
+                    closed: false,
+                }
+            }
+            _ => TypeExpr::Any,
+        }
+    }
+
+    fn nullable(self) -> Self {
+        match self {
+            TypeExpr::Nullable(_) | TypeExpr::Any => self,
+            ty => TypeExpr::Nullable(Box::new(ty)),
+        }
+    }
+
+    /// Checks `value`; the error names the first mismatch by its path from `$`.
+    pub(crate) fn check(&self, value: &Value) -> Result<(), Error> {
+        self.check_at(value, "$")
+    }
+
+    fn check_at(&self, value: &Value, path: &str) -> Result<(), Error> {
+        let mismatch = || {
+            Err(Error::new(
+                DECODE,
+                format!("{path} is {}, expected {self}", value.kind()),
+            ))
+        };
+        match (self, value) {
+            (TypeExpr::Any, _)
+            | (TypeExpr::Null, Value::Null)
+            | (TypeExpr::Bool, Value::Bool(_))
+            | (TypeExpr::I64, Value::I64(_))
+            | (TypeExpr::String, Value::String(_))
+            | (TypeExpr::Bytes, Value::Bytes(_))
+            | (TypeExpr::Unit, Value::Unit)
+            | (TypeExpr::Record, Value::Record(_))
+            | (TypeExpr::Array(None), Value::Array(_))
+            | (TypeExpr::Nullable(_), Value::Null) => Ok(()),
+            (TypeExpr::Nullable(inner), _) => inner.check_at(value, path),
+            (TypeExpr::Array(Some(item)), Value::Array(items)) => items
+                .iter()
+                .enumerate()
+                .try_for_each(|(idx, value)| item.check_at(value, &format!("{path}[{idx}]"))),
+            (TypeExpr::Fields { fields, closed }, Value::Record(record)) => {
+                for (name, ty) in fields {
+                    let field_path = format!("{path}.{name}");
+                    match record.get(name.as_str()) {
+                        Some(value) => ty.check_at(value, &field_path)?,
+                        None if matches!(ty, TypeExpr::Nullable(_) | TypeExpr::Any) => {}
+                        None => {
+                            return Err(Error::new(
+                                DECODE,
+                                format!("{field_path} is missing, expected {ty}"),
+                            ))
+                        }
+                    }
+                }
+                match record
+                    .keys()
+                    .find(|key| !fields.iter().any(|(name, _)| name == key.as_str()))
+                {
+                    Some(key) if *closed => Err(Error::new(
+                        STRICT,
+                        format!("{path}.{} is not a field of {self}", key.as_str()),
+                    )),
+                    _ => Ok(()),
+                }
+            }
+            _ => mismatch(),
+        }
+    }
+}
+
+impl fmt::Display for TypeExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeExpr::Any => f.write_str("any"),
+            TypeExpr::Null => f.write_str("null"),
+            TypeExpr::Bool => f.write_str("bool"),
+            TypeExpr::I64 => f.write_str("i64"),
+            TypeExpr::String => f.write_str("string"),
+            TypeExpr::Bytes => f.write_str("bytes"),
+            TypeExpr::Unit => f.write_str("unit"),
+            TypeExpr::Record => f.write_str("record"),
+            TypeExpr::Array(None) => f.write_str("array"),
+            TypeExpr::Array(Some(item)) => write!(f, "array<{item}>"),
+            TypeExpr::Fields { fields, .. } => {
+                f.write_str("{")?;
+                for (idx, (name, ty)) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{name}: {ty}")?;
+                }
+                f.write_str("}")
+            }
+            TypeExpr::Nullable(inner) => write!(f, "{inner}?"),
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    closed: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn parse_type(&mut self) -> Result<TypeExpr, String> {
+        self.skip_ws();
+        let mut ty = if self.eat('{') {
+            self.parse_fields()?
+        } else {
+            let name = self.name();
+            match name {
+                "any" => TypeExpr::Any,
+                "null" => TypeExpr::Null,
+                "bool" => TypeExpr::Bool,
+                "i64" => TypeExpr::I64,
+                "string" => TypeExpr::String,
+                "bytes" => TypeExpr::Bytes,
+                "unit" => TypeExpr::Unit,
+                "record" => TypeExpr::Record,
+                "array" if self.eat('<') => {
+                    let item = self.parse_type()?;
+                    self.expect('>')?;
+                    TypeExpr::Array(Some(Box::new(item)))
+                }
+                "array" => TypeExpr::Array(None),
+                "" => return Err(self.error("expected a type")),
+                other => return Err(format!("unknown type {other}")),
+            }
+        };
+        while self.eat('?') {
+            ty = TypeExpr::Nullable(Box::new(ty));
+        }
+        Ok(ty)
+    }
+
+    fn parse_fields(&mut self) -> Result<TypeExpr, String> {
+        let mut fields = Vec::new();
+        if !self.eat('}') {
+            loop {
+                self.skip_ws();
+                let name = self.name().to_string();
+                if name.is_empty() {
+                    return Err(self.error("expected a field name"));
+                }
+                self.expect(':')?;
+                fields.push((name, self.parse_type()?));
+                if self.eat('}') {
+                    break;
+                }
+                self.expect(',')?;
+            }
+        }
+        Ok(TypeExpr::Fields {
+            fields,
+            closed: self.closed,
+        })
+    }
+
+    fn name(&mut self) -> &'a str {
+        let (text, start) = (self.text, self.pos);
+        let len = text[start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(text.len() - start);
+        self.pos += len;
+        &text[start..self.pos]
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if self.text[self.pos..].starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {c}")))
+        }
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{message} at offset {} of {}", self.pos, self.text)
+    }
+}
@@ -0,0 +1,76 @@
+//! Non-fatal diagnostics a run reports apart from its errors and logs.
+
+use dsl_syntax::LineCol;
+use serde_json::{Map, Value as JsonValue};
+use std::fmt;
+
+/// What a [`Warning`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WarningKind {
+    /// A stage ran under a name a grammar migration renamed.
+    Deprecated,
+    /// A bare codec both encoded and decoded items of one stream.
+    MixedDirection,
+    /// A stage dropped items to stay within a limit.
+    Truncated,
+    /// `rbac.evaluate` found a cycle in its resource ancestors.
+    AncestorCycle,
+}
+
+impl WarningKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            WarningKind::Deprecated => "deprecated",
+            WarningKind::MixedDirection => "mixed_direction",
+            WarningKind::Truncated => "truncated",
+            WarningKind::AncestorCycle => "ancestor_cycle",
+        }
+    }
+}
+
+/// One non-fatal diagnostic from a run. Displays as `message at line:column`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub message: String,
+    /// The source or stage it arose at, when the run had the program source.
+    pub at: Option<LineCol>,
+}
+
+impl Warning {
+    /// `{"kind", "message"}`, plus `"line"` and `"column"` when it has a position.
+    pub fn to_json(&self) -> JsonValue {
+        let mut out = Map::from_iter([
+            (
+                "kind".to_string(),
+                JsonValue::String(self.kind.name().to_string()),
+            ),
+            (
+                "message".to_string(),
+                JsonValue::String(self.message.clone()),
+            ),
+        ]);
+        if let Some(at) = self.at {
+            out.insert(
+                "line".to_string(),
+                JsonValue::Number((at.line as i64).into()),
+            );
+            out.insert(
+                "column".to_string(),
+                JsonValue::Number((at.column as i64).into()),
+            );
+        }
+        JsonValue::Object(out)
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.at {
+            Some(at) => write!(f, "{} at {at}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Incremental output: an [`OutputWriter`] receives each `ui.table` row and `ui.log`
+//! record as its sink produces it, so a run need not hold every output in `Outputs`.
+
+use crate::sink::Sink;
+use crate::LogRecord;
+use serde_json::{Map, Value as JsonValue};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Destination for the table rows and log records of [`crate::run_with_writer`].
+pub trait OutputWriter {
+    fn write_row(&mut self, table: &str, row: JsonValue) -> Result<(), String>;
+
+    fn write_log(&mut self, log: &str, record: LogRecord) -> Result<(), String>;
+
+    /// Called once after the last item of a successful run; flushes buffered output.
+    fn finish(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Keeps everything in memory, like [`crate::Outputs::tables`] and
+/// [`crate::Outputs::logs`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryWriter {
+    pub tables: BTreeMap<String, Vec<JsonValue>>,
+    pub logs: BTreeMap<String, Vec<LogRecord>>,
+}
+
+impl OutputWriter for MemoryWriter {
+    fn write_row(&mut self, table: &str, row: JsonValue) -> Result<(), String> {
+        self.tables.entry(table.to_string()).or_default().push(row);
+        Ok(())
+    }
+
+    fn write_log(&mut self, log: &str, record: LogRecord) -> Result<(), String> {
+        self.logs.entry(log.to_string()).or_default().push(record);
+        Ok(())
+    }
+}
+
+/// Writes one JSON object per line: `{"table": name, "row": row}` for rows and
+/// `{"log": name, "level", "message", "item"}` for log records.
+pub struct JsonlWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> JsonlWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn line(&mut self, line: JsonValue) -> Result<(), String> {
+        let text = serde_json::to_string(&line).map_err(|e| e.to_string())?;
+        writeln!(self.out, "{text}").map_err(|e| e.to_string())
+    }
+}
+
+impl<W: Write> OutputWriter for JsonlWriter<W> {
+    fn write_row(&mut self, table: &str, row: JsonValue) -> Result<(), String> {
+        self.line(JsonValue::Object(Map::from_iter([
+            ("table".to_string(), JsonValue::String(table.to_string())),
+            ("row".to_string(), row),
+        ])))
+    }
+
+    fn write_log(&mut self, log: &str, record: LogRecord) -> Result<(), String> {
+        let JsonValue::Object(mut line) = record.to_json() else {
+            unreachable!("LogRecord::to_json returns an object");
+        };
+        line.insert("log".to_string(), JsonValue::String(log.to_string()));
+        self.line(JsonValue::Object(line))
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.out.flush().map_err(|e| e.to_string())
+    }
+}
+
+/// One item passed to a [`CallbackWriter`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputEvent {
+    Row(JsonValue),
+    Log(LogRecord),
+}
+
+/// Passes every item to `f(name, event)`, where `name` is the table or log; an
+/// error from `f` stops the run.
+pub struct CallbackWriter<F>(pub F);
+
+impl<F: FnMut(&str, OutputEvent) -> Result<(), String>> OutputWriter for CallbackWriter<F> {
+    fn write_row(&mut self, table: &str, row: JsonValue) -> Result<(), String> {
+        (self.0)(table, OutputEvent::Row(row))
+    }
+
+    fn write_log(&mut self, log: &str, record: LogRecord) -> Result<(), String> {
+        (self.0)(log, OutputEvent::Log(record))
+    }
+}
+
+/// Adapts an [`OutputWriter`] to the [`Sink`] hook sink stages call.
+pub(crate) struct WriterSink<'a>(pub(crate) &'a mut dyn OutputWriter);
+
+impl Sink for WriterSink<'_> {
+    fn table_row(&mut self, table: &str, row: &JsonValue) -> Result<bool, String> {
+        self.0.write_row(table, row.clone())?;
+        Ok(true)
+    }
+
+    fn log_record(&mut self, log: &str, record: &LogRecord) -> Result<bool, String> {
+        self.0.write_log(log, record.clone())?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,140 @@
+use dsl_runtime::{block_on, Runtime, StageArgs, Value};
+use serde_json::json;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Resolves after a background thread wakes it, like a network reply.
+struct Delay(Option<Arc<AtomicBool>>);
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match &self.0 {
+            Some(done) if done.load(Ordering::SeqCst) => Poll::Ready(()),
+            Some(_) => Poll::Pending,
+            None => {
+                let done = Arc::new(AtomicBool::new(false));
+                let (flag, waker) = (Arc::clone(&done), cx.waker().clone());
+                std::thread::spawn(move || {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                    flag.store(true, Ordering::SeqCst);
+                    waker.wake();
+                });
+                self.0 = Some(done);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[test]
+fn async_host_functions_are_awaited_and_cached() {
+    let calls = Rc::new(Cell::new(0));
+    let mut runtime = Runtime::new();
+    let counter = Rc::clone(&calls);
+    runtime.register_async_host_fn("fetch_name", move |id| {
+        counter.set(counter.get() + 1);
+        async move {
+            Delay(None).await;
+            match id {
+                Value::I64(n) => Ok(Value::String(format!("user-{n}"))),
+                _ => Err("expected I64".to_string()),
+            }
+        }
+    });
+
+    let program = r#"input.json("ids") |> json |> host.call("fetch_name", _) |> ui.table("out");"#;
+    let out = block_on(runtime.run_async(program, json!({"ids": [1, 2, 1]})))
+        .expect("program should run");
+    assert_eq!(
+        out.tables["out"],
+        vec![json!("user-1"), json!("user-2"), json!("user-1")]
+    );
+    assert_eq!(calls.get(), 2, "repeated arguments share one call");
+
+    let err = block_on(runtime.run_async(program, json!({"ids": ["x"]})))
+        .expect_err("host error should surface")
+        .to_string();
+    assert_eq!(err, "host.call(fetch_name): expected I64");
+
+    let err = runtime
+        .run(program, json!({"ids": [1]}))
+        .expect_err("sync run cannot await")
+        .to_string();
+    assert_eq!(
+        err,
+        "host function fetch_name is async; use Runtime::run_async at 1:30"
+    );
+}
+
+#[test]
+fn async_sources_feed_dependent_host_calls() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut runtime = Runtime::new();
+    let seen = Rc::clone(&log);
+    runtime
+        .register_async_source("input.remote", move |args: &StageArgs| {
+            seen.borrow_mut().push(args.text());
+            let count = args.named("count");
+            async move {
+                Delay(None).await;
+                match count? {
+                    Value::I64(n) => Ok((1..=n).map(Value::I64).collect()),
+                    _ => Err("count must be an integer".to_string()),
+                }
+            }
+        })
+        .expect("name should be accepted")
+        .register_async_host_fn("square", |arg| async move {
+            match arg {
+                Value::I64(n) => Ok(Value::I64(n * n)),
+                _ => Err("expected I64".to_string()),
+            }
+        });
+
+    let program = r#"input.remote(count=3) |> host.call("square", _) |> ui.table("out");"#;
+    let out = block_on(runtime.run_async(program, json!({}))).expect("program should run");
+    assert_eq!(out.tables["out"], vec![json!(1), json!(4), json!(9)]);
+    assert_eq!(*log.borrow(), vec!["count=3".to_string()]);
+
+    let err = runtime
+        .run(program, json!({}))
+        .expect_err("sync run cannot await")
+        .to_string();
+    assert_eq!(err, "input.remote is async; use Runtime::run_async at 1:1");
+}
+
+#[test]
+fn stages_after_a_pending_call_never_see_placeholders() {
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let mut runtime = Runtime::new();
+    let record = Rc::clone(&seen);
+    runtime
+        .register_async_host_fn("double", |arg| async move {
+            Delay(None).await;
+            match arg {
+                Value::I64(n) => Ok(Value::I64(n * 2)),
+                _ => Err("expected I64".to_string()),
+            }
+        })
+        .register_host_fn("record", move |arg| {
+            record.borrow_mut().push(arg.clone());
+            Ok(arg)
+        });
+
+    let program = r#"input.json("ids") |> json |> host.call("double", _) |> host.call("record", _) |> ui.table("out");"#;
+    let out = block_on(runtime.run_async(program, json!({"ids": [1, 2, 3]})))
+        .expect("program should run");
+    assert_eq!(out.tables["out"], vec![json!(2), json!(4), json!(6)]);
+    assert_eq!(
+        *seen.borrow(),
+        vec![Value::I64(2), Value::I64(4), Value::I64(6)],
+        "the sync host function only runs once the async results are in"
+    );
+}
@@ -0,0 +1,33 @@
+use dsl_runtime::bench::{measure, run_with_timing};
+use serde_json::json;
+
+#[test]
+fn run_with_timing_reports_each_top_level_stage() {
+    let program = r#"input.json("xs") |> json |> filter(_ > 1) |> ui.table("out");"#;
+    let timed = run_with_timing(program, json!({"xs": [1, 2, 3]})).expect("program should run");
+
+    assert_eq!(timed.outputs.tables["out"], vec![json!(2), json!(3)]);
+    let stages: Vec<(&str, usize, usize)> = timed
+        .stages
+        .iter()
+        .map(|t| (t.stage.as_str(), t.items_in, t.items_out))
+        .collect();
+    assert_eq!(
+        stages,
+        vec![
+            ("json", 3, 3),
+            ("filter(_ > 1)", 3, 2),
+            ("ui.table(\"out\")", 2, 1)
+        ]
+    );
+    assert!(timed.stages.iter().all(|t| t.elapsed <= timed.total));
+}
+
+#[test]
+fn measure_summarizes_samples() {
+    let mut calls = 0;
+    let summary = measure(5, || calls += 1);
+    assert_eq!(calls, 6, "one warm-up call plus five timed calls");
+    assert_eq!(summary.iterations, 5);
+    assert!(summary.min <= summary.median);
+}
@@ -0,0 +1,258 @@
+use dsl_runtime::codecs::{self, Codec};
+use dsl_runtime::{run_with_options, RunOptions, Value};
+use serde_json::json;
+
+#[test]
+#[cfg(feature = "codecs")]
+fn every_codec_round_trips_accepted_sample_values() {
+    let samples = codecs::sample_values(42, 500);
+    for codec in Codec::ALL {
+        let mut checked = 0;
+        for value in samples.iter().filter(|v| codecs::accepts(codec, v)) {
+            if let Err(e) = codecs::roundtrip_check(value, codec) {
+                panic!("{e}");
+            }
+            checked += 1;
+        }
+        assert!(checked > 0, "{} accepted no samples", codec.name());
+    }
+}
+
+#[test]
+#[cfg(feature = "codecs")]
+fn codec_functions_match_stage_semantics() {
+    assert_eq!(Codec::from_name("base64"), Some(Codec::Base64));
+    assert_eq!(Codec::from_name("gzip"), None);
+
+    let encoded = codecs::forward(Codec::Utf8, Value::String("hi".to_string())).unwrap();
+    assert_eq!(encoded, Value::Bytes(b"hi".to_vec()));
+    assert!(codecs::accepts_inverse(Codec::Utf8, &encoded));
+
+    let lines = codecs::inverse(Codec::Ndjson, Value::Bytes(b"1\n\n[2]\n".to_vec())).unwrap();
+    assert_eq!(
+        lines,
+        vec![Value::I64(1), Value::Array(vec![Value::I64(2)])]
+    );
+
+    let err = codecs::roundtrip_check(&Value::I64(1), Codec::Utf8).expect_err("utf8 rejects I64");
+    assert!(err.contains("does not accept"), "{err}");
+}
+
+#[test]
+fn json_decoding_rejects_unquoted_keys_and_runaway_nesting() {
+    let decode = |text: String| codecs::inverse(Codec::Json, Value::Bytes(text.into_bytes()));
+    assert!(decode(r#"{a":1}"#.to_string()).is_err());
+
+    let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    assert!(decode(nested(128)).is_ok());
+    let err = decode(nested(100_000)).expect_err("too deep");
+    assert_eq!(err, "recursion limit exceeded");
+}
+
+#[test]
+fn strict_mode_needs_an_explicit_decode() {
+    let strict = RunOptions {
+        strict: true,
+        ..RunOptions::default()
+    };
+    let fixtures = || json!({"xs": [{"id": 1}]});
+    let program = r#"input.json("xs") |> json |> ui.table("t");"#;
+
+    let out = run_with_options(program, fixtures(), &RunOptions::default()).expect("auto mode");
+    assert_eq!(out.tables["t"], vec![json!({"id": 1})]);
+    let err = run_with_options(program, fixtures(), &strict)
+        .expect_err("strict mode")
+        .to_string();
+    assert_eq!(
+        err,
+        "json only encodes here; decode with ~json or json.decode at 1:21"
+    );
+
+    let explicit = r#"input.json("xs") |> ~json |> json |> ~json |> ui.table("t");"#;
+    let out = run_with_options(explicit, fixtures(), &strict).expect("explicit directions");
+    assert_eq!(out.tables["t"], vec![json!({"id": 1})]);
+
+    let err = run_with_options(
+        r#"input.ndjson("lines") |> ui.table("t");"#,
+        json!({"lines": "1\n2"}),
+        &strict,
+    )
+    .expect_err("strict fixtures are arrays")
+    .to_string();
+    assert_eq!(
+        err,
+        "fixture lines must be an array in strict mode (wrap the string in [...])"
+    );
+    assert!(
+        RunOptions::from_json(&json!({"strict": true}))
+            .unwrap()
+            .strict
+    );
+}
+
+#[test]
+#[cfg(feature = "codecs")]
+fn explicit_directions_bypass_auto_mode() {
+    let program = r#"
+input.json("docs") |> json.decode |> map(_.body) |> utf8.encode |> base64.encode |> ui.table("b64");
+input.json("docs") |> json.decode |> ~json.decode |> ~json.encode |> ui.table("docs");
+"#;
+    let out = dsl_runtime::run(program, json!({"docs": [{"body": "hi"}]})).expect("program runs");
+    assert_eq!(out.tables["b64"], vec![json!("aGk=")]);
+    assert_eq!(out.tables["docs"], vec![json!({"body": "hi"})]);
+
+    let err = dsl_runtime::run(
+        r#"input.json("docs") |> json.encode |> ui.table("t");"#,
+        json!({"docs": [1]}),
+    )
+    .expect_err("json.encode does not decode")
+    .to_string();
+    assert_eq!(
+        err,
+        "json only encodes here; decode with ~json or json.decode at 1:23"
+    );
+    let err = dsl_runtime::run(
+        r#"input.json("docs") |> json.zip |> ui.table("t");"#,
+        json!({"docs": [1]}),
+    )
+    .expect_err("not a codec direction")
+    .to_string();
+    assert!(err.starts_with("unsupported expression"), "{err}");
+}
+
+#[test]
+fn lenses_invert_and_check_round_trips() {
+    let program = r#"
+shift := lens(forward=_ + 10, inverse=_ + -10);
+wrap := shift >> lens(forward={v: _}, inverse=_.v);
+input.json("xs") |> ~json |> wrap |> ui.table("wrapped");
+input.json("xs") |> ~json |> wrap |> ~wrap |> ui.table("back");
+"#;
+    let out = dsl_runtime::run(program, json!({"xs": [1, 2]})).expect("lenses should run");
+    assert_eq!(
+        out.tables["wrapped"],
+        vec![json!({"v": 11}), json!({"v": 12})]
+    );
+    assert_eq!(out.tables["back"], vec![json!(1), json!(2)]);
+
+    let err = dsl_runtime::run(
+        r#"input.json("xs") |> ~json |> lens(forward={v: _}, inverse=_) |> ui.table("t");"#,
+        json!({"xs": [1]}),
+    )
+    .expect_err("inverse does not undo forward")
+    .to_string();
+    assert_eq!(
+        err,
+        r#"lens does not round-trip item 0: 1 came back as {"v":1} at 1:30"#
+    );
+}
+
+#[test]
+fn json_expect_names_the_item_and_path_that_mismatch() {
+    let fixtures =
+        || json!({"xs": [[1, 2], [3, "4"]], "users": [{"id": 1, "tags": ["a"], "extra": 0}]});
+    let run = |program: &str, options: &RunOptions| run_with_options(program, fixtures(), options);
+    let permissive = RunOptions::default();
+
+    let out = run(
+        r#"input.json("users") |> json(expect="{id: i64, tags: array<string>, nick: string?}") |> ui.table("u");"#,
+        &permissive,
+    )
+    .expect("users match");
+    assert_eq!(out.tables["u"].len(), 1);
+    assert!(out.explain.contains(
+        &"  [reversible] json expect={id: i64, tags: array<string>, nick: string?}".to_string()
+    ));
+
+    let err = run(
+        r#"input.json("xs") |> json.decode(expect="array<i64>") |> ui.table("t");"#,
+        &permissive,
+    )
+    .expect_err("the second item has a string")
+    .to_string();
+    assert_eq!(
+        err,
+        "json expect=array<i64>: item 1: $[1] is string, expected i64 at 1:21"
+    );
+
+    let err = run(
+        r#"input.json("users") |> ~json |> json.encode(expect="{id: string}") |> ui.table("t");"#,
+        &permissive,
+    )
+    .expect_err("encoded values are checked too")
+    .to_string();
+    assert_eq!(
+        err,
+        "json expect={id: string}: item 0: $.id is i64, expected string at 1:33"
+    );
+
+    let strict = RunOptions {
+        strict: true,
+        ..RunOptions::default()
+    };
+    let err = run(
+        r#"input.json("users") |> ~json(expect="{id: i64, tags: array}") |> ui.table("t");"#,
+        &strict,
+    )
+    .expect_err("strict records are closed")
+    .to_string();
+    assert_eq!(
+        err,
+        "json expect={id: i64, tags: array}: item 0: $.extra is not a field of {id: i64, tags: array} at 1:24"
+    );
+
+    let err = run(
+        r#"input.json("xs") |> json(expect="array<i6") |> ui.table("t");"#,
+        &permissive,
+    )
+    .expect_err("bad type")
+    .to_string();
+    assert_eq!(err, "json expect: unknown type i6 at 1:21");
+}
+
+/// A text item and an already-encoded one.
+#[cfg(feature = "codecs")]
+struct Mixed;
+
+#[cfg(feature = "codecs")]
+impl dsl_runtime::Source for Mixed {
+    fn read(
+        &self,
+        _args: &dsl_runtime::StageArgs,
+        _ctx: &dsl_runtime::SourceContext<'_>,
+    ) -> Result<Vec<Value>, String> {
+        Ok(vec![
+            Value::String("hi".to_string()),
+            Value::Bytes(b"yo".to_vec()),
+        ])
+    }
+}
+
+#[test]
+#[cfg(feature = "codecs")]
+fn auto_mode_warns_when_one_stream_goes_both_ways() {
+    let mut runtime = dsl_runtime::Runtime::new();
+    runtime
+        .register_source("input.mixed", Mixed)
+        .expect("name should be accepted");
+    let out = runtime
+        .run("input.mixed()\n  |> utf8\n  |> ui.table(\"t\");", json!({}))
+        .expect("program runs");
+    assert_eq!(out.warnings.len(), 1);
+    assert_eq!(
+        out.warnings[0].kind,
+        dsl_runtime::WarningKind::MixedDirection
+    );
+    assert_eq!(
+        out.warnings[0].to_string(),
+        "utf8 encoded 1 items and decoded 1 by auto direction; write utf8.encode or utf8.decode to pick one at 2:6"
+    );
+
+    let out = runtime
+        .run(
+            r#"input.json("xs") |> json |> ui.table("t");"#,
+            json!({"xs": [1]}),
+        )
+        .expect("program runs");
+    assert!(out.warnings.is_empty());
+}
@@ -0,0 +1,78 @@
+use dsl_runtime::{infer_schema, run_with_options, ColumnType, ColumnarTable, RunOptions};
+use serde_json::json;
+
+#[test]
+fn record_rows_become_sorted_typed_columns() {
+    let table = ColumnarTable::from_rows(vec![
+        json!({"name": "ada", "age": 36}),
+        json!({"name": "grace", "age": null, "tags": ["x"]}),
+        json!({"name": 7, "age": 40}),
+    ]);
+    assert_eq!(table.row_count, 3);
+    let summary: Vec<(&str, ColumnType)> = table
+        .columns
+        .iter()
+        .map(|column| (column.name.as_str(), column.kind))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("age", ColumnType::Number),
+            ("name", ColumnType::Mixed),
+            ("tags", ColumnType::Array),
+        ]
+    );
+    assert_eq!(
+        table.columns[2].values,
+        vec![json!(null), json!(["x"]), json!(null)]
+    );
+
+    let scalars = ColumnarTable::from_rows(vec![json!(1), json!({"a": 1})]);
+    assert_eq!(scalars.columns.len(), 1);
+    assert_eq!(scalars.columns[0].name, "value");
+    assert_eq!(scalars.columns[0].kind, ColumnType::Mixed);
+}
+
+#[test]
+fn columnar_option_moves_tables_out_of_row_form() {
+    let program = r#"input.json("xs") |> json |> ui.table("out"); input.json("ys") |> json |> ui.table("empty");"#;
+    let options = RunOptions::from_json(&json!({"columnar": true})).expect("valid options");
+    let out = run_with_options(
+        program,
+        json!({"xs": [{"id": 1}, {"id": 2}], "ys": []}),
+        &options,
+    )
+    .expect("program should run");
+    assert!(out.tables.is_empty());
+    let tables = out.tables_columnar.expect("columnar tables");
+    assert_eq!(
+        tables["out"].to_json(),
+        json!({"row_count": 2, "columns": [{"name": "id", "type": "number", "values": [1, 2]}]})
+    );
+    assert_eq!(tables["empty"].columns[0].kind, ColumnType::Null);
+}
+
+#[test]
+fn every_table_gets_an_inferred_schema() {
+    let program = r#"input.json("xs") |> json |> ui.table("out"); input.json("ys") |> json |> ui.table("plain");"#;
+    let out = dsl_runtime::run(
+        program,
+        json!({"xs": [{"id": 1, "note": "a"}, {"id": 2}], "ys": [1, null]}),
+    )
+    .expect("program should run");
+    let schema = |table: &str| -> Vec<serde_json::Value> {
+        out.schemas[table].iter().map(|c| c.to_json()).collect()
+    };
+    assert_eq!(
+        schema("out"),
+        vec![
+            json!({"name": "id", "type": "number", "nullable": false}),
+            json!({"name": "note", "type": "string", "nullable": true}),
+        ]
+    );
+    assert_eq!(
+        schema("plain"),
+        vec![json!({"name": "value", "type": "number", "nullable": true})]
+    );
+    assert_eq!(infer_schema(&[])[0].kind, ColumnType::Null);
+}
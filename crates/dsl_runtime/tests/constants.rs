@@ -0,0 +1,93 @@
+use dsl_runtime::{run, run_with_options, RunOptions, Session};
+use serde_json::json;
+
+const PROGRAM: &str = r#"
+const REGION := "eu";
+const MIN_MS := 100;
+const TOP := 2;
+input.json("events")
+  |> json
+  |> filter(_.ms > MIN_MS)
+  |> rank.topk(k=TOP, by=_.ms, order="desc")
+  |> select(id=_.id, region=REGION, tag=REGION + "-" + _.kind)
+  |> ui.table("out");
+"#;
+
+fn events() -> serde_json::Value {
+    json!({"events": [
+        {"id": 1, "ms": 50, "kind": "a"},
+        {"id": 2, "ms": 300, "kind": "b"},
+        {"id": 3, "ms": 200, "kind": "c"},
+        {"id": 4, "ms": 150, "kind": "d"}
+    ]})
+}
+
+#[test]
+fn const_statements_are_substituted_into_later_statements() {
+    let out = run(PROGRAM, events()).expect("program should run");
+    assert_eq!(
+        out.tables["out"],
+        vec![
+            json!({"id": 2, "region": "eu", "tag": "eu-b"}),
+            json!({"id": 3, "region": "eu", "tag": "eu-c"})
+        ]
+    );
+}
+
+#[test]
+fn host_constants_shadow_declared_ones() {
+    let mut options = RunOptions::default();
+    options.constants.insert("REGION".to_string(), json!("us"));
+    options.constants.insert("TOP".to_string(), json!(1));
+    options.constants.insert("UNUSED".to_string(), json!(true));
+    let out = run_with_options(PROGRAM, events(), &options).expect("program should run");
+    assert_eq!(
+        out.tables["out"],
+        vec![json!({"id": 2, "region": "us", "tag": "us-b"})]
+    );
+
+    options.constants.insert("TOP".to_string(), json!("1"));
+    let err = run_with_options(PROGRAM, events(), &options)
+        .expect_err("type mismatch")
+        .to_string();
+    assert_eq!(
+        err,
+        "const TOP: host value is a string, but the program declares a number"
+    );
+}
+
+#[test]
+fn const_names_must_be_unique_and_unreserved() {
+    let err = run(r#"const A := 1; const A := 2;"#, json!({}))
+        .expect_err("duplicate")
+        .to_string();
+    assert_eq!(err, "const A is already defined");
+
+    let err = run(
+        r#"xs := input.json("xs"); const xs := 1;"#,
+        json!({"xs": []}),
+    )
+    .expect_err("clashes with binding")
+    .to_string();
+    assert_eq!(err, "const xs: name is already bound");
+
+    let err = run(r#"const json := 1;"#, json!({}))
+        .expect_err("codec name")
+        .to_string();
+    assert_eq!(err, "const json: name is reserved");
+}
+
+#[test]
+fn session_keeps_constants_between_evals() {
+    let mut session = Session::new(json!({"xs": [1, 2]})).expect("fixtures should parse");
+    session
+        .eval("const STEP := 10;")
+        .expect("const should bind");
+    let out = session
+        .eval(r#"input.json("xs") |> json |> map(_ + STEP) |> ui.table("out");"#)
+        .expect("program should run");
+    assert_eq!(out.tables["out"], vec![json!(11), json!(12)]);
+    assert!(session
+        .bindings()
+        .contains(&("STEP".to_string(), "const 10".to_string())));
+}
@@ -0,0 +1,75 @@
+use dsl_runtime::{run_with_options, CoverageKind, RunOptions};
+use serde_json::json;
+
+const PROGRAM: &str = r#"
+events := input.json("events") |> json;
+events
+  |> map(match _ {
+    { type: "click", .. } => "click",
+    { type: "view", .. } => "view",
+    _ => "other"
+  })
+  |> ui.table("kinds");
+events |> filter(_.type == "none") |> map(_.type) |> ui.table("none");
+"#;
+
+fn coverage_options() -> RunOptions {
+    RunOptions {
+        coverage: true,
+        ..RunOptions::default()
+    }
+}
+
+#[test]
+fn coverage_counts_items_per_statement_stage_and_arm() {
+    let out = run_with_options(
+        PROGRAM,
+        json!({"events": [{"type": "click"}, {"type": "click"}, {"type": "view"}]}),
+        &coverage_options(),
+    )
+    .expect("program should run");
+    let lines: Vec<String> = out
+        .coverage
+        .expect("coverage requested")
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            "2:1 statement events: 3 items",
+            "2:11 source input.json(\"events\"): 3 items",
+            "2:35 stage json: 3 items",
+            "3:1 statement pipeline: 3 items",
+            "3:1 source events: 3 items",
+            "4:6 stage map(match _ { { type: \"click\", .. } => \"click\", { type: \"view\", .. } => \"view\", _ => \"other\" }): 3 items",
+            "5:5 arm { type: \"click\", .. }: 2 items",
+            "6:5 arm { type: \"view\", .. }: 1 item",
+            "7:5 arm _: 0 items",
+            "9:6 stage ui.table(\"kinds\"): 3 items",
+            "10:1 statement pipeline: 3 items",
+            "10:1 source events: 3 items",
+            "10:11 stage filter(_.type == \"none\"): 3 items",
+            "10:39 stage map(_.type): 0 items",
+            "10:54 stage ui.table(\"none\"): 0 items",
+        ]
+    );
+}
+
+#[test]
+fn coverage_is_only_recorded_on_request() {
+    let out = run_with_options(PROGRAM, json!({"events": []}), &RunOptions::default())
+        .expect("program should run");
+    assert_eq!(out.coverage, None);
+
+    let options = RunOptions::from_json(&json!({"coverage": true})).expect("valid options");
+    let out =
+        run_with_options(PROGRAM, json!({"events": []}), &options).expect("program should run");
+    let coverage = out.coverage.expect("coverage requested");
+    assert!(coverage.iter().all(|entry| !entry.covered()));
+    assert_eq!(
+        coverage[7].to_json(),
+        json!({"kind": "arm", "text": "{ type: \"view\", .. }", "items": 0, "line": 6, "column": 5})
+    );
+    assert_eq!(coverage[7].kind, CoverageKind::Arm);
+}
@@ -0,0 +1,91 @@
+use dsl_runtime::{to_dot, to_mermaid};
+
+#[cfg(feature = "kv")]
+const PROGRAM: &str = r#"
+chain := base64 >> ~base64;
+xs := input.json("xs") |> json;
+xs |> chain |> ui.table("out");
+input.json("users") |> json |> kv.load(store="users");
+xs |> lookup.kv(store="users", key=_.id) |> ui.log("joined");
+"#;
+
+#[test]
+#[cfg(feature = "kv")]
+fn to_dot_links_sources_bindings_stages_and_sinks() {
+    let dot = to_dot(PROGRAM).expect("program should render");
+    assert_eq!(
+        dot,
+        r#"digraph program {
+  rankdir=LR;
+  n0 [label="xs", shape=ellipse];
+  n1 [label="input.json(\"xs\")", shape=invhouse];
+  n2 [label="json", shape=box];
+  n3 [label="chain", shape=box];
+  n4 [label="ui.table(\"out\")", shape=house];
+  n5 [label="input.json(\"users\")", shape=invhouse];
+  n6 [label="json", shape=box];
+  n7 [label="kv.load(store=\"users\")", shape=house];
+  n8 [label="lookup.kv(store=\"users\", key=_.id)", shape=box];
+  n9 [label="ui.log(\"joined\")", shape=house];
+  n1 -> n2;
+  n2 -> n0;
+  n0 -> n3;
+  n3 -> n4;
+  n5 -> n6;
+  n6 -> n7;
+  n0 -> n8;
+  n8 -> n9;
+  n7 -> n8 [style=dashed];
+}
+"#
+    );
+}
+
+#[test]
+fn to_mermaid_uses_flowchart_shapes() {
+    let mermaid = to_mermaid(r#"input.json("xs") |> json |> ui.table("out");"#)
+        .expect("program should render");
+    assert_eq!(
+        mermaid,
+        r#"flowchart LR
+  n0[/"input.json(#quot;xs#quot;)"/]
+  n1["json"]
+  n2[["ui.table(#quot;out#quot;)"]]
+  n0 --> n1
+  n1 --> n2
+"#
+    );
+}
+
+#[test]
+fn diagrams_report_parse_errors() {
+    assert!(to_dot("x :=").is_err());
+    assert!(to_mermaid("x :=").is_err());
+}
+
+#[test]
+fn diagrams_carry_statement_comments() {
+    let program =
+        "// raw events\nxs := input.json(\"xs\") |> json;\n# report\nxs |> ui.table(\"out\");";
+    let dot = to_dot(program).expect("program should render");
+    assert!(dot.contains("  // raw events\n  n0 [label=\"xs\""), "{dot}");
+    assert!(dot.contains("  // report\n  n3 [label=\"ui.table"), "{dot}");
+    let mermaid = to_mermaid(program).expect("program should render");
+    assert!(
+        mermaid.contains("  %% raw events\n  n0([\"xs\"])"),
+        "{mermaid}"
+    );
+}
+
+#[test]
+#[cfg(feature = "rbac")]
+fn diagrams_link_rbac_load_to_evaluate_by_policy_name() {
+    let mermaid = to_mermaid(
+        r#"
+input.json("bindings") |> rbac.load(principal_bindings="bindings", role_perms="perms", resource_ancestors="tree");
+input.json("requests") |> rbac.evaluate(policy="policy") |> ui.table("decisions");
+"#,
+    )
+    .expect("program should render");
+    assert!(mermaid.contains("  n1 -.-> n3\n"), "{mermaid}");
+}
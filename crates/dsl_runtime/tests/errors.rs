@@ -0,0 +1,195 @@
+use dsl_runtime::{compile, error_codes, run, run_compiled, RuntimeError};
+use serde_json::json;
+
+fn code_of(program: &str) -> &'static str {
+    let err = run(program, json!({"xs": [1]})).expect_err("program should fail");
+    err.code().code
+}
+
+#[test]
+fn compile_and_runtime_errors_have_stable_codes() {
+    let syntax = compile("xs := ;").expect_err("no expression");
+    assert_eq!(syntax.code().code, "E0001");
+    assert_eq!(syntax.code().kind, "syntax");
+
+    assert_eq!(code_of("xs := input.json(\"xs\");\nxs := xs;"), "E0002");
+    assert_eq!(code_of("a := b;\nb := input.json(\"xs\");"), "E0003");
+    assert_eq!(code_of("ui := input.json(\"xs\");"), "E0004");
+    assert_eq!(code_of("nope |> ui.table(\"out\");"), "E0005");
+    assert_eq!(
+        code_of("input.json(\"xs\") |> json |> grup.count(by_key=_);"),
+        "E0006"
+    );
+    assert_eq!(
+        code_of("input.json(\"xs\") |> json |> filter(_ > $min);"),
+        "E0007"
+    );
+    assert_eq!(code_of("const A := 1;\nconst A := 2;"), "E0008");
+    assert_eq!(
+        code_of("input.json(\"xs\") |> json |> rank.topk(k=-1, by=_, order=\"asc\");"),
+        "E0009"
+    );
+    assert_eq!(
+        code_of("input.json(\"nope\") |> ui.table(\"out\");"),
+        "E0011"
+    );
+    assert_eq!(
+        code_of("input.json(\"xs\") |> json |> map(match _ { 2 => 1 });"),
+        "E0013"
+    );
+    assert_eq!(
+        code_of("input.json(\"xs\") |> json |> map(_ + 9223372036854775807);"),
+        "E0015"
+    );
+    assert_eq!(
+        code_of("input.json(\"xs\") |> json |> map(_ > \"a\");"),
+        "E0017"
+    );
+    assert_eq!(code_of("input.json(\"xs\") |> json |> map(_.id);"), "E0017");
+    let err = run(
+        "input.json(\"xs\") |> json |> map(_.id) |> ui.table(\"out\");",
+        json!({"xs": [{"name": "a"}]}),
+    )
+    .expect_err("no id field");
+    assert_eq!(err.code().kind, "missing_field");
+    let err =
+        run("input.json(\"xs\") |> ui.table(\"out\");", json!([])).expect_err("not an object");
+    assert_eq!(err.code().kind, "invalid_fixtures");
+    let err = run("input.json(\"xs\") |> ui.table(\"out\");", json!({"xs": 1}))
+        .expect_err("not an array");
+    assert_eq!(err.code().kind, "invalid_fixtures");
+    let err = run(
+        r#"input.json("events") |> json |> fsm.evaluate(transitions="transitions", initial="a", event=_) |> ui.table("out");"#,
+        json!({
+            "transitions": [
+                {"from": "a", "event": "go", "to": "b"},
+                {"from": "a", "event": "go", "to": "c"}
+            ],
+            "events": ["go"]
+        }),
+    )
+    .expect_err("ambiguous transition");
+    assert_eq!(err.code().kind, "duplicate_transition");
+    assert_eq!(
+        RuntimeError::from("something new".to_string()).code().kind,
+        "other"
+    );
+}
+
+#[test]
+fn codes_come_from_where_the_error_is_raised_not_its_wording() {
+    for name in ["const x", "duplicate binding", "unexpected token"] {
+        let program = format!("input.json({name:?}) |> ui.table(\"out\");");
+        let err = run(&program, json!({})).expect_err("fixture is missing");
+        assert_eq!(err.code().code, "E0011", "{err}");
+        let RuntimeError::MissingFixture { name: missing, .. } = &err else {
+            panic!("expected a missing fixture, got {err:?}");
+        };
+        assert_eq!(missing, name);
+    }
+}
+
+#[test]
+fn error_codes_are_unique_and_ordered() {
+    let codes: Vec<&str> = error_codes().map(|entry| entry.code).collect();
+    assert_eq!(codes.first(), Some(&"E0000"));
+    assert!(codes.windows(2).all(|pair| pair[0] < pair[1]), "{codes:?}");
+    let mut kinds: Vec<&str> = error_codes().map(|entry| entry.kind).collect();
+    kinds.sort();
+    kinds.dedup();
+    assert_eq!(kinds.len(), codes.len());
+}
+
+#[test]
+fn runtime_errors_can_be_matched_by_kind() {
+    let err = compile("xs := ;").expect_err("no expression");
+    let RuntimeError::ParseError { span, .. } = &err else {
+        panic!("expected a parse error, got {err:?}");
+    };
+    assert_eq!(span.start, 6);
+
+    let err = run("input.json(\"ys\") |> ui.table(\"t\");", json!({})).expect_err("no ys");
+    let RuntimeError::MissingFixture { message, name, .. } = &err else {
+        panic!("expected a missing fixture, got {err:?}");
+    };
+    assert_eq!(message, "missing fixture: ys at 1:1");
+    assert_eq!(name, "ys");
+
+    let program = "input.json(\"xs\") |> json |> filter(_ > 1) |> ui.table(\"t\");";
+    let err = run(program, json!({"xs": [2, "x"]})).expect_err("a string item");
+    let RuntimeError::TypeError {
+        expected,
+        got,
+        span,
+        stage,
+        item_index,
+        ..
+    } = &err
+    else {
+        panic!("expected a type error, got {err:?}");
+    };
+    assert_eq!(expected, "i64 operands");
+    assert_eq!(got.as_deref(), Some("string and i64"));
+    assert_eq!(stage.as_deref(), Some("filter(_ > 1)"));
+    assert_eq!(*item_index, Some(1));
+    assert_eq!(
+        &program[span.unwrap().start..span.unwrap().end],
+        "filter(_ > 1)"
+    );
+    assert_eq!(err.to_string(), "operator > expects i64 operands at 1:29");
+
+    let program = "input.json(\"xs\") |> json |> map(int(_)) |> ui.table(\"t\");";
+    let err = run(program, json!({"xs": ["abc"]})).expect_err("not a number");
+    let RuntimeError::TypeError {
+        expected,
+        got,
+        item_index,
+        ..
+    } = &err
+    else {
+        panic!("expected a type error, got {err:?}");
+    };
+    assert_eq!(expected, "I64");
+    assert_eq!(got.as_deref(), Some("string"));
+    assert_eq!(*item_index, Some(0));
+
+    let program = "input.json(\"xs\") |> json |> filter(_) |> ui.table(\"t\");";
+    let err = run(program, json!({"xs": [1]})).expect_err("not a bool");
+    let RuntimeError::TypeError { expected, got, .. } = &err else {
+        panic!("expected a type error, got {err:?}");
+    };
+    assert_eq!(expected, "bool");
+    assert_eq!(got.as_deref(), Some("i64"));
+}
+
+#[test]
+fn stage_errors_name_the_stage_and_failing_item() {
+    let program = "input.json(\"xs\") |> json |> map(_ + 1) |> ui.table(\"t\");";
+    let err = run(program, json!({"xs": [1, 2, 9223372036854775807]})).expect_err("overflow");
+    let RuntimeError::StageError {
+        stage, item_index, ..
+    } = &err
+    else {
+        panic!("expected a stage error, got {err:?}");
+    };
+    assert_eq!(stage, "map(_ + 1)");
+    assert_eq!(*item_index, Some(2));
+
+    let compiled = compile(program).expect("program compiles");
+    let err = run_compiled(&compiled, json!({"xs": [9223372036854775807]})).expect_err("overflow");
+    let RuntimeError::StageError { stage, .. } = &err else {
+        panic!("expected a stage error, got {err:?}");
+    };
+    assert_eq!(stage, "map(_ + 1)");
+}
+
+#[test]
+#[cfg(feature = "rbac")]
+fn rbac_stages_report_unloaded_policies() {
+    let err = run(
+        r#"input.json("xs") |> json |> rbac.evaluate(policy="p");"#,
+        json!({"xs": [{"principal": "ada", "action": "read", "resource": "doc:a"}]}),
+    )
+    .expect_err("nothing loaded");
+    assert_eq!(err.code().kind, "unknown_policy");
+}
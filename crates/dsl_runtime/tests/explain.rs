@@ -0,0 +1,192 @@
+use dsl_runtime::{run_with_options, ExplainLevel, RunOptions};
+use serde_json::json;
+
+#[cfg(feature = "kv")]
+const PROGRAM: &str = r#"
+input.json("users") |> json |> kv.load(store="users");
+input.json("events")
+  |> json
+  |> lookup.batch_kv(store="users", key=_.user_id, batch_size=2, within_ms=10)
+  |> map(_.left.id)
+  |> ui.table("ids");
+"#;
+
+#[cfg(feature = "kv")]
+fn fixtures() -> serde_json::Value {
+    json!({
+        "users": [{"key": "u1", "value": "Ada"}],
+        "events": [
+            {"id": 1, "user_id": "u1"},
+            {"id": 2, "user_id": "u2"},
+            {"id": 3, "user_id": "u1"}
+        ]
+    })
+}
+
+#[cfg(feature = "kv")]
+fn explain_with(level: ExplainLevel) -> Vec<String> {
+    let options = RunOptions {
+        explain: level,
+        ..RunOptions::default()
+    };
+    run_with_options(PROGRAM, fixtures(), &options)
+        .expect("program should run")
+        .explain
+}
+
+#[test]
+#[cfg(feature = "kv")]
+fn explain_levels_control_detail() {
+    assert!(explain_with(ExplainLevel::Quiet).is_empty());
+
+    let normal = explain_with(ExplainLevel::Normal);
+    assert!(normal.contains(&"  [pure] lookup.batch_kv(users)".to_string()));
+    assert!(normal.iter().all(|line| !line.starts_with("    ")));
+
+    let verbose = explain_with(ExplainLevel::Verbose { items: 1 });
+    let start = verbose
+        .iter()
+        .position(|line| line == "  [pure] lookup.batch_kv(users)")
+        .expect("stage line");
+    assert_eq!(
+        verbose[start..start + 5],
+        [
+            "  [pure] lookup.batch_kv(users)".to_string(),
+            "    batches: 2 of up to 2 keys (simulated within_ms=10)".to_string(),
+            "    type: stream<{id: i64, user_id: string}> -> stream<{left: {id: i64, user_id: string}, right: string?}>".to_string(),
+            "    #0 {\"left\":{\"id\":1,\"user_id\":\"u1\"},\"right\":\"Ada\"}".to_string(),
+            "    ... 2 more".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn verbose_explain_infers_stream_types() {
+    let options = RunOptions {
+        explain: ExplainLevel::Verbose { items: 0 },
+        ..RunOptions::default()
+    };
+    let out = run_with_options(
+        r#"input.json("rows") |> json |> map(_.id) |> filter(_ > 5) |> ui.table("ids");"#,
+        json!({"rows": [{"id": 1, "tags": []}, {"id": 2, "tags": ["a"], "note": null}]}),
+        &options,
+    )
+    .expect("program should run");
+    let types: Vec<&str> = out
+        .explain
+        .iter()
+        .filter_map(|line| line.strip_prefix("    type: "))
+        .collect();
+    assert_eq!(
+        types,
+        [
+            "stream<bytes>",
+            "stream<bytes> -> stream<{id: i64, tags: array<string>, note: null?}>",
+            "stream<{id: i64, tags: array<string>, note: null?}> -> stream<i64>",
+            "stream<i64> -> empty",
+            "empty -> stream<unit>",
+        ]
+    );
+}
+
+#[test]
+fn run_options_parse_from_json() {
+    let options = RunOptions::from_json(&json!({
+        "explain": "verbose",
+        "explain_items": 5,
+        "constants": {"REGION": "eu"}
+    }))
+    .expect("options should parse");
+    assert_eq!(options.explain, ExplainLevel::Verbose { items: 5 });
+    assert_eq!(options.constants["REGION"], json!("eu"));
+
+    let err = RunOptions::from_json(&json!({"explain": "loud"})).expect_err("bad level");
+    assert_eq!(
+        err,
+        "unknown explain level: loud (expected quiet, normal, or verbose)"
+    );
+    let err = RunOptions::from_json(&json!({"explain_items": 2})).expect_err("needs verbose");
+    assert_eq!(err, "explain_items requires explain: \"verbose\"");
+    let err = RunOptions::from_json(&json!({"pretty": true})).expect_err("unknown key");
+    assert_eq!(err, "unknown run option: pretty");
+}
+
+#[test]
+fn verbose_explain_counts_dedupe_drops() {
+    let options = RunOptions {
+        explain: ExplainLevel::Verbose { items: 0 },
+        ..RunOptions::default()
+    };
+    let out = run_with_options(
+        r#"input.json("xs") |> json |> dedupe.within(by=_.id, within_ms=10, ts=_.ts) |> ui.table("kept");"#,
+        json!({"xs": [{"id": 1, "ts": 0}, {"id": 1, "ts": 5}, {"id": 1, "ts": 20}]}),
+        &options,
+    )
+    .expect("program should run");
+    assert!(
+        out.explain
+            .contains(&"    dropped: 1 duplicates (within_ms=10)".to_string()),
+        "{:?}",
+        out.explain
+    );
+}
+
+#[test]
+#[cfg(feature = "rbac")]
+fn verbose_explain_reports_rbac_cache_hit_rates() {
+    let options = RunOptions {
+        explain: ExplainLevel::Verbose { items: 0 },
+        ..RunOptions::default()
+    };
+    let out = run_with_options(
+        r#"input.json("requests") |> json |> rbac.evaluate(principal_bindings="bindings", role_perms="perms", resource_ancestors="tree") |> map(_.decision) |> ui.table("decisions");"#,
+        json!({
+            "bindings": [{"principal": "ada", "role": "reader"}],
+            "perms": [{"role": "reader", "action": "read", "resource": "folder:root"}],
+            "tree": [{"resource": "doc:a", "ancestor": "folder:root"}],
+            "requests": [
+                {"principal": "ada", "action": "read", "resource": "doc:a"},
+                {"principal": "ada", "action": "read", "resource": "doc:a"},
+                {"principal": "bob", "action": "read", "resource": "doc:a"},
+                {"principal": "ada", "action": "read", "resource": "doc:b"}
+            ]
+        }),
+        &options,
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["decisions"],
+        vec![json!("allow"), json!("allow"), json!("deny"), json!("deny")]
+    );
+    assert!(
+        out.explain
+            .contains(&"    cache: decisions: 1/4 cached, ancestors: 1/3 cached".to_string()),
+        "{:?}",
+        out.explain
+    );
+}
+
+#[test]
+fn iterate_explains_its_step_once_and_counts_rounds_when_verbose() {
+    let options = RunOptions {
+        explain: ExplainLevel::Verbose { items: 0 },
+        ..RunOptions::default()
+    };
+    let out = run_with_options(
+        r#"input.json("ns") |> json |> iterate(step=map(_ + _) >> filter(10 > _));"#,
+        json!({"ns": [1]}),
+        &options,
+    )
+    .expect("program should run");
+    let iterate = out
+        .explain
+        .iter()
+        .position(|line| line == "  [pure] iterate(max_iters=100)")
+        .expect("iterate is explained");
+    assert_eq!(
+        out.explain[iterate + 1..iterate + 4],
+        ["  [pure] map", "  [pure] filter", "    iterations: 4"],
+        "{:?}",
+        out.explain
+    );
+}
@@ -0,0 +1,64 @@
+use dsl_runtime::{capabilities, Runtime, StageArgs, Value};
+
+#[cfg(all(
+    feature = "codecs",
+    feature = "graph",
+    feature = "kv",
+    feature = "rbac",
+    feature = "stats"
+))]
+#[test]
+fn default_builds_report_every_optional_feature() {
+    let caps = capabilities();
+    assert_eq!(caps.features, ["codecs", "graph", "kv", "rbac", "stats"]);
+    for stage in [
+        "map",
+        "group.count",
+        "rbac.evaluate",
+        "lookup.kv",
+        "stats.outliers",
+        "base64",
+    ] {
+        assert!(caps.stages.iter().any(|s| s == stage), "{stage} missing");
+    }
+    assert_eq!(
+        caps.sources,
+        ["input.bytes", "input.http", "input.json", "input.ndjson"]
+    );
+}
+
+#[test]
+fn runtime_capabilities_include_registered_stages_and_sources() {
+    let mut runtime = Runtime::new();
+    runtime
+        .register_stage("mycorp.scrub", |_: &StageArgs, input: Vec<Value>| Ok(input))
+        .expect("stage should register");
+    let caps = runtime.capabilities();
+    assert!(caps.stages.iter().any(|s| s == "mycorp.scrub"));
+    assert!(caps.stages.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert_eq!(caps.features, capabilities().features);
+}
+
+#[cfg(not(feature = "rbac"))]
+#[test]
+fn stages_from_a_left_out_feature_name_it() {
+    let err = dsl_runtime::run(
+        r#"input.json("p") |> json |> rbac.load(name="p") |> ui.table("out");"#,
+        serde_json::json!({"p": []}),
+    )
+    .expect_err("rbac is compiled out")
+    .to_string();
+    assert!(err.contains("`rbac` feature"), "{err}");
+}
+
+#[cfg(not(feature = "codecs"))]
+#[test]
+fn codecs_from_a_left_out_feature_name_it() {
+    let err = dsl_runtime::run(
+        r#"input.json("xs") |> json |> base64 |> ui.table("out");"#,
+        serde_json::json!({"xs": []}),
+    )
+    .expect_err("base64 is compiled out")
+    .to_string();
+    assert!(err.contains("`codecs` feature"), "{err}");
+}
@@ -0,0 +1,95 @@
+use dsl_runtime::{generate_fixtures, run};
+use serde_json::{json, Value};
+
+fn spec() -> Value {
+    json!({
+        "seed": 7,
+        "fixtures": {
+            "events": {
+                "count": 20,
+                "fields": {
+                    "id": {"type": "seq", "start": 100, "step": 2},
+                    "ms": {"type": "int", "min": 10, "max": 20},
+                    "user": {"type": "string", "prefix": "u", "min": 1, "max": 3},
+                    "kind": {"type": "choice", "values": ["click", "view"]},
+                    "ok": {"type": "bool"},
+                    "src": {"type": "const", "value": "gen"},
+                    "tags": {"type": "array", "items": {"type": "int", "min": 0, "max": 1}, "max_len": 2}
+                }
+            },
+            "xs": {"count": 3, "value": {"type": "seq"}}
+        }
+    })
+}
+
+#[test]
+fn generate_fixtures_is_deterministic_and_respects_ranges() {
+    let a = generate_fixtures(&spec()).expect("spec should generate");
+    let b = generate_fixtures(&spec()).expect("spec should generate");
+    assert_eq!(a, b);
+
+    let Value::Object(map) = &a else {
+        panic!("fixtures should be an object")
+    };
+    assert_eq!(map.get("xs"), Some(&json!([0, 1, 2])));
+
+    let Some(Value::Array(events)) = map.get("events") else {
+        panic!("events should be an array")
+    };
+    assert_eq!(events.len(), 20);
+    for (idx, event) in events.iter().enumerate() {
+        let Value::Object(event) = event else {
+            panic!("event should be a record")
+        };
+        assert_eq!(
+            event.get("id"),
+            Some(&Value::Number((100 + 2 * idx as i64).into()))
+        );
+        assert_eq!(event.get("src"), Some(&json!("gen")));
+        let Some(Value::Number(ms)) = event.get("ms") else {
+            panic!("ms should be a number")
+        };
+        assert!((10..=20).contains(&ms.as_i64().unwrap()));
+        assert!(matches!(
+            event.get("user"),
+            Some(Value::String(u)) if ["u1", "u2", "u3"].contains(&u.as_str())
+        ));
+        assert!(matches!(
+            event.get("kind"),
+            Some(Value::String(k)) if k == "click" || k == "view"
+        ));
+        assert!(matches!(event.get("ok"), Some(Value::Bool(_))));
+        assert!(matches!(event.get("tags"), Some(Value::Array(t)) if t.len() <= 2));
+    }
+}
+
+#[test]
+fn generated_fixtures_feed_programs() {
+    let fixtures = generate_fixtures(&spec()).expect("spec should generate");
+    let out = run(
+        r#"input.json("events") |> json |> group.count(by_key=_.src) |> ui.table("out");"#,
+        fixtures,
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables.get("out"),
+        Some(&vec![json!({"key": "gen", "count": 20})])
+    );
+}
+
+#[test]
+fn generate_fixtures_reports_bad_specs() {
+    let err =
+        generate_fixtures(&json!({"fixtures": {"x": {"count": 1, "value": {"type": "nope"}}}}))
+            .expect_err("unknown type should fail");
+    assert!(err.contains("unknown field type: nope"));
+
+    let err = generate_fixtures(
+        &json!({"fixtures": {"x": {"count": 1, "value": {"type": "int", "min": 5, "max": 1}}}}),
+    )
+    .expect_err("inverted range should fail");
+    assert!(err.contains("range min 5 must be <= max 1"));
+
+    let err = generate_fixtures(&json!({"seed": 1})).expect_err("missing fixtures should fail");
+    assert!(err.contains("must contain 'fixtures'"));
+}
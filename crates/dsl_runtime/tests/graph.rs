@@ -0,0 +1,126 @@
+#![cfg(feature = "graph")]
+
+use dsl_runtime::run;
+use serde_json::json;
+
+fn org_chart() -> serde_json::Value {
+    json!({
+        "reports_to": [
+            {"from": "ceo", "to": "cto"},
+            {"from": "ceo", "to": "cfo"},
+            {"from": "cto", "to": "eng1"},
+            {"from": "cto", "to": "eng2"},
+            {"from": "eng1", "to": "intern"}
+        ],
+        "people": [{"name": "cto"}, {"name": "intern"}, {"name": "nobody"}]
+    })
+}
+
+#[test]
+fn graph_reachable_walks_edges_in_each_direction() {
+    let program = r#"
+people := input.json("people") |> json;
+people |> graph.reachable(edges="reports_to", from=_.name) |> map(_.reachable) |> ui.table("down");
+people |> graph.reachable(edges="reports_to", from=_.name, direction="up") |> map(_.reachable) |> ui.table("up");
+people |> graph.reachable("reports_to", _.name, "both") |> map(_.reachable) |> ui.table("both");
+"#;
+    let out = run(program, org_chart()).expect("program should run");
+    assert_eq!(
+        out.tables["down"],
+        vec![json!(["eng1", "eng2", "intern"]), json!([]), json!([])]
+    );
+    assert_eq!(
+        out.tables["up"],
+        vec![json!(["ceo"]), json!(["eng1", "cto", "ceo"]), json!([])]
+    );
+    assert_eq!(
+        out.tables["both"][1],
+        json!(["eng1", "cto", "ceo", "eng2", "cfo"])
+    );
+}
+
+#[test]
+fn graph_reachable_keeps_the_item_and_survives_cycles() {
+    let out = run(
+        r#"input.json("xs") |> json |> graph.reachable(edges="edges", from=_) |> ui.table("out");"#,
+        json!({
+            "edges": [{"from": "a", "to": "b"}, {"from": "b", "to": "a"}],
+            "xs": ["a"]
+        }),
+    )
+    .expect("cycles terminate");
+    assert_eq!(
+        out.tables["out"],
+        vec![json!({"item": "a", "reachable": ["b"]})]
+    );
+
+    let err = run(
+        r#"input.json("xs") |> json |> graph.reachable(edges="edges", from=_, direction="sideways");"#,
+        json!({"edges": [], "xs": []}),
+    )
+    .expect_err("unknown direction").to_string();
+    assert_eq!(
+        err,
+        "unknown direction: sideways (expected down, up, or both) at 1:29"
+    );
+}
+
+#[test]
+fn graph_shortest_path_emits_the_fewest_hops_or_null() {
+    let fixtures = json!({
+        "edges": [
+            {"from": "a", "to": "b"},
+            {"from": "b", "to": "c"},
+            {"from": "c", "to": "d"},
+            {"from": "a", "to": "d"},
+            {"from": "e", "to": "a"}
+        ],
+        "pairs": [{"a": "a", "b": "d"}, {"a": "b", "b": "d"}, {"a": "d", "b": "a"}, {"a": "c", "b": "c"}]
+    });
+    let out = run(
+        r#"
+pairs := input.json("pairs") |> json;
+pairs |> graph.shortest_path(edges="edges", from=_.a, to=_.b) |> map(_.path) |> ui.table("down");
+pairs |> graph.shortest_path(edges="edges", from=_.a, to=_.b, direction="both") |> map(_.path) |> ui.table("both");
+"#,
+        fixtures,
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["down"],
+        vec![
+            json!(["a", "d"]),
+            json!(["b", "c", "d"]),
+            json!(null),
+            json!(["c"])
+        ]
+    );
+    assert_eq!(out.tables["both"][2], json!(["d", "a"]));
+}
+
+#[test]
+fn graph_components_numbers_weakly_connected_nodes_in_edge_order() {
+    let out = run(
+        r#"input.json("xs") |> json |> graph.components(edges="edges") |> ui.table("out");"#,
+        json!({
+            "edges": [
+                {"from": "x", "to": "y"},
+                {"from": "p", "to": "q"},
+                {"from": "z", "to": "y"},
+                {"from": "q", "to": "p"}
+            ],
+            "xs": []
+        }),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["out"],
+        vec![
+            json!({"node": "x", "component": 0}),
+            json!({"node": "y", "component": 0}),
+            json!({"node": "p", "component": 1}),
+            json!({"node": "q", "component": 1}),
+            json!({"node": "z", "component": 0})
+        ]
+    );
+}
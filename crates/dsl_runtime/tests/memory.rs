@@ -0,0 +1,70 @@
+use dsl_runtime::{run_with_options, RunOptions, Value};
+use serde_json::json;
+
+const PROGRAM: &str = r#"
+xs := input.json("xs") |> json;
+xs |> map(_.name) |> ui.table("names");
+"#;
+
+fn fixtures() -> serde_json::Value {
+    json!({"xs": [{"name": "ada"}, {"name": "grace"}]})
+}
+
+#[test]
+fn approx_size_counts_overhead_strings_and_keys() {
+    assert_eq!(Value::I64(7).approx_size(), 16);
+    assert_eq!(Value::String("abc".to_string()).approx_size(), 19);
+    let record = Value::from_json(json!({"name": "ada"}));
+    assert_eq!(record.approx_size(), 16 + 4 + 19);
+    let array = Value::from_json(json!([1, 2]));
+    assert_eq!(array.approx_size(), 48);
+}
+
+#[test]
+fn peak_counts_bound_streams_and_the_current_pipeline() {
+    let options = RunOptions {
+        track_memory: true,
+        ..RunOptions::default()
+    };
+    let out = run_with_options(PROGRAM, fixtures(), &options).expect("program should run");
+    // The bound records (39 + 41 bytes) plus the names stream (19 + 21 bytes).
+    assert_eq!(out.peak_stream_bytes, Some(120));
+
+    let untracked =
+        run_with_options(PROGRAM, fixtures(), &RunOptions::default()).expect("program should run");
+    assert_eq!(untracked.peak_stream_bytes, None);
+}
+
+#[test]
+fn memory_limit_names_the_stage_that_crossed_it() {
+    let options = RunOptions {
+        memory_limit: Some(100),
+        ..RunOptions::default()
+    };
+    let err = run_with_options(PROGRAM, fixtures(), &options)
+        .expect_err("limit should trip")
+        .to_string();
+    assert_eq!(
+        err,
+        "memory limit exceeded at stage map(_.name) (120 bytes > 100) at 3:7"
+    );
+
+    let options = RunOptions::from_json(&json!({"memory_limit": 120})).expect("valid options");
+    let out = run_with_options(PROGRAM, fixtures(), &options).expect("limit is inclusive");
+    assert_eq!(out.peak_stream_bytes, Some(120));
+}
+
+#[test]
+fn parse_limits_come_from_run_options() {
+    let options = RunOptions::from_json(&json!({"max_statements": 1})).expect("valid options");
+    let err = run_with_options(PROGRAM, fixtures(), &options)
+        .expect_err("the program has two statements")
+        .to_string();
+    assert!(
+        err.starts_with("program has more than 1 statements"),
+        "{err}"
+    );
+
+    let err = RunOptions::from_json(&json!({"max_depth": "deep"})).expect_err("not a number");
+    assert_eq!(err, "max_depth must be a number");
+}
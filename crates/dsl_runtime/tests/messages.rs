@@ -0,0 +1,94 @@
+use dsl_runtime::{compile, run, LOCALES};
+use serde_json::json;
+
+fn args(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn errors_carry_message_ids_and_arguments() {
+    let err = run(
+        "xs := input.json(\"xs\") |> json;\nxz |> ui.table(\"out\");",
+        json!({"xs": []}),
+    )
+    .expect_err("misspelled binding");
+    let message = err.localized();
+    assert_eq!(message.id, "unknown_ident");
+    assert_eq!(message.args, args(&[("name", "xz")]));
+    assert_eq!(message.suggestions, ["xs"]);
+    assert_eq!(message.position.as_deref(), Some("2:1"));
+
+    let err =
+        compile("xs := input.json(\"xs\");\nxs := input.json(\"ys\");").expect_err("bound twice");
+    let message = err.localized();
+    assert_eq!(message.id, "duplicate_binding");
+    assert_eq!(message.args, args(&[("name", "xs"), ("first", "1:1")]));
+    assert_eq!(message.render("en"), err.message());
+
+    let err = compile("xs := ys |> json;\nys := xs |> json;").expect_err("a cycle");
+    assert_eq!(err.localized().id, "used_in_cycle");
+    assert_eq!(err.localized().render("en"), err.message());
+
+    let err = compile("xs |> map(_ * 2);").expect_err("no * operator");
+    assert_eq!(err.localized().id, "unexpected_token");
+    assert_eq!(
+        err.localized().args,
+        args(&[("token", "*"), ("expected", "')'")])
+    );
+    assert_eq!(err.localized().render("en"), err.message());
+}
+
+#[test]
+fn messages_without_a_template_use_their_kind() {
+    let program = "input.json(\"xs\") |> json |> map(_ + 1) |> ui.table(\"out\");";
+    let err = run(program, json!({"xs": [9223372036854775807]})).expect_err("overflow");
+    let message = err.localized();
+    assert_eq!(message.id, err.code().kind);
+    assert_eq!(message.args, args(&[("message", "operator + overflowed")]));
+    assert_eq!(err.localize("es"), err.message());
+}
+
+#[test]
+fn errors_render_in_each_locale() {
+    assert_eq!(LOCALES, ["en", "es", "fr"]);
+    let err = run(
+        "xs := input.json(\"xs\") |> json;\nxz |> ui.table(\"out\");",
+        json!({"xs": []}),
+    )
+    .expect_err("misspelled binding");
+    assert_eq!(err.localize("en"), err.message());
+    assert_eq!(
+        err.localize("es"),
+        "identificador desconocido xz (¿quisiste decir `xs`?) en 2:1"
+    );
+    assert_eq!(
+        err.localize("fr"),
+        "identifiant inconnu xz (vouliez-vous dire `xs` ?) à 2:1"
+    );
+    assert_eq!(err.localize("xx"), err.message(), "unknown locale");
+
+    let err = run(
+        r#"input.json("xs") |> json |> map(let total = _ in totl) |> ui.table("out");"#,
+        json!({"xs": [1]}),
+    )
+    .expect_err("misspelled local");
+    assert!(
+        err.localize("en").starts_with("unknown local totl"),
+        "{err}"
+    );
+    assert!(err
+        .localize("es")
+        .starts_with("identificador local desconocido totl"));
+    assert!(err
+        .localize("fr")
+        .starts_with("identifiant local inconnu totl"));
+
+    let err = compile("xs |> map(_ * 2);").expect_err("no * operator");
+    assert_eq!(
+        err.localize("fr"),
+        "jeton inattendu `*`, ')' attendu à 1:13"
+    );
+}
@@ -0,0 +1,77 @@
+use dsl_runtime::{bind_params, compile, param_names, run, run_with_params};
+use serde_json::json;
+
+const PROGRAM: &str = r#"
+input.json("events")
+  |> json
+  |> filter(_.ms > $threshold)
+  |> rank.topk(k=$k, by=_.ms, order="desc")
+  |> map({ id: _.id, label: $label })
+  |> ui.table("slow");
+"#;
+
+fn events() -> serde_json::Value {
+    json!({"events": [{"id": 1, "ms": 50}, {"id": 2, "ms": 300}, {"id": 3, "ms": 200}]})
+}
+
+#[test]
+fn params_are_substituted_before_running() {
+    let program = compile(PROGRAM).expect("program should parse");
+    assert_eq!(param_names(&program), vec!["k", "label", "threshold"]);
+
+    let out = run_with_params(
+        PROGRAM,
+        events(),
+        &json!({"threshold": 100, "k": 1, "label": "slow"}),
+    )
+    .expect("program should run");
+    assert_eq!(out.tables["slow"], vec![json!({"id": 2, "label": "slow"})]);
+
+    let out = run_with_params(
+        PROGRAM,
+        events(),
+        &json!({"threshold": 10, "k": 5, "label": "all"}),
+    )
+    .expect("program should run");
+    assert_eq!(out.tables["slow"].len(), 3);
+}
+
+#[test]
+fn params_are_checked_against_the_program() {
+    let program = compile(PROGRAM).expect("program should parse");
+    let err = bind_params(&program, &json!({"threshold": 1, "k": 1})).expect_err("missing param");
+    assert_eq!(err, "missing param: $label");
+
+    let err = bind_params(
+        &program,
+        &json!({"threshold": 1, "k": 1, "label": "x", "typo": 2}),
+    )
+    .expect_err("unknown param");
+    assert_eq!(
+        err,
+        "unknown param: $typo (program uses: $k, $label, $threshold)"
+    );
+
+    let err = run_with_params(
+        PROGRAM,
+        events(),
+        &json!({"threshold": [1], "k": 1, "label": "x"}),
+    )
+    .expect_err("arrays cannot be compared")
+    .to_string();
+    assert!(err.contains("operator > expects i64 operands"), "{err}");
+
+    let err = run_with_params(
+        PROGRAM,
+        events(),
+        &json!({"threshold": 1, "k": "x", "label": "x"}),
+    )
+    .expect_err("k must be an integer literal")
+    .to_string();
+    assert!(err.contains("i64"), "{err}");
+
+    let err = run(PROGRAM, events())
+        .expect_err("params are required")
+        .to_string();
+    assert_eq!(err, "unbound param $threshold (run it with params) at 4:6");
+}
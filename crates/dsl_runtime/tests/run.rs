@@ -13,6 +13,7 @@ xs |> map(_ + 1) |> filter(_ > 2) |> ui.table("out");
 }
 
 #[test]
+#[cfg(feature = "codecs")]
 fn acceptance_program_b_roundtrip_base64() {
     let program = r#"
 chain := base64 >> ~base64;
@@ -30,6 +31,7 @@ input.json("bs") |> chain |> ui.table("t");
 }
 
 #[test]
+#[cfg(feature = "codecs")]
 fn acceptance_program_c_utf8_roundtrip() {
     let program = r#"
 input.json("ss") |> json |> utf8 |> ~utf8 |> ui.table("rt");
@@ -134,6 +136,161 @@ input.json("rows")
 }
 
 #[test]
+fn group_collect_all_overflow_error_names_the_key() {
+    let program = r#"
+input.json("rows")
+  |> json
+  |> group.collect_all(by_key=_.k, limit=1, overflow="error")
+  |> ui.table("out");
+"#;
+
+    let err = run(
+        program,
+        json!({"rows": [{"k": "x"}, {"k": "y"}, {"k": "y"}]}),
+    )
+    .expect_err("group y overflows")
+    .to_string();
+    assert!(
+        err.contains("group.collect_all: group \"y\" has more than 1 items"),
+        "{err}"
+    );
+}
+
+#[test]
+fn group_collect_all_overflow_spill_emits_more_records() {
+    let program = r#"
+input.json("rows")
+  |> json
+  |> group.collect_all(by_key=_.k, limit=2, overflow="spill")
+  |> ui.table("out");
+"#;
+
+    let out = run(
+        program,
+        json!({"rows": [
+            {"k": "x", "v": 1},
+            {"k": "y", "v": 2},
+            {"k": "x", "v": 3},
+            {"k": "x", "v": 4}
+        ]}),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables.get("out"),
+        Some(&vec![
+            json!({"key": "x", "items": [{"k": "x", "v": 1}, {"k": "x", "v": 3}]}),
+            json!({"key": "x", "items": [{"k": "x", "v": 4}]}),
+            json!({"key": "y", "items": [{"k": "y", "v": 2}]}),
+        ])
+    );
+
+    let err =
+        dsl_runtime::compile("xs |> group.collect_all(by_key=_.k, limit=0, overflow=\"spill\");")
+            .expect_err("spill needs room")
+            .to_string();
+    assert!(
+        err.contains("limit must be > 0 with overflow=\"spill\""),
+        "{err}"
+    );
+    let err = dsl_runtime::compile("xs |> group.collect_all(by_key=_.k, overflow=\"keep\");")
+        .expect_err("unknown overflow")
+        .to_string();
+    assert!(err.contains("overflow must be"), "{err}");
+}
+
+#[test]
+fn group_collect_all_orders_items_per_group() {
+    let program = r#"
+input.json("rows")
+  |> json
+  |> group.collect_all(by_key=_.k, order_items_by=_.ts, order="desc")
+  |> ui.table("out");
+"#;
+
+    let out = run(
+        program,
+        json!({"rows": [
+            {"k": "x", "ts": 1, "v": "a"},
+            {"k": "y", "ts": 5, "v": "b"},
+            {"k": "x", "ts": 3, "v": "c"},
+            {"k": "x", "ts": 3, "v": "d"}
+        ]}),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables.get("out"),
+        Some(&vec![
+            json!({"key": "x", "items": [
+                {"k": "x", "ts": 3, "v": "c"},
+                {"k": "x", "ts": 3, "v": "d"},
+                {"k": "x", "ts": 1, "v": "a"}
+            ]}),
+            json!({"key": "y", "items": [{"k": "y", "ts": 5, "v": "b"}]}),
+        ])
+    );
+
+    let err = run(
+        "input.json(\"rows\") |> json |> group.collect_all(by_key=_.k, order_items_by=_.ts) |> ui.table(\"out\");",
+        json!({"rows": [{"k": "x", "ts": true}]}),
+    )
+    .expect_err("ts is not sortable").to_string();
+    assert!(
+        err.contains("order_items_by must evaluate to I64, String, or Null"),
+        "{err}"
+    );
+}
+
+#[test]
+fn group_collect_all_warns_about_dropped_items() {
+    let program = r#"
+input.json("rows")
+  |> json
+  |> group.collect_all(by_key=_.k, limit=1)
+  |> ui.table("out");
+"#;
+
+    let out = run(
+        program,
+        json!({"rows": [{"k": "x"}, {"k": "x"}, {"k": "y"}, {"k": "x"}]}),
+    )
+    .expect("program should run");
+    let warnings: Vec<String> = out.warnings.iter().map(|w| w.to_string()).collect();
+    assert_eq!(
+        warnings,
+        ["group.collect_all dropped 2 items over limit=1 at 4:6"]
+    );
+    assert_eq!(out.warnings[0].kind, dsl_runtime::WarningKind::Truncated);
+}
+
+#[test]
+fn group_collect_all_defaults_within_ms_and_limit() {
+    let program = r#"
+input.json("rows") |> json |> group.collect_all(by_key=_.k) |> ui.table("out");
+"#;
+    let out = run(
+        program,
+        json!({"rows": [{"k": "x"}, {"k": "y"}, {"k": "x"}]}),
+    )
+    .expect("within_ms and limit have defaults");
+    assert_eq!(
+        out.tables.get("out"),
+        Some(&vec![
+            json!({"key": "x", "items": [{"k": "x"}, {"k": "x"}]}),
+            json!({"key": "y", "items": [{"k": "y"}]}),
+        ])
+    );
+
+    let err = dsl_runtime::compile("xs |> group.collect_all(limit=5);")
+        .expect_err("no by_key")
+        .to_string();
+    assert_eq!(
+        err,
+        "group.collect_all: missing argument by_key (expected group.collect_all(by_key, within_ms=0, limit=1000, overflow=\"drop\", order_items_by?, order=\"asc\", nulls=\"last\")) at 1:7"
+    );
+}
+
+#[test]
+#[cfg(feature = "rbac")]
 fn rbac_evaluate_outputs_decisions_and_matches() {
     let program = r#"
 requests := input.json("requests") |> json;
@@ -205,6 +362,228 @@ requests
 }
 
 #[test]
+#[cfg(feature = "rbac")]
+fn rbac_evaluate_warns_about_ancestor_cycles_and_limits_depth() {
+    let program = r#"
+input.json("requests")
+  |> json
+  |> rbac.evaluate(principal_bindings="bindings", role_perms="perms", resource_ancestors="tree", max_depth=2)
+  |> map(_.decision)
+  |> ui.table("decisions");
+"#;
+    let fixtures = |tree| {
+        let mut fixtures = json!({
+            "bindings": [{"principal": "ada", "role": "reader"}],
+            "perms": [{"role": "reader", "action": "read", "resource": "folder:b"}],
+            "requests": [{"principal": "ada", "action": "read", "resource": "doc:a"}]
+        });
+        if let serde_json::Value::Object(map) = &mut fixtures {
+            map.insert("tree".to_string(), tree);
+        }
+        fixtures
+    };
+
+    let out = run(
+        program,
+        fixtures(json!([
+            {"resource": "doc:a", "ancestor": "folder:b"},
+            {"resource": "folder:b", "ancestor": "folder:c"},
+            {"resource": "folder:c", "ancestor": "folder:b"}
+        ])),
+    )
+    .expect("cycles are reported, not fatal");
+    assert_eq!(out.tables["decisions"], vec![json!("allow")]);
+    let warnings: Vec<String> = out.warnings.iter().map(|w| w.to_string()).collect();
+    assert_eq!(
+        warnings,
+        vec!["rbac.evaluate: resource ancestors form a cycle: folder:b -> folder:c -> folder:b at 4:6"]
+    );
+    assert_eq!(
+        out.warnings[0].kind,
+        dsl_runtime::WarningKind::AncestorCycle
+    );
+
+    let err = run(
+        program,
+        fixtures(json!([
+            {"resource": "doc:a", "ancestor": "folder:b"},
+            {"resource": "folder:b", "ancestor": "folder:c"},
+            {"resource": "folder:c", "ancestor": "folder:root"}
+        ])),
+    )
+    .expect_err("folder:root is three levels up")
+    .to_string();
+    assert_eq!(
+        err,
+        "rbac.evaluate: ancestors of doc:a go deeper than max_depth=2 (doc:a -> folder:b -> folder:c -> folder:root) at 4:6"
+    );
+
+    let err = run(
+        &program.replace("max_depth=2", "max_depth=-1"),
+        fixtures(json!([])),
+    )
+    .expect_err("negative depth")
+    .to_string();
+    assert_eq!(err, "rbac.evaluate max_depth must be >= 0 at 4:6");
+}
+
+#[test]
+#[cfg(feature = "rbac")]
+fn rbac_load_compiles_a_policy_reused_by_evaluate() {
+    let fixtures = json!({
+        "bindings": [{"principal": "ada", "role": "reader"}],
+        "perms": [{"role": "reader", "action": "read", "resource": "folder:root"}],
+        "tree": [{"resource": "doc:a", "ancestor": "folder:root"}],
+        "requests": [
+            {"principal": "ada", "action": "read", "resource": "doc:a"},
+            {"principal": "ada", "action": "write", "resource": "doc:a"}
+        ]
+    });
+    let load = r#"input.json("bindings") |> rbac.load(principal_bindings="bindings", role_perms="perms", resource_ancestors="tree");"#;
+    let decide = r#"input.json("requests") |> json |> rbac.evaluate(policy="policy") |> map(_.decision) |> ui.table("decisions");"#;
+
+    let out =
+        run(&format!("{load}\n{decide}\n{decide}"), fixtures.clone()).expect("program should run");
+    assert_eq!(
+        out.tables["decisions"],
+        vec![json!("allow"), json!("deny"), json!("allow"), json!("deny")]
+    );
+    assert!(out
+        .explain
+        .contains(&"  [pure] rbac.evaluate(policy)".to_string()));
+
+    let mut session = dsl_runtime::Session::new(fixtures.clone()).expect("fixtures load");
+    session.eval(load).expect("policy loads");
+    let out = session.eval(decide).expect("policy is reused");
+    assert_eq!(out.tables["decisions"], vec![json!("allow"), json!("deny")]);
+
+    let err = run(decide, fixtures.clone())
+        .expect_err("nothing loaded")
+        .to_string();
+    assert_eq!(
+        err,
+        "rbac.evaluate: no policy named \"policy\"; load it first with rbac.load(name=\"policy\", ...) at 1:35"
+    );
+    let err = run(
+        r#"input.json("requests") |> json |> rbac.evaluate(policy="p", principal_bindings="bindings");"#,
+        fixtures,
+    )
+    .expect_err("both policy sources").to_string();
+    assert_eq!(
+        err,
+        "rbac.evaluate takes policy or fixture names, not both at 1:35"
+    );
+}
+
+#[test]
+#[cfg(feature = "rbac")]
+fn rbac_evaluate_effective_at_honors_validity_windows() {
+    let program = r#"
+input.json("requests")
+  |> json
+  |> rbac.evaluate(principal_bindings="bindings", role_perms="perms", resource_ancestors="tree", effective_at=_.ts)
+  |> map(_.decision)
+  |> ui.table("decisions");
+"#;
+    let fixtures = json!({
+        "bindings": [
+            {"principal": "ada", "role": "reader", "valid_from": 100, "valid_until": 200},
+            {"principal": "bob", "role": "reader"}
+        ],
+        "perms": [
+            {"role": "reader", "action": "read", "resource": "doc:a", "valid_until": 300}
+        ],
+        "tree": [],
+        "requests": [
+            {"principal": "ada", "action": "read", "resource": "doc:a", "ts": 50},
+            {"principal": "ada", "action": "read", "resource": "doc:a", "ts": 100},
+            {"principal": "ada", "action": "read", "resource": "doc:a", "ts": 200},
+            {"principal": "bob", "action": "read", "resource": "doc:a", "ts": 250},
+            {"principal": "bob", "action": "read", "resource": "doc:a", "ts": 300}
+        ]
+    });
+    let out = run(program, fixtures.clone()).expect("program should run");
+    assert_eq!(
+        out.tables["decisions"],
+        vec![
+            json!("deny"),
+            json!("allow"),
+            json!("deny"),
+            json!("allow"),
+            json!("deny")
+        ]
+    );
+
+    let out = run(
+        &program.replace(", effective_at=_.ts", ""),
+        fixtures.clone(),
+    )
+    .expect("program should run");
+    assert!(out.tables["decisions"].iter().all(|d| d == &json!("allow")));
+
+    let err = run(&program.replace("_.ts", "\"2026-01-01\""), fixtures)
+        .expect_err("string time against number bounds")
+        .to_string();
+    assert_eq!(
+        err,
+        "rbac.evaluate: cannot compare number 100 with string \"2026-01-01\" at 4:6"
+    );
+}
+
+#[test]
+#[cfg(feature = "rbac")]
+fn rbac_diff_lists_requests_whose_decision_changed() {
+    let program = r#"
+input.json("old_perms") |> rbac.load(name="old", principal_bindings="bindings", role_perms="old_perms", resource_ancestors="tree");
+input.json("new_perms") |> rbac.load(name="new", principal_bindings="bindings", role_perms="new_perms", resource_ancestors="tree");
+input.json("requests") |> rbac.diff(before="old", after="new", requests="requests") |> ui.table("changed");
+input.json("requests") |> json |> rbac.diff(before="old", after="new") |> map(_.request.principal) |> ui.table("principals");
+"#;
+    let fixtures = json!({
+        "bindings": [
+            {"principal": "ada", "role": "reader"},
+            {"principal": "bob", "role": "writer"}
+        ],
+        "old_perms": [{"role": "reader", "action": "read", "resource": "folder:root"}],
+        "new_perms": [
+            {"role": "reader", "action": "read", "resource": "doc:a"},
+            {"role": "writer", "action": "write", "resource": "doc:a"}
+        ],
+        "tree": [
+            {"resource": "doc:a", "ancestor": "folder:root"},
+            {"resource": "doc:b", "ancestor": "folder:root"}
+        ],
+        "requests": [
+            {"principal": "ada", "action": "read", "resource": "doc:a"},
+            {"principal": "ada", "action": "read", "resource": "doc:b"},
+            {"principal": "bob", "action": "write", "resource": "doc:a"}
+        ]
+    });
+    let out = run(program, fixtures).expect("program should run");
+    assert_eq!(
+        out.tables["changed"],
+        vec![
+            json!({
+                "request": {"principal": "ada", "action": "read", "resource": "doc:b"},
+                "before": "allow",
+                "after": "deny",
+                "matches_before": [{"role": "reader", "action": "read", "resource": "folder:root"}],
+                "matches_after": []
+            }),
+            json!({
+                "request": {"principal": "bob", "action": "write", "resource": "doc:a"},
+                "before": "deny",
+                "after": "allow",
+                "matches_before": [],
+                "matches_after": [{"role": "writer", "action": "write", "resource": "doc:a"}]
+            })
+        ]
+    );
+    assert_eq!(out.tables["principals"], vec![json!("ada"), json!("bob")]);
+}
+
+#[test]
+#[cfg(feature = "kv")]
 fn kv_load_and_lookup_supports_single_and_batch_lookup() {
     let program = r#"
 input.json("users")
@@ -379,6 +758,125 @@ input.json("rows")
     );
 }
 
+#[test]
+fn rank_topk_breaks_ties_with_secondary_keys() {
+    let program = r#"
+input.json("rows")
+  |> json
+  |> rank.topk(k=3, by=[_.score, _.created_at], order=["desc", "asc"])
+  |> ui.table("out");
+"#;
+
+    let out = run(
+        program,
+        json!({"rows": [
+            {"id": "a", "score": 5, "created_at": 9},
+            {"id": "b", "score": 8, "created_at": 4},
+            {"id": "c", "score": 5, "created_at": 2},
+            {"id": "d", "score": 1, "created_at": 1}
+        ]}),
+    )
+    .expect("program should run");
+
+    assert_eq!(
+        out.tables.get("out"),
+        Some(&vec![
+            json!({"id": "b", "score": 8, "created_at": 4}),
+            json!({"id": "c", "score": 5, "created_at": 2}),
+            json!({"id": "a", "score": 5, "created_at": 9})
+        ])
+    );
+
+    let err = dsl_runtime::compile("xs |> rank.topk(k=1, by=[_.a, _.b], order=[\"asc\"]);")
+        .expect_err("one order for two keys")
+        .to_string();
+    assert!(err.contains("order has 1 entries for 2 sort keys"), "{err}");
+}
+
+#[test]
+fn sort_orders_whole_stream_by_keys() {
+    let program = r#"
+input.json("rows")
+  |> json
+  |> sort(by=[_.team, _.ms], order=["asc", "desc"])
+  |> map(_.id)
+  |> ui.table("out");
+input.json("rows") |> json |> sort(_.team) |> map(_.id) |> ui.table("stable");
+"#;
+
+    let out = run(
+        program,
+        json!({"rows": [
+            {"id": 1, "team": "b", "ms": 10},
+            {"id": 2, "team": "a", "ms": 5},
+            {"id": 3, "team": "b", "ms": 30},
+            {"id": 4, "team": "a", "ms": 7}
+        ]}),
+    )
+    .expect("program should run");
+
+    assert_eq!(
+        out.tables.get("out"),
+        Some(&vec![json!(4), json!(2), json!(3), json!(1)])
+    );
+    assert_eq!(
+        out.tables.get("stable"),
+        Some(&vec![json!(2), json!(4), json!(1), json!(3)])
+    );
+
+    let err = run(
+        "input.json(\"rows\") |> json |> sort(by=_.flag) |> ui.table(\"out\");",
+        json!({"rows": [{"flag": true}]}),
+    )
+    .expect_err("bool is not a sort key")
+    .to_string();
+    assert!(
+        err.contains("sort by expression must evaluate to I64, String, or Null"),
+        "{err}"
+    );
+}
+
+#[test]
+fn sorts_place_null_keys_by_nulls_option() {
+    let program = r#"
+rows := input.json("rows") |> json;
+rows |> sort(by=_.at, order="desc") |> map(_.id) |> ui.table("last");
+rows |> sort(by=_.at, order="desc", nulls="first") |> map(_.id) |> ui.table("first");
+rows |> rank.topk(k=2, by=_.at, order="asc", nulls="first") |> map(_.id) |> ui.table("top");
+rows
+  |> group.topn_items(by_key=_.team, n=2, order_by=_.at, order="asc")
+  |> map(array.map(_.items, _.id))
+  |> ui.table("groups");
+"#;
+
+    let out = run(
+        program,
+        json!({"rows": [
+            {"id": 1, "team": "a", "at": 5},
+            {"id": 2, "team": "a", "at": null},
+            {"id": 3, "team": "a", "at": 9},
+            {"id": 4, "team": "a", "at": null}
+        ]}),
+    )
+    .expect("null keys sort");
+
+    assert_eq!(
+        out.tables.get("last"),
+        Some(&vec![json!(3), json!(1), json!(2), json!(4)])
+    );
+    assert_eq!(
+        out.tables.get("first"),
+        Some(&vec![json!(2), json!(4), json!(3), json!(1)])
+    );
+    assert_eq!(out.tables.get("top"), Some(&vec![json!(2), json!(4)]));
+    assert_eq!(out.tables.get("groups"), Some(&vec![json!([1, 3])]));
+
+    let err = dsl_runtime::compile("xs |> sort(by=_, nulls=\"middle\");")
+        .expect_err("bad nulls")
+        .to_string();
+    assert!(err.contains("nulls must be \"first\" or \"last\""), "{err}");
+}
+
 #[test]
 fn group_count_counts_by_key_and_preserves_first_seen_group_order() {
     let program = r#"
@@ -458,7 +956,8 @@ input.json("rows")
             {"obj": {"nested": true}}
         ]}),
     )
-    .expect_err("program should fail");
+    .expect_err("program should fail")
+    .to_string();
 
     assert!(err.contains("group.count by_key must evaluate to I64 or String"));
 }
@@ -567,6 +1066,1615 @@ input.json("rows")
   |> ui.table("out");
 "#;
 
-    let err = run(program, json!({"rows": [[1, 2, 3]]})).expect_err("program should fail");
+    let err = run(program, json!({"rows": [[1, 2, 3]]}))
+        .expect_err("program should fail")
+        .to_string();
     assert!(err.contains("rank.kmerge_arrays input value must be Array[Array[Value]]"));
 }
+
+#[test]
+fn json_pointer_and_path_query_nested_values() {
+    let program = r#"
+input.json("docs")
+  |> json
+  |> map({
+    first_id: json.pointer(_, "/items/0/id"),
+    slash_key: json.pointer(_, "/meta/a~1b"),
+    missing: json.pointer(_, "/items/9/id"),
+    ids: json.path(_, "$.items[*].id"),
+    owner: json.path(_, "$['meta'].owner")
+  })
+  |> ui.table("out");
+"#;
+
+    let out = run(
+        program,
+        json!({"docs": [
+            {"items": [{"id": 1}, {"id": 2}], "meta": {"a/b": "slash", "owner": "ada"}}
+        ]}),
+    )
+    .expect("program should run");
+
+    assert_eq!(
+        out.tables.get("out"),
+        Some(&vec![json!({
+            "first_id": 1,
+            "slash_key": "slash",
+            "missing": null,
+            "ids": [1, 2],
+            "owner": ["ada"]
+        })])
+    );
+}
+
+#[test]
+fn json_path_rejects_invalid_paths() {
+    let program = r#"
+input.json("docs") |> json |> map(json.path(_, "items")) |> ui.table("out");
+"#;
+
+    let err = run(program, json!({"docs": [{"items": []}]}))
+        .expect_err("program should fail")
+        .to_string();
+    assert!(err.contains("invalid json.path: items"));
+}
+
+#[test]
+#[cfg(feature = "codecs")]
+fn input_ndjson_parses_each_line_of_string_fixture() {
+    let program = r#"
+input.ndjson("events") |> map(_.id) |> ui.table("ids");
+"#;
+
+    let out = run(program, json!({"events": "{\"id\": 1}\n\n{\"id\": 2}\n"}))
+        .expect("program should run");
+    assert_eq!(out.tables.get("ids"), Some(&vec![json!(1), json!(2)]));
+}
+
+#[test]
+#[cfg(feature = "codecs")]
+fn ndjson_codec_roundtrips_and_emits_line_bytes() {
+    let program = r#"
+input.json("rows") |> json |> ndjson |> utf8 |> ui.table("lines");
+input.json("rows") |> json |> ndjson >> ~ndjson |> ui.table("rt");
+"#;
+
+    let out = run(program, json!({"rows": [{"a": 1}, [2]]})).expect("program should run");
+    assert_eq!(
+        out.tables.get("lines"),
+        Some(&vec![json!("{\"a\":1}\n"), json!("[2]\n")])
+    );
+    assert_eq!(
+        out.tables.get("rt"),
+        Some(&vec![json!({"a": 1}), json!([2])])
+    );
+}
+
+#[test]
+#[cfg(feature = "codecs")]
+fn input_ndjson_reports_bad_lines_and_non_string_fixtures() {
+    let err = run(
+        r#"input.ndjson("events") |> ui.table("out");"#,
+        json!({"events": "{\"id\": 1}\nnope"}),
+    )
+    .expect_err("program should fail")
+    .to_string();
+    assert!(err.contains("ndjson line 2"));
+
+    let err = run(
+        r#"input.ndjson("events") |> ui.table("out");"#,
+        json!({"events": [1]}),
+    )
+    .expect_err("program should fail")
+    .to_string();
+    assert!(err.contains("input.ndjson fixture entries must be strings"));
+}
+
+#[test]
+#[cfg(feature = "codecs")]
+fn input_bytes_decodes_base64_fixture_entries() {
+    let program = r#"
+input.bytes("blobs") |> utf8 |> ui.table("text");
+input.bytes("blobs") |> base64 |> ui.table("b64");
+"#;
+
+    let out = run(program, json!({"blobs": ["aGk=", "b2s="]})).expect("program should run");
+    assert_eq!(
+        out.tables.get("text"),
+        Some(&vec![json!("hi"), json!("ok")])
+    );
+    assert_eq!(
+        out.tables.get("b64"),
+        Some(&vec![json!("aGk="), json!("b2s=")])
+    );
+    assert!(out
+        .explain
+        .contains(&"  [source] input.bytes(blobs) base64 -> Bytes".to_string()));
+}
+
+#[test]
+#[cfg(feature = "codecs")]
+fn input_bytes_rejects_non_string_and_invalid_base64() {
+    let err = run(r#"input.bytes("b") |> ui.table("out");"#, json!({"b": [1]}))
+        .expect_err("program should fail")
+        .to_string();
+    assert!(err.contains("input.bytes fixture entries must be base64 strings"));
+
+    let err = run(
+        r#"input.bytes("b") |> ui.table("out");"#,
+        json!({"b": ["abc"]}),
+    )
+    .expect_err("program should fail")
+    .to_string();
+    assert!(err.contains("invalid base64 length"));
+}
+
+#[test]
+fn run_streaming_hands_table_rows_to_callback_without_buffering() {
+    let program = r#"
+input.json("xs") |> json |> ui.table("out");
+input.json("xs") |> json |> ui.log("log");
+"#;
+
+    let mut rows = Vec::new();
+    let out = dsl_runtime::run_streaming(program, json!({"xs": [1, 2]}), |table, row| {
+        rows.push((table.to_string(), row))
+    })
+    .expect("program should run");
+
+    assert_eq!(
+        rows,
+        vec![("out".to_string(), json!(1)), ("out".to_string(), json!(2))]
+    );
+    assert!(out.tables.is_empty());
+    let lines: Vec<String> = out.logs["log"].iter().map(|r| r.line()).collect();
+    assert_eq!(lines, vec!["1", "2"]);
+}
+
+#[test]
+fn run_cancellable_stops_when_token_is_cancelled() {
+    let program = r#"
+input.json("xs") |> json |> ui.table("out");
+"#;
+
+    let token = dsl_runtime::CancelToken::new();
+    let out = dsl_runtime::run_cancellable(program, json!({"xs": [1]}), &token)
+        .expect("program should run");
+    assert!(!out.cancelled);
+    assert_eq!(out.tables.get("out"), Some(&vec![json!(1)]));
+
+    token.cancel();
+    let out = dsl_runtime::run_cancellable(program, json!({"xs": [1]}), &token)
+        .expect("cancelled run still returns outputs");
+    assert!(out.cancelled);
+    assert!(out.tables.is_empty());
+}
+
+#[test]
+fn run_with_yield_calls_the_hook_every_interval_until_it_stops() {
+    let program = r#"
+input.json("xs") |> json |> ui.table("first");
+input.json("xs") |> json |> map(_ + 1) |> ui.table("second");
+"#;
+    let fixtures = json!({"xs": [1, 2, 3, 4, 5, 6, 7, 8]});
+
+    let mut seen = Vec::new();
+    let mut keep_going = |points: u64| {
+        seen.push(points);
+        true
+    };
+    let out = dsl_runtime::run_with_yield(program, fixtures.clone(), 5, &mut keep_going)
+        .expect("program should run");
+    assert!(!out.cancelled);
+    assert_eq!(out.tables, run(program, fixtures.clone()).unwrap().tables);
+    assert!(!seen.is_empty());
+    assert!(seen.iter().all(|points| points % 5 == 0), "{seen:?}");
+
+    let mut calls = 0;
+    let mut stop_at_second = |_: u64| {
+        calls += 1;
+        calls < 2
+    };
+    let out = dsl_runtime::run_with_yield(program, fixtures, 5, &mut stop_at_second)
+        .expect("a stopped run still returns outputs");
+    assert!(out.cancelled);
+    assert_eq!(calls, 2, "the hook isn't called after it stops the run");
+    assert!(!out.tables.contains_key("second"));
+}
+
+#[test]
+fn grouping_sorting_and_ranking_stages_check_for_cancellation_per_item() {
+    let fixtures = json!({"xs": [3, 1, 2, 3, 1, 2, 3, 1]});
+    let points = |stage: &str| {
+        let program = format!(r#"input.json("xs") |> json{stage};"#);
+        let mut last = 0;
+        let mut hook = |points: u64| {
+            last = points;
+            true
+        };
+        dsl_runtime::run_with_yield(&program, fixtures.clone(), 1, &mut hook)
+            .expect("program should run");
+        last
+    };
+
+    let base = points("");
+    for stage in [
+        " |> group.count(by_key=_)",
+        " |> group.collect_all(by_key=_)",
+        " |> group.topn_items(by_key=_, n=1, order_by=_, order=\"asc\")",
+        " |> sort(by=_)",
+        " |> rank.topk(k=2, by=_, order=\"asc\")",
+    ] {
+        assert!(points(stage) > base + 8, "{stage} has no per-item checks");
+    }
+}
+
+#[test]
+fn a_stopped_run_ends_inside_a_keyed_fsm_evaluate() {
+    let fixtures = json!({
+        "transitions": [{"from": "start", "event": "go", "to": "start"}],
+        "events": [
+            {"door": "a", "event": "go"},
+            {"door": "b", "event": "go"},
+            {"door": "a", "event": "go"},
+            {"door": "c", "event": "go"}
+        ]
+    });
+    let last_point = |program: &str, stop_at: u64| {
+        let mut last = 0;
+        let mut hook = |points: u64| {
+            last = points;
+            points < stop_at
+        };
+        let out = dsl_runtime::run_with_yield(program, fixtures.clone(), 1, &mut hook)
+            .expect("a stopped run still returns outputs");
+        (out, last)
+    };
+
+    let (_, before) = last_point(r#"input.json("events") |> json;"#, u64::MAX);
+    // One point for the stage boundary, then the first event fsm.evaluate groups.
+    let stop_at = before + 2;
+    let (out, last) = last_point(
+        r#"input.json("events") |> json |> fsm.evaluate(transitions="transitions", initial="start", event=_.event, by_key=_.door);"#,
+        stop_at,
+    );
+    assert!(out.cancelled);
+    assert_eq!(last, stop_at);
+}
+
+#[test]
+fn session_keeps_bindings_between_evals_until_reset() {
+    let mut session =
+        dsl_runtime::Session::new(json!({"xs": [1, 2, 3]})).expect("fixtures should load");
+
+    let out = session
+        .eval(r#"xs := input.json("xs") |> json; inc := map(_ + 1);"#)
+        .expect("bindings should evaluate");
+    assert!(out.tables.is_empty());
+    assert_eq!(
+        session.bindings(),
+        vec![
+            ("inc".to_string(), "stage".to_string()),
+            ("xs".to_string(), "stream (3 items)".to_string())
+        ]
+    );
+
+    let out = session
+        .eval(r#"xs |> inc |> ui.table("out");"#)
+        .expect("pipeline should use earlier bindings");
+    assert_eq!(
+        out.tables.get("out"),
+        Some(&vec![json!(2), json!(3), json!(4)])
+    );
+    assert!(session
+        .last_explain()
+        .contains(&"  [sink] ui.table(out)".to_string()));
+
+    session.reset();
+    assert!(session.bindings().is_empty());
+    let err = session
+        .eval(r#"xs |> ui.table("out");"#)
+        .expect_err("bindings should be gone after reset")
+        .to_string();
+    assert!(err.contains("unknown ident xs"));
+}
+
+#[test]
+#[cfg(feature = "kv")]
+fn session_round_trips_through_serialize_and_restore() {
+    let fixtures = json!({
+        "xs": [1, 2, 3],
+        "ids": ["a", "b"],
+        "users": [{"key": "a", "value": "Ada"}, {"key": "c", "value": "Cy"}]
+    });
+    let mut session = dsl_runtime::Session::new(fixtures).expect("fixtures should load");
+    session
+        .eval(
+            r#"
+const STEP := 10;
+xs := input.json("xs") |> json;
+inc := map(_ + STEP);
+twice := inc >> inc;
+input.json("users") |> json |> kv.load(store="users");
+"#,
+        )
+        .expect("bindings should evaluate");
+
+    let snapshot = session.serialize();
+    let text = serde_json::to_string(&snapshot).expect("snapshot is json");
+    let mut restored =
+        dsl_runtime::Session::restore(serde_json::from_str(&text).expect("snapshot parses"))
+            .expect("snapshot restores");
+    assert_eq!(restored.bindings(), session.bindings());
+    assert_eq!(restored.serialize(), snapshot);
+
+    let out = restored
+        .eval(
+            r#"xs |> twice |> ui.table("out");
+input.json("ids") |> json |> lookup.kv(store="users", key=_) |> map(_.right) |> ui.table("names");"#,
+        )
+        .expect("restored bindings, constants, and stores should work");
+    assert_eq!(out.tables["out"], vec![json!(21), json!(22), json!(23)]);
+    assert_eq!(out.tables["names"], vec![json!("Ada"), json!(null)]);
+
+    let err = dsl_runtime::Session::restore(json!({"version": 2}))
+        .err()
+        .expect("unknown versions are refused")
+        .to_string();
+    assert_eq!(err, "invalid session snapshot: unsupported version 2");
+}
+
+#[test]
+fn restored_sessions_keep_run_statement_results() {
+    let program = r#"
+xs := input.json("xs") |> json |> map(_ + _);
+xs |> ui.table("out");
+"#;
+    let fixtures = json!({"xs": [1, 2]});
+    let mut session = dsl_runtime::Session::new(fixtures.clone()).expect("fixtures load");
+    dsl_runtime::run_statement(program, 0, fixtures.clone(), &mut session)
+        .expect("statement should run");
+
+    let mut restored =
+        dsl_runtime::Session::restore(session.serialize()).expect("snapshot restores");
+    let out = dsl_runtime::run_statement(program, 1, fixtures, &mut restored)
+        .expect("statement should run");
+    assert_eq!(out.tables["out"], vec![json!(2), json!(4)]);
+    assert!(
+        !out.explain.contains(&"binding xs".to_string()),
+        "xs should not rerun: {:?}",
+        out.explain
+    );
+}
+
+#[test]
+fn grouping_preserves_first_seen_order_across_many_keys() {
+    let program = r#"
+input.json("xs") |> json |> group.count(by_key=_) |> map(_.key) |> ui.table("keys");
+"#;
+    let num = |k: i64| serde_json::Value::Number(k.into());
+    let keys = (0..500).rev().chain(0..500).map(num).collect();
+    let mut fixtures = serde_json::Map::new();
+    fixtures.insert("xs".to_string(), serde_json::Value::Array(keys));
+    let out = run(program, serde_json::Value::Object(fixtures)).expect("program should run");
+    let expected: Vec<_> = (0..500).rev().map(num).collect();
+    assert_eq!(out.tables["keys"], expected);
+}
+
+#[test]
+fn select_projects_named_columns_into_records() {
+    let program = r#"
+input.json("orders") |> json |> select(id=_.user_id, total=_.a + _.b) |> ui.table("out");
+"#;
+    let out = run(
+        program,
+        json!({"orders": [{"user_id": 1, "a": 2, "b": 3}, {"user_id": 2, "a": 10, "b": 0}]}),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["out"],
+        vec![json!({"id": 1, "total": 5}), json!({"id": 2, "total": 10})]
+    );
+    assert!(out
+        .explain
+        .contains(&"  [projection] select(id, total)".to_string()));
+
+    let err = run(
+        r#"input.json("xs") |> json |> select(_.id) |> ui.table("out");"#,
+        json!({"xs": []}),
+    )
+    .expect_err("positional columns are rejected")
+    .to_string();
+    assert_eq!(
+        err,
+        "select expects named columns, e.g. select(id=_.id) at 1:29"
+    );
+}
+
+#[test]
+fn where_and_having_filter_rows_and_groups() {
+    let program = r#"
+rows := input.json("rows") |> json |> where(_.ms > 10, 400 > _.ms);
+rows |> ui.table("slow");
+rows
+  |> group.collect_all(by_key=_.team, within_ms=1000, limit=10)
+  |> having(array.any(_.items, _.ms > 100))
+  |> map(_.key)
+  |> ui.table("teams");
+"#;
+    let out = run(
+        program,
+        json!({"rows": [
+            {"team": "a", "ms": 50},
+            {"team": "b", "ms": 5},
+            {"team": "b", "ms": 150},
+            {"team": "x", "ms": 500}
+        ]}),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["slow"],
+        vec![
+            json!({"team": "a", "ms": 50}),
+            json!({"team": "b", "ms": 150})
+        ]
+    );
+    assert_eq!(out.tables["teams"], vec![json!("b")]);
+    assert!(out.explain.contains(&"  [pure] having".to_string()));
+
+    let err = run(
+        r#"input.json("xs") |> json |> having(_ > 1) |> ui.table("out");"#,
+        json!({"xs": [1, 2]}),
+    )
+    .expect_err("having needs group records")
+    .to_string();
+    assert_eq!(
+        err,
+        "having expects {key, items} group records; use it after a group.* stage at 1:29"
+    );
+
+    let err = run(
+        r#"input.json("xs") |> json |> where() |> ui.table("out");"#,
+        json!({"xs": [1]}),
+    )
+    .expect_err("where needs a predicate")
+    .to_string();
+    assert_eq!(
+        err,
+        "where: missing argument pred (expected where(pred...)) at 1:29"
+    );
+}
+
+#[test]
+fn ui_table_columns_and_format_shape_rows() {
+    let program = r#"
+input.json("rows") |> json
+  |> ui.table("scores", columns=["name", "score", "rank"], format={score: "%.2f", name: "<%s>"});
+input.json("xs") |> json |> ui.table("ms", format={value: "%d ms"});
+"#;
+    let out = run(
+        program,
+        json!({
+            "rows": [{"score": 7, "name": "ada", "extra": true}, {"score": null, "name": "lin"}],
+            "xs": [5]
+        }),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["scores"],
+        vec![
+            json!({"name": "<ada>", "score": "7.00", "rank": null}),
+            json!({"name": "<lin>", "score": null, "rank": null}),
+        ]
+    );
+    assert_eq!(out.column_order["scores"], vec!["name", "score", "rank"]);
+    let names: Vec<&str> = out.schemas["scores"]
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+    assert_eq!(names, vec!["name", "score", "rank"]);
+    assert_eq!(out.tables["ms"], vec![json!("5 ms")]);
+
+    let err = run(
+        r#"input.json("rows") |> json |> ui.table("t", format={name: "%d"});"#,
+        json!({"rows": [{"name": "ada"}]}),
+    )
+    .expect_err("string cell with %d")
+    .to_string();
+    assert_eq!(
+        err,
+        "ui.table(t) format for name: expected a number, got a string at 1:31"
+    );
+
+    let err = run(
+        r#"input.json("rows") |> json |> ui.table("t", format={name: "%x"});"#,
+        json!({"rows": []}),
+    )
+    .expect_err("unsupported format")
+    .to_string();
+    assert!(
+        err.starts_with("ui.table format for name: unsupported format \"%x\""),
+        "{err}"
+    );
+
+    let err = run(
+        r#"input.json("rows") |> json |> ui.table("t", colums=["a"]);"#,
+        json!({"rows": []}),
+    )
+    .expect_err("typo in argument name")
+    .to_string();
+    assert_eq!(
+        err,
+        "ui.table: unknown argument colums (expected columns or format) at 1:31"
+    );
+}
+
+#[test]
+fn stage_arguments_bind_by_position_or_name() {
+    let program = r#"
+xs := input.json("xs") |> json;
+xs |> map(f=_ + 1) |> filter(pred=_ > 2) |> ui.table(name="named");
+xs |> rank.topk(2, _, "desc") |> ui.table("positional");
+"#;
+    let out = run(program, json!({"xs": [1, 2, 3]})).expect("both forms bind");
+    assert_eq!(out.tables.get("named"), Some(&vec![json!(3), json!(4)]));
+    assert_eq!(
+        out.tables.get("positional"),
+        Some(&vec![json!(3), json!(2)])
+    );
+
+    for (program, expected) in [
+        (
+            "xs |> map(g=_);",
+            "map: unknown argument g (expected f) at 1:7",
+        ),
+        ("xs |> map(_, f=_);", "map: f is given twice at 1:7"),
+        (
+            "xs |> map(_, _);",
+            "map: too many arguments (expected map(f)) at 1:7",
+        ),
+        (
+            "xs |> ui.log(level=\"warn\", \"l\");",
+            "ui.log: positional arguments must come before named ones at 1:7",
+        ),
+        (
+            "xs |> rank.topk(2);",
+            "rank.topk: missing argument by (expected rank.topk(k, by, order, nulls=\"last\")) at 1:7",
+        ),
+    ] {
+        let err = dsl_runtime::compile(program)
+            .expect_err(program)
+            .to_string();
+        assert_eq!(err, expected);
+    }
+}
+
+#[test]
+fn ui_log_level_is_validated() {
+    let err = run(
+        r#"input.json("xs") |> json |> ui.log("l", level="loud");"#,
+        json!({"xs": [1]}),
+    )
+    .expect_err("unknown level")
+    .to_string();
+    assert_eq!(
+        err,
+        "unknown log level: loud (expected debug, info, warn, or error) at 1:29"
+    );
+    let out = run(
+        r#"input.json("xs") |> json |> ui.log("l", level="error");"#,
+        json!({"xs": ["boom"]}),
+    )
+    .expect("program should run");
+    assert_eq!(out.logs["l"][0].level, dsl_runtime::LogLevel::Error);
+    assert_eq!(out.logs["l"][0].line(), "[error] \"boom\"");
+}
+
+#[test]
+fn ui_diff_reports_added_removed_and_changed_rows() {
+    let fixtures = json!({
+        "before": [{"id": 1, "v": "a"}, {"id": 2, "v": "b"}, {"id": 3, "v": "c"}],
+        "after": [{"id": 3, "v": "c"}, {"id": 2, "v": "B"}, {"id": 4, "v": "d"}]
+    });
+    let out = run(
+        r#"input.json("after") |> json |> ui.diff("changes", expected="before", key=_.id);"#,
+        fixtures.clone(),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["changes"],
+        vec![
+            json!({"op": "removed", "key": 1, "expected": {"id": 1, "v": "a"}}),
+            json!({
+                "op": "changed", "key": 2, "fields": ["v"],
+                "expected": {"id": 2, "v": "b"}, "actual": {"id": 2, "v": "B"}
+            }),
+            json!({"op": "added", "key": 4, "actual": {"id": 4, "v": "d"}}),
+        ]
+    );
+    assert!(out
+        .explain
+        .contains(&"  [sink] ui.diff(changes)".to_string()));
+
+    // Without key=, rows are matched by position.
+    let out = run(
+        r#"input.json("after") |> json |> map(_.v) |> ui.diff("by_index", expected="before");"#,
+        json!({"before": ["c", "b"], "after": [{"id": 3, "v": "c"}, {"id": 2, "v": "B"}, {"id": 4, "v": "d"}]}),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["by_index"],
+        vec![
+            json!({"op": "changed", "key": 1, "expected": "b", "actual": "B"}),
+            json!({"op": "added", "key": 2, "actual": "d"}),
+        ]
+    );
+
+    let err = run(
+        r#"input.json("after") |> json |> ui.diff("d", expected="after", key=_.v);"#,
+        json!({"after": [{"v": 1}, {"v": 1}]}),
+    )
+    .expect_err("duplicate keys")
+    .to_string();
+    assert_eq!(err, "ui.diff: duplicate key 1 in expected rows at 1:32");
+}
+
+#[test]
+fn ui_graph_collects_nodes_and_edges() {
+    let program = r#"
+input.json("resources")
+  |> json
+  |> ui.graph("tree", node=_.id, edges=_.parent);
+"#;
+    let out = run(
+        program,
+        json!({"resources": [
+            {"id": "doc", "parent": "folder"},
+            {"id": "folder", "parent": ["org"]},
+            {"id": "org", "parent": null},
+            {"id": "doc", "parent": "folder"}
+        ]}),
+    )
+    .expect("program should run");
+    let graph = &out.graphs["tree"];
+    assert_eq!(
+        graph.to_json(),
+        json!({
+            "nodes": [
+                {"id": "doc", "item": {"id": "doc", "parent": "folder"}},
+                {"id": "folder", "item": {"id": "folder", "parent": ["org"]}},
+                {"id": "org", "item": {"id": "org", "parent": null}}
+            ],
+            "edges": [{"from": "doc", "to": "folder"}, {"from": "folder", "to": "org"}]
+        })
+    );
+    assert_eq!(
+        graph.to_mermaid(),
+        "flowchart TD\n  g0[\"doc\"]\n  g1[\"folder\"]\n  g2[\"org\"]\n  g0 --> g1\n  g1 --> g2\n"
+    );
+
+    let out = run(
+        r#"input.json("xs") |> json |> ui.graph("g", node=_.id, edges=_.deps);"#,
+        json!({"xs": [{"id": 1, "deps": [2, 3]}]}),
+    )
+    .expect("program should run");
+    let ids: Vec<_> = out.graphs["g"].nodes.iter().map(|n| n.id.clone()).collect();
+    assert_eq!(ids, vec![json!(1), json!(2), json!(3)]);
+    assert_eq!(out.graphs["g"].nodes[1].item, json!(null));
+
+    let err = run(
+        r#"input.json("xs") |> json |> ui.graph("g", node=_, edges=_.deps);"#,
+        json!({"xs": [{"id": 1, "deps": []}]}),
+    )
+    .expect_err("record node id")
+    .to_string();
+    assert!(
+        err.contains("ui.graph node must evaluate to strings or numbers"),
+        "{err}"
+    );
+}
+
+#[test]
+fn fsm_evaluate_runs_items_and_grouped_sequences() {
+    let fixtures = json!({
+        "transitions": [
+            {"from": "start", "event": "open", "to": "opened"},
+            {"from": "opened", "event": "close", "to": "closed"},
+            {"from": "closed", "event": "open", "to": "opened"}
+        ],
+        "events": [
+            {"door": "a", "event": "open"},
+            {"door": "b", "event": "close"},
+            {"door": "a", "event": "close"}
+        ]
+    });
+    let out = run(
+        r#"input.json("events") |> json |> fsm.evaluate(transitions="transitions", initial="start", event=_.event, by_key=_.door) |> ui.table("doors");"#,
+        fixtures.clone(),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["doors"],
+        vec![
+            json!({"key": "a", "state": "closed", "path": ["start", "opened", "closed"], "accepted": true}),
+            json!({"key": "b", "state": "start", "path": ["start"], "accepted": false}),
+        ]
+    );
+
+    let out = run(
+        r#"input.json("events") |> json |> fsm.evaluate(transitions="transitions", initial="start", event=_.event) |> select(state=_.state) |> ui.table("each");"#,
+        fixtures.clone(),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["each"],
+        vec![
+            json!({"state": "opened"}),
+            json!({"state": "start"}),
+            json!({"state": "start"}),
+        ]
+    );
+
+    let out = run(
+        r#"input.json("runs") |> json |> fsm.evaluate(transitions="transitions", initial="start", event=_.events) |> select(state=_.state) |> ui.table("runs");"#,
+        json!({
+            "transitions": [
+                {"from": "start", "event": "open", "to": "opened"},
+                {"from": "opened", "event": "close", "to": "closed"},
+                {"from": "closed", "event": "open", "to": "opened"}
+            ],
+            "runs": [{"events": ["open", "close", "open"]}]
+        }),
+    )
+    .expect("program should run");
+    assert_eq!(out.tables["runs"], vec![json!({"state": "opened"})]);
+
+    let err = run(
+        r#"input.json("events") |> json |> fsm.evaluate(transitions="events", initial="start", event=_.event) |> ui.table("x");"#,
+        fixtures,
+    )
+    .expect_err("events are not transitions").to_string();
+    assert!(err.contains("missing from"), "{err}");
+}
+
+#[test]
+fn sessionize_splits_each_key_on_inactivity_gaps() {
+    let out = run(
+        r#"input.json("events") |> json |> sessionize(by_key=_.user, gap_ms=100, ts=_.ts) |> ui.table("sessions");"#,
+        json!({"events": [
+            {"user": "a", "ts": 250},
+            {"user": "b", "ts": 10},
+            {"user": "a", "ts": 0},
+            {"user": "a", "ts": 100},
+            {"user": "a", "ts": 201}
+        ]}),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["sessions"],
+        vec![
+            json!({"key": "a", "session_id": 0, "items": [{"user": "a", "ts": 0}, {"user": "a", "ts": 100}]}),
+            json!({"key": "a", "session_id": 1, "items": [{"user": "a", "ts": 201}, {"user": "a", "ts": 250}]}),
+            json!({"key": "b", "session_id": 0, "items": [{"user": "b", "ts": 10}]}),
+        ]
+    );
+
+    let err = run(
+        r#"input.json("events") |> json |> sessionize(by_key=_.user, gap_ms=100, ts=_.at) |> ui.table("s");"#,
+        json!({"events": [{"user": "a", "at": "noon"}]}),
+    )
+    .expect_err("string ts").to_string();
+    assert!(err.contains("sessionize ts must evaluate to I64"), "{err}");
+
+    let out = run(
+        r#"input.json("events") |> json |> sessionize(by_key=1, gap_ms=100, ts=_) |> map(_.session_id) |> ui.table("far");"#,
+        json!({"events": [-9223372036854775808, 9223372036854775807]}),
+    )
+    .expect("far-apart timestamps");
+    assert_eq!(out.tables["far"], vec![json!(0), json!(1)]);
+}
+
+#[test]
+fn dedupe_within_drops_redeliveries_inside_the_window() {
+    let program = r#"input.json("deliveries") |> json |> dedupe.within(by=_.event_id, within_ms=1000, ts=_.ts) |> map(_.ts) |> ui.table("kept");"#;
+    let fixtures = json!({"deliveries": [
+        {"event_id": "e1", "ts": 0},
+        {"event_id": "e2", "ts": 100},
+        {"event_id": "e1", "ts": 900},
+        {"event_id": "e1", "ts": 1500},
+        {"event_id": "e1", "ts": 1800}
+    ]});
+    let out = run(program, fixtures).expect("program should run");
+    assert_eq!(out.tables["kept"], vec![json!(0), json!(100), json!(1500)]);
+
+    let fixtures = json!({"deliveries": [
+        {"event_id": "e1", "ts": -9223372036854775808},
+        {"event_id": "e1", "ts": 9223372036854775807}
+    ]});
+    let out = run(program, fixtures).expect("far-apart timestamps");
+    assert_eq!(
+        out.tables["kept"],
+        vec![json!(-9223372036854775808), json!(9223372036854775807)]
+    );
+}
+
+#[test]
+fn throttle_applies_a_token_bucket_per_key() {
+    let fixtures = json!({"requests": [
+        {"user": "a", "ts": 0},
+        {"user": "a", "ts": 0},
+        {"user": "b", "ts": 0},
+        {"user": "a", "ts": 0},
+        {"user": "a", "ts": 500},
+        {"user": "a", "ts": 600}
+    ]});
+    let out = run(
+        r#"input.json("requests") |> json |> throttle(by_key=_.user, limit=2, per_ms=1000, ts=_.ts) |> map(_.ts) |> ui.table("allowed");"#,
+        fixtures.clone(),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["allowed"],
+        vec![json!(0), json!(0), json!(0), json!(500)]
+    );
+    assert!(out.explain.contains(&"    dropped: a=2".to_string()));
+
+    let out = run(
+        r#"input.json("requests") |> json |> throttle(by_key=_.user, limit=2, per_ms=1000, ts=_.ts, mode="mark") |> map(_.allowed) |> ui.table("marks");"#,
+        fixtures,
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["marks"],
+        vec![
+            json!(true),
+            json!(true),
+            json!(true),
+            json!(false),
+            json!(true),
+            json!(false)
+        ]
+    );
+    assert!(out.explain.contains(&"    over limit: a=2".to_string()));
+
+    let out = run(
+        r#"input.json("xs") |> json |> throttle(by_key=_, limit=1, per_ms=2) |> ui.table("kept");"#,
+        json!({"xs": [7, 7, 7, 7]}),
+    )
+    .expect("program should run");
+    assert_eq!(out.tables["kept"], vec![json!(7), json!(7)]);
+}
+
+#[test]
+fn throttle_saturates_instead_of_overflowing_on_large_values() {
+    let out = run(
+        r#"input.json("xs") |> json |> throttle(by_key=1, limit=9000000000000, per_ms=9000000000000, ts=_) |> ui.table("kept");"#,
+        json!({"xs": [-9223372036854775808, 0, 9223372036854775807]}),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["kept"],
+        vec![
+            json!(-9223372036854775808),
+            json!(0),
+            json!(9223372036854775807)
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "stats")]
+fn stats_outliers_flags_or_drops_values_outside_the_fences() {
+    let fixtures = json!({"calls": [
+        {"id": 1, "latency": 10},
+        {"id": 2, "latency": 12},
+        {"id": 3, "latency": 11},
+        {"id": 4, "latency": 13},
+        {"id": 5, "latency": 12},
+        {"id": 6, "latency": 100}
+    ]});
+    let flagged = |method: &str| -> Vec<serde_json::Value> {
+        let program = format!(
+            r#"input.json("calls") |> json |> stats.outliers(field=_.latency, method="{method}") |> filter(_.is_outlier) |> map(_.id) |> ui.table("slow");"#
+        );
+        run(&program, fixtures.clone())
+            .expect("program should run")
+            .tables["slow"]
+            .clone()
+    };
+    assert_eq!(flagged("iqr"), vec![json!(6)]);
+    assert_eq!(flagged("zscore"), Vec::<serde_json::Value>::new());
+
+    let out = run(
+        r#"input.json("calls") |> json |> map(_.latency) |> stats.outliers(field=_, mode="drop") |> ui.table("kept");"#,
+        fixtures.clone(),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["kept"],
+        vec![json!(10), json!(12), json!(11), json!(13), json!(12)]
+    );
+
+    let err = run(
+        r#"input.json("calls") |> json |> stats.outliers(field=_.latency, method="mad") |> ui.table("x");"#,
+        fixtures,
+    )
+    .expect_err("unknown method").to_string();
+    assert!(err.contains("unknown method mad"), "{err}");
+}
+
+#[test]
+fn destructuring_binds_one_stream_per_field() {
+    let out = run(
+        r#"
+{id, tags} := input.json("rows") |> json;
+id |> ui.table("ids");
+tags |> ui.table("tags");
+"#,
+        json!({"rows": [{"id": 1, "tags": ["a"]}, {"id": 2, "tags": []}]}),
+    )
+    .expect("program should run");
+    assert_eq!(out.tables["ids"], vec![json!(1), json!(2)]);
+    assert_eq!(out.tables["tags"], vec![json!(["a"]), json!([])]);
+    assert!(out.explain.contains(&"binding {id, tags}".to_string()));
+
+    let err = run(
+        r#"{id, name} := input.json("rows") |> json; id |> ui.table("ids");"#,
+        json!({"rows": [{"id": 1}]}),
+    )
+    .expect_err("missing field")
+    .to_string();
+    assert_eq!(err, "destructuring name: field not found: name at 1:1");
+}
+
+#[test]
+fn string_interpolation_renders_values() {
+    let out = run(
+        r#"input.json("scores") |> json |> map("user ${_.id} scored ${_.score} ${_.tags}") |> ui.log("out");"#,
+        json!({"scores": [{"id": "u1", "score": 7, "tags": ["a"]}]}),
+    )
+    .expect("program should run");
+    assert_eq!(out.logs["out"][0].item, json!("user u1 scored 7 [\"a\"]"));
+
+    let out = run(
+        r#"input.json("xs") |> json |> map("n=${_}" + "!") |> ui.table("t");"#,
+        json!({"xs": [1]}),
+    )
+    .expect("program should run");
+    assert_eq!(out.tables["t"], vec![json!("n=1!")]);
+}
+
+#[test]
+fn let_binds_a_local_inside_an_expression() {
+    let program = r#"
+rows := input.json("rows") |> json;
+rows |> map(let total = _.a + _.b in { id: _.id, total: total }) |> ui.table("totals");
+rows |> map(let t = _.a + _.b in let twice = t + t in twice + 1) |> ui.table("nested");
+rows |> filter(let a = _.a in a > 1) |> map(_.id) |> ui.table("big");
+"#;
+    let out = run(
+        program,
+        json!({"rows": [{"id": 1, "a": 2, "b": 3}, {"id": 2, "a": 1, "b": 0}]}),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["totals"],
+        vec![json!({"id": 1, "total": 5}), json!({"id": 2, "total": 1})]
+    );
+    assert_eq!(out.tables["nested"], vec![json!(11), json!(3)]);
+    assert_eq!(out.tables["big"], vec![json!(1)]);
+
+    let err = run(
+        "const T := 1;\nxs := input.json(\"xs\") |> json |> map(let T = _ in T);",
+        json!({"xs": [1]}),
+    )
+    .expect_err("T is a constant")
+    .to_string();
+    assert!(
+        err.starts_with("let T: name is already a constant"),
+        "{err}"
+    );
+}
+
+#[test]
+fn let_locals_reach_array_lambdas() {
+    let program = r#"
+rows := input.json("rows") |> json;
+rows |> map(let k = 10 in array.map(_.b, _ + k)) |> ui.table("mapped");
+rows |> map(let k = 10 in array.flat_map(_.b, [_, _ + k])) |> ui.table("flat");
+"#;
+    let out = run(program, json!({"rows": [{"b": [1, 2]}]})).expect("program should run");
+    assert_eq!(out.tables["mapped"], vec![json!([11, 12])]);
+    assert_eq!(out.tables["flat"], vec![json!([1, 11, 2, 12])]);
+}
+
+#[test]
+fn match_dispatches_on_the_shape_of_each_value() {
+    let program = r#"
+events := input.json("events") |> json;
+events
+  |> map(match _ {
+    { type: "click", x, .. } => "click at " + x,
+    { type: "view", page: p } => { viewed: p },
+    { type, .. } => "other " + type,
+    n => n + 1
+  })
+  |> ui.table("out");
+"#;
+    let out = run(
+        program,
+        json!({"events": [
+            {"type": "click", "x": "3", "at": 1},
+            {"type": "view", "page": "/"},
+            {"type": "view", "page": "/", "extra": true},
+            4
+        ]}),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["out"],
+        vec![
+            json!("click at 3"),
+            json!({"viewed": "/"}),
+            json!("other view"),
+            json!(5)
+        ]
+    );
+
+    let err = run(
+        r#"input.json("xs") |> json |> map(match _ { 1 => "one" });"#,
+        json!({"xs": [1, {"a": 2}]}),
+    )
+    .expect_err("no arm fits the record")
+    .to_string();
+    assert!(err.starts_with("match: no arm fits {\"a\":2}"), "{err}");
+}
+
+#[test]
+fn tagged_values_carry_their_variant_through_match() {
+    let program = r#"
+events := input.json("events") |> json;
+tagged := events
+  |> map(match _ {
+    { kind: "click", x, .. } => tag({ x: x }, "click"),
+    { kind: "key", key: k } => tag(k, "key"),
+    _ => tag(_, "other")
+  });
+tagged |> ui.table("tagged");
+tagged |> map(match _ { click({ x }) => x, key(k) => "key " + k, other(_) => null }) |> ui.table("out");
+tagged |> map(untag(tag(_, "again"))) |> ui.table("untagged");
+"#;
+    let out = run(
+        program,
+        json!({"events": [
+            {"kind": "click", "x": 3},
+            {"kind": "key", "key": "a"},
+            {"kind": "scroll"}
+        ]}),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["tagged"],
+        vec![
+            json!({"tag": "click", "value": {"x": 3}}),
+            json!({"tag": "key", "value": "a"}),
+            json!({"tag": "other", "value": {"kind": "scroll"}})
+        ]
+    );
+    assert_eq!(
+        out.tables["out"],
+        vec![json!(3), json!("key a"), json!(null)]
+    );
+    assert_eq!(
+        out.tables["untagged"],
+        vec![json!({"x": 3}), json!("a"), json!({"kind": "scroll"})]
+    );
+
+    let err = run(
+        r#"input.json("xs") |> json |> map(match tag(_, "a") { { tag, .. } => tag, b(_) => 0 });"#,
+        json!({"xs": [1]}),
+    )
+    .expect_err("record patterns don't fit tagged values")
+    .to_string();
+    assert!(err.starts_with("match: no arm fits"), "{err}");
+    let err = run(
+        r#"input.json("xs") |> json |> map(tag(_, 1));"#,
+        json!({"xs": [1]}),
+    )
+    .expect_err("tag names are strings")
+    .to_string();
+    assert!(err.contains("tag name must evaluate to String"), "{err}");
+}
+
+#[test]
+fn conversion_builtins_coerce_or_fail() {
+    let program = r#"
+rows := input.json("rows") |> json;
+rows |> map([int(_.n), str(_.id), bool(_.flag)]) |> ui.table("converted");
+rows |> sort(by=int(_.n)) |> map(_.id) |> ui.table("sorted");
+rows |> map(int?(_.flag)) |> ui.table("lenient");
+"#;
+    let out = run(
+        program,
+        json!({"rows": [
+            {"id": 7, "n": "10", "flag": "true"},
+            {"id": "b", "n": " 9 ", "flag": 0},
+            {"id": true, "n": -3, "flag": false}
+        ]}),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["converted"],
+        vec![
+            json!([10, "7", true]),
+            json!([9, "b", false]),
+            json!([-3, "true", false])
+        ]
+    );
+    assert_eq!(
+        out.tables["sorted"],
+        vec![json!(true), json!("b"), json!(7)]
+    );
+    assert_eq!(out.tables["lenient"], vec![json!(null), json!(0), json!(0)]);
+
+    let err = run(
+        r#"input.json("xs") |> json |> map(int(_)) |> ui.table("t");"#,
+        json!({"xs": ["12", "1.5"]}),
+    )
+    .expect_err("1.5 is not an integer")
+    .to_string();
+    assert!(err.contains("int: cannot convert \"1.5\""), "{err}");
+    let err = run(
+        r#"input.json("xs") |> json |> map(bool(_)) |> ui.table("t");"#,
+        json!({"xs": [2]}),
+    )
+    .expect_err("only 0 and 1 are bools")
+    .to_string();
+    assert!(err.contains("bool: cannot convert 2"), "{err}");
+}
+
+#[test]
+fn bool_null_and_negative_literals_evaluate() {
+    let out = run(
+        r#"input.json("users") |> json |> filter(_.active == true) |> map({ id: _.id, flag: false, none: null, neg: -_.score, n: -2 }) |> ui.table("t");"#,
+        json!({"users": [
+            {"id": "a", "active": true, "score": 5},
+            {"id": "b", "active": false, "score": 1}
+        ]}),
+    )
+    .expect("program should run");
+    assert_eq!(
+        out.tables["t"],
+        vec![json!({"id": "a", "flag": false, "none": null, "neg": -5, "n": -2})]
+    );
+
+    let out = run(
+        r#"input.json("xs") |> json |> map(-_ + 10) |> ui.table("t");"#,
+        json!({"xs": [3]}),
+    )
+    .expect("program should run");
+    assert_eq!(out.tables["t"], vec![json!(7)]);
+}
+
+#[test]
+fn runtime_errors_point_at_the_failing_stage() {
+    let program = "xs := input.json(\"xs\") |> json;\nxs\n  |> map(_ + 1)\n  |> select(_.id)\n  |> ui.table(\"out\");\n";
+    let err = run(program, json!({"xs": [1]}))
+        .expect_err("positional select")
+        .to_string();
+    assert_eq!(
+        err,
+        "select expects named columns, e.g. select(id=_.id) at 4:6"
+    );
+
+    let err = run("\n  nope |> ui.table(\"out\");", json!({}))
+        .expect_err("unknown ident")
+        .to_string();
+    assert_eq!(err, "unknown ident nope at 2:3");
+}
+
+#[test]
+fn unknown_names_suggest_the_nearest_known_ones() {
+    let err = run(
+        r#"input.json("xs") |> json |> grup.count(by_key=_) |> ui.table("out");"#,
+        json!({"xs": []}),
+    )
+    .expect_err("misspelled stage")
+    .to_string();
+    assert_eq!(
+        err,
+        "unsupported call: grup.count (did you mean `group.count`?) at 1:29"
+    );
+    assert_eq!(dsl_runtime::suggestions(&err), ["group.count"]);
+
+    let err = run(
+        "xs := input.json(\"xs\") |> json;\nxz |> ui.table(\"out\");",
+        json!({"xs": []}),
+    )
+    .expect_err("misspelled binding")
+    .to_string();
+    assert_eq!(err, "unknown ident xz (did you mean `xs`?) at 2:1");
+
+    let err = run(
+        r#"input.json("xs") |> json |> map(let total = _ in totl) |> ui.table("out");"#,
+        json!({"xs": [1]}),
+    )
+    .expect_err("misspelled local")
+    .to_string();
+    assert!(err.contains("(did you mean `total`?)"), "{err}");
+
+    let err = run(
+        r#"input.json("xs") |> json |> frobnicate(_) |> ui.table("out");"#,
+        json!({"xs": []}),
+    )
+    .expect_err("nothing close")
+    .to_string();
+    assert_eq!(err, "unsupported call: frobnicate at 1:29");
+    assert!(dsl_runtime::suggestions(&err).is_empty());
+}
+
+#[test]
+fn run_statement_reruns_only_stale_dependencies() {
+    let program = r#"xs := input.json("xs") |> json;
+const STEP := 10;
+ys := xs |> map(_ + STEP);
+ys |> ui.table("out");
+"#;
+    let fixtures = json!({"xs": [1, 2]});
+    let mut session = dsl_runtime::Session::new(fixtures.clone()).expect("fixtures load");
+    let ran = |out: &dsl_runtime::Outputs| -> Vec<String> {
+        out.explain
+            .iter()
+            .filter(|line| !line.starts_with(' '))
+            .cloned()
+            .collect()
+    };
+
+    let out = dsl_runtime::run_statement(program, 3, fixtures.clone(), &mut session)
+        .expect("statement should run");
+    assert_eq!(out.tables.get("out"), Some(&vec![json!(11), json!(12)]));
+    assert_eq!(ran(&out), ["binding xs", "binding ys", "pipeline"]);
+
+    let out = dsl_runtime::run_statement(program, 3, fixtures.clone(), &mut session)
+        .expect("statement should run");
+    assert_eq!(ran(&out), ["pipeline"]);
+
+    let edited = program.replace("_ + STEP", "_ + STEP + 1");
+    let out = dsl_runtime::run_statement(&edited, 3, fixtures.clone(), &mut session)
+        .expect("statement should run");
+    assert_eq!(out.tables.get("out"), Some(&vec![json!(12), json!(13)]));
+    assert_eq!(ran(&out), ["binding ys", "pipeline"]);
+
+    let out = dsl_runtime::run_statement(&edited, 3, json!({"xs": [5]}), &mut session)
+        .expect("new fixtures recompute everything");
+    assert_eq!(out.tables.get("out"), Some(&vec![json!(16)]));
+    assert_eq!(ran(&out), ["binding xs", "binding ys", "pipeline"]);
+
+    let out = dsl_runtime::run_statement(program, 1, json!({"xs": [5]}), &mut session)
+        .expect("constants do nothing on their own");
+    assert!(out.explain.is_empty());
+    let err = dsl_runtime::run_statement(program, 4, json!({"xs": [5]}), &mut session)
+        .expect_err("no such statement")
+        .to_string();
+    assert_eq!(
+        err,
+        "statement 4 is out of range (the program has 4 statements)"
+    );
+}
+
+#[test]
+#[cfg(feature = "kv")]
+fn run_statement_reloads_kv_stores_it_reads() {
+    let program = r#"input.json("users") |> json |> kv.load(store="users");
+input.json("events") |> json |> ui.table("unrelated");
+input.json("events")
+  |> json
+  |> lookup.kv(store="users", key=_.user_id)
+  |> ui.table("joined");
+"#;
+    let fixtures = json!({
+        "users": [{"key": "u1", "value": {"name": "Ada"}}],
+        "events": [{"user_id": "u1"}]
+    });
+    let mut session = dsl_runtime::Session::new(fixtures.clone()).expect("fixtures load");
+    let out = dsl_runtime::run_statement(program, 2, fixtures, &mut session)
+        .expect("statement should run");
+    assert_eq!(
+        out.tables.get("joined"),
+        Some(&vec![
+            json!({"left": {"user_id": "u1"}, "right": {"name": "Ada"}})
+        ])
+    );
+    assert!(!out.tables.contains_key("unrelated"));
+    assert!(out.explain.contains(&"  [sink] kv.load(users)".to_string()));
+    assert!(!out
+        .explain
+        .contains(&"  [sink] ui.table(unrelated)".to_string()));
+}
+
+#[test]
+fn diff_outputs_compares_tables_and_logs_by_index() {
+    let fixtures = json!({"xs": [1, 2]});
+    let a = run(
+        r#"xs := input.json("xs") |> json; xs |> ui.table("out"); xs |> ui.log("seen");"#,
+        fixtures.clone(),
+    )
+    .expect("program a should run");
+    let b = run(
+        r#"xs := input.json("xs") |> json; xs |> map(_ + 1) |> ui.table("out"); xs |> ui.log("seen"); xs |> ui.table("extra");"#,
+        fixtures,
+    )
+    .expect("program b should run");
+
+    assert!(dsl_runtime::diff_outputs(&a, &a).is_empty());
+    let diff = dsl_runtime::diff_outputs(&a, &b);
+    assert!(diff.logs.is_empty());
+    assert_eq!(
+        diff.to_json(),
+        json!({
+            "tables": {
+                "extra": [
+                    {"op": "added", "key": 0, "actual": 1},
+                    {"op": "added", "key": 1, "actual": 2}
+                ],
+                "out": [
+                    {"op": "changed", "key": 0, "expected": 1, "actual": 2},
+                    {"op": "changed", "key": 1, "expected": 2, "actual": 3}
+                ]
+            },
+            "logs": {}
+        })
+    );
+}
+
+#[test]
+fn deprecated_stage_names_run_with_a_warning() {
+    let program = r#"xs := input.json("xs") |> json;
+xs
+  |> topk(k=2, by=_, order="desc")
+  |> ui.table("top");
+"#;
+    let out = run(program, json!({"xs": [3, 1, 2]})).expect("aliases still run");
+    assert_eq!(out.tables.get("top"), Some(&vec![json!(3), json!(2)]));
+    assert_eq!(
+        out.warnings,
+        [dsl_runtime::Warning {
+            kind: dsl_runtime::WarningKind::Deprecated,
+            message: "topk is deprecated; use rank.topk".to_string(),
+            at: Some(dsl_syntax::LineCol { line: 3, column: 6 }),
+        }]
+    );
+    assert_eq!(
+        out.warnings[0].to_string(),
+        "topk is deprecated; use rank.topk at 3:6"
+    );
+    assert!(out
+        .explain
+        .contains(&"  [deprecated] topk -> rank.topk".to_string()));
+
+    let out = run(
+        r#"input.json("xs") |> json |> rank.topk(k=1, by=_, order="asc") |> ui.table("t");"#,
+        json!({"xs": [3, 1]}),
+    )
+    .expect("current names run");
+    assert!(out.warnings.is_empty());
+}
+
+#[test]
+#[cfg(feature = "codecs")]
+fn bound_compositions_invert_and_name_their_irreversible_part() {
+    let program = r#"
+chain := utf8 >> base64;
+input.json("xs") |> ~json |> chain |> ~chain |> ui.table("t");
+"#;
+    let out = run(program, json!({"xs": ["hi"]})).expect("~chain should invert");
+    assert_eq!(out.tables["t"], vec![json!("hi")]);
+
+    let err = run(
+        "bad := ~json >> map(_.id) >> utf8;\ninput.json(\"xs\") |> ~bad |> ui.table(\"t\");",
+        json!({"xs": []}),
+    )
+    .expect_err("map has no inverse")
+    .to_string();
+    assert_eq!(
+        err,
+        "cannot invert bad: its component map(_.id) is not reversible at 2:21"
+    );
+    let err = run(
+        r#"input.json("xs") |> ~(~json >> map(_.id)) |> ui.table("t");"#,
+        json!({"xs": []}),
+    )
+    .expect_err("map has no inverse")
+    .to_string();
+    assert_eq!(
+        err,
+        "cannot invert ~json >> map(_.id): map(_.id) is not reversible at 1:32"
+    );
+
+    let mut session = dsl_runtime::Session::new(json!({})).expect("fixtures are valid");
+    session
+        .eval("chain := utf8 >> ~base64.encode; bad := chain >> map(_); inc := map(_ + 1);")
+        .expect("bindings should evaluate");
+    assert_eq!(
+        session.bindings(),
+        vec![
+            ("bad".to_string(), "stage".to_string()),
+            ("chain".to_string(), "stage (reversible)".to_string()),
+            ("inc".to_string(), "stage".to_string()),
+        ]
+    );
+    let err = session
+        .eval("inv := ~bad;")
+        .expect_err("bad has a map")
+        .to_string();
+    assert_eq!(
+        err,
+        "cannot invert bad: its component map(_) is not reversible at 1:8"
+    );
+}
+
+#[test]
+fn rebinding_a_name_needs_override() {
+    let program =
+        "xs := input.json(\"xs\") |> json;\nxs := xs |> map(_ + 1);\nxs |> ui.table(\"t\");";
+    let err = run(program, json!({"xs": [1]}))
+        .expect_err("xs is bound twice")
+        .to_string();
+    assert_eq!(
+        err,
+        "duplicate binding xs (first bound at 1:1; write `override xs := ...;` to replace it) at 2:1"
+    );
+    let err = dsl_runtime::compile("{a} := ys;\nzs := ys;\n{b, a} := ys;")
+        .expect_err("a is bound twice")
+        .to_string();
+    assert!(
+        err.starts_with("duplicate binding a (first bound at 1:1;"),
+        "{err}"
+    );
+
+    let fixed = program.replace("\nxs := xs", "\noverride xs := xs");
+    let out = run(&fixed, json!({"xs": [1]})).expect("override rebinds");
+    assert_eq!(out.tables["t"], vec![json!(2)]);
+
+    let options =
+        dsl_runtime::RunOptions::from_json(&json!({"rebinding": "allow"})).expect("valid options");
+    assert_eq!(options.rebinding, dsl_runtime::Rebinding::Allow);
+    let out = dsl_runtime::run_with_options(program, json!({"xs": [1]}), &options)
+        .expect("rebinding allowed");
+    assert_eq!(out.tables["t"], vec![json!(2)]);
+}
+
+#[test]
+fn binding_a_builtin_namespace_is_a_compile_error() {
+    let err = dsl_runtime::compile("xs := input.json(\"xs\");\nui := xs |> json;")
+        .expect_err("ui is reserved")
+        .to_string();
+    assert_eq!(err, "cannot bind reserved namespace 'ui' at 2:1");
+    let err = dsl_runtime::compile("{ok, rank} := input.json(\"runs\") |> json;")
+        .expect_err("rank is reserved")
+        .to_string();
+    assert_eq!(err, "cannot bind reserved namespace 'rank' at 1:1");
+    let err = run("const lookup := 1;", json!({}))
+        .expect_err("lookup is reserved")
+        .to_string();
+    assert_eq!(err, "const lookup: name is reserved");
+    for name in ["stats", "fsm", "dedupe", "utf8"] {
+        let err = dsl_runtime::compile(&format!("{name} := input.json(\"xs\");"))
+            .expect_err(name)
+            .to_string();
+        assert_eq!(
+            err,
+            format!("cannot bind reserved namespace '{name}' at 1:1")
+        );
+    }
+}
+
+#[test]
+fn forward_references_are_compile_errors() {
+    let err = dsl_runtime::compile("a := b |> map(_ + 1);\nb := input.json(\"xs\") |> json;")
+        .expect_err("b is bound later")
+        .to_string();
+    assert_eq!(err, "b is used before it is bound (bound at 2:1) at 1:6");
+
+    let err = dsl_runtime::compile("a := b |> map(_);\nc := a;\nb := c |> filter(_ > 1);")
+        .expect_err("a needs b, which needs a")
+        .to_string();
+    assert_eq!(
+        err,
+        "cycle a -> b -> c -> a: b is used before it is bound (bound at 3:1) at 1:6"
+    );
+
+    let err = dsl_runtime::compile("xs := xs |> map(_);")
+        .expect_err("xs reads itself")
+        .to_string();
+    assert_eq!(
+        err,
+        "cycle xs -> xs: xs is used before it is bound (bound at 1:1) at 1:7"
+    );
+
+    dsl_runtime::compile("xs := input.json(\"xs\");\noverride xs := xs |> json;")
+        .expect("an earlier binding may be read");
+}
+
+#[test]
+fn literal_stage_arguments_are_checked_at_compile_time() {
+    let err = dsl_runtime::compile(
+        "xs := input.json(\"xs\");\nxs |> rank.topk(k=-1, by=_, order=\"desc\");",
+    )
+    .expect_err("k is negative")
+    .to_string();
+    assert_eq!(err, "rank.topk k must be >= 0 at 2:7");
+
+    let err = dsl_runtime::compile(
+        "xs := input.json(\"xs\");\nxs |> rank.topk(k=2, by=_, order=\"up\");",
+    )
+    .expect_err("order is unknown")
+    .to_string();
+    assert!(err.ends_with(" at 2:7"), "{err}");
+
+    let err = dsl_runtime::compile("xs := input.json(\"xs\");\nxs |> rank.topk(k=2, by=_);")
+        .expect_err("order is missing")
+        .to_string();
+    assert!(err.contains("order"), "{err}");
+
+    dsl_runtime::compile(
+        "const K := -1;\nxs := input.json(\"xs\");\nxs |> rank.topk(k=K, by=_, order=\"desc\");",
+    )
+    .expect("arguments that read a const wait for the run");
+}
+
+#[test]
+fn iterate_adds_new_items_until_a_fixed_point() {
+    let program = r#"
+input.json("xs")
+  |> json
+  |> iterate(step=match _ { "root" => ["a", "b"], "a" => ["c"], "c" => ["root"], _ => [] })
+  |> ui.table("closure");
+input.json("xs") |> json |> iterate(step=map(_ + "!"), until=_ == "root!!!", max_iters=10) |> ui.table("until");
+double := map(_ + _);
+input.json("ns") |> json |> iterate(double, _ > 20) |> ui.table("doubled");
+"#;
+    let out = run(program, json!({"xs": ["root"], "ns": [1, 3]})).expect("program should run");
+    assert_eq!(
+        out.tables["closure"],
+        vec![json!("root"), json!("a"), json!("b"), json!("c")]
+    );
+    assert_eq!(
+        out.tables["until"],
+        vec![
+            json!("root"),
+            json!("root!"),
+            json!("root!!"),
+            json!("root!!!")
+        ]
+    );
+    assert_eq!(
+        out.tables["doubled"],
+        vec![
+            json!(1),
+            json!(3),
+            json!(2),
+            json!(6),
+            json!(4),
+            json!(12),
+            json!(8),
+            json!(24)
+        ]
+    );
+
+    let err = run(
+        r#"input.json("ns") |> json |> iterate(step=map(_ + 1), max_iters=3);"#,
+        json!({"ns": [0]}),
+    )
+    .expect_err("never settles")
+    .to_string();
+    assert_eq!(
+        err,
+        "iterate: no fixed point after max_iters=3 iterations at 1:29"
+    );
+
+    let err = dsl_runtime::compile(r#"input.json("ns") |> iterate(step=map(_), max_iters=-1);"#)
+        .expect_err("negative max_iters")
+        .to_string();
+    assert_eq!(err, "iterate max_iters must be >= 0 at 1:21");
+}
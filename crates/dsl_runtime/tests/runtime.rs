@@ -0,0 +1,196 @@
+use dsl_runtime::{Key, Runtime, StageArgs, Value};
+use serde_json::json;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn scrub(args: &StageArgs, input: Vec<Value>) -> Result<Vec<Value>, String> {
+    let Value::String(field) = args.named("field")? else {
+        return Err("field must be a string".to_string());
+    };
+    input
+        .into_iter()
+        .map(|item| match item {
+            Value::Record(mut rec) => {
+                rec.insert(field.as_str().into(), Value::String("***".to_string()));
+                Ok(Value::Record(rec))
+            }
+            _ => Err("expected record".to_string()),
+        })
+        .collect()
+}
+
+#[test]
+fn registered_stage_runs_inside_pipelines() {
+    let mut runtime = Runtime::new();
+    runtime
+        .register_stage("mycorp.scrub", scrub)
+        .expect("name should be accepted")
+        .register_stage(
+            "mycorp.keep_if",
+            |args: &StageArgs, input: Vec<Value>| -> Result<Vec<Value>, String> {
+                let mut out = Vec::new();
+                for item in input {
+                    if args.positional_for(0, &item)? == Value::Bool(true) {
+                        out.push(item);
+                    }
+                }
+                Ok(out)
+            },
+        )
+        .expect("name should be accepted");
+
+    let program = r#"
+input.json("users") |> json
+  |> mycorp.keep_if(_.age > 17)
+  |> mycorp.scrub(field="email")
+  |> ui.table("out");
+"#;
+    let fixtures = json!({"users": [
+        {"name": "a", "age": 30, "email": "a@x"},
+        {"name": "b", "age": 12, "email": "b@x"}
+    ]});
+    let out = runtime
+        .run(program, fixtures.clone())
+        .expect("program should run");
+    assert_eq!(
+        out.tables["out"],
+        vec![json!({"name": "a", "age": 30, "email": "***"})]
+    );
+    assert!(out.explain.contains(&"  [custom] mycorp.scrub".to_string()));
+
+    let err = dsl_runtime::run(program, fixtures)
+        .expect_err("free run has no custom stages")
+        .to_string();
+    assert!(err.contains("unsupported call: mycorp.keep_if"), "{err}");
+}
+
+#[test]
+fn register_stage_rejects_reserved_or_bare_names() {
+    let mut runtime = Runtime::new();
+    assert!(runtime.register_stage("scrub", scrub).is_err());
+    assert!(runtime.register_stage("group.scrub", scrub).is_err());
+    assert!(runtime.register_stage("stats.outliers", scrub).is_err());
+    assert!(runtime.register_stage("fsm.scrub", scrub).is_err());
+    assert!(runtime.register_stage("mycorp.scrub", scrub).is_ok());
+    assert_eq!(runtime.stage_names(), vec!["mycorp.scrub".to_string()]);
+
+    let err = runtime
+        .run(
+            r#"input.json("xs") |> mycorp.scrub(field="x") |> ui.table("t");"#,
+            json!({"xs": [1]}),
+        )
+        .expect_err("stage error should surface")
+        .to_string();
+    assert!(err.starts_with("mycorp.scrub: "), "{err}");
+}
+
+#[test]
+fn host_call_maps_items_through_registered_functions() {
+    let mut runtime = Runtime::new();
+    runtime.register_host_fn("double", |arg| match arg {
+        Value::I64(n) => Ok(Value::I64(n * 2)),
+        _ => Err("expected I64".to_string()),
+    });
+
+    let program = r#"input.json("xs") |> json |> host.call("double", _.n) |> ui.table("out");"#;
+    let out = runtime
+        .run(program, json!({"xs": [{"n": 1}, {"n": 5}]}))
+        .expect("program should run");
+    assert_eq!(out.tables["out"], vec![json!(2), json!(10)]);
+    assert!(out
+        .explain
+        .contains(&"  [host] host.call(double)".to_string()));
+
+    let err = runtime
+        .run(
+            r#"input.json("xs") |> json |> host.call("triple", _) |> ui.table("out");"#,
+            json!({"xs": [1]}),
+        )
+        .expect_err("unregistered host fn")
+        .to_string();
+    assert_eq!(err, "unknown host function: triple at 1:29");
+
+    let err = runtime
+        .run(program, json!({"xs": [{"n": "x"}]}))
+        .expect_err("host error should surface")
+        .to_string();
+    assert_eq!(err, "host.call(double): expected I64 at 1:29");
+}
+
+#[test]
+fn record_keys_are_shared_across_items_and_group_records() {
+    let mut runtime = Runtime::new();
+    runtime
+        .register_stage(
+            "mycorp.shared_keys",
+            |_: &StageArgs, input: Vec<Value>| -> Result<Vec<Value>, String> {
+                let keys: Vec<Vec<Key>> = input
+                    .iter()
+                    .map(|item| match item {
+                        Value::Record(rec) => rec.keys().cloned().collect(),
+                        _ => Vec::new(),
+                    })
+                    .collect();
+                let shared = keys.windows(2).all(|pair| {
+                    pair[0].len() == pair[1].len()
+                        && pair[0].iter().zip(&pair[1]).all(|(a, b)| Key::ptr_eq(a, b))
+                });
+                Ok(vec![Value::Bool(shared)])
+            },
+        )
+        .expect("name should be accepted");
+
+    let program = r#"
+xs := input.json("xs") |> json;
+xs |> mycorp.shared_keys() |> ui.table("decoded");
+xs |> group.count(by_key=_.tag) |> mycorp.shared_keys() |> ui.table("grouped");
+"#;
+    let fixtures =
+        json!({"xs": [{"tag": "a", "id": 1}, {"tag": "b", "id": 2}, {"tag": "a", "id": 3}]});
+    let out = runtime.run(program, fixtures).expect("program should run");
+    assert_eq!(out.tables["decoded"], vec![json!(true)]);
+    assert_eq!(out.tables["grouped"], vec![json!(true)]);
+
+    let a = Key::from("id");
+    assert_eq!(a, Key::from("id"));
+    assert!(!Key::ptr_eq(&a, &Key::from("id")));
+}
+
+#[test]
+fn a_bindings_last_read_moves_its_items_instead_of_copying_them() {
+    let seen: Rc<RefCell<Vec<Vec<usize>>>> = Rc::default();
+    let probe = Rc::clone(&seen);
+    let mut runtime = Runtime::new();
+    runtime
+        .register_stage(
+            "mycorp.probe",
+            move |_: &StageArgs, input: Vec<Value>| -> Result<Vec<Value>, String> {
+                let addresses = input.iter().map(|item| match item {
+                    Value::String(s) => s.as_ptr() as usize,
+                    _ => 0,
+                });
+                probe.borrow_mut().push(addresses.collect());
+                Ok(input)
+            },
+        )
+        .expect("name should be accepted");
+
+    let program = r#"
+xs := input.json("words") |> json |> mycorp.probe();
+xs |> mycorp.probe() |> ui.table("copy");
+xs |> mycorp.probe() |> ui.table("moved");
+"#;
+    let out = runtime
+        .run(program, json!({"words": ["ada", "grace"]}))
+        .expect("program should run");
+    assert_eq!(out.tables["moved"], vec![json!("ada"), json!("grace")]);
+    let seen = seen.borrow();
+    let [bound, copy, moved] = &seen[..] else {
+        panic!("probe should run three times: {seen:?}");
+    };
+    assert_eq!(moved, bound, "the last read hands over the bound items");
+    assert!(
+        copy.iter().zip(bound).all(|(copy, bound)| copy != bound),
+        "an earlier read copies, since the binding is read again"
+    );
+}
@@ -0,0 +1,84 @@
+use dsl_runtime::{run_with_options, RunOptions, Runtime};
+use serde_json::{json, Value};
+
+const PROGRAM: &str = r#"input.json("xs") |> json |> filter(_ > 50) |> ui.table("big");"#;
+
+fn fixtures() -> Value {
+    let xs = (1..=100).map(|n: i64| Value::Number(n.into())).collect();
+    let mut fixtures = json!({"small": [1, 2]});
+    if let Value::Object(map) = &mut fixtures {
+        map.insert("xs".to_string(), Value::Array(xs));
+    }
+    fixtures
+}
+
+#[test]
+fn sample_input_runs_on_an_ordered_subset_of_each_fixture() {
+    let options = RunOptions::default().sample_input(10, 7);
+    let out = run_with_options(PROGRAM, fixtures(), &options).expect("program should run");
+    let rows = &out.tables["big"];
+    assert!(rows.len() <= 10);
+    let values: Vec<i64> = rows
+        .iter()
+        .map(|row| match row {
+            Value::Number(n) => n.as_i64().unwrap(),
+            other => panic!("expected a number, got {other:?}"),
+        })
+        .collect();
+    assert!(
+        values.windows(2).all(|pair| pair[0] < pair[1]),
+        "{values:?}"
+    );
+
+    let again = run_with_options(PROGRAM, fixtures(), &options).expect("program should run");
+    assert_eq!(again.tables, out.tables, "same seed, same sample");
+    let other = run_with_options(
+        PROGRAM,
+        fixtures(),
+        &RunOptions::default().sample_input(10, 8),
+    )
+    .expect("program should run");
+    assert_ne!(other.tables, out.tables);
+
+    let report = out.sample.expect("sampling requested");
+    assert_eq!((report.fixture_items, report.sampled_items), (102, 12));
+    assert_eq!(report.scale(), 8.5);
+    assert_eq!(report.sampled[0].stage, "json");
+    assert_eq!(report.sampled[0].items_in, 10);
+    assert_eq!(report.estimated[0].items_in, 85);
+    assert_eq!(
+        report.estimated[1].items_out,
+        (rows.len() as f64 * 8.5).round() as usize
+    );
+}
+
+#[test]
+fn sample_input_keeps_fixtures_smaller_than_the_sample() {
+    let options = RunOptions::default().sample_input(1000, 0);
+    let out = run_with_options(PROGRAM, fixtures(), &options).expect("program should run");
+    assert_eq!(out.tables["big"].len(), 50);
+    let report = out.sample.expect("sampling requested");
+    assert_eq!(report.scale(), 1.0);
+    assert_eq!(report.estimated, report.sampled);
+
+    let full =
+        run_with_options(PROGRAM, fixtures(), &RunOptions::default()).expect("program should run");
+    assert_eq!(full.sample, None);
+}
+
+#[test]
+fn runtime_runs_sample_with_its_host_functions() {
+    let mut runtime = Runtime::new();
+    runtime.register_host_fn("double", |arg| match arg {
+        dsl_runtime::Value::I64(n) => Ok(dsl_runtime::Value::I64(n * 2)),
+        _ => Err("expected I64".to_string()),
+    });
+    let program = r#"input.json("xs") |> json |> host.call("double", _) |> ui.table("doubled");"#;
+    let options = RunOptions::default().sample_input(5, 3);
+    let out = runtime
+        .run_with_options(program, fixtures(), &options)
+        .expect("program should run");
+    assert_eq!(out.tables["doubled"].len(), 5);
+    let report = out.sample.expect("sampling requested");
+    assert_eq!(report.sampled[0].items_in, 5);
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "serde")]
+
+use dsl_runtime::{compile, run, Outputs, Program, Value};
+use serde_json::{from_value, json, to_value, Value as Serialized};
+
+#[test]
+fn programs_round_trip_through_serde() {
+    let program = compile(
+        r#"
+xs := input.json("xs") |> json;
+xs |> map(_ + 1) |> filter(_ > 2 ?? false) |> ui.table("out");
+"#,
+    )
+    .expect("program should compile");
+
+    let encoded = to_value(&program).expect("program should serialize");
+    let decoded: Program = from_value(encoded).expect("program should deserialize");
+    assert_eq!(decoded, program);
+}
+
+#[test]
+fn values_round_trip_through_serde() {
+    let value = Value::from_json(json!({"id": 1, "tags": ["a", "b"], "ok": true, "none": null}));
+    let tagged = Value::Tagged("some".to_string(), Box::new(value.clone()));
+    for value in [value, tagged, Value::Bytes(vec![0, 255]), Value::Unit] {
+        let encoded = to_value(&value).expect("value should serialize");
+        let decoded: Value = from_value(encoded).expect("value should deserialize");
+        assert_eq!(decoded, value);
+    }
+}
+
+#[test]
+fn outputs_and_warnings_round_trip_through_serde() {
+    let program = r#"
+input.json("rows")
+  |> json
+  |> group.collect_all(by_key=_.k, limit=1)
+  |> ui.table("out");
+input.json("rows") |> json |> ui.log("log");
+"#;
+    let out = run(
+        program,
+        json!({"rows": [{"k": "x"}, {"k": "x"}, {"k": "y"}]}),
+    )
+    .expect("program should run");
+    assert!(!out.warnings.is_empty());
+
+    let encoded = to_value(&out).expect("outputs should serialize");
+    let Serialized::Object(fields) = &encoded else {
+        panic!("outputs should serialize as an object");
+    };
+    assert!(
+        serde_json::to_string(&fields["warnings"])
+            .expect("warnings should encode")
+            .contains(r#""kind":"Truncated""#),
+        "unit variants serialize as their name"
+    );
+    let decoded: Outputs = from_value(encoded).expect("outputs should deserialize");
+    assert_eq!(decoded, out);
+}
+
+#[test]
+fn error_codes_serialize_as_objects() {
+    let err = compile("xs := ;").expect_err("no expression");
+    assert_eq!(
+        to_value(err.code()).expect("error code should serialize"),
+        json!({"code": "E0001", "kind": "syntax", "summary": "The program doesn't parse."})
+    );
+}
@@ -0,0 +1,110 @@
+use dsl_runtime::{LogLevel, LogRecord, Runtime, Sink, Value};
+use serde_json::{json, Value as JsonValue};
+
+#[derive(Default)]
+struct Recorder {
+    rows: Vec<(String, JsonValue)>,
+    custom: Vec<(String, Value)>,
+}
+
+impl Sink for Recorder {
+    fn table_row(&mut self, table: &str, row: &JsonValue) -> Result<bool, String> {
+        if table == "keep" {
+            return Ok(false);
+        }
+        self.rows.push((table.to_string(), row.clone()));
+        Ok(true)
+    }
+
+    fn custom(&mut self, name: &str, item: &Value) -> Result<bool, String> {
+        self.custom.push((name.to_string(), item.clone()));
+        Ok(true)
+    }
+}
+
+const PROGRAM: &str = r#"
+xs := input.json("xs") |> json;
+xs |> ui.table("routed");
+xs |> ui.table("keep");
+xs |> ui.log("log");
+xs |> sink.custom("audit");
+"#;
+
+#[test]
+fn sink_takes_items_it_accepts_and_leaves_the_rest_in_outputs() {
+    let mut sink = Recorder::default();
+    let out = Runtime::new()
+        .run_with_sink(PROGRAM, json!({"xs": [1, 2]}), &mut sink)
+        .expect("program should run");
+
+    assert_eq!(
+        sink.rows,
+        vec![
+            ("routed".to_string(), json!(1)),
+            ("routed".to_string(), json!(2))
+        ]
+    );
+    assert_eq!(
+        sink.custom,
+        vec![
+            ("audit".to_string(), Value::I64(1)),
+            ("audit".to_string(), Value::I64(2))
+        ]
+    );
+    assert!(!out.tables.contains_key("routed"));
+    assert_eq!(out.tables["keep"], vec![json!(1), json!(2)]);
+    let messages: Vec<&str> = out.logs["log"].iter().map(|r| r.message.as_str()).collect();
+    assert_eq!(messages, vec!["1", "2"]);
+    assert!(out
+        .explain
+        .contains(&"  [sink] sink.custom(audit)".to_string()));
+}
+
+#[test]
+fn sink_custom_without_a_sink_is_an_error() {
+    let err = dsl_runtime::run(PROGRAM, json!({"xs": [1]}))
+        .expect_err("no sink configured")
+        .to_string();
+    assert_eq!(
+        err,
+        "sink.custom(audit): no embedder sink accepted the item at 6:7"
+    );
+}
+
+#[derive(Default)]
+struct WarningsOnly {
+    lines: Vec<(String, String)>,
+}
+
+impl Sink for WarningsOnly {
+    fn log_record(&mut self, log: &str, record: &LogRecord) -> Result<bool, String> {
+        if record.level < LogLevel::Warn {
+            return Ok(false);
+        }
+        self.lines.push((log.to_string(), record.line()));
+        Ok(true)
+    }
+}
+
+#[test]
+fn sinks_see_structured_log_records() {
+    let program = r#"
+xs := input.json("xs") |> json;
+xs |> ui.log("trace", level="debug");
+xs |> ui.log("audit", level="warn");
+"#;
+    let mut sink = WarningsOnly::default();
+    let out = Runtime::new()
+        .run_with_sink(program, json!({"xs": [{"id": 1}]}), &mut sink)
+        .expect("program should run");
+
+    assert_eq!(
+        sink.lines,
+        vec![("audit".to_string(), "[warn] {\"id\":1}".to_string())]
+    );
+    assert!(out.logs["audit"].is_empty());
+    assert_eq!(
+        out.logs["trace"][0].to_json(),
+        json!({"level": "debug", "message": "{\"id\":1}", "item": {"id": 1}})
+    );
+}
@@ -0,0 +1,174 @@
+use dsl_runtime::{Key, Runtime, Source, SourceContext, StageArgs, Value};
+use serde_json::json;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Emits `start..start+count` without touching fixtures.
+struct Range;
+
+impl Source for Range {
+    fn read(&self, args: &StageArgs, _ctx: &SourceContext<'_>) -> Result<Vec<Value>, String> {
+        let (Value::I64(start), Value::I64(count)) = (args.named("start")?, args.named("count")?)
+        else {
+            return Err("start and count must be integers".to_string());
+        };
+        Ok((start..start + count).map(Value::I64).collect())
+    }
+}
+
+/// Reads a fixture through the context, wrapping each entry.
+struct Wrapped;
+
+impl Source for Wrapped {
+    fn read(&self, args: &StageArgs, ctx: &SourceContext<'_>) -> Result<Vec<Value>, String> {
+        let Value::String(name) = args.positional(0)? else {
+            return Err("fixture name must be a string".to_string());
+        };
+        Ok(ctx
+            .fixture(&name)?
+            .iter()
+            .map(|item| Value::Array(vec![Value::from_json(item.clone())]))
+            .collect())
+    }
+
+    fn explain(&self, _call: &str, _args: &StageArgs) -> String {
+        "input.wrapped (custom)".to_string()
+    }
+}
+
+#[test]
+fn registered_sources_are_resolved_by_name() {
+    let mut runtime = Runtime::new();
+    runtime
+        .register_source("input.range", Range)
+        .expect("name should be accepted")
+        .register_source("input.wrapped", Wrapped)
+        .expect("name should be accepted");
+
+    let program = r#"
+input.range(start=3, count=2) |> ui.table("range");
+input.wrapped("xs") |> ui.table("wrapped");
+input.json("xs") |> json |> ui.table("json");
+"#;
+    let out = runtime
+        .run(program, json!({"xs": [1]}))
+        .expect("program should run");
+    assert_eq!(out.tables["range"], vec![json!(3), json!(4)]);
+    assert_eq!(out.tables["wrapped"], vec![json!([1])]);
+    assert_eq!(out.tables["json"], vec![json!(1)]);
+    assert!(out
+        .explain
+        .contains(&"  [source] input.range(start=3, count=2)".to_string()));
+    assert!(out
+        .explain
+        .contains(&"  [source] input.wrapped (custom)".to_string()));
+}
+
+#[test]
+fn unknown_sources_and_bad_names_have_clear_errors() {
+    let mut runtime = Runtime::new();
+    runtime
+        .register_source("input.range", Range)
+        .expect("name should be accepted");
+    assert!(runtime.register_source("input.json", Range).is_err());
+    assert!(runtime.register_source("range", Range).is_err());
+
+    let err = runtime
+        .run(r#"input.sqlite("x") |> ui.table("t");"#, json!({}))
+        .expect_err("unknown provider")
+        .to_string();
+    let available = if cfg!(feature = "codecs") {
+        "input.bytes, input.http, input.json, input.ndjson, input.range"
+    } else {
+        "input.http, input.json, input.range"
+    };
+    assert_eq!(
+        err,
+        format!("unknown source provider: input.sqlite (available: {available}) at 1:1")
+    );
+    assert!(runtime.register_source("input.http", Range).is_err());
+}
+
+#[test]
+fn input_http_answers_requests_from_mock_fixtures() {
+    let fixtures = json!({
+        "users_api": [
+            {"request": "GET /users", "headers": {"content-type": "application/json"},
+             "body": [{"id": 1, "name": "Ada"}, {"id": 2, "name": "Lin"}]},
+            {"request": "GET /users/3", "status": 404, "body": {"error": "not found"}},
+            {"request": "POST /users", "status": 201}
+        ]
+    });
+    let program = r#"
+input.http("GET /users", mock="users_api")
+  |> flat_map(_.body)
+  |> map(_.name)
+  |> ui.table("names");
+input.http("GET /users/3", mock="users_api") |> ui.table("missing");
+input.http("POST /users", mock="users_api") |> ui.table("created");
+"#;
+    let out = dsl_runtime::run(program, fixtures.clone()).expect("program should run");
+    assert_eq!(out.tables["names"], vec![json!("Ada"), json!("Lin")]);
+    assert_eq!(
+        out.tables["missing"],
+        vec![json!({"status": 404, "headers": {}, "body": {"error": "not found"}})]
+    );
+    assert_eq!(
+        out.tables["created"],
+        vec![json!({"status": 201, "headers": {}, "body": null})]
+    );
+    assert!(out
+        .explain
+        .contains(&"  [source] input.http(GET /users <- mock users_api) simulated".to_string()));
+
+    for (program, expected) in [
+        (
+            r#"input.http("GET /orders", mock="users_api") |> ui.table("t");"#,
+            "input.http: no response in mock users_api matches GET /orders at 1:1",
+        ),
+        (
+            r#"input.http("FETCH users", mock="users_api") |> ui.table("t");"#,
+            "input.http request must look like \"GET /path\" (methods: DELETE, GET, HEAD, OPTIONS, PATCH, POST, PUT), got \"FETCH users\" at 1:1",
+        ),
+        (
+            r#"input.http("GET /users", mock="nope") |> ui.table("t");"#,
+            "missing fixture: nope at 1:1",
+        ),
+    ] {
+        let err = dsl_runtime::run(program, fixtures.clone())
+            .expect_err(program)
+            .to_string();
+        assert_eq!(err, expected);
+    }
+}
+
+#[test]
+fn input_http_records_share_keys_with_the_rest_of_the_run() {
+    let seen: Rc<RefCell<Vec<Key>>> = Rc::default();
+    let mut runtime = Runtime::new();
+    let keys = Rc::clone(&seen);
+    runtime
+        .register_stage(
+            "mycorp.status_key",
+            move |_: &StageArgs, input: Vec<Value>| -> Result<Vec<Value>, String> {
+                for item in &input {
+                    if let Value::Record(rec) = item {
+                        let key = rec.keys().find(|key| key.as_str() == "status");
+                        keys.borrow_mut().extend(key.cloned());
+                    }
+                }
+                Ok(input)
+            },
+        )
+        .expect("name should be accepted");
+
+    let program = r#"
+input.json("xs") |> json |> mycorp.status_key() |> ui.table("decoded");
+input.http("GET /health", mock="api") |> mycorp.status_key() |> ui.table("http");
+"#;
+    let fixtures = json!({"xs": [{"status": 1}], "api": [{"request": "GET /health"}]});
+    runtime.run(program, fixtures).expect("program should run");
+    let seen = seen.borrow();
+    assert_eq!(seen.len(), 2);
+    assert!(Key::ptr_eq(&seen[0], &seen[1]));
+}
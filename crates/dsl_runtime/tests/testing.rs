@@ -0,0 +1,84 @@
+use dsl_runtime::testing::{assert_program_snapshot, check_program_snapshot, run_repeatedly};
+use dsl_runtime::{run, run_with_options, RunOptions};
+use serde_json::json;
+use std::path::PathBuf;
+
+const PROGRAM: &str = r#"input.json("xs") |> json |> map(_ + 1) |> ui.table("out");"#;
+
+fn snapshot_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dsl_snapshots_{}", std::process::id()));
+    let path = dir.join(name);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn snapshot_round_trips_after_update() {
+    let path = snapshot_path("round_trip.json");
+    let missing = check_program_snapshot(PROGRAM, json!({"xs": [1, 2]}), &path, false)
+        .expect_err("missing snapshot should fail");
+    assert!(missing.contains("does not exist"), "{missing}");
+
+    check_program_snapshot(PROGRAM, json!({"xs": [1, 2]}), &path, true)
+        .expect("update should write the snapshot");
+    let stored = std::fs::read_to_string(&path).expect("snapshot written");
+    assert!(
+        stored.contains("\"out\": [\n      2,\n      3\n    ]"),
+        "{stored}"
+    );
+
+    assert_program_snapshot(PROGRAM, json!({"xs": [1, 2]}), &path);
+}
+
+#[test]
+fn snapshot_mismatch_reports_line_diff() {
+    let path = snapshot_path("mismatch.json");
+    check_program_snapshot(PROGRAM, json!({"xs": [1, 2]}), &path, true)
+        .expect("update should write the snapshot");
+
+    let err = check_program_snapshot(PROGRAM, json!({"xs": [1, 5]}), &path, false)
+        .expect_err("changed output should fail");
+    assert!(err.contains("- snapshot, + actual"), "{err}");
+    assert!(err.contains("\n-       3\n+       6\n"), "{err}");
+}
+
+#[test]
+fn audit_mode_records_a_fingerprint_of_all_outputs() {
+    let audit = RunOptions {
+        audit: true,
+        ..RunOptions::default()
+    };
+    assert_eq!(run(PROGRAM, json!({"xs": [1]})).unwrap().fingerprint, None);
+
+    let a = run_with_options(PROGRAM, json!({"xs": [1, 2]}), &audit).unwrap();
+    let b = run_with_options(PROGRAM, json!({"xs": [1, 3]}), &audit).unwrap();
+    let fingerprint = a.fingerprint.clone().expect("audit sets a fingerprint");
+    assert_eq!(fingerprint.len(), 16);
+    assert_eq!(fingerprint, a.execution_fingerprint());
+    assert_ne!(a.fingerprint, b.fingerprint);
+}
+
+#[test]
+fn run_repeatedly_checks_grouping_output_is_stable() {
+    let program = r#"
+input.json("events")
+  |> json
+  |> group.count(by_key=_.tag)
+  |> rank.topk(k=3, by=_.count, order="desc")
+  |> ui.table("top");
+"#;
+    let tags: Vec<_> = (0..200)
+        .map(|i| serde_json::Value::String(format!("t{}", i % 7)))
+        .map(|tag| {
+            let mut event = serde_json::Map::new();
+            event.insert("tag".to_string(), tag);
+            serde_json::Value::Object(event)
+        })
+        .collect();
+    let mut fixtures = serde_json::Map::new();
+    fixtures.insert("events".to_string(), serde_json::Value::Array(tags));
+
+    let out = run_repeatedly(program, serde_json::Value::Object(fixtures), 5);
+    assert_eq!(out.tables["top"].len(), 3);
+    assert!(out.fingerprint.is_some());
+}
@@ -0,0 +1,87 @@
+use dsl_runtime::{run_workspace, RuntimeError};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+fn fixtures() -> serde_json::Value {
+    json!({
+        "users": [{"key": "u1", "value": {"name": "Ada"}}],
+        "events": [{"user_id": "u1"}]
+    })
+}
+
+#[test]
+#[cfg(feature = "kv")]
+fn workspace_programs_share_fixtures_and_kv_stores() {
+    let programs = BTreeMap::from([
+        (
+            "1-load".to_string(),
+            r#"input.json("users") |> json |> kv.load(store="users");
+input.json("events") |> json |> ui.table("events");"#,
+        ),
+        (
+            "2-join".to_string(),
+            r#"input.json("events")
+  |> json
+  |> lookup.kv(store="users", key=_.user_id)
+  |> ui.table("joined");"#,
+        ),
+    ]);
+
+    let outputs = run_workspace(programs, fixtures()).expect("workspace should run");
+    assert_eq!(outputs.keys().collect::<Vec<_>>(), vec!["1-load", "2-join"]);
+    assert_eq!(
+        outputs["1-load"].tables.get("events"),
+        Some(&vec![json!({"user_id": "u1"})])
+    );
+    assert!(!outputs["1-load"].tables.contains_key("joined"));
+    assert_eq!(
+        outputs["2-join"].tables.get("joined"),
+        Some(&vec![
+            json!({"left": {"user_id": "u1"}, "right": {"name": "Ada"}})
+        ])
+    );
+}
+
+#[test]
+fn workspace_programs_do_not_share_bindings() {
+    let programs = BTreeMap::from([
+        (
+            "a".to_string(),
+            r#"events := input.json("events") |> json;"#,
+        ),
+        ("b".to_string(), r#"events |> ui.table("out");"#),
+    ]);
+
+    let err = run_workspace(programs, fixtures()).expect_err("b can't see a's binding");
+    assert!(
+        err.to_string().starts_with("b: unknown ident events"),
+        "{err}"
+    );
+}
+
+#[test]
+fn workspace_errors_name_the_failing_program() {
+    let programs = BTreeMap::from([
+        (
+            "ok".to_string(),
+            r#"input.json("events") |> json |> ui.table("out");"#,
+        ),
+        ("broken".to_string(), "input.json(\"events\") |>"),
+    ]);
+
+    let err = run_workspace(programs, fixtures()).expect_err("broken doesn't parse");
+    assert!(matches!(err, RuntimeError::ParseError { .. }), "{err:?}");
+    assert!(err.to_string().starts_with("broken: "), "{err}");
+    assert_eq!(err.localized().id, "unexpected_end");
+    assert!(err
+        .localized()
+        .args
+        .contains(&("program".to_string(), "broken".to_string())));
+    assert_eq!(err.localize("en"), err.to_string());
+    assert!(
+        err.localize("es")
+            .starts_with("broken: fin de entrada inesperado"),
+        "{}",
+        err.localize("es")
+    );
+}
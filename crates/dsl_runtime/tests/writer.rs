@@ -0,0 +1,66 @@
+use dsl_runtime::{
+    run_with_writer, CallbackWriter, JsonlWriter, LogLevel, MemoryWriter, OutputEvent, Runtime,
+};
+use serde_json::json;
+
+const PROGRAM: &str = r#"
+xs := input.json("xs") |> json;
+xs |> ui.table("out");
+xs |> ui.log("log", level="warn");
+"#;
+
+#[test]
+fn memory_writer_collects_rows_and_logs_instead_of_outputs() {
+    let mut writer = MemoryWriter::default();
+    let out =
+        run_with_writer(PROGRAM, json!({"xs": [1, 2]}), &mut writer).expect("program should run");
+
+    assert!(out.tables.is_empty());
+    assert!(out.logs.is_empty());
+    assert_eq!(writer.tables["out"], vec![json!(1), json!(2)]);
+    let messages: Vec<&str> = writer.logs["log"]
+        .iter()
+        .map(|record| record.message.as_str())
+        .collect();
+    assert_eq!(messages, ["1", "2"]);
+    assert_eq!(writer.logs["log"][0].level, LogLevel::Warn);
+}
+
+#[test]
+fn jsonl_writer_writes_one_line_per_item() {
+    let mut writer = JsonlWriter::new(Vec::new());
+    Runtime::new()
+        .run_with_writer(PROGRAM, json!({"xs": [{"id": 1}]}), &mut writer)
+        .expect("program should run");
+
+    let text = String::from_utf8(writer.into_inner()).expect("utf-8 output");
+    assert_eq!(
+        text,
+        concat!(
+            r#"{"row":{"id":1},"table":"out"}"#,
+            "\n",
+            r#"{"item":{"id":1},"level":"warn","log":"log","message":"{\"id\":1}"}"#,
+            "\n"
+        )
+    );
+}
+
+#[test]
+fn callback_writer_sees_items_in_order_and_can_stop_the_run() {
+    let mut seen = Vec::new();
+    let mut writer = CallbackWriter(|name: &str, event: OutputEvent| {
+        seen.push(match event {
+            OutputEvent::Row(row) => format!("{name} row {}", serde_json::to_string(&row).unwrap()),
+            OutputEvent::Log(record) => format!("{name} log {}", record.message),
+        });
+        Ok(())
+    });
+    run_with_writer(PROGRAM, json!({"xs": [1, 2]}), &mut writer).expect("program should run");
+    assert_eq!(seen, ["out row 1", "out row 2", "log log 1", "log log 2"]);
+
+    let mut failing = CallbackWriter(|_: &str, _: OutputEvent| Err("disk full".to_string()));
+    let err = run_with_writer(PROGRAM, json!({"xs": [1]}), &mut failing)
+        .expect_err("writer error should stop the run")
+        .to_string();
+    assert!(err.contains("disk full"), "{err}");
+}
@@ -1,4 +1,10 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     pub start: usize,
     pub end: usize,
@@ -8,35 +14,159 @@ impl Span {
     pub fn new(start: usize, end: usize) -> Self {
         Self { start, end }
     }
+
+    /// Line/column of `start` and `end` in `src`, the text the span came from.
+    pub fn resolve(&self, src: &str) -> (LineCol, LineCol) {
+        (LineCol::at(src, self.start), LineCol::at(src, self.end))
+    }
+}
+
+/// A 1-based source position; `column` counts characters. Displays as `line:column`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl LineCol {
+    pub fn at(src: &str, offset: usize) -> Self {
+        let before = src.get(..offset).unwrap_or(src);
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl core::fmt::Display for LineCol {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
     pub statements: Vec<Stmt>,
+    /// Comments after the last statement.
+    pub comments: Vec<Comment>,
     pub span: Span,
 }
 
+/// A `// text` or `# text` line comment; `text` excludes the marker and surrounding
+/// whitespace.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Comment {
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Stmt {
     Binding {
         name: String,
         expr: Expr,
+        /// Written `override name := expr;`: replacing an earlier binding is intended.
+        overrides: bool,
+        comments: Vec<Comment>,
+        span: Span,
+    },
+    /// `{a, b} := expr;`: binds each name to the stream of that field of every item.
+    Destructure {
+        names: Vec<String>,
+        expr: Expr,
+        overrides: bool,
+        comments: Vec<Comment>,
         span: Span,
     },
     Pipeline {
         expr: Expr,
+        comments: Vec<Comment>,
+        span: Span,
+    },
+    /// `const NAME := expr;`, substituted into later statements before running.
+    Const {
+        name: String,
+        expr: Expr,
+        comments: Vec<Comment>,
         span: Span,
     },
 }
 
+impl Stmt {
+    /// The comment lines right before the statement, then any comment on the line it
+    /// ends.
+    pub fn comments(&self) -> &[Comment] {
+        match self {
+            Stmt::Binding { comments, .. }
+            | Stmt::Destructure { comments, .. }
+            | Stmt::Pipeline { comments, .. }
+            | Stmt::Const { comments, .. } => comments,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Binding { span, .. }
+            | Stmt::Destructure { span, .. }
+            | Stmt::Pipeline { span, .. }
+            | Stmt::Const { span, .. } => *span,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
-    Ident { name: String, span: Span },
-    Placeholder { span: Span },
-    Number { value: i64, span: Span },
-    String { value: String, span: Span },
-    Array { items: Vec<Expr>, span: Span },
-    Record { fields: Vec<RecordField>, span: Span },
+    Ident {
+        name: String,
+        span: Span,
+    },
+    Placeholder {
+        span: Span,
+    },
+    /// `$name`, filled from run-time params before execution.
+    Param {
+        name: String,
+        span: Span,
+    },
+    Number {
+        value: i64,
+        span: Span,
+    },
+    Bool {
+        value: bool,
+        span: Span,
+    },
+    Null {
+        span: Span,
+    },
+    /// `-expr`; `-5` parses as a negative [`Expr::Number`].
+    Negate {
+        expr: Box<Expr>,
+        span: Span,
+    },
+    String {
+        value: String,
+        span: Span,
+    },
+    /// `"user ${_.id}"`; plain strings stay [`Expr::String`].
+    Interpolated {
+        parts: Vec<StringPart>,
+        span: Span,
+    },
+    Array {
+        items: Vec<Expr>,
+        span: Span,
+    },
+    Record {
+        fields: Vec<RecordField>,
+        span: Span,
+    },
     FieldAccess {
         expr: Box<Expr>,
         field: String,
@@ -61,10 +191,142 @@ pub enum Expr {
         expr: Box<Expr>,
         span: Span,
     },
-    Raw { text: String, span: Span },
+    /// `left op right`, for the operators in the parser's operator table.
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+        span: Span,
+    },
+    /// `let name = value in body`: `name` is `value` inside `body`.
+    Let {
+        name: String,
+        value: Box<Expr>,
+        body: Box<Expr>,
+        span: Span,
+    },
+    /// `match expr { pattern => body, ... }`: the body of the first arm whose
+    /// pattern fits the value.
+    Match {
+        expr: Box<Expr>,
+        arms: Vec<MatchArm>,
+        span: Span,
+    },
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Ident { span, .. }
+            | Expr::Placeholder { span }
+            | Expr::Param { span, .. }
+            | Expr::Number { span, .. }
+            | Expr::String { span, .. }
+            | Expr::Array { span, .. }
+            | Expr::Record { span, .. }
+            | Expr::FieldAccess { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::Pipeline { span, .. }
+            | Expr::Compose { span, .. }
+            | Expr::Inverse { span, .. }
+            | Expr::Interpolated { span, .. }
+            | Expr::Bool { span, .. }
+            | Expr::Null { span }
+            | Expr::Negate { span, .. }
+            | Expr::Binary { span, .. }
+            | Expr::Let { span, .. }
+            | Expr::Match { span, .. } => *span,
+        }
+    }
+}
+
+/// A value operator; `|>` and `>>` build [`Expr::Pipeline`] and [`Expr::Compose`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BinaryOp {
+    /// `??`: the left value unless it is `null`.
+    Coalesce,
+    /// `==`
+    Eq,
+    /// `>`
+    Gt,
+    /// `+`
+    Add,
+}
+
+impl BinaryOp {
+    /// How the operator is written, such as `==`.
+    pub fn symbol(self) -> &'static str {
+        crate::parser::operator(crate::parser::Infix::Binary(self)).token
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Pattern {
+    /// `_`: fits anything.
+    Wildcard { span: Span },
+    /// A scalar literal (number, string, bool, or null) the value must equal.
+    Literal(Expr),
+    /// `name`: fits anything and binds it to `name` in the arm's body.
+    Bind { name: String, span: Span },
+    /// `{ field: pattern, ... }`: a record with these fields, and no others unless
+    /// `rest` (written `..`).
+    Record {
+        fields: Vec<(String, Pattern)>,
+        rest: bool,
+        span: Span,
+    },
+    /// `tag(pattern)`: a value tagged `tag` whose inner value fits `pattern`.
+    Tagged {
+        tag: String,
+        pattern: Box<Pattern>,
+        span: Span,
+    },
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Wildcard { span }
+            | Pattern::Bind { span, .. }
+            | Pattern::Record { span, .. }
+            | Pattern::Tagged { span, .. } => *span,
+            Pattern::Literal(expr) => expr.span(),
+        }
+    }
+
+    /// Names the pattern binds, in source order.
+    pub fn bindings(&self) -> Vec<&str> {
+        match self {
+            Pattern::Bind { name, .. } => vec![name],
+            Pattern::Record { fields, .. } => fields
+                .iter()
+                .flat_map(|(_, pattern)| pattern.bindings())
+                .collect(),
+            Pattern::Tagged { pattern, .. } => pattern.bindings(),
+            Pattern::Wildcard { .. } | Pattern::Literal(_) => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StringPart {
+    Text(String),
+    Expr(Expr),
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CallArg {
     Positional(Expr),
     Named {
@@ -75,6 +337,7 @@ pub enum CallArg {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordField {
     pub name: String,
     pub value: Expr,
@@ -0,0 +1,302 @@
+use crate::ast::{CallArg, Comment, Expr, Pattern, Program, Stmt, StringPart};
+use crate::parser::{operator, parse_program, Assoc, Infix, ParseError};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+
+/// Pipelines that fit within this many columns stay on one line.
+const MAX_INLINE_WIDTH: usize = 80;
+
+/// Parses `src` and pretty-prints it, keeping single blank lines between
+/// statements where the source had them. Comments are written as `// text` above
+/// the statement they belong to.
+pub fn format_source(src: &str) -> Result<String, ParseError> {
+    let program = parse_program(src)?;
+    let mut out = String::new();
+    let mut prev_end: Option<usize> = None;
+    let mut blank_before = |out: &mut String, start: usize, end: usize| {
+        if let Some(prev) = prev_end {
+            if src[prev..start].matches('\n').count() >= 2 {
+                out.push('\n');
+            }
+        }
+        prev_end = Some(end);
+    };
+    for stmt in &program.statements {
+        let span = stmt.span();
+        let start = stmt.comments().first().map_or(span.start, |c| c.span.start);
+        let end = stmt.comments().last().map_or(span.end, |c| c.span.end);
+        blank_before(&mut out, start.min(span.start), end.max(span.end));
+        push_comments(&mut out, stmt.comments());
+        out.push_str(&format_stmt(stmt));
+        out.push('\n');
+    }
+    if let (Some(first), Some(last)) = (program.comments.first(), program.comments.last()) {
+        blank_before(&mut out, first.span.start, last.span.end);
+        push_comments(&mut out, &program.comments);
+    }
+    Ok(out)
+}
+
+pub fn format_program(program: &Program) -> String {
+    let mut out = String::new();
+    for stmt in &program.statements {
+        push_comments(&mut out, stmt.comments());
+        out.push_str(&format_stmt(stmt));
+        out.push('\n');
+    }
+    push_comments(&mut out, &program.comments);
+    out
+}
+
+fn push_comments(out: &mut String, comments: &[Comment]) {
+    for comment in comments {
+        match comment.text.as_str() {
+            "" => out.push_str("//\n"),
+            text => out.push_str(&format!("// {text}\n")),
+        }
+    }
+}
+
+fn format_stmt(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Binding {
+            name,
+            expr,
+            overrides,
+            ..
+        } => {
+            let name = override_prefix(*overrides) + name;
+            format!("{name} := {};", format_top_level(expr, name.len() + 4))
+        }
+        Stmt::Destructure {
+            names,
+            expr,
+            overrides,
+            ..
+        } => {
+            let names = format!("{}{{{}}}", override_prefix(*overrides), names.join(", "));
+            format!("{names} := {};", format_top_level(expr, names.len() + 4))
+        }
+        Stmt::Pipeline { expr, .. } => format!("{};", format_top_level(expr, 0)),
+        Stmt::Const { name, expr, .. } => format!("const {name} := {};", format_expr(expr)),
+    }
+}
+
+fn override_prefix(overrides: bool) -> String {
+    if overrides { "override " } else { "" }.to_string()
+}
+
+fn format_top_level(expr: &Expr, indent: usize) -> String {
+    let inline = format_expr(expr);
+    match expr {
+        Expr::Pipeline { input, stages, .. } if indent + inline.len() + 1 > MAX_INLINE_WIDTH => {
+            let mut out = format_operand(input);
+            for stage in stages {
+                out.push_str("\n  |> ");
+                out.push_str(&format_operand(stage));
+            }
+            out
+        }
+        _ => inline,
+    }
+}
+
+pub fn format_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Ident { name, .. } => name.clone(),
+        Expr::Placeholder { .. } => "_".to_string(),
+        Expr::Param { name, .. } => format!("${name}"),
+        Expr::Number { value, .. } => value.to_string(),
+        Expr::Bool { value, .. } => value.to_string(),
+        Expr::Null { .. } => "null".to_string(),
+        // `-5.a` would read back as the literal `-5` followed by a stray `.a`.
+        Expr::Negate { expr, .. } => match format_unary(expr) {
+            operand if operand.starts_with(|c: char| c.is_ascii_digit()) => {
+                format!("-({operand})")
+            }
+            operand => format!("-{operand}"),
+        },
+        Expr::String { value, .. } => quote(value),
+        Expr::Interpolated { parts, .. } => {
+            let mut out = String::from("\"");
+            for part in parts {
+                match part {
+                    StringPart::Text(text) => out.push_str(&escape(text)),
+                    StringPart::Expr(expr) => out.push_str(&format!("${{{}}}", format_expr(expr))),
+                }
+            }
+            out.push('"');
+            out
+        }
+        Expr::Array { items, .. } => format!(
+            "[{}]",
+            items.iter().map(format_expr).collect::<Vec<_>>().join(", ")
+        ),
+        Expr::Record { fields, .. } if fields.is_empty() => "{}".to_string(),
+        Expr::Record { fields, .. } => format!(
+            "{{ {} }}",
+            fields
+                .iter()
+                .map(|f| format!("{}: {}", f.name, format_expr(&f.value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Expr::FieldAccess { expr, field, .. } => format!("{}.{field}", format_postfix_base(expr)),
+        Expr::Call { callee, args, .. } => format!(
+            "{}({})",
+            format_postfix_base(callee),
+            args.iter()
+                .map(|arg| match arg {
+                    CallArg::Positional(value) => format_expr(value),
+                    CallArg::Named { name, value, .. } => format!("{name}={}", format_expr(value)),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Expr::Pipeline { input, stages, .. } => {
+            let mut parts = vec![format_operand(input)];
+            parts.extend(stages.iter().map(format_operand));
+            parts.join(" |> ")
+        }
+        Expr::Compose { left, right, .. } => {
+            let op = operator(Infix::Compose);
+            format!(
+                "{} >> {}",
+                format_at(left, op.precedence),
+                format_at(right, op.precedence + 1)
+            )
+        }
+        Expr::Binary {
+            op, left, right, ..
+        } => {
+            let op = operator(Infix::Binary(*op));
+            let (left_min, right_min) = match op.assoc {
+                Assoc::Left => (op.precedence, op.precedence + 1),
+                Assoc::Right => (op.precedence + 1, op.precedence),
+            };
+            format!(
+                "{} {} {}",
+                format_at(left, left_min),
+                op.token,
+                format_at(right, right_min)
+            )
+        }
+        Expr::Inverse { expr, .. } => format!("~{}", format_unary(expr)),
+        Expr::Let {
+            name, value, body, ..
+        } => format!(
+            "let {name} = {} in {}",
+            format_expr(value),
+            format_expr(body)
+        ),
+        Expr::Match { expr, arms, .. } => format!(
+            "match {} {{ {} }}",
+            format_expr(expr),
+            arms.iter()
+                .map(|arm| format!(
+                    "{} => {}",
+                    format_pattern(&arm.pattern),
+                    format_expr(&arm.body)
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+pub fn format_pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard { .. } => "_".to_string(),
+        Pattern::Literal(expr) => format_expr(expr),
+        Pattern::Bind { name, .. } => name.clone(),
+        Pattern::Record { fields, rest, .. } => {
+            let mut parts: Vec<String> = fields
+                .iter()
+                .map(|(name, pattern)| match pattern {
+                    Pattern::Bind { name: bound, .. } if bound == name => name.clone(),
+                    _ => format!("{name}: {}", format_pattern(pattern)),
+                })
+                .collect();
+            if *rest {
+                parts.push("..".to_string());
+            }
+            if parts.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", parts.join(", "))
+            }
+        }
+        Pattern::Tagged { tag, pattern, .. } => format!("{tag}({})", format_pattern(pattern)),
+    }
+}
+
+/// How tightly `expr` binds: its operator's precedence, 0 for a `let` (its body takes
+/// every operator after it), and above every operator otherwise.
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Pipeline { .. } => operator(Infix::Pipe).precedence,
+        Expr::Compose { .. } => operator(Infix::Compose).precedence,
+        Expr::Binary { op, .. } => operator(Infix::Binary(*op)).precedence,
+        Expr::Let { .. } => 0,
+        _ => u8::MAX,
+    }
+}
+
+/// `expr`, parenthesized unless it binds at least as tightly as `min`.
+fn format_at(expr: &Expr, min: u8) -> String {
+    if precedence(expr) < min {
+        format!("({})", format_expr(expr))
+    } else {
+        format_expr(expr)
+    }
+}
+
+/// Operands of `|>` bind tighter than the pipeline itself.
+fn format_operand(expr: &Expr) -> String {
+    format_at(expr, operator(Infix::Pipe).precedence + 1)
+}
+
+/// Operands of `~` and unary `-` must be unary or tighter.
+fn format_unary(expr: &Expr) -> String {
+    format_at(expr, u8::MAX)
+}
+
+/// The base of `.field` / `(args)` must be a postfix or primary expression; a
+/// negative number literal is a unary minus in disguise.
+fn format_postfix_base(expr: &Expr) -> String {
+    match expr {
+        Expr::Number { value, .. } if *value < 0 => format!("({value})"),
+        Expr::Pipeline { .. }
+        | Expr::Compose { .. }
+        | Expr::Inverse { .. }
+        | Expr::Negate { .. }
+        | Expr::Binary { .. }
+        | Expr::Let { .. }
+        | Expr::Match { .. } => format!("({})", format_expr(expr)),
+        _ => format_expr(expr),
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", escape(value))
+}
+
+fn escape(value: &str) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{0008}' => out.push_str("\\b"),
+            '\u{000C}' => out.push_str("\\f"),
+            '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
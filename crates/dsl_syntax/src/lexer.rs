@@ -0,0 +1,140 @@
+//! Splits source text into [`Token`]s with their spans. The parser reads the token at
+//! its position for error messages and to find where a sub-expression ends; editors
+//! and the formatter can lex the same way with [`lex`].
+
+use crate::ast::Span;
+use crate::parser::ParseError;
+use alloc::vec::Vec;
+
+/// Operators and punctuation, longest first so `|>` wins over `|`.
+const PUNCTUATION: &[&str] = &[
+    "|>", ">>", ":=", "=>", "==", "!=", "<=", ">=", "&&", "||", "??", "..", "(", ")", "[", "]",
+    "{", "}", ",", ";", ":", ".", "=", "+", "-", "*", "/", "%", "<", ">", "!", "~", "?", "|", "&",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TokenKind {
+    /// A name, including the keywords `let`, `in`, `match`, `true`, `false`, and `null`.
+    Ident,
+    /// Unsigned digits; a leading `-` is its own token.
+    Number,
+    /// A whole string literal, quotes and `${...}` interpolations included.
+    String,
+    /// `$name`.
+    Param,
+    /// `_` on its own (`_name` is an [`TokenKind::Ident`]).
+    Placeholder,
+    /// One of the operators and punctuation marks, such as `|>` or `(`.
+    Punct,
+    /// A `//` or `#` line comment, without its newline.
+    Comment,
+    /// A character no other token starts with.
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Token {
+    /// The token's text in `src`, the source it was lexed from.
+    pub fn text<'a>(&self, src: &'a str) -> &'a str {
+        &src[self.span.start..self.span.end]
+    }
+}
+
+/// Every token in `src`, comments included; whitespace separates tokens and is
+/// dropped. Fails only on an unterminated string literal.
+pub fn lex(src: &str) -> Result<Vec<Token>, ParseError> {
+    Lexer::new(src, 0)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.located(src))
+}
+
+/// Tokens of `src` from byte offset `pos` on, lexed one at a time.
+pub(crate) struct Lexer<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub(crate) fn new(src: &'a str, pos: usize) -> Self {
+        Self { src, pos }
+    }
+
+    fn string(&mut self, start: usize) -> Result<TokenKind, ParseError> {
+        let mut escaped = false;
+        for (idx, c) in self.src[start + 1..].char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => {
+                    self.pos = start + 1 + idx + 1;
+                    return Ok(TokenKind::String);
+                }
+                _ => {}
+            }
+        }
+        self.pos = self.src.len();
+        Err(
+            ParseError::new("unterminated string literal", Span::new(start, self.pos))
+                .with_id("unterminated_string", &[]),
+        )
+    }
+
+    fn skip_while(&mut self, keep: impl Fn(char) -> bool) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.find(|c| !keep(c)).unwrap_or(rest.len());
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.skip_while(char::is_whitespace);
+        let start = self.pos;
+        let rest = &self.src[start..];
+        let first = rest.chars().next()?;
+        let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let kind = if rest.starts_with("//") || first == '#' {
+            self.skip_while(|c| c != '\n');
+            TokenKind::Comment
+        } else if first == '"' {
+            match self.string(start) {
+                Ok(kind) => kind,
+                Err(err) => return Some(Err(err)),
+            }
+        } else if first.is_ascii_digit() {
+            self.skip_while(|c| c.is_ascii_digit());
+            TokenKind::Number
+        } else if first == '_' || first.is_ascii_alphabetic() {
+            self.skip_while(is_word);
+            if self.pos == start + 1 && first == '_' {
+                TokenKind::Placeholder
+            } else {
+                TokenKind::Ident
+            }
+        } else if first == '$'
+            && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        {
+            self.pos += 1;
+            self.skip_while(is_word);
+            TokenKind::Param
+        } else if let Some(punct) = PUNCTUATION.iter().find(|p| rest.starts_with(**p)) {
+            self.pos += punct.len();
+            TokenKind::Punct
+        } else {
+            self.pos += first.len_utf8();
+            TokenKind::Unknown
+        };
+        Some(Ok(Token {
+            kind,
+            span: Span::new(start, self.pos),
+        }))
+    }
+}
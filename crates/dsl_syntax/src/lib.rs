@@ -1,5 +1,20 @@
+//! Lexer, parser, AST, and formatter for the pipeline language. The crate is
+//! `no_std` and needs only `alloc`, so it also builds for targets without `std`.
+
+#![no_std]
+
+extern crate alloc;
+
 pub mod ast;
+pub mod format;
+pub mod lexer;
+pub mod migrate;
 pub mod parser;
 
 pub use ast::*;
-pub use parser::{parse_program, ParseError};
+pub use format::{format_expr, format_pattern, format_program, format_source};
+pub use lexer::{lex, Token, TokenKind};
+pub use migrate::{migrate, CURRENT_VERSION};
+pub use parser::{
+    parse_expr, parse_program, parse_program_with_limits, ParseError, ParseLimits, MAX_NESTING,
+};
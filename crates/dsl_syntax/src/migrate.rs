@@ -0,0 +1,179 @@
+//! Mechanical upgrades of stored programs as the grammar evolves.
+
+use crate::ast::{CallArg, Expr, Span, Stmt, StringPart};
+use crate::parser::parse_program;
+use alloc::collections::BTreeSet;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+
+/// The grammar version programs are written against today.
+pub const CURRENT_VERSION: u32 = 1;
+
+/// The upgrade from version `from` to `from + 1`.
+struct Migration {
+    from: u32,
+    /// `(old, new)` stage names.
+    stage_renames: &'static [(&'static str, &'static str)],
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 0,
+    stage_renames: &[
+        ("topk", "rank.topk"),
+        ("kmerge_arrays", "rank.kmerge_arrays"),
+    ],
+}];
+
+/// Upgrades `src` from grammar version `from_version` to `to_version`, one version at
+/// a time. Only the renamed text changes; layout and comments are kept. Names the
+/// program binds itself are left alone.
+pub fn migrate(src: &str, from_version: u32, to_version: u32) -> Result<String, String> {
+    if to_version > CURRENT_VERSION {
+        return Err(format!(
+            "unknown version {to_version} (the current version is {CURRENT_VERSION})"
+        ));
+    }
+    if from_version > to_version {
+        return Err(format!(
+            "cannot migrate from version {from_version} down to {to_version}"
+        ));
+    }
+    let mut out = src.to_string();
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|m| m.from >= from_version && m.from < to_version)
+    {
+        out = rename_stages(&out, migration.stage_renames)
+            .map_err(|e| format!("migrating from version {}: {e}", migration.from))?;
+    }
+    Ok(out)
+}
+
+/// The current name of a stage some migration renamed away from `name`.
+pub fn current_stage_name(name: &str) -> Option<&'static str> {
+    let mut current = None;
+    for migration in MIGRATIONS {
+        let from = current.unwrap_or(name);
+        if let Some((_, new)) = migration.stage_renames.iter().find(|(old, _)| *old == from) {
+            current = Some(*new);
+        }
+    }
+    current
+}
+
+fn rename_stages(src: &str, renames: &[(&str, &str)]) -> Result<String, String> {
+    let program = parse_program(src).map_err(|e| e.to_string())?;
+    let bound: BTreeSet<&str> = program
+        .statements
+        .iter()
+        .flat_map(|stmt| match stmt {
+            Stmt::Binding { name, .. } | Stmt::Const { name, .. } => vec![name.as_str()],
+            Stmt::Destructure { names, .. } => names.iter().map(String::as_str).collect(),
+            Stmt::Pipeline { .. } => Vec::new(),
+        })
+        .collect();
+    let mut edits: Vec<(Span, &str)> = Vec::new();
+    for stmt in &program.statements {
+        let (Stmt::Binding { expr, .. }
+        | Stmt::Destructure { expr, .. }
+        | Stmt::Pipeline { expr, .. }
+        | Stmt::Const { expr, .. }) = stmt;
+        visit_stages(expr, &mut |stage| {
+            let Some(name) = path(stage) else {
+                return;
+            };
+            if bound.contains(name.as_str()) {
+                return;
+            }
+            if let Some((_, new)) = renames.iter().find(|(old, _)| *old == name) {
+                edits.push((stage.span(), new));
+            }
+        });
+    }
+    edits.sort_by_key(|(span, _)| span.start);
+    let mut out = src.to_string();
+    for (span, new) in edits.into_iter().rev() {
+        out.replace_range(span.start..span.end, new);
+    }
+    Ok(out)
+}
+
+/// Calls `visit` with every stage name: call callees and bare pipeline stages.
+fn visit_stages(expr: &Expr, visit: &mut impl FnMut(&Expr)) {
+    match expr {
+        Expr::Call { callee, args, .. } => {
+            visit(callee);
+            for arg in args {
+                match arg {
+                    CallArg::Positional(value) | CallArg::Named { value, .. } => {
+                        visit_stages(value, visit)
+                    }
+                }
+            }
+        }
+        Expr::Pipeline { input, stages, .. } => {
+            visit_stages(input, visit);
+            for stage in stages {
+                if path(stage).is_some() {
+                    visit(stage);
+                }
+                visit_stages(stage, visit);
+            }
+        }
+        Expr::Compose { left, right, .. } => {
+            for side in [left, right] {
+                if path(side).is_some() {
+                    visit(side);
+                }
+                visit_stages(side, visit);
+            }
+        }
+        Expr::Inverse { expr, .. } => {
+            if path(expr).is_some() {
+                visit(expr);
+            }
+            visit_stages(expr, visit);
+        }
+        Expr::Negate { expr, .. } | Expr::FieldAccess { expr, .. } => visit_stages(expr, visit),
+        Expr::Binary { left, right, .. } => {
+            visit_stages(left, visit);
+            visit_stages(right, visit);
+        }
+        Expr::Let { value, body, .. } => {
+            visit_stages(value, visit);
+            visit_stages(body, visit);
+        }
+        Expr::Match { expr, arms, .. } => {
+            visit_stages(expr, visit);
+            arms.iter().for_each(|arm| visit_stages(&arm.body, visit));
+        }
+        Expr::Array { items, .. } => items.iter().for_each(|item| visit_stages(item, visit)),
+        Expr::Record { fields, .. } => fields
+            .iter()
+            .for_each(|field| visit_stages(&field.value, visit)),
+        Expr::Interpolated { parts, .. } => {
+            for part in parts {
+                if let StringPart::Expr(expr) = part {
+                    visit_stages(expr, visit);
+                }
+            }
+        }
+        Expr::Ident { .. }
+        | Expr::Placeholder { .. }
+        | Expr::Param { .. }
+        | Expr::Number { .. }
+        | Expr::Bool { .. }
+        | Expr::Null { .. }
+        | Expr::String { .. } => {}
+    }
+}
+
+/// `a.b.c` for identifier and field-access chains.
+fn path(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Ident { name, .. } => Some(name.clone()),
+        Expr::FieldAccess { expr, field, .. } => path(expr).map(|base| format!("{base}.{field}")),
+        _ => None,
+    }
+}
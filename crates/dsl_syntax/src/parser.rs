@@ -1,131 +1,650 @@
-use crate::ast::{CallArg, Expr, Program, RecordField, Span, Stmt};
+use crate::ast::{
+    BinaryOp, CallArg, Comment, Expr, LineCol, MatchArm, Pattern, Program, RecordField, Span, Stmt,
+    StringPart,
+};
+use crate::lexer::{Lexer, TokenKind};
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ParseError {
     pub message: String,
     pub span: Span,
+    /// Where `span` starts in the parsed source.
+    pub start: LineCol,
+    /// Message-catalog id such as `unexpected_token`, when the message has one.
+    pub message_id: Option<String>,
+    /// The arguments `message` interpolates, by name.
+    pub message_args: Vec<(String, String)>,
 }
 
-impl std::fmt::Display for ParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} at {}..{}", self.message, self.span.start, self.span.end)
+impl ParseError {
+    pub(crate) fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span,
+            start: LineCol { line: 1, column: 1 },
+            message_id: None,
+            message_args: Vec::new(),
+        }
+    }
+
+    /// The same error as catalog message `id` with `args`.
+    pub(crate) fn with_id(mut self, id: &str, args: &[(&str, &str)]) -> Self {
+        self.message_id = Some(id.to_string());
+        self.message_args = args
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        self
+    }
+
+    pub(crate) fn located(mut self, src: &str) -> Self {
+        self.start = LineCol::at(src, self.span.start);
+        self
+    }
+}
+
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} at {}", self.message, self.start)
     }
 }
 
-impl std::error::Error for ParseError {}
+impl core::error::Error for ParseError {}
 
+/// How deeply expressions and patterns may nest by default. Parsing and every later
+/// pass recurse over the tree, so deeper input would exhaust the stack rather than
+/// fail with an error.
+pub const MAX_NESTING: usize = 128;
+
+/// Caps on what a parse accepts, so untrusted source can't exhaust the stack or
+/// memory. Exceeding one is a parse error naming the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParseLimits {
+    /// Longest source, in bytes.
+    pub max_source_len: usize,
+    /// Deepest nesting of expressions and patterns, counting each operator,
+    /// bracket, field access, and call.
+    pub max_depth: usize,
+    /// Most top-level statements.
+    pub max_statements: usize,
+}
+
+impl Default for ParseLimits {
+    /// 1 MiB of source, [`MAX_NESTING`] levels, and 10,000 statements.
+    fn default() -> Self {
+        Self {
+            max_source_len: 1 << 20,
+            max_depth: MAX_NESTING,
+            max_statements: 10_000,
+        }
+    }
+}
+
+/// Parses a program under the default [`ParseLimits`].
 pub fn parse_program(input: &str) -> Result<Program, ParseError> {
-    let mut p = Parser { src: input, pos: 0 };
-    p.parse_program()
+    parse_program_with_limits(input, &ParseLimits::default())
+}
+
+/// Parses a program, failing once it exceeds any of `limits`.
+pub fn parse_program_with_limits(input: &str, limits: &ParseLimits) -> Result<Program, ParseError> {
+    if input.len() > limits.max_source_len {
+        let at = (0..=limits.max_source_len)
+            .rev()
+            .find(|idx| input.is_char_boundary(*idx))
+            .unwrap_or(0);
+        return Err(ParseError::new(
+            format!(
+                "program is {} bytes, more than the {} allowed",
+                input.len(),
+                limits.max_source_len
+            ),
+            Span::new(at, input.len()),
+        )
+        .located(input));
+    }
+    let mut p = Parser::new(input, *limits);
+    p.parse_program().map_err(|e| e.located(input))
+}
+
+/// Parses a single expression.
+pub fn parse_expr(input: &str) -> Result<Expr, ParseError> {
+    let mut p = Parser::new(input, ParseLimits::default());
+    let expr = p.parse_expr().map_err(|e| e.located(input))?;
+    p.skip_ws();
+    if !p.eof() {
+        return Err(p.unexpected("end of expression").located(input));
+    }
+    Ok(expr)
+}
+
+/// What an infix operator builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Infix {
+    /// `a |> b`; chained pipes flatten into one [`Expr::Pipeline`].
+    Pipe,
+    /// `a >> b`.
+    Compose,
+    Binary(BinaryOp),
+}
+
+/// How an operator groups with others of its precedence: `a + b + c` is
+/// `(a + b) + c` when `Left`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Assoc {
+    Left,
+    Right,
+}
+
+pub(crate) struct Operator {
+    pub(crate) token: &'static str,
+    /// Higher binds tighter.
+    pub(crate) precedence: u8,
+    pub(crate) assoc: Assoc,
+    pub(crate) builds: Infix,
+}
+
+/// Every infix operator, loosest first. Prefix `-` and `~` bind tighter than all of
+/// them, and field access and calls tighter still. A new value operator is a row
+/// here plus a [`BinaryOp`] the runtime knows how to evaluate.
+pub(crate) const OPERATORS: &[Operator] = &[
+    Operator {
+        token: "|>",
+        precedence: 1,
+        assoc: Assoc::Left,
+        builds: Infix::Pipe,
+    },
+    Operator {
+        token: ">>",
+        precedence: 2,
+        assoc: Assoc::Left,
+        builds: Infix::Compose,
+    },
+    Operator {
+        token: "??",
+        precedence: 3,
+        assoc: Assoc::Right,
+        builds: Infix::Binary(BinaryOp::Coalesce),
+    },
+    Operator {
+        token: "==",
+        precedence: 4,
+        assoc: Assoc::Left,
+        builds: Infix::Binary(BinaryOp::Eq),
+    },
+    Operator {
+        token: ">",
+        precedence: 5,
+        assoc: Assoc::Left,
+        builds: Infix::Binary(BinaryOp::Gt),
+    },
+    Operator {
+        token: "+",
+        precedence: 6,
+        assoc: Assoc::Left,
+        builds: Infix::Binary(BinaryOp::Add),
+    },
+];
+
+/// The table row for `builds`.
+pub(crate) fn operator(builds: Infix) -> &'static Operator {
+    OPERATORS
+        .iter()
+        .find(|op| op.builds == builds)
+        .expect("every infix form has an operator")
 }
 
 struct Parser<'a> {
     src: &'a str,
     pos: usize,
+    limits: ParseLimits,
+    /// Nesting levels entered so far; at most `limits.max_depth`.
+    depth: usize,
 }
 
 impl<'a> Parser<'a> {
+    fn new(src: &'a str, limits: ParseLimits) -> Self {
+        Self {
+            src,
+            pos: 0,
+            limits,
+            depth: 0,
+        }
+    }
+
     fn parse_program(&mut self) -> Result<Program, ParseError> {
         let start = self.pos;
         let mut statements = Vec::new();
-        self.skip_ws();
+        let mut comments = self.take_comments();
         while !self.eof() {
-            statements.push(self.parse_stmt()?);
-            self.skip_ws();
+            if statements.len() == self.limits.max_statements {
+                return Err(ParseError::new(
+                    format!(
+                        "program has more than {} statements",
+                        self.limits.max_statements
+                    ),
+                    Span::new(self.pos, self.pos),
+                ));
+            }
+            statements.push(self.parse_stmt(comments)?);
+            comments = self.take_comments();
         }
         Ok(Program {
             statements,
+            comments,
             span: Span::new(start, self.pos),
         })
     }
 
-    fn parse_stmt(&mut self) -> Result<Stmt, ParseError> {
+    fn parse_stmt(&mut self, comments: Vec<Comment>) -> Result<Stmt, ParseError> {
         self.skip_ws();
         let start = self.pos;
 
+        let overrides = self.consume_override();
         let checkpoint = self.pos;
+        if let Some(names) = self.try_parse_destructure_names()? {
+            self.skip_ws();
+            let expr = self.parse_expr()?;
+            self.skip_ws();
+            self.expect(";")?;
+            let span = Span::new(start, self.pos);
+            return Ok(Stmt::Destructure {
+                names,
+                expr,
+                overrides,
+                comments: self.with_trailing_comment(comments),
+                span,
+            });
+        }
         if let Some(name) = self.parse_ident() {
             self.skip_ws();
+            if name == "const" && !overrides {
+                if let Some(name) = self.parse_ident() {
+                    self.skip_ws();
+                    self.expect(":=")?;
+                    let expr = self.parse_expr()?;
+                    self.skip_ws();
+                    self.expect(";")?;
+                    let span = Span::new(start, self.pos);
+                    return Ok(Stmt::Const {
+                        name,
+                        expr,
+                        comments: self.with_trailing_comment(comments),
+                        span,
+                    });
+                }
+            }
             if self.consume(":=") {
                 self.skip_ws();
                 let expr = self.parse_expr()?;
                 self.skip_ws();
                 self.expect(";")?;
+                let span = Span::new(start, self.pos);
                 return Ok(Stmt::Binding {
                     name,
                     expr,
-                    span: Span::new(start, self.pos),
+                    overrides,
+                    comments: self.with_trailing_comment(comments),
+                    span,
                 });
             }
         }
         self.pos = checkpoint;
+        if overrides {
+            return Err(ParseError::new(
+                "override must be followed by a binding",
+                Span::new(start, checkpoint),
+            ));
+        }
 
         let expr = self.parse_expr()?;
         self.skip_ws();
         self.expect(";")?;
+        let span = Span::new(start, self.pos);
         Ok(Stmt::Pipeline {
             expr,
-            span: Span::new(start, self.pos),
+            comments: self.with_trailing_comment(comments),
+            span,
         })
     }
 
+    /// The `override` keyword before a binding; `override := ...` binds a name.
+    fn consume_override(&mut self) -> bool {
+        let checkpoint = self.pos;
+        if self.parse_ident().as_deref() == Some("override") {
+            self.skip_ws();
+            if !self.src[self.pos..].starts_with(":=") {
+                return true;
+            }
+        }
+        self.pos = checkpoint;
+        false
+    }
+
+    /// `{a, b} :=`, leaving the position unchanged when the statement doesn't start
+    /// with one.
+    fn try_parse_destructure_names(&mut self) -> Result<Option<Vec<String>>, ParseError> {
+        let checkpoint = self.pos;
+        if !self.consume("{") {
+            return Ok(None);
+        }
+        let mut names: Vec<(String, Span)> = Vec::new();
+        loop {
+            self.skip_ws();
+            let name_start = self.pos;
+            let Some(name) = self.parse_ident() else {
+                break;
+            };
+            names.push((name, Span::new(name_start, self.pos)));
+            self.skip_ws();
+            if !self.consume(",") {
+                break;
+            }
+        }
+        self.skip_ws();
+        if !self.consume("}") {
+            self.pos = checkpoint;
+            return Ok(None);
+        }
+        self.skip_ws();
+        if !self.consume(":=") {
+            self.pos = checkpoint;
+            return Ok(None);
+        }
+        if names.is_empty() {
+            return Err(ParseError::new(
+                "expected at least one name to destructure",
+                Span::new(checkpoint, self.pos),
+            ));
+        }
+        for (idx, (name, span)) in names.iter().enumerate() {
+            if names[..idx].iter().any(|(earlier, _)| earlier == name) {
+                return Err(ParseError::new(
+                    format!("{name} is destructured twice"),
+                    *span,
+                ));
+            }
+        }
+        Ok(Some(names.into_iter().map(|(name, _)| name).collect()))
+    }
+
     fn parse_expr(&mut self) -> Result<Expr, ParseError> {
-        self.parse_pipeline()
+        self.parse_binary(0)
     }
 
-    fn parse_pipeline(&mut self) -> Result<Expr, ParseError> {
+    /// `match expr { pattern => body, ... }`.
+    fn parse_match(&mut self) -> Result<Expr, ParseError> {
         let start = self.pos;
-        let input = self.parse_compose()?;
+        self.pos += "match".len();
+        let expr = self.parse_expr()?;
         self.skip_ws();
-        if !self.consume("|>") {
-            return Ok(input);
-        }
-        let mut stages = Vec::new();
+        self.expect("{")?;
+        let mut arms = Vec::new();
         loop {
             self.skip_ws();
-            stages.push(self.parse_compose()?);
+            if self.consume("}") {
+                break;
+            }
+            let arm_start = self.pos;
+            let pattern = self.parse_pattern()?;
+            self.skip_ws();
+            self.expect("=>")?;
+            let body = self.parse_expr()?;
+            arms.push(MatchArm {
+                pattern,
+                body,
+                span: Span::new(arm_start, self.pos),
+            });
             self.skip_ws();
-            if !self.consume("|>") {
+            if !self.consume(",") {
+                self.skip_ws();
+                self.expect("}")?;
                 break;
             }
         }
-        Ok(Expr::Pipeline {
-            input: Box::new(input),
-            stages,
+        if arms.is_empty() {
+            return Err(ParseError::new(
+                "match needs at least one arm",
+                Span::new(start, self.pos),
+            ));
+        }
+        Ok(Expr::Match {
+            expr: Box::new(expr),
+            arms,
             span: Span::new(start, self.pos),
         })
     }
 
-    fn parse_compose(&mut self) -> Result<Expr, ParseError> {
+    /// `_`, a name, a scalar literal, `{ field: pattern, field, .. }`, or
+    /// `tag(pattern)`.
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        self.skip_ws();
+        let start = self.pos;
+        if self.consume("{") {
+            let (mut fields, mut rest) = (Vec::new(), false);
+            loop {
+                self.skip_ws();
+                if self.consume("}") {
+                    break;
+                }
+                if self.consume("..") {
+                    rest = true;
+                    self.skip_ws();
+                    self.expect("}")?;
+                    break;
+                }
+                let name_start = self.pos;
+                let name = self.parse_ident().ok_or_else(|| {
+                    ParseError::new(
+                        "expected a field name or `..` in a record pattern",
+                        Span::new(name_start, name_start),
+                    )
+                })?;
+                self.skip_ws();
+                let pattern = if self.consume(":") {
+                    self.nested(Self::parse_pattern)?
+                } else {
+                    Pattern::Bind {
+                        name: name.clone(),
+                        span: Span::new(name_start, name_start + name.len()),
+                    }
+                };
+                fields.push((name, pattern));
+                self.skip_ws();
+                if !self.consume(",") {
+                    self.skip_ws();
+                    self.expect("}")?;
+                    break;
+                }
+            }
+            return Ok(Pattern::Record {
+                fields,
+                rest,
+                span: Span::new(start, self.pos),
+            });
+        }
+        if let Some(tag) = self.parse_ident() {
+            self.skip_ws();
+            if self.consume("(") {
+                let pattern = self.nested(Self::parse_pattern)?;
+                self.skip_ws();
+                self.expect(")")?;
+                return Ok(Pattern::Tagged {
+                    tag,
+                    pattern: Box::new(pattern),
+                    span: Span::new(start, self.pos),
+                });
+            }
+        }
+        self.pos = start;
+        match self.parse_unary()? {
+            Expr::Placeholder { span } => Ok(Pattern::Wildcard { span }),
+            Expr::Ident { name, span } => Ok(Pattern::Bind { name, span }),
+            expr @ (Expr::Number { .. }
+            | Expr::String { .. }
+            | Expr::Bool { .. }
+            | Expr::Null { .. }) => Ok(Pattern::Literal(expr)),
+            expr => Err(ParseError::new(
+                "expected a pattern: `_`, a name, a literal, `{ field: pattern, .. }`, or `tag(pattern)`",
+                expr.span(),
+            )),
+        }
+    }
+
+    /// `let name = value in body`. The body takes every operator after it, so it
+    /// runs to the end of the enclosing argument, field, or statement.
+    fn parse_let(&mut self) -> Result<Expr, ParseError> {
+        let start = self.pos;
+        self.pos += "let".len();
+        let name_start = self.pos;
+        let name = self.parse_ident().ok_or_else(|| {
+            ParseError::new(
+                "expected a name after `let`",
+                Span::new(name_start, name_start),
+            )
+        })?;
+        self.skip_ws();
+        self.expect("=")?;
+        let value = self.parse_expr()?;
+        self.skip_ws();
+        if !self.at_keyword("in") {
+            return Err(ParseError::new(
+                format!("expected `in` after the value of let {name}"),
+                Span::new(self.pos, self.pos),
+            ));
+        }
+        self.pos += "in".len();
+        let body = self.parse_expr()?;
+        Ok(Expr::Let {
+            name,
+            value: Box::new(value),
+            body: Box::new(body),
+            span: Span::new(start, self.pos),
+        })
+    }
+
+    /// Whether the keyword `word` starts here, as a whole word.
+    fn at_keyword(&self, word: &str) -> bool {
+        let before = self.src[..self.pos].chars().next_back();
+        let after = self.src[self.pos..]
+            .strip_prefix(word)
+            .map(|rest| rest.chars().next());
+        let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        matches!(after, Some(next) if !next.is_some_and(is_word))
+            && !before.is_some_and(|c| is_word(c) || c == '.' || c == '$')
+    }
+
+    /// Operands joined by operators of at least `min_precedence`, grouped by the
+    /// operator table.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, ParseError> {
+        self.nested(|p| p.parse_operators(min_precedence))
+    }
+
+    /// An operand and the operators after it binding at least as tightly as
+    /// `min_precedence`. Each operator but `|>` nests the tree one level deeper.
+    fn parse_operators(&mut self, min_precedence: u8) -> Result<Expr, ParseError> {
+        self.skip_ws();
         let start = self.pos;
         let mut left = self.parse_unary()?;
         loop {
             self.skip_ws();
-            if !self.consume(">>") {
+            let Some((op, len)) = self.peek_operator() else {
+                break;
+            };
+            if op.precedence < min_precedence {
                 break;
             }
-            self.skip_ws();
-            let right = self.parse_unary()?;
-            left = Expr::Compose {
-                left: Box::new(left),
-                right: Box::new(right),
-                span: Span::new(start, self.pos),
+            self.pos += len;
+            if op.builds != Infix::Pipe {
+                self.descend()?;
+            }
+            let right = self.parse_binary(match op.assoc {
+                Assoc::Left => op.precedence + 1,
+                Assoc::Right => op.precedence,
+            })?;
+            let span = Span::new(start, self.pos);
+            left = match (op.builds, left) {
+                (
+                    Infix::Pipe,
+                    Expr::Pipeline {
+                        input, mut stages, ..
+                    },
+                ) => {
+                    stages.push(right);
+                    Expr::Pipeline {
+                        input,
+                        stages,
+                        span,
+                    }
+                }
+                (Infix::Pipe, input) => Expr::Pipeline {
+                    input: Box::new(input),
+                    stages: vec![right],
+                    span,
+                },
+                (Infix::Compose, left) => Expr::Compose {
+                    left: Box::new(left),
+                    right: Box::new(right),
+                    span,
+                },
+                (Infix::Binary(op), left) => Expr::Binary {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                    span,
+                },
             };
         }
         Ok(left)
     }
 
+    /// The operator whose token starts here, and the token's length.
+    fn peek_operator(&self) -> Option<(&'static Operator, usize)> {
+        let token = Lexer::new(self.src, self.pos).next()?.ok()?;
+        let text = token.text(self.src);
+        let op = OPERATORS.iter().find(|op| op.token == text)?;
+        (token.kind == TokenKind::Punct).then_some((op, text.len()))
+    }
+
     fn parse_unary(&mut self) -> Result<Expr, ParseError> {
         self.skip_ws();
         if self.consume("~") {
             let start = self.pos - 1;
             self.skip_ws();
-            let expr = self.parse_unary()?;
+            let expr = self.nested(Self::parse_unary)?;
             return Ok(Expr::Inverse {
                 expr: Box::new(expr),
                 span: Span::new(start, self.pos),
             });
         }
+        if self.peek() == Some('-') {
+            let start = self.pos;
+            if let Some(value) = self.parse_i64() {
+                return Ok(Expr::Number {
+                    value,
+                    span: Span::new(start, self.pos),
+                });
+            }
+            self.pos += 1;
+            self.skip_ws();
+            let expr = self.nested(Self::parse_unary)?;
+            let span = Span::new(start, self.pos);
+            return Ok(match expr {
+                Expr::Number { value, .. } if value.checked_neg().is_some() => Expr::Number {
+                    value: -value,
+                    span,
+                },
+                expr => Expr::Negate {
+                    expr: Box::new(expr),
+                    span,
+                },
+            });
+        }
         self.parse_postfix()
     }
 
@@ -134,10 +653,13 @@ impl<'a> Parser<'a> {
         loop {
             self.skip_ws();
             if self.consume(".") {
+                self.descend()?;
                 let field_start = self.pos;
-                let field = self.parse_ident().ok_or_else(|| ParseError {
-                    message: "expected field name after '.'".to_string(),
-                    span: Span::new(field_start, field_start),
+                let field = self.parse_ident().ok_or_else(|| {
+                    ParseError::new(
+                        "expected field name after '.'",
+                        Span::new(field_start, field_start),
+                    )
                 })?;
                 let span = Span::new(expr.span().start, self.pos);
                 expr = Expr::FieldAccess {
@@ -148,6 +670,7 @@ impl<'a> Parser<'a> {
                 continue;
             }
             if self.consume("(") {
+                self.descend()?;
                 let call_start = expr.span().start;
                 let args = self.parse_call_args()?;
                 self.expect(")")?;
@@ -177,127 +700,47 @@ impl<'a> Parser<'a> {
                 self.skip_ws();
                 self.expect("=")?;
                 self.skip_ws();
-                let value = self.parse_subexpr_until(&[',', ')']);
+                let value = self.parse_expr()?;
                 let span = Span::new(arg_start, self.pos);
                 args.push(CallArg::Named { name, value, span });
             } else {
-                let value = self.parse_subexpr_until(&[',', ')']);
+                let value = self.parse_expr()?;
                 args.push(CallArg::Positional(value));
             }
             self.skip_ws();
-            if self.consume(",") {
-                continue;
+            if !self.consume(",") {
+                break;
+            }
+            self.skip_ws();
+            if self.peek() == Some(')') {
+                break;
             }
-            break;
         }
         Ok(args)
     }
 
+    /// `name` in `name = value`, leaving the position before the `=`.
     fn try_parse_named_arg_name(&mut self) -> Option<String> {
         let checkpoint = self.pos;
-        self.parse_ident()?;
-        self.skip_ws();
-        if self.peek() == Some('=') {
-            self.pos = checkpoint;
-            self.parse_ident()
+        let name = self.parse_ident()?;
+        let next = Lexer::new(self.src, self.pos).next().and_then(Result::ok);
+        if next.is_some_and(|token| token.text(self.src) == "=") {
+            Some(name)
         } else {
             self.pos = checkpoint;
             None
         }
     }
 
-    fn parse_subexpr_until(&mut self, delimiters: &[char]) -> Expr {
-        let start = self.pos;
-        let mut depth_paren = 0usize;
-        let mut depth_brack = 0usize;
-        let mut depth_brace = 0usize;
-        let mut in_string = false;
-        let mut escaped = false;
-
-        while let Some(c) = self.peek() {
-            if in_string {
-                self.pos += c.len_utf8();
-                if escaped {
-                    escaped = false;
-                } else if c == '\\' {
-                    escaped = true;
-                } else if c == '"' {
-                    in_string = false;
-                }
-                continue;
-            }
-
-            match c {
-                '"' => {
-                    in_string = true;
-                    self.pos += 1;
-                }
-                '(' => {
-                    depth_paren += 1;
-                    self.pos += 1;
-                }
-                ')' => {
-                    if depth_paren == 0 && depth_brack == 0 && depth_brace == 0 && delimiters.contains(&')') {
-                        break;
-                    }
-                    depth_paren = depth_paren.saturating_sub(1);
-                    self.pos += 1;
-                }
-                '[' => {
-                    depth_brack += 1;
-                    self.pos += 1;
-                }
-                ']' => {
-                    depth_brack = depth_brack.saturating_sub(1);
-                    self.pos += 1;
-                }
-                '{' => {
-                    depth_brace += 1;
-                    self.pos += 1;
-                }
-                '}' => {
-                    depth_brace = depth_brace.saturating_sub(1);
-                    self.pos += 1;
-                }
-                _ if depth_paren == 0
-                    && depth_brack == 0
-                    && depth_brace == 0
-                    && delimiters.contains(&c) =>
-                {
-                    break;
-                }
-                _ => self.pos += c.len_utf8(),
-            }
-        }
-
-        let end = self.pos;
-        let raw = self.src[start..end].trim();
-        let trimmed_start = start + self.src[start..end].find(raw).unwrap_or(0);
-        let span = Span::new(trimmed_start, trimmed_start + raw.len());
-        if raw.is_empty() {
-            return Expr::Raw {
-                text: String::new(),
-                span,
-            };
-        }
-
-        let mut nested = Parser { src: raw, pos: 0 };
-        if let Ok(expr) = nested.parse_expr() {
-            nested.skip_ws();
-            if nested.eof() {
-                return rebase_expr(expr, span.start);
-            }
-        }
-
-        Expr::Raw {
-            text: raw.to_string(),
-            span,
-        }
-    }
-
     fn parse_primary(&mut self) -> Result<Expr, ParseError> {
         self.skip_ws();
         let start = self.pos;
+        if self.at_keyword("let") {
+            return self.parse_let();
+        }
+        if self.at_keyword("match") {
+            return self.parse_match();
+        }
 
         if self.consume("(") {
             let expr = self.parse_expr()?;
@@ -315,6 +758,10 @@ impl<'a> Parser<'a> {
                     items.push(self.parse_expr()?);
                     self.skip_ws();
                     if self.consume(",") {
+                        self.skip_ws();
+                        if self.consume("]") {
+                            break;
+                        }
                         continue;
                     }
                     self.expect("]")?;
@@ -334,14 +781,14 @@ impl<'a> Parser<'a> {
                 loop {
                     self.skip_ws();
                     let field_start = self.pos;
-                    let name = self.parse_ident().ok_or_else(|| ParseError {
-                        message: "expected record field name".to_string(),
-                        span: Span::new(self.pos, self.pos),
+                    let name = self.parse_ident().ok_or_else(|| {
+                        ParseError::new("expected record field name", Span::new(self.pos, self.pos))
                     })?;
                     self.skip_ws();
                     self.expect(":")?;
                     self.skip_ws();
                     let value = self.parse_expr()?;
+                    let value_end = value.span().end;
                     fields.push(RecordField {
                         name,
                         value,
@@ -349,6 +796,14 @@ impl<'a> Parser<'a> {
                     });
                     self.skip_ws();
                     if self.consume(",") {
+                        self.skip_ws();
+                        if self.consume("}") {
+                            break;
+                        }
+                        continue;
+                    }
+                    // A field on the next line needs no comma.
+                    if self.src[value_end..self.pos].contains('\n') && self.peek() != Some('}') {
                         continue;
                     }
                     self.expect("}")?;
@@ -361,10 +816,18 @@ impl<'a> Parser<'a> {
             });
         }
 
-        if let Some(s) = self.parse_string()? {
-            return Ok(Expr::String {
-                value: s,
-                span: Span::new(start, self.pos),
+        if let Some(mut parts) = self.parse_string()? {
+            let span = Span::new(start, self.pos);
+            return Ok(match parts.as_mut_slice() {
+                [] => Expr::String {
+                    value: String::new(),
+                    span,
+                },
+                [StringPart::Text(value)] => Expr::String {
+                    value: core::mem::take(value),
+                    span,
+                },
+                _ => Expr::Interpolated { parts, span },
             });
         }
 
@@ -391,23 +854,42 @@ impl<'a> Parser<'a> {
             });
         }
 
-        if let Some(name) = self.parse_ident() {
-            return Ok(Expr::Ident {
+        if self.consume("$") {
+            let name = self.parse_ident().ok_or_else(|| {
+                ParseError::new(
+                    "expected param name after `$`",
+                    Span::new(self.pos, self.pos),
+                )
+            })?;
+            return Ok(Expr::Param {
                 name,
                 span: Span::new(start, self.pos),
             });
         }
 
-        Err(ParseError {
-            message: "expected expression".to_string(),
-            span: Span::new(self.pos, self.pos),
-        })
+        if let Some(mut name) = self.parse_ident() {
+            // `int?(x)`: the `?` belongs to the callee's name.
+            if self.src[self.pos..].starts_with("?(") {
+                self.pos += 1;
+                name.push('?');
+            }
+            let span = Span::new(start, self.pos);
+            return Ok(match name.as_str() {
+                "true" => Expr::Bool { value: true, span },
+                "false" => Expr::Bool { value: false, span },
+                "null" => Expr::Null { span },
+                _ => Expr::Ident { name, span },
+            });
+        }
+
+        Err(self.unexpected("expression"))
     }
 
-    fn parse_string(&mut self) -> Result<Option<String>, ParseError> {
+    fn parse_string(&mut self) -> Result<Option<Vec<StringPart>>, ParseError> {
         if !self.consume("\"") {
             return Ok(None);
         }
+        let mut parts = Vec::new();
         let mut out = String::new();
         let mut escaped = false;
         while let Some(c) = self.peek() {
@@ -422,11 +904,12 @@ impl<'a> Parser<'a> {
                     'n' => out.push('\n'),
                     'r' => out.push('\r'),
                     't' => out.push('\t'),
+                    '$' => out.push('$'),
                     _ => {
-                        return Err(ParseError {
-                            message: format!("unsupported escape: \\{c}"),
-                            span: Span::new(self.pos - 1, self.pos),
-                        })
+                        return Err(ParseError::new(
+                            format!("unsupported escape: \\{c}"),
+                            Span::new(self.pos - 1, self.pos),
+                        ))
                     }
                 }
                 escaped = false;
@@ -434,14 +917,67 @@ impl<'a> Parser<'a> {
             }
             match c {
                 '\\' => escaped = true,
-                '"' => return Ok(Some(out)),
+                '"' => {
+                    if !out.is_empty() {
+                        parts.push(StringPart::Text(out));
+                    }
+                    return Ok(Some(parts));
+                }
+                '$' if self.peek() == Some('{') => {
+                    if !out.is_empty() {
+                        parts.push(StringPart::Text(core::mem::take(&mut out)));
+                    }
+                    parts.push(StringPart::Expr(self.parse_interpolation()?));
+                }
                 _ => out.push(c),
             }
         }
-        Err(ParseError {
-            message: "unterminated string literal".to_string(),
-            span: Span::new(self.pos, self.pos),
-        })
+        Err(
+            ParseError::new("unterminated string literal", Span::new(self.pos, self.pos))
+                .with_id("unterminated_string", &[]),
+        )
+    }
+
+    /// The `{expr}` after a `$` in a string literal; the expression can't contain
+    /// string literals.
+    fn parse_interpolation(&mut self) -> Result<Expr, ParseError> {
+        let open = self.pos;
+        self.pos += 1;
+        let mut depth = 0usize;
+        let end = loop {
+            match self.peek() {
+                Some('}') if depth == 0 => break self.pos,
+                Some('}') => depth -= 1,
+                Some('{') => depth += 1,
+                Some('"') | None => {
+                    return Err(ParseError::new(
+                        "unterminated ${...} in string literal",
+                        Span::new(open - 1, self.pos),
+                    ))
+                }
+                Some(_) => {}
+            }
+            self.pos += self.peek().map_or(1, char::len_utf8);
+        };
+        if self.src[open + 1..end].trim().is_empty() {
+            return Err(ParseError::new(
+                "empty ${} in string literal",
+                Span::new(open - 1, end + 1),
+            ));
+        }
+        let mut inner = Parser {
+            src: &self.src[..end],
+            pos: open + 1,
+            limits: self.limits,
+            depth: self.depth,
+        };
+        let expr = inner.parse_expr()?;
+        inner.skip_ws();
+        if !inner.eof() {
+            return Err(inner.unexpected("'}'"));
+        }
+        self.pos = end + 1;
+        Ok(expr)
     }
 
     fn parse_i64(&mut self) -> Option<i64> {
@@ -485,13 +1021,60 @@ impl<'a> Parser<'a> {
         if self.consume(text) {
             Ok(())
         } else {
-            Err(ParseError {
-                message: format!("expected '{text}'"),
-                span: Span::new(self.pos, self.pos),
-            })
+            Err(self.unexpected(&format!("'{text}'")))
+        }
+    }
+
+    /// An error at the token here: "unexpected token `|>`, expected {expected}".
+    fn unexpected(&self, expected: &str) -> ParseError {
+        match Lexer::new(self.src, self.pos).next() {
+            Some(Ok(token)) => {
+                let text = token.text(self.src);
+                ParseError::new(
+                    format!("unexpected token `{text}`, expected {expected}"),
+                    token.span,
+                )
+                .with_id(
+                    "unexpected_token",
+                    &[("token", text), ("expected", expected)],
+                )
+            }
+            Some(Err(err)) => err,
+            None => ParseError::new(
+                format!("unexpected end of input, expected {expected}"),
+                Span::new(self.pos, self.pos),
+            )
+            .with_id("unexpected_end", &[("expected", expected)]),
         }
     }
 
+    /// Runs `parse` one nesting level deeper. Levels `parse` adds with
+    /// [`Self::descend`] end with it.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        let depth = self.depth;
+        let result = self.descend().and_then(|()| parse(self));
+        self.depth = depth;
+        result
+    }
+
+    /// Goes one nesting level deeper, failing past `limits.max_depth`.
+    fn descend(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > self.limits.max_depth {
+            return Err(ParseError::new(
+                format!(
+                    "expression nests more than {} levels deep",
+                    self.limits.max_depth
+                ),
+                Span::new(self.pos, self.pos),
+            ));
+        }
+        Ok(())
+    }
+
     fn consume(&mut self, text: &str) -> bool {
         if self.src[self.pos..].starts_with(text) {
             self.pos += text.len();
@@ -502,120 +1085,58 @@ impl<'a> Parser<'a> {
     }
 
     fn skip_ws(&mut self) {
-        while let Some(c) = self.peek() {
-            if c.is_whitespace() {
+        self.take_comments();
+    }
+
+    /// Skips whitespace and `//` / `#` line comments, returning the comments.
+    fn take_comments(&mut self) -> Vec<Comment> {
+        let mut comments = Vec::new();
+        loop {
+            while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
                 self.pos += c.len_utf8();
-            } else {
-                break;
+            }
+            match self.parse_comment() {
+                Some(comment) => comments.push(comment),
+                None => return comments,
             }
         }
     }
 
-    fn peek(&self) -> Option<char> {
-        self.src[self.pos..].chars().next()
+    /// Adds a comment that follows the statement on its last line.
+    fn with_trailing_comment(&mut self, mut comments: Vec<Comment>) -> Vec<Comment> {
+        let checkpoint = self.pos;
+        while let Some(c) = self.peek().filter(|c| *c == ' ' || *c == '\t') {
+            self.pos += c.len_utf8();
+        }
+        match self.parse_comment() {
+            Some(comment) => comments.push(comment),
+            None => self.pos = checkpoint,
+        }
+        comments
     }
 
-    fn eof(&self) -> bool {
-        self.pos >= self.src.len()
+    fn parse_comment(&mut self) -> Option<Comment> {
+        let rest = &self.src[self.pos..];
+        let marker = if rest.starts_with("//") {
+            2
+        } else if rest.starts_with('#') {
+            1
+        } else {
+            return None;
+        };
+        let start = self.pos;
+        self.pos += rest.find('\n').unwrap_or(rest.len());
+        Some(Comment {
+            text: self.src[start + marker..self.pos].trim().to_string(),
+            span: Span::new(start, self.pos),
+        })
     }
-}
 
-trait Spanned {
-    fn span(&self) -> Span;
-}
-
-impl Spanned for Expr {
-    fn span(&self) -> Span {
-        match self {
-            Expr::Ident { span, .. }
-            | Expr::Placeholder { span }
-            | Expr::Number { span, .. }
-            | Expr::String { span, .. }
-            | Expr::Array { span, .. }
-            | Expr::Record { span, .. }
-            | Expr::FieldAccess { span, .. }
-            | Expr::Call { span, .. }
-            | Expr::Pipeline { span, .. }
-            | Expr::Compose { span, .. }
-            | Expr::Inverse { span, .. }
-            | Expr::Raw { span, .. } => *span,
-        }
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
     }
-}
 
-fn rebase_expr(expr: Expr, offset: usize) -> Expr {
-    match expr {
-        Expr::Ident { name, span } => Expr::Ident {
-            name,
-            span: shift(span, offset),
-        },
-        Expr::Placeholder { span } => Expr::Placeholder {
-            span: shift(span, offset),
-        },
-        Expr::Number { value, span } => Expr::Number {
-            value,
-            span: shift(span, offset),
-        },
-        Expr::String { value, span } => Expr::String {
-            value,
-            span: shift(span, offset),
-        },
-        Expr::Array { items, span } => Expr::Array {
-            items: items.into_iter().map(|e| rebase_expr(e, offset)).collect(),
-            span: shift(span, offset),
-        },
-        Expr::Record { fields, span } => Expr::Record {
-            fields: fields
-                .into_iter()
-                .map(|f| RecordField {
-                    name: f.name,
-                    value: rebase_expr(f.value, offset),
-                    span: shift(f.span, offset),
-                })
-                .collect(),
-            span: shift(span, offset),
-        },
-        Expr::FieldAccess { expr, field, span } => Expr::FieldAccess {
-            expr: Box::new(rebase_expr(*expr, offset)),
-            field,
-            span: shift(span, offset),
-        },
-        Expr::Call { callee, args, span } => Expr::Call {
-            callee: Box::new(rebase_expr(*callee, offset)),
-            args: args
-                .into_iter()
-                .map(|arg| match arg {
-                    CallArg::Positional(e) => CallArg::Positional(rebase_expr(e, offset)),
-                    CallArg::Named { name, value, span } => CallArg::Named {
-                        name,
-                        value: rebase_expr(value, offset),
-                        span: shift(span, offset),
-                    },
-                })
-                .collect(),
-            span: shift(span, offset),
-        },
-        Expr::Pipeline { input, stages, span } => Expr::Pipeline {
-            input: Box::new(rebase_expr(*input, offset)),
-            stages: stages.into_iter().map(|e| rebase_expr(e, offset)).collect(),
-            span: shift(span, offset),
-        },
-        Expr::Compose { left, right, span } => Expr::Compose {
-            left: Box::new(rebase_expr(*left, offset)),
-            right: Box::new(rebase_expr(*right, offset)),
-            span: shift(span, offset),
-        },
-        Expr::Inverse { expr, span } => Expr::Inverse {
-            expr: Box::new(rebase_expr(*expr, offset)),
-            span: shift(span, offset),
-        },
-        Expr::Raw { text, span } => Expr::Raw {
-            text,
-            span: shift(span, offset),
-        },
+    fn eof(&self) -> bool {
+        self.pos >= self.src.len()
     }
 }
-
-fn shift(span: Span, offset: usize) -> Span {
-    Span::new(span.start + offset, span.end + offset)
-}
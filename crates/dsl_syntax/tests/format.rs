@@ -0,0 +1,140 @@
+use dsl_syntax::{format_source, parse_program};
+
+#[test]
+fn formats_short_pipelines_inline_and_normalizes_spacing() {
+    let src = "xs:=input.json( \"xs\" )|>json;\nxs|>map(_ + 1)|>filter(_ > 2)|>ui.table(\"out\");";
+    assert_eq!(
+        format_source(src).expect("should format"),
+        "xs := input.json(\"xs\") |> json;\nxs |> map(_ + 1) |> filter(_ > 2) |> ui.table(\"out\");\n"
+    );
+}
+
+#[test]
+fn breaks_long_pipelines_and_keeps_blank_lines_between_statements() {
+    let src = r#"
+requests := input.json("requests") |> json;
+
+
+requests |> rbac.evaluate(principal_bindings="principal_bindings", role_perms="role_perms", resource_ancestors="resource_ancestors") |> ui.table("decisions");
+"#;
+    assert_eq!(
+        format_source(src).expect("should format"),
+        r#"requests := input.json("requests") |> json;
+
+requests
+  |> rbac.evaluate(principal_bindings="principal_bindings", role_perms="role_perms", resource_ancestors="resource_ancestors")
+  |> ui.table("decisions");
+"#
+    );
+}
+
+#[test]
+fn formatting_is_idempotent_and_preserves_the_ast_shape() {
+    let src = r#"
+chain := base64 >> ~(utf8 >> base64);
+input.json("rows")
+  |> json
+  |> map({id: _.id, tags: ["a", "b\n"], empty: {}})
+  |> group.collect_all(by_key=_.team, within_ms=250, limit=10)
+  |> ui.table("out");
+"#;
+    let once = format_source(src).expect("should format");
+    let twice = format_source(&once).expect("formatted output should parse");
+    assert_eq!(once, twice);
+    assert_eq!(
+        parse_program(src).expect("source parses").statements.len(),
+        parse_program(&once)
+            .expect("output parses")
+            .statements
+            .len()
+    );
+    assert!(once.contains("chain := base64 >> ~(utf8 >> base64);"));
+    assert!(once.contains(r#"map({ id: _.id, tags: ["a", "b\n"], empty: {} })"#));
+}
+
+#[test]
+fn formats_const_statements_and_params() {
+    let src = "const  LIMIT:=3;\nxs|>rank.topk(k=$k,by=_,order=\"desc\");";
+    assert_eq!(
+        format_source(src).expect("should format"),
+        "const LIMIT := 3;\nxs |> rank.topk(k=$k, by=_, order=\"desc\");\n"
+    );
+}
+
+#[test]
+fn formats_destructuring_bindings() {
+    assert_eq!(
+        format_source("{ a,b }:=xs|>json;").expect("should format"),
+        "{a, b} := xs |> json;\n"
+    );
+    assert_eq!(
+        format_source("override  x:=xs;override{a}:=xs;").expect("should format"),
+        "override x := xs;\noverride {a} := xs;\n"
+    );
+}
+
+#[test]
+fn formats_interpolated_strings() {
+    let src = r#"xs |> map("user ${ _.id } costs \${x}") |> ui.log("out");"#;
+    let formatted = format_source(src).expect("should format");
+    assert_eq!(
+        formatted,
+        "xs |> map(\"user ${_.id} costs \\${x}\") |> ui.log(\"out\");\n"
+    );
+    assert_eq!(format_source(&formatted).expect("should format"), formatted);
+}
+
+#[test]
+fn formats_let_expressions() {
+    let src = "xs |> map(let t=_.a + _.b in let u = t + t in {t: t, u: u});";
+    let formatted = format_source(src).expect("should format");
+    assert_eq!(
+        formatted,
+        "xs |> map(let t = _.a + _.b in let u = t + t in { t: t, u: u });\n"
+    );
+    assert_eq!(format_source(&formatted).expect("should format"), formatted);
+}
+
+#[test]
+fn formats_match_expressions() {
+    let src = r#"xs |> map(match _.kind {{type:"a",n:n,..}=>n+1,{}=>0,_=>null});"#;
+    let formatted = format_source(src).expect("should format");
+    assert_eq!(
+        formatted,
+        "xs |> map(match _.kind { { type: \"a\", n, .. } => n + 1, {} => 0, _ => null });\n"
+    );
+    assert_eq!(format_source(&formatted).expect("should format"), formatted);
+
+    let src = "xs |> map(match _ {click( {x,..} )=>x,key(_)=>0});";
+    let formatted = format_source(src).expect("should format");
+    assert_eq!(
+        formatted,
+        "xs |> map(match _ { click({ x, .. }) => x, key(_) => 0 });\n"
+    );
+}
+
+#[test]
+fn formats_comments_above_their_statements() {
+    let src = "# fixtures\nxs:=input.json(\"xs\")|>json; // decoded\n\n//\n//   totals\nxs|>ui.table(\"out\");\n\n// end\n";
+    let formatted = format_source(src).expect("should format");
+    assert_eq!(
+        formatted,
+        "// fixtures\n// decoded\nxs := input.json(\"xs\") |> json;\n\n//\n// totals\nxs |> ui.table(\"out\");\n\n// end\n"
+    );
+    assert_eq!(format_source(&formatted).expect("should format"), formatted);
+}
+
+#[test]
+fn keeps_parentheses_that_negative_literals_need() {
+    for (src, formatted) in [
+        ("x := (-5).a;", "x := (-5).a;\n"),
+        ("x := -(5).a;", "x := -(5.a);\n"),
+        ("x := -(5.a);", "x := -(5.a);\n"),
+    ] {
+        assert_eq!(format_source(src).expect("should format"), formatted);
+        assert_eq!(
+            format_source(formatted).expect("should reformat"),
+            formatted
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use dsl_syntax::{lex, parse_program, TokenKind};
+
+#[test]
+fn lexes_tokens_with_spans() {
+    let src = "xs := input.json(\"a ${_.id}\") |> map(_ + $n) // done\n";
+    let tokens = lex(src).expect("source should lex");
+    let got: Vec<(TokenKind, &str)> = tokens
+        .iter()
+        .map(|token| (token.kind, token.text(src)))
+        .collect();
+    use TokenKind::*;
+    assert_eq!(
+        got,
+        [
+            (Ident, "xs"),
+            (Punct, ":="),
+            (Ident, "input"),
+            (Punct, "."),
+            (Ident, "json"),
+            (Punct, "("),
+            (String, "\"a ${_.id}\""),
+            (Punct, ")"),
+            (Punct, "|>"),
+            (Ident, "map"),
+            (Punct, "("),
+            (Placeholder, "_"),
+            (Punct, "+"),
+            (Param, "$n"),
+            (Punct, ")"),
+            (Comment, "// done"),
+        ]
+    );
+
+    let err = lex("x := \"abc").expect_err("unterminated");
+    assert_eq!(err.to_string(), "unterminated string literal at 1:6");
+}
+
+#[test]
+fn parse_errors_name_the_unexpected_token() {
+    let err = parse_program("xs |> |> ui.log(\"out\");").expect_err("missing stage");
+    assert_eq!(
+        err.to_string(),
+        "unexpected token `|>`, expected expression at 1:7"
+    );
+
+    let err = parse_program("xs := [1, 2 3];").expect_err("missing comma");
+    assert_eq!(
+        err.to_string(),
+        "unexpected token `3`, expected ']' at 1:13"
+    );
+
+    let err = parse_program("xs := ys").expect_err("missing semicolon");
+    assert_eq!(
+        err.to_string(),
+        "unexpected end of input, expected ';' at 1:9"
+    );
+    assert_eq!(err.message_id.as_deref(), Some("unexpected_end"));
+    assert_eq!(
+        err.message_args,
+        [("expected".to_string(), "';'".to_string())]
+    );
+}
+
+#[test]
+fn comments_and_strings_do_not_end_call_arguments() {
+    let program = parse_program("xs |> map(_ + 1 // ) is fine here\n) |> ui.log(\")\");")
+        .expect("program should parse");
+    assert_eq!(program.statements.len(), 1);
+}
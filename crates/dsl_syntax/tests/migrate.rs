@@ -0,0 +1,37 @@
+use dsl_syntax::{migrate, CURRENT_VERSION};
+
+#[test]
+fn renames_stages_and_keeps_layout() {
+    let src = r#"// top scores
+xs := input.json("xs")  |>  json;
+best := topk(k=2, by=_.score, order="desc");
+xs |> best |> ui.table("best");
+input.json("runs") |> json |> kmerge_arrays(by=_, limit=3) |> ui.table("merged");
+"#;
+    assert_eq!(
+        migrate(src, 0, CURRENT_VERSION).expect("should migrate"),
+        src.replace("topk(", "rank.topk(")
+            .replace("kmerge_arrays(", "rank.kmerge_arrays(")
+    );
+    assert_eq!(migrate(src, 1, 1).expect("nothing to do"), src);
+}
+
+#[test]
+fn leaves_names_the_program_binds() {
+    let src = "topk := map(_ + 1);\ninput.json(\"xs\") |> json |> topk |> ui.table(\"out\");\n";
+    assert_eq!(migrate(src, 0, 1).expect("should migrate"), src);
+}
+
+#[test]
+fn rejects_unknown_or_backwards_versions() {
+    assert_eq!(
+        migrate("", 0, 9).expect_err("future version"),
+        "unknown version 9 (the current version is 1)"
+    );
+    assert_eq!(
+        migrate("", 1, 0).expect_err("downgrade"),
+        "cannot migrate from version 1 down to 0"
+    );
+    let err = migrate("xs := ;", 0, 1).expect_err("parse error");
+    assert!(err.starts_with("migrating from version 0: "), "{err}");
+}
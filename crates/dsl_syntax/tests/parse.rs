@@ -13,9 +13,55 @@ xs |> map(_ + 1) |> filter(_ > 2) |> ui.table("out");
     let got = parse_debug(src);
     assert!(got.contains("Binding"));
     assert!(got.contains("Pipeline"));
-    assert!(got.contains("Raw"));
-    assert!(got.contains("_ + 1"));
-    assert!(got.contains("_ > 2"));
+    assert!(got.contains("op: Add"));
+    assert!(got.contains("op: Gt"));
+}
+
+#[test]
+fn parses_operators_by_precedence() {
+    let expr = |src: &str| dsl_syntax::parse_expr(src).expect("expression should parse");
+    let grouped = |src: &str| match expr(src) {
+        dsl_syntax::Expr::Binary {
+            op, left, right, ..
+        } => (
+            op,
+            dsl_syntax::format_expr(&left),
+            dsl_syntax::format_expr(&right),
+        ),
+        other => panic!("expected a binary expression, got {other:?}"),
+    };
+    use dsl_syntax::BinaryOp::*;
+    assert_eq!(
+        grouped("_.a + 1 > 2 == true"),
+        (Eq, "_.a + 1 > 2".to_string(), "true".to_string())
+    );
+    assert_eq!(
+        grouped("1 + 2 + 3"),
+        (Add, "1 + 2".to_string(), "3".to_string())
+    );
+    assert_eq!(
+        grouped("_.a ?? _.b ?? 0"),
+        (Coalesce, "_.a".to_string(), "_.b ?? 0".to_string())
+    );
+    assert_eq!(
+        grouped("-_.a + (1 + 2)"),
+        (Add, "-_.a".to_string(), "1 + 2".to_string())
+    );
+    assert_eq!(dsl_syntax::format_expr(&expr("1 + (2 + 3)")), "1 + (2 + 3)");
+    assert_eq!(
+        dsl_syntax::format_expr(&expr("(1 ?? 2) ?? 3")),
+        "(1 ?? 2) ?? 3"
+    );
+    assert_eq!(
+        dsl_syntax::format_expr(&expr("xs |> map(_ + 1) >> ~utf8 |> ui.log(\"out\")")),
+        "xs |> map(_ + 1) >> ~utf8 |> ui.log(\"out\")"
+    );
+
+    let err = parse_program("xs |> map(_ * 2);").expect_err("no * operator");
+    assert_eq!(
+        err.to_string(),
+        "unexpected token `*`, expected ')' at 1:13"
+    );
 }
 
 #[test]
@@ -128,3 +174,246 @@ input.json("rows")
     assert!(got.contains("Named"));
     assert!(got.contains("limit"));
 }
+
+#[test]
+fn parses_params() {
+    let src = r#"
+input.json("xs") |> json |> rank.topk(k=$k, by=_, order="desc") |> map({ at_least: $min });
+"#;
+    let got = parse_debug(src);
+    assert!(got.contains("Param"));
+    assert!(got.contains("\"k\""));
+    assert!(got.contains("\"min\""));
+}
+
+#[test]
+fn parses_const_statements() {
+    let got = parse_debug(r#"const REGION := "eu"; const := 1;"#);
+    assert!(got.contains("Const"));
+    assert!(got.contains("\"REGION\""));
+    assert!(
+        got.contains("Binding"),
+        "`const` alone is still a binding name"
+    );
+    assert!(parse_program("const LIMIT 3;").is_err());
+}
+
+#[test]
+fn parses_record_destructuring_bindings() {
+    let program =
+        parse_program("{ok, failed} := input.json(\"runs\") |> json;").expect("should parse");
+    match &program.statements[0] {
+        dsl_syntax::Stmt::Destructure { names, .. } => assert_eq!(names, &["ok", "failed"]),
+        other => panic!("expected Destructure, got {other:?}"),
+    }
+
+    let err = parse_program("{a, a} := xs;").expect_err("duplicate name");
+    assert_eq!(err.to_string(), "a is destructured twice at 1:5");
+    let err = parse_program("{} := xs;").expect_err("no names");
+    assert!(err.message.contains("at least one name"), "{err}");
+}
+
+#[test]
+fn parses_override_bindings() {
+    let program = parse_program("override xs := ys; override {a} := xs; override := 1;")
+        .expect("should parse");
+    let overrides: Vec<bool> = program
+        .statements
+        .iter()
+        .map(|stmt| match stmt {
+            dsl_syntax::Stmt::Binding { overrides, .. }
+            | dsl_syntax::Stmt::Destructure { overrides, .. } => *overrides,
+            other => panic!("expected a binding, got {other:?}"),
+        })
+        .collect();
+    assert_eq!(overrides, [true, true, false]);
+
+    let err = parse_program("override xs |> ui.log(\"out\");").expect_err("not a binding");
+    assert_eq!(
+        err.to_string(),
+        "override must be followed by a binding at 1:1"
+    );
+}
+
+#[test]
+fn parses_string_interpolation() {
+    let got = parse_debug(r#"xs |> map("user ${_.id} scored ${_.a + 1}") |> ui.log("out");"#);
+    assert!(got.contains("Interpolated"));
+    assert!(got.contains("FieldAccess"));
+    assert!(got.contains("op: Add"));
+
+    let got = parse_debug(r#"xs |> map("cost: \${_.id}") |> ui.log("out");"#);
+    assert!(!got.contains("Interpolated"));
+    assert!(got.contains("cost: ${_.id}"));
+
+    let err = parse_program(r#"label := "a ${_.id";"#).expect_err("unterminated");
+    assert!(err.message.contains("unterminated ${...}"), "{err}");
+}
+
+#[test]
+fn parses_let_expressions() {
+    let got = parse_debug("xs |> map(let total = _.a + _.b in { id: _.id, total: total });");
+    assert!(got.contains("Let"));
+    assert!(got.contains("op: Add"));
+    assert!(got.contains("Record"));
+
+    let got = parse_debug("xs |> map(let index = _.in in { x: index, letter: _.letter });");
+    assert!(got.contains("\"index\""));
+    assert!(got.contains("\"letter\""));
+
+    let err = parse_program("xs |> map(let t = _.a);").expect_err("no in");
+    assert!(
+        err.message
+            .contains("expected `in` after the value of let t"),
+        "{err}"
+    );
+}
+
+#[test]
+fn parses_match_expressions() {
+    let got = parse_debug(
+        r#"xs |> map(match _ { {type: "click", x, ..} => x + 1, {n: -1} => 0, other => other });"#,
+    );
+    assert!(got.contains("Match"));
+    assert!(got.contains("rest: true"));
+    assert!(got.contains("Bind"));
+    assert!(got.contains("op: Add"));
+    let got = parse_debug("xs |> map(match _ { click({x, ..}) => x, key(k) => k });");
+    assert!(got.contains("Tagged"));
+    assert!(got.contains("\"click\""));
+
+    let err = parse_program("xs |> map(match _ { _.a => 1 });").expect_err("not a pattern");
+    assert!(err.message.starts_with("expected a pattern"), "{err}");
+    let err = parse_program("xs |> map(match _ { });").expect_err("no arms");
+    assert!(err.message.contains("at least one arm"), "{err}");
+}
+
+#[test]
+fn parses_bool_null_and_negative_literals() {
+    let src = "xs |> map({ ok: true, off: false, none: null, n: - 3, m: -_.x });";
+    let program = parse_program(src).expect("should parse");
+    let dsl_syntax::Stmt::Pipeline { expr, .. } = &program.statements[0] else {
+        panic!("expected a pipeline");
+    };
+    assert_eq!(
+        dsl_syntax::format_expr(expr),
+        "xs |> map({ ok: true, off: false, none: null, n: -3, m: -_.x })"
+    );
+    let got = parse_debug("xs |> f(_.x, false) |> g(flag=true, min=-9223372036854775808);");
+    assert!(got.contains("Bool"));
+    assert!(got.contains("-9223372036854775808"));
+}
+
+#[test]
+fn resolves_spans_to_lines_and_columns() {
+    let src = "xs := [1];\nlabel := \"é\" |> nope(;\n";
+    let start = src.find("nope").expect("nope in src");
+    let span = dsl_syntax::Span {
+        start,
+        end: start + 4,
+    };
+    let (from, to) = span.resolve(src);
+    assert_eq!((from.line, from.column), (2, 17));
+    assert_eq!(to.to_string(), "2:21");
+
+    let err = parse_program("xs := [1];\nys := xs |> ;").expect_err("missing stage");
+    assert_eq!((err.start.line, err.start.column), (2, 13));
+    assert!(err.to_string().ends_with(" at 2:13"), "{err}");
+}
+
+#[test]
+fn attaches_comments_to_the_statements_they_lead() {
+    let src = "// load\n# decode too\nxs := input.json(\"xs\") // not trailing\n  |> json; // trailing\nxs |> ui.table(\"out\");\n// done\n";
+    let program = parse_program(src).expect("should parse");
+    let texts = |comments: &[dsl_syntax::Comment]| -> Vec<String> {
+        comments.iter().map(|c| c.text.clone()).collect()
+    };
+    assert_eq!(
+        texts(program.statements[0].comments()),
+        ["load", "decode too", "trailing"]
+    );
+    assert!(program.statements[1].comments().is_empty());
+    assert_eq!(texts(&program.comments), ["done"]);
+    assert_eq!(
+        &src[program.comments[0].span.start..program.comments[0].span.end],
+        "// done"
+    );
+}
+
+#[test]
+fn parses_lenient_conversion_callees() {
+    let program = parse_program("xs |> map([int?(_.n), int(_.m)]);").expect("should parse");
+    let got = format!("{program:?}");
+    assert!(got.contains("name: \"int?\""), "{got}");
+}
+
+#[test]
+fn accepts_trailing_commas_and_newline_separated_fields() {
+    let src = "xs |> map({\n  id: _.id\n  tags: [\"a\", \"b\",],\n  n: 1,\n},) |> rank.topk(k=1, by=_.n,);";
+    let program = parse_program(src).expect("should parse");
+    assert_eq!(
+        dsl_syntax::format_program(&program),
+        "xs |> map({ id: _.id, tags: [\"a\", \"b\"], n: 1 }) |> rank.topk(k=1, by=_.n);\n"
+    );
+
+    let err = parse_program("xs |> map({ id: _.id tags: 1 });").expect_err("same-line fields");
+    assert!(err.message.contains("expected '}'"), "{err}");
+    assert!(parse_program("xs |> map([1,,2]);").is_err());
+    assert!(parse_program("xs |> f(,);").is_err());
+}
+
+#[test]
+fn parse_errors_are_std_errors() {
+    let err: Box<dyn std::error::Error> =
+        parse_program("xs := ;").expect_err("no expression").into();
+    assert!(err.to_string().ends_with("at 1:7"), "{err}");
+}
+
+#[test]
+fn rejects_nesting_past_the_limit_instead_of_overflowing() {
+    let depth = dsl_syntax::MAX_NESTING;
+    for src in [
+        format!("x := {}1{};", "(".repeat(100_000), ")".repeat(100_000)),
+        format!("x := {}1;", "-".repeat(100_000)),
+        format!("x := 1{};", " + 1".repeat(100_000)),
+        format!("x := _{};", ".a".repeat(100_000)),
+    ] {
+        let err = parse_program(&src).expect_err("too deep");
+        assert_eq!(
+            err.message,
+            format!("expression nests more than {depth} levels deep")
+        );
+    }
+
+    let shallow = format!("x := {}1{};", "(".repeat(depth / 2), ")".repeat(depth / 2));
+    parse_program(&shallow).expect("moderate nesting parses");
+}
+
+#[test]
+fn enforces_configurable_parse_limits() {
+    use dsl_syntax::{parse_program_with_limits, ParseLimits};
+
+    let limits = ParseLimits {
+        max_source_len: 40,
+        max_depth: 4,
+        max_statements: 2,
+    };
+    let parse = |src: &str| {
+        parse_program_with_limits(src, &limits)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    };
+    assert_eq!(parse("a := 1; b := ((2));"), Ok(()));
+    assert_eq!(
+        parse("a := 1; b := 2; c := 3;"),
+        Err("program has more than 2 statements at 1:17".to_string())
+    );
+    assert_eq!(
+        parse("a := ((((1))));"),
+        Err("expression nests more than 4 levels deep at 1:10".to_string())
+    );
+    assert_eq!(
+        parse(&format!("a := \"{}\";", "x".repeat(40))),
+        Err("program is 48 bytes, more than the 40 allowed at 1:41".to_string())
+    );
+}
@@ -1,6 +1,190 @@
 //! Minimal stable API surface for wasm-facing bindings.
 
+use dsl_runtime::RuntimeError;
 use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/// Compiled programs kept alive between `run_handle` calls.
+const PROGRAM_CACHE_CAPACITY: usize = 32;
+/// Finished [`run`] outputs kept for [`get_table_page`]; older runs are evicted.
+const RUN_STORE_CAPACITY: usize = 8;
+/// Envelopes of recent [`run`], [`run_with_params`], and [`run_with_options`] calls
+/// kept for identical repeat calls.
+const RESULT_CACHE_CAPACITY: usize = 16;
+
+thread_local! {
+    static CANCEL_TOKENS: RefCell<HashMap<u32, dsl_runtime::CancelToken>> =
+        RefCell::new(HashMap::new());
+    static PROGRAM_CACHE: RefCell<ProgramCache> = RefCell::new(ProgramCache::default());
+    static RUN_STORE: RefCell<RunStore> = RefCell::new(RunStore::default());
+    static RESULT_CACHE: RefCell<ResultCache> = RefCell::new(ResultCache::default());
+    /// Bindings kept between [`run_statement`] calls.
+    static SESSION: RefCell<Option<dsl_runtime::Session>> = const { RefCell::new(None) };
+    /// Locale [`compile`] and [`run_v2`] render errors in; see [`set_locale`].
+    static LOCALE: RefCell<&'static str> = const { RefCell::new("en") };
+}
+
+/// Least-recently-used cache of compiled programs; the back of `entries` is the most
+/// recently used.
+#[derive(Default)]
+struct ProgramCache {
+    next_id: u32,
+    entries: Vec<(u32, dsl_runtime::Program)>,
+}
+
+impl ProgramCache {
+    fn insert(&mut self, program: dsl_runtime::Program) -> u32 {
+        self.next_id += 1;
+        if self.entries.len() >= PROGRAM_CACHE_CAPACITY {
+            self.entries.remove(0);
+        }
+        self.entries.push((self.next_id, program));
+        self.next_id
+    }
+
+    fn touch(&mut self, id: u32) -> Option<&dsl_runtime::Program> {
+        let idx = self
+            .entries
+            .iter()
+            .position(|(entry_id, _)| *entry_id == id)?;
+        let entry = self.entries.remove(idx);
+        self.entries.push(entry);
+        self.entries.last().map(|(_, program)| program)
+    }
+
+    fn remove(&mut self, id: u32) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|(entry_id, _)| *entry_id != id);
+        self.entries.len() != before
+    }
+}
+
+type RetainedTables = Rc<BTreeMap<String, Vec<Value>>>;
+
+/// Tables of recent runs, oldest first.
+#[derive(Default)]
+struct RunStore {
+    next_id: u32,
+    entries: Vec<(u32, RetainedTables)>,
+}
+
+impl RunStore {
+    fn insert(&mut self, tables: RetainedTables) -> u32 {
+        self.next_id += 1;
+        if self.entries.len() >= RUN_STORE_CAPACITY {
+            self.entries.remove(0);
+        }
+        self.entries.push((self.next_id, tables));
+        self.next_id
+    }
+
+    fn get(&self, id: u32) -> Option<RetainedTables> {
+        self.entries
+            .iter()
+            .find(|(entry_id, _)| *entry_id == id)
+            .map(|(_, tables)| Rc::clone(tables))
+    }
+
+    fn remove(&mut self, id: u32) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|(entry_id, _)| *entry_id != id);
+        self.entries.len() != before
+    }
+}
+
+/// A finished run's envelope and, for [`run`], the tables to retain again on a hit.
+#[derive(Clone)]
+struct CachedRun {
+    body: Value,
+    tables: Option<RetainedTables>,
+}
+
+/// Identity of a run: the entry point and every argument that shapes its envelope.
+/// The cached entry points run without host registrations, so nothing else does.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct RunKey {
+    entry: &'static str,
+    program: String,
+    fixtures_json: String,
+    /// `params_json` or `options_json`, for the entry points that take one.
+    extra: Option<String>,
+    pretty: bool,
+}
+
+impl RunKey {
+    /// A hash of every field, so a lookup only compares whole programs and fixtures
+    /// with entries that already hash the same.
+    fn digest(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Least-recently-used cache of run envelopes keyed by [`RunKey`] and its
+/// [`RunKey::digest`]; the back of `entries` is the most recently used.
+#[derive(Default)]
+struct ResultCache {
+    entries: Vec<(u64, RunKey, CachedRun)>,
+}
+
+impl ResultCache {
+    fn get(&mut self, digest: u64, key: &RunKey) -> Option<CachedRun> {
+        let idx = self
+            .entries
+            .iter()
+            .position(|(entry_digest, entry_key, _)| *entry_digest == digest && entry_key == key)?;
+        let entry = self.entries.remove(idx);
+        self.entries.push(entry);
+        self.entries.last().map(|(_, _, run)| run.clone())
+    }
+
+    fn insert(&mut self, digest: u64, key: RunKey, run: CachedRun) {
+        if self.entries.len() >= RESULT_CACHE_CAPACITY {
+            self.entries.remove(0);
+        }
+        self.entries.push((digest, key, run));
+    }
+}
+
+/// The envelope for `key`: a cached copy with `cached: true` when an identical run
+/// is in [`RESULT_CACHE`], else `run()`'s, which is cached. Retained tables get a
+/// fresh `run_id` either way.
+fn cached_run(key: RunKey, run: impl FnOnce() -> CachedRun) -> Value {
+    let digest = key.digest();
+    let hit = RESULT_CACHE.with(|cache| cache.borrow_mut().get(digest, &key));
+    let cached = hit.is_some();
+    let CachedRun { mut body, tables } = hit.unwrap_or_else(|| {
+        let run = run();
+        RESULT_CACHE.with(|cache| cache.borrow_mut().insert(digest, key, run.clone()));
+        run
+    });
+    if let Value::Object(map) = &mut body {
+        if cached {
+            map.insert("cached".to_string(), Value::Bool(true));
+        }
+        if let Some(tables) = tables {
+            let row_counts = tables
+                .iter()
+                .map(|(name, rows)| (name.clone(), Value::Number((rows.len() as i64).into())))
+                .collect();
+            let id = RUN_STORE.with(|store| store.borrow_mut().insert(tables));
+            map.insert("run_id".to_string(), Value::Number((id as i64).into()));
+            map.insert("row_counts".to_string(), Value::Object(row_counts));
+        }
+    }
+    body
+}
+
+/// Empties the cache of run envelopes to free their memory. Cached envelopes never go
+/// stale: their [`RunKey`] holds everything a run depends on.
+pub fn clear_result_cache() {
+    RESULT_CACHE.with(|cache| cache.borrow_mut().entries.clear());
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct JsValue(String);
@@ -19,6 +203,14 @@ fn json_string(value: &Value) -> String {
     serde_json::to_string(value).unwrap_or_else(|_| "{}".to_string())
 }
 
+fn output_json_string(value: &Value, pretty: bool) -> String {
+    if pretty {
+        serde_json::to_string_pretty(value).unwrap_or_else(|_| "{}".to_string())
+    } else {
+        json_string(value)
+    }
+}
+
 fn object(entries: Vec<(&str, Value)>) -> Value {
     let mut map = Map::new();
     for (k, v) in entries {
@@ -27,61 +219,742 @@ fn object(entries: Vec<(&str, Value)>) -> Value {
     Value::Object(map)
 }
 
+/// Renders later [`compile`] diagnostics and [`run_v2`] errors in `locale`, one of
+/// [`dsl_runtime::LOCALES`]; any other locale is refused and the current one kept.
+pub fn set_locale(locale: String) -> bool {
+    match dsl_runtime::LOCALES.iter().find(|known| **known == locale) {
+        Some(known) => {
+            LOCALE.with(|current| *current.borrow_mut() = known);
+            true
+        }
+        None => false,
+    }
+}
+
+/// `{ ok, diagnostics }`; failures add the error's catalog `code` and `kind` and its
+/// `message_id` and `message_args`, with `diagnostics` in the [`set_locale`] locale.
 pub fn compile(program: String) -> JsValue {
-    let (ok, diagnostics) = match dsl_runtime::compile(&program) {
-        Ok(_) => (true, String::new()),
-        Err(e) => (false, e),
+    let body = match dsl_runtime::compile(&program) {
+        Ok(_) => object(vec![
+            ("ok", Value::Bool(true)),
+            ("diagnostics", Value::String(String::new())),
+        ]),
+        Err(e) => {
+            let mut entries = vec![("ok", Value::Bool(false))];
+            entries.extend(error_details(&e));
+            entries.push(("diagnostics", Value::String(localized(&e))));
+            object(entries)
+        }
     };
+    JsValue::from_json_string(json_string(&body))
+}
+
+/// `code`, `kind`, `message_id`, and `message_args` of error `e`. A message without
+/// a template of its own has its `kind` as `message_id` and its text as `message`.
+fn error_details(e: &RuntimeError) -> Vec<(&'static str, Value)> {
+    let code = e.code();
+    let message = e.localized();
+    vec![
+        ("code", Value::String(code.code.to_string())),
+        ("kind", Value::String(code.kind.to_string())),
+        ("message_id", Value::String(message.id.clone())),
+        (
+            "message_args",
+            Value::Object(
+                message
+                    .args
+                    .iter()
+                    .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+                    .collect(),
+            ),
+        ),
+    ]
+}
 
+fn localized(e: &RuntimeError) -> String {
+    LOCALE.with(|locale| e.localize(&locale.borrow()))
+}
+
+/// Graphviz DOT dataflow diagram: `{ ok, diagram, diagnostics }`.
+pub fn to_dot(program: String) -> JsValue {
+    diagram_response(dsl_runtime::to_dot(&program))
+}
+
+/// Mermaid flowchart dataflow diagram: `{ ok, diagram, diagnostics }`.
+pub fn to_mermaid(program: String) -> JsValue {
+    diagram_response(dsl_runtime::to_mermaid(&program))
+}
+
+fn diagram_response(result: Result<String, String>) -> JsValue {
+    let (ok, diagram, diagnostics) = match result {
+        Ok(diagram) => (true, diagram, String::new()),
+        Err(e) => (false, String::new(), e),
+    };
     JsValue::from_json_string(json_string(&object(vec![
         ("ok", Value::Bool(ok)),
+        ("diagram", Value::String(diagram)),
         ("diagnostics", Value::String(diagnostics)),
     ])))
 }
 
-pub fn run(program: String, fixtures_json: String) -> JsValue {
-    let fixtures = match serde_json::from_str(&fixtures_json) {
+/// Runs both programs on `fixtures_json` and compares their outputs:
+/// `{ ok, diff: {tables, logs}, diagnostics }` (see [`dsl_runtime::OutputsDiff`]).
+/// `diff` is `null` when either run fails.
+pub fn run_and_diff(program_a: String, program_b: String, fixtures_json: String) -> JsValue {
+    let result = serde_json::from_str(&fixtures_json)
+        .map_err(|e| RuntimeError::from(format!("invalid fixtures_json: {e}")))
+        .and_then(|fixtures: Value| {
+            let a = dsl_runtime::run(&program_a, fixtures.clone())
+                .map_err(|e| format!("program a: {e}"))?;
+            let b =
+                dsl_runtime::run(&program_b, fixtures).map_err(|e| format!("program b: {e}"))?;
+            Ok(dsl_runtime::diff_outputs(&a, &b))
+        });
+    let (diff, diagnostics) = match result {
+        Ok(diff) => (diff.to_json(), String::new()),
+        Err(e) => (Value::Null, e.into()),
+    };
+    JsValue::from_json_string(json_string(&object(vec![
+        ("ok", Value::Bool(diagnostics.is_empty())),
+        ("diff", diff),
+        ("diagnostics", Value::String(diagnostics)),
+    ])))
+}
+
+/// Runs every program in `programs_json` (an object of name → source) on the same
+/// fixtures with shared `kv.load` stores and `rbac.load` policies (see
+/// [`dsl_runtime::run_workspace`]): `{ ok, programs, diagnostics }`, where
+/// `programs` holds each program's [`run`] body by name and is empty on failure.
+pub fn run_workspace(programs_json: String, fixtures_json: String, pretty: bool) -> JsValue {
+    let result = serde_json::from_str(&programs_json)
+        .map_err(|e| format!("invalid programs_json: {e}"))
+        .and_then(|programs: Value| match programs {
+            Value::Object(programs) => Ok(programs),
+            _ => Err("programs_json must be a JSON object".to_string()),
+        })
+        .and_then(|programs| {
+            let fixtures: Value = serde_json::from_str(&fixtures_json)
+                .map_err(|e| format!("invalid fixtures_json: {e}"))?;
+            Ok((programs, fixtures))
+        })
+        .map_err(RuntimeError::from)
+        .and_then(|(programs, fixtures)| {
+            let mut sources = BTreeMap::new();
+            for (name, source) in &programs {
+                let Value::String(source) = source else {
+                    return Err(format!("program {name} must be a string").into());
+                };
+                sources.insert(name.clone(), source.as_str());
+            }
+            dsl_runtime::run_workspace(sources, fixtures)
+        });
+    let (outputs, diagnostics) = match result {
+        Ok(outputs) => {
+            let bodies = outputs
+                .into_iter()
+                .map(|(name, out)| (name, run_body(Ok(out), pretty)))
+                .collect();
+            (Value::Object(bodies), String::new())
+        }
+        Err(e) => (Value::Object(Map::new()), e.into()),
+    };
+    JsValue::from_json_string(json_string(&object(vec![
+        ("ok", Value::Bool(diagnostics.is_empty())),
+        ("programs", outputs),
+        ("diagnostics", Value::String(diagnostics)),
+    ])))
+}
+
+/// Compiles `program` once and caches it: `{ ok, handle, diagnostics }`. `handle` is
+/// `null` when compilation fails.
+pub fn compile_handle(program: String) -> JsValue {
+    let (handle, diagnostics) = match dsl_runtime::compile(&program) {
+        Ok(compiled) => {
+            let id = PROGRAM_CACHE.with(|cache| cache.borrow_mut().insert(compiled));
+            (Value::Number((id as i64).into()), String::new())
+        }
+        Err(e) => (Value::Null, e.into()),
+    };
+
+    JsValue::from_json_string(json_string(&object(vec![
+        ("ok", Value::Bool(!matches!(handle, Value::Null))),
+        ("handle", handle),
+        ("diagnostics", Value::String(diagnostics)),
+    ])))
+}
+
+/// Runs a program cached by [`compile_handle`]; the envelope matches [`run`].
+pub fn run_handle(handle: u32, fixtures_json: String) -> JsValue {
+    let fixtures = match parse_fixtures_json(&fixtures_json) {
         Ok(value) => value,
-        Err(e) => {
-            return JsValue::from_json_string(json_string(&object(vec![
-                ("tables_json", Value::String("{}".to_string())),
-                ("logs_json", Value::String("{}".to_string())),
+        Err(body) => return JsValue::from_json_string(json_string(&body)),
+    };
+    let result = PROGRAM_CACHE.with(|cache| match cache.borrow_mut().touch(handle) {
+        Some(program) => dsl_runtime::run_compiled(program, fixtures),
+        None => Err(format!("unknown program handle: {handle}").into()),
+    });
+    JsValue::from_json_string(json_string(&run_body(result, false)))
+}
+
+/// Runs statement `index` of `program`, reusing the bindings earlier calls computed
+/// (see [`dsl_runtime::run_statement`]); the envelope matches [`run_handle`].
+pub fn run_statement(program: String, index: u32, fixtures_json: String) -> JsValue {
+    let fixtures = match parse_fixtures_json(&fixtures_json) {
+        Ok(value) => value,
+        Err(body) => return JsValue::from_json_string(json_string(&body)),
+    };
+    let result = SESSION.with(|slot| {
+        let mut slot = slot.borrow_mut();
+        let mut session = match slot.take() {
+            Some(session) => session,
+            None => dsl_runtime::Session::new(fixtures.clone())?,
+        };
+        let result = dsl_runtime::run_statement(&program, index as usize, fixtures, &mut session);
+        *slot = Some(session);
+        result
+    });
+    JsValue::from_json_string(json_string(&run_body(result, false)))
+}
+
+/// The [`run_statement`] session as `{ ok, session, diagnostics }`, where `session`
+/// is [`dsl_runtime::Session::serialize`] output, or `null` before the first
+/// `run_statement`. Hand it back to [`restore_session`] after a page reload.
+pub fn save_session() -> JsValue {
+    let session = SESSION.with(|slot| {
+        slot.borrow()
+            .as_ref()
+            .map_or(Value::Null, dsl_runtime::Session::serialize)
+    });
+    JsValue::from_json_string(json_string(&object(vec![
+        ("ok", Value::Bool(true)),
+        ("session", session),
+        ("diagnostics", Value::String(String::new())),
+    ])))
+}
+
+/// Replaces the [`run_statement`] session with one saved by [`save_session`]:
+/// `{ ok, diagnostics }`. The current session is kept when `session_json` is invalid.
+pub fn restore_session(session_json: String) -> JsValue {
+    let result = serde_json::from_str(&session_json)
+        .map_err(|e| RuntimeError::from(format!("invalid session_json: {e}")))
+        .and_then(dsl_runtime::Session::restore);
+    let diagnostics = match result {
+        Ok(session) => {
+            SESSION.with(|slot| *slot.borrow_mut() = Some(session));
+            String::new()
+        }
+        Err(e) => e.into(),
+    };
+    JsValue::from_json_string(json_string(&object(vec![
+        ("ok", Value::Bool(diagnostics.is_empty())),
+        ("diagnostics", Value::String(diagnostics)),
+    ])))
+}
+
+/// Drops a cached program. Returns `false` if the handle was unknown or already evicted.
+pub fn invalidate(handle: u32) -> bool {
+    PROGRAM_CACHE.with(|cache| cache.borrow_mut().remove(handle))
+}
+
+/// Runs `program` against `fixtures_json`.
+///
+/// When `pretty` is set, `tables_json` and `logs_json` are indented for display;
+/// the outer envelope stays compact either way. `schemas` maps each table to its
+/// inferred `[{name, type, nullable}]` columns, and `log_records_json` holds the
+/// logs as `{name: [{level, message, item}]}`; programs with `ui.graph` add
+/// `graphs_json` (`{name: {nodes: [{id, item}], edges: [{from, to}]}}`). Successful runs are retained: the
+/// envelope gains `run_id` and `row_counts` (`{table: rows}`), and
+/// [`get_table_page`] reads rows back without reserializing whole tables.
+///
+/// Repeating a recent call with the same program, fixtures, and `pretty` returns
+/// the earlier envelope with `cached: true` instead of running again.
+pub fn run(program: String, fixtures_json: String, pretty: bool) -> JsValue {
+    let key = RunKey {
+        entry: "run",
+        program: program.clone(),
+        fixtures_json: fixtures_json.clone(),
+        extra: None,
+        pretty,
+    };
+    let body = cached_run(key, || {
+        let fixtures = match parse_fixtures_json(&fixtures_json) {
+            Ok(value) => value,
+            Err(body) => return CachedRun { body, tables: None },
+        };
+        let result = dsl_runtime::run(&program, fixtures);
+        let tables = result.as_ref().ok().map(|out| Rc::new(out.tables.clone()));
+        CachedRun {
+            body: run_body(result, pretty),
+            tables,
+        }
+    });
+    JsValue::from_json_string(json_string(&body))
+}
+
+/// Up to `limit` rows of `table` from a retained [`run`], starting at `offset`:
+/// `{ ok, rows, offset, total, diagnostics }`. Runs are evicted after
+/// [`RUN_STORE_CAPACITY`] newer ones or by [`release_run`].
+pub fn get_table_page(run_id: u32, table: String, offset: u32, limit: u32) -> JsValue {
+    let page = RUN_STORE
+        .with(|store| store.borrow().get(run_id))
+        .map_or_else(
+            || Err(format!("unknown run id: {run_id}")),
+            |tables| match tables.get(&table) {
+                Some(rows) => {
+                    let start = (offset as usize).min(rows.len());
+                    let end = start.saturating_add(limit as usize).min(rows.len());
+                    Ok((rows[start..end].to_vec(), rows.len()))
+                }
+                None => Err(format!("unknown table: {table}")),
+            },
+        );
+    let (rows, total, diagnostics) = match page {
+        Ok((rows, total)) => (rows, total, String::new()),
+        Err(e) => (Vec::new(), 0, e),
+    };
+    JsValue::from_json_string(json_string(&object(vec![
+        ("ok", Value::Bool(diagnostics.is_empty())),
+        ("rows", Value::Array(rows)),
+        ("offset", Value::Number(i64::from(offset).into())),
+        ("total", Value::Number((total as i64).into())),
+        ("diagnostics", Value::String(diagnostics)),
+    ])))
+}
+
+/// Frees a retained run. Returns `false` if the id was unknown or already evicted.
+pub fn release_run(run_id: u32) -> bool {
+    RUN_STORE.with(|store| store.borrow_mut().remove(run_id))
+}
+
+/// Like [`run`], with `$name` placeholders filled from `params_json` (an object).
+/// Identical repeat calls come from the result cache, as for [`run`].
+pub fn run_with_params(
+    program: String,
+    fixtures_json: String,
+    params_json: String,
+    pretty: bool,
+) -> JsValue {
+    let key = RunKey {
+        entry: "run_with_params",
+        program: program.clone(),
+        fixtures_json: fixtures_json.clone(),
+        extra: Some(params_json.clone()),
+        pretty,
+    };
+    let body = cached_run(key, || {
+        let body = match (
+            parse_fixtures_json(&fixtures_json),
+            serde_json::from_str(&params_json),
+        ) {
+            (Err(body), _) => body,
+            (_, Err(e)) => error_body(format!("error: invalid params_json: {e}")),
+            (Ok(fixtures), Ok(params)) => run_body(
+                dsl_runtime::run_with_params(&program, fixtures, &params),
+                pretty,
+            ),
+        };
+        CachedRun { body, tables: None }
+    });
+    JsValue::from_json_string(json_string(&body))
+}
+
+/// Like [`run`], configured by `options_json`:
+/// `{ params, constants, explain: "quiet" | "normal" | "verbose", explain_items, audit }`
+/// (all optional; see `dsl_runtime::RunOptions::from_json`, which also takes
+/// `track_memory`, `memory_limit`, `columnar`, `strict`, `rebinding`, and `coverage`). With `audit: true` the envelope
+/// gains `fingerprint`; with memory tracking, `peak_stream_bytes`; with `coverage:
+/// true`, `coverage` (`[{kind, text, items, line, column}]`); with `columnar:
+/// true`, `tables_columnar_json` (`{name: {row_count, columns: [{name, type,
+/// values}]}}`) replaces the rows in `tables_json`. Identical repeat calls come
+/// from the result cache, as for [`run`].
+pub fn run_with_options(
+    program: String,
+    fixtures_json: String,
+    options_json: String,
+    pretty: bool,
+) -> JsValue {
+    let key = RunKey {
+        entry: "run_with_options",
+        program: program.clone(),
+        fixtures_json: fixtures_json.clone(),
+        extra: Some(options_json.clone()),
+        pretty,
+    };
+    let body = cached_run(key, || {
+        let fixtures = match parse_fixtures_json(&fixtures_json) {
+            Ok(value) => value,
+            Err(body) => return CachedRun { body, tables: None },
+        };
+        let options = serde_json::from_str(&options_json)
+            .map_err(|e| format!("invalid options_json: {e}"))
+            .and_then(|options| dsl_runtime::RunOptions::from_json(&options));
+        let body = match options {
+            Ok(options) => run_body(
+                dsl_runtime::run_with_options(&program, fixtures, &options),
+                pretty,
+            ),
+            Err(e) => error_body(format!("error: {e}")),
+        };
+        CachedRun { body, tables: None }
+    });
+    JsValue::from_json_string(json_string(&body))
+}
+
+/// The `$name` params `program` references: `{ ok, params: [name...], diagnostics }`.
+pub fn param_names(program: String) -> JsValue {
+    let (params, diagnostics) = match dsl_runtime::compile(&program) {
+        Ok(compiled) => (dsl_runtime::param_names(&compiled), String::new()),
+        Err(e) => (Vec::new(), e.into()),
+    };
+    JsValue::from_json_string(json_string(&object(vec![
+        ("ok", Value::Bool(diagnostics.is_empty())),
+        (
+            "params",
+            Value::Array(params.into_iter().map(Value::String).collect()),
+        ),
+        ("diagnostics", Value::String(diagnostics)),
+    ])))
+}
+
+/// What this build offers: `{ features: [name...], stages: [name...], sources: [name...] }`,
+/// where `features` lists the optional runtime features compiled in.
+pub fn capabilities() -> JsValue {
+    let capabilities = dsl_runtime::capabilities();
+    let strings = |names: Vec<String>| Value::Array(names.into_iter().map(Value::String).collect());
+    JsValue::from_json_string(json_string(&object(vec![
+        ("features", strings(capabilities.features)),
+        ("stages", strings(capabilities.stages)),
+        ("sources", strings(capabilities.sources)),
+    ])))
+}
+
+/// Like [`run`], but registers `token` so a concurrent [`cancel`] call can stop the
+/// run. The envelope gains `status`: `"ok"`, `"cancelled"` (partial outputs), or
+/// `"error"`.
+pub fn run_cancellable(program: String, fixtures_json: String, token: u32) -> JsValue {
+    let cancel_token = dsl_runtime::CancelToken::new();
+    CANCEL_TOKENS.with(|tokens| tokens.borrow_mut().insert(token, cancel_token.clone()));
+    let (status, mut body) = match parse_fixtures_json(&fixtures_json) {
+        Ok(fixtures) => match dsl_runtime::run_cancellable(&program, fixtures, &cancel_token) {
+            Ok(out) if out.cancelled => ("cancelled", run_body(Ok(out), false)),
+            Ok(out) => ("ok", run_body(Ok(out), false)),
+            Err(e) => ("error", run_body(Err(e), false)),
+        },
+        Err(body) => ("error", body),
+    };
+    CANCEL_TOKENS.with(|tokens| tokens.borrow_mut().remove(&token));
+
+    if let Value::Object(map) = &mut body {
+        map.insert("status".to_string(), Value::String(status.to_string()));
+    }
+    JsValue::from_json_string(json_string(&body))
+}
+
+/// Like [`run_cancellable`], but calls `on_yield(points)` at every `interval`-th
+/// yield point (see [`dsl_runtime::run_with_yield`]) so the host can update its UI
+/// or check a deadline; returning `false` stops the run with status `"cancelled"`.
+pub fn run_with_yield(
+    program: String,
+    fixtures_json: String,
+    interval: u32,
+    mut on_yield: impl FnMut(u32) -> bool,
+) -> JsValue {
+    let mut hook = |points: u64| on_yield(u32::try_from(points).unwrap_or(u32::MAX));
+    let (status, mut body) = match parse_fixtures_json(&fixtures_json) {
+        Ok(fixtures) => {
+            match dsl_runtime::run_with_yield(&program, fixtures, interval.into(), &mut hook) {
+                Ok(out) if out.cancelled => ("cancelled", run_body(Ok(out), false)),
+                Ok(out) => ("ok", run_body(Ok(out), false)),
+                Err(e) => ("error", run_body(Err(e), false)),
+            }
+        }
+        Err(body) => ("error", body),
+    };
+    if let Value::Object(map) = &mut body {
+        map.insert("status".to_string(), Value::String(status.to_string()));
+    }
+    JsValue::from_json_string(json_string(&body))
+}
+
+/// Requests cancellation of the run registered under `token`; a no-op when no run
+/// with that token is in progress.
+pub fn cancel(token: u32) {
+    CANCEL_TOKENS.with(|tokens| {
+        if let Some(cancel_token) = tokens.borrow().get(&token) {
+            cancel_token.cancel();
+        }
+    });
+}
+
+/// Runs `program` and invokes `on_row(table_name, row_json)` for every `ui.table` row
+/// as it is produced. The returned envelope has the same shape as [`run`], with an
+/// empty `tables_json`.
+pub fn run_streaming(
+    program: String,
+    fixtures_json: String,
+    mut on_row: impl FnMut(String, String),
+) -> JsValue {
+    let fixtures = match parse_fixtures_json(&fixtures_json) {
+        Ok(value) => value,
+        Err(body) => return JsValue::from_json_string(json_string(&body)),
+    };
+    let result = dsl_runtime::run_streaming(&program, fixtures, |table, row| {
+        on_row(table.to_string(), json_string(&row))
+    });
+    JsValue::from_json_string(json_string(&run_body(result, false)))
+}
+
+/// Like [`run`], but returns one nested object instead of JSON-in-JSON strings:
+/// `{ ok, tables: {name: [row...]}, logs: {name: [line...]}, explain: [line...] }`.
+/// Failures set `ok: false` and carry the message in `error` (in the [`set_locale`]
+/// locale), its catalog `code` and `kind`, its `message_id` and `message_args`, and
+/// the names it suggests for a misspelled stage or identifier in `suggestions`.
+pub fn run_v2(program: String, fixtures_json: String) -> JsValue {
+    let result = serde_json::from_str(&fixtures_json)
+        .map_err(|e| RuntimeError::from(format!("invalid fixtures_json: {e}")))
+        .and_then(|fixtures| dsl_runtime::run(&program, fixtures));
+    JsValue::from_json_string(json_string(&run_v2_body(result)))
+}
+
+/// Runs `program` with host functions for `host.call("name", arg)`.
+///
+/// `host_json` maps each function name to the results known so far, keyed by
+/// `arg_json`: `{ "fetch_user": { "42": {...} } }`. Calls without a known result
+/// are collected and the run reports `status: "pending"` with
+/// `pending: [{ name, arg_json }]`; an async host resolves those and calls again.
+/// Otherwise the envelope is [`run_v2`]'s plus `status` (`"ok"` or `"error"`).
+pub fn run_with_host(program: String, fixtures_json: String, host_json: String) -> JsValue {
+    let pending: Rc<RefCell<Vec<(String, String)>>> = Rc::default();
+    let result = serde_json::from_str(&fixtures_json)
+        .map_err(|e| RuntimeError::from(format!("invalid fixtures_json: {e}")))
+        .and_then(|fixtures| {
+            let runtime = host_runtime(&host_json, &pending)?;
+            runtime.run(&program, fixtures)
+        });
+
+    let pending = pending.take();
+    let (status, mut body) = if !pending.is_empty() {
+        let calls = pending
+            .into_iter()
+            .map(|(name, arg_json)| {
+                object(vec![
+                    ("name", Value::String(name)),
+                    ("arg_json", Value::String(arg_json)),
+                ])
+            })
+            .collect();
+        let mut body = run_v2_body(Err("waiting on host calls".to_string().into()));
+        if let Value::Object(map) = &mut body {
+            map.insert("pending".to_string(), Value::Array(calls));
+        }
+        ("pending", body)
+    } else {
+        let status = if result.is_ok() { "ok" } else { "error" };
+        (status, run_v2_body(result))
+    };
+    if let Value::Object(map) = &mut body {
+        map.insert("status".to_string(), Value::String(status.to_string()));
+    }
+    JsValue::from_json_string(json_string(&body))
+}
+
+/// Registers one host function per `host_json` entry. Unknown arguments record a
+/// pending call and evaluate to null so the run can discover every call at once.
+fn host_runtime(
+    host_json: &str,
+    pending: &Rc<RefCell<Vec<(String, String)>>>,
+) -> Result<dsl_runtime::Runtime, String> {
+    let Ok(Value::Object(host)) = serde_json::from_str(host_json) else {
+        return Err("host_json must be an object of {name: {arg_json: result}}".to_string());
+    };
+    let mut runtime = dsl_runtime::Runtime::new();
+    for (name, results) in host {
+        let Value::Object(results) = results else {
+            return Err(format!("host results for {name} must be an object"));
+        };
+        let pending = Rc::clone(pending);
+        let fn_name = name.clone();
+        runtime.register_host_fn(&name, move |arg| {
+            let arg_json = json_string(&arg.to_json());
+            if let Some(result) = results.get(&arg_json) {
+                return Ok(dsl_runtime::Value::from_json(result.clone()));
+            }
+            let call = (fn_name.clone(), arg_json);
+            let mut pending = pending.borrow_mut();
+            if !pending.contains(&call) {
+                pending.push(call);
+            }
+            Ok(dsl_runtime::Value::Null)
+        });
+    }
+    Ok(runtime)
+}
+
+fn run_v2_body(result: Result<dsl_runtime::Outputs, RuntimeError>) -> Value {
+    match result {
+        Ok(out) => {
+            let tables = out
+                .tables
+                .into_iter()
+                .map(|(name, rows)| (name, Value::Array(rows)))
+                .collect();
+            let logs = out
+                .logs
+                .into_iter()
+                .map(|(name, records)| (name, log_lines(&records)))
+                .collect();
+            object(vec![
+                ("ok", Value::Bool(true)),
+                ("tables", Value::Object(tables)),
+                ("logs", Value::Object(logs)),
                 (
                     "explain",
-                    Value::String(format!("error: invalid fixtures_json: {e}")),
+                    Value::Array(out.explain.into_iter().map(Value::String).collect()),
                 ),
-            ])));
+            ])
         }
-    };
+        Err(e) => {
+            let mut entries = vec![("ok", Value::Bool(false))];
+            entries.extend(error_details(&e));
+            entries.extend([
+                (
+                    "span",
+                    e.span().map_or(Value::Null, |span| {
+                        object(vec![
+                            ("start", Value::Number((span.start as i64).into())),
+                            ("end", Value::Number((span.end as i64).into())),
+                        ])
+                    }),
+                ),
+                (
+                    "suggestions",
+                    Value::Array(
+                        dsl_runtime::suggestions(e.message())
+                            .into_iter()
+                            .map(Value::String)
+                            .collect(),
+                    ),
+                ),
+                ("error", Value::String(localized(&e))),
+                ("tables", Value::Object(Map::new())),
+                ("logs", Value::Object(Map::new())),
+                ("explain", Value::Array(Vec::new())),
+            ]);
+            object(entries)
+        }
+    }
+}
+
+fn parse_fixtures_json(fixtures_json: &str) -> Result<Value, Value> {
+    serde_json::from_str(fixtures_json)
+        .map_err(|e| error_body(format!("error: invalid fixtures_json: {e}")))
+}
+
+fn error_body(explain: String) -> Value {
+    object(vec![
+        ("tables_json", Value::String("{}".to_string())),
+        ("logs_json", Value::String("{}".to_string())),
+        ("explain", Value::String(explain)),
+    ])
+}
 
-    match dsl_runtime::run(&program, fixtures) {
+/// v0 log lines: the JSON item, prefixed with `[level] ` unless it is `info`.
+fn log_lines(records: &[dsl_runtime::LogRecord]) -> Value {
+    Value::Array(
+        records
+            .iter()
+            .map(|record| Value::String(record.line()))
+            .collect(),
+    )
+}
+
+fn run_body(result: Result<dsl_runtime::Outputs, RuntimeError>, pretty: bool) -> Value {
+    match result {
         Ok(out) => {
+            let schemas: Map = out
+                .schemas
+                .iter()
+                .map(|(name, columns)| {
+                    let columns = columns.iter().map(|column| column.to_json()).collect();
+                    (name.clone(), Value::Array(columns))
+                })
+                .collect();
             let mut table_obj: Map = Map::new();
             for (name, rows) in out.tables {
                 table_obj.insert(name, Value::Array(rows));
             }
-            let tables_json = json_string(&Value::Object(table_obj));
+            let tables_json = output_json_string(&Value::Object(table_obj), pretty);
 
             let mut log_obj: Map = Map::new();
-            for (name, rows) in out.logs {
-                log_obj.insert(
+            let mut log_records: Map = Map::new();
+            for (name, records) in out.logs {
+                log_obj.insert(name.clone(), log_lines(&records));
+                log_records.insert(
                     name,
-                    Value::Array(rows.into_iter().map(Value::String).collect()),
+                    Value::Array(records.iter().map(|record| record.to_json()).collect()),
                 );
             }
-            let logs_json = json_string(&Value::Object(log_obj));
+            let logs_json = output_json_string(&Value::Object(log_obj), pretty);
+            let log_records_json = output_json_string(&Value::Object(log_records), pretty);
 
-            JsValue::from_json_string(json_string(&object(vec![
+            let mut body = vec![
                 ("tables_json", Value::String(tables_json)),
                 ("logs_json", Value::String(logs_json)),
+                ("log_records_json", Value::String(log_records_json)),
                 ("explain", Value::String(out.explain.join("\n"))),
-            ])))
+                ("schemas", Value::Object(schemas)),
+            ];
+            if let Some(fingerprint) = out.fingerprint {
+                body.push(("fingerprint", Value::String(fingerprint)));
+            }
+            if !out.warnings.is_empty() {
+                let warnings = out
+                    .warnings
+                    .iter()
+                    .map(|warning| Value::String(warning.to_string()))
+                    .collect();
+                body.push(("warnings", Value::Array(warnings)));
+                let records = out
+                    .warnings
+                    .iter()
+                    .map(dsl_runtime::Warning::to_json)
+                    .collect();
+                body.push(("warning_records", Value::Array(records)));
+            }
+            if let Some(coverage) = &out.coverage {
+                let records = coverage
+                    .iter()
+                    .map(dsl_runtime::CoverageEntry::to_json)
+                    .collect();
+                body.push(("coverage", Value::Array(records)));
+            }
+            if let Some(peak) = out.peak_stream_bytes {
+                body.push(("peak_stream_bytes", Value::Number((peak as i64).into())));
+            }
+            if !out.graphs.is_empty() {
+                let graphs: Map = out
+                    .graphs
+                    .iter()
+                    .map(|(name, graph)| (name.clone(), graph.to_json()))
+                    .collect();
+                body.push((
+                    "graphs_json",
+                    Value::String(output_json_string(&Value::Object(graphs), pretty)),
+                ));
+            }
+            if let Some(columnar) = out.tables_columnar {
+                let columnar_obj: Map = columnar
+                    .into_iter()
+                    .map(|(name, table)| (name, table.to_json()))
+                    .collect();
+                body.push((
+                    "tables_columnar_json",
+                    Value::String(output_json_string(&Value::Object(columnar_obj), pretty)),
+                ));
+            }
+            object(body)
         }
-        Err(e) => JsValue::from_json_string(json_string(&object(vec![
-            ("tables_json", Value::String("{}".to_string())),
-            ("logs_json", Value::String("{}".to_string())),
-            ("explain", Value::String(format!("error: {e}"))),
-        ]))),
+        Err(e) => error_body(format!("error: {e}")),
     }
 }
 
@@ -112,6 +985,28 @@ mod tests {
         assert!(!diagnostics.is_empty());
     }
 
+    #[test]
+    fn capabilities_list_compiled_features_and_stages() {
+        let text = super::capabilities()
+            .as_string()
+            .expect("capabilities should return string JsValue");
+        let body: Value = serde_json::from_str(&text).expect("valid json object");
+
+        let names = |key: &str| match get_field(&body, key) {
+            Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::String(name) => name.clone(),
+                    _ => panic!("names should be strings"),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("{key} should be an array"),
+        };
+        assert_eq!(names("features"), dsl_runtime::capabilities().features);
+        assert!(names("stages").contains(&"map".to_string()));
+        assert!(names("sources").contains(&"input.json".to_string()));
+    }
+
     #[test]
     fn run_returns_output_json_strings() {
         let program = r#"
@@ -119,7 +1014,7 @@ xs := input.json("xs") |> json;
 xs |> map(_ + 1) |> ui.table("out");
 "#;
 
-        let out = super::run(program.to_string(), "{\"xs\": [1, 2]}".to_string());
+        let out = super::run(program.to_string(), "{\"xs\": [1, 2]}".to_string(), false);
         let text = out.as_string().expect("run should return string JsValue");
         let body: Value = serde_json::from_str(&text).expect("valid json object");
 
@@ -130,6 +1025,10 @@ xs |> map(_ + 1) |> ui.table("out");
         let tables: Value =
             serde_json::from_str(tables_text).expect("tables_json should be valid json");
         assert_eq!(get_field(&tables, "out"), &serde_json::json!([2, 3]));
+        assert_eq!(
+            get_field(&body, "schemas"),
+            &serde_json::json!({"out": [{"name": "value", "type": "number", "nullable": false}]})
+        );
         match get_field(&body, "logs_json") {
             Value::String(_) => {}
             _ => panic!("logs_json should be string"),
@@ -139,4 +1038,660 @@ xs |> map(_ + 1) |> ui.table("out");
             _ => panic!("explain should be string"),
         }
     }
+
+    #[test]
+    fn run_statement_runs_one_statement_with_its_dependencies() {
+        let program = r#"xs := input.json("xs") |> json;
+xs |> ui.table("all");
+xs |> map(_ + 1) |> ui.table("out");
+"#;
+        let out = super::run_statement(program.to_string(), 2, "{\"xs\": [1]}".to_string());
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        let Value::String(tables_text) = get_field(&body, "tables_json") else {
+            panic!("tables_json should be string");
+        };
+        let tables: Value = serde_json::from_str(tables_text).expect("valid tables json");
+        assert_eq!(tables, serde_json::json!({"out": [2]}));
+    }
+
+    #[test]
+    fn save_and_restore_session_keep_run_statement_bindings() {
+        let body: Value =
+            serde_json::from_str(&super::save_session().as_string().expect("string JsValue"))
+                .expect("valid json object");
+        assert_eq!(get_field(&body, "session"), &Value::Null);
+
+        let program = "xs := input.json(\"xs\") |> json;\nxs |> ui.table(\"out\");";
+        super::run_statement(program.to_string(), 0, "{\"xs\": [1]}".to_string());
+        let body: Value =
+            serde_json::from_str(&super::save_session().as_string().expect("string JsValue"))
+                .expect("valid json object");
+        let saved = serde_json::to_string(get_field(&body, "session")).expect("session json");
+
+        super::SESSION.with(|slot| *slot.borrow_mut() = None);
+        let body: Value =
+            serde_json::from_str(&super::restore_session(saved).as_string().expect("string"))
+                .expect("valid json object");
+        assert_eq!(get_field(&body, "ok"), &Value::Bool(true));
+        let out = super::run_statement(program.to_string(), 1, "{\"xs\": [1]}".to_string());
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        let Value::String(explain) = get_field(&body, "explain") else {
+            panic!("explain should be string");
+        };
+        assert!(!explain.contains("binding xs"), "{explain}");
+
+        let body: Value = serde_json::from_str(
+            &super::restore_session("[]".to_string())
+                .as_string()
+                .expect("string JsValue"),
+        )
+        .expect("valid json object");
+        assert_eq!(get_field(&body, "ok"), &Value::Bool(false));
+    }
+
+    #[test]
+    fn run_and_diff_compares_two_programs() {
+        let out = super::run_and_diff(
+            r#"input.json("xs") |> json |> ui.table("out");"#.to_string(),
+            r#"input.json("xs") |> json |> filter(_ > 1) |> ui.table("out");"#.to_string(),
+            "{\"xs\": [1, 2]}".to_string(),
+        );
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(get_field(&body, "ok"), &Value::Bool(true));
+        assert_eq!(
+            get_field(&body, "diff"),
+            &serde_json::json!({"tables": {"out": [
+                {"op": "changed", "key": 0, "expected": 1, "actual": 2},
+                {"op": "removed", "key": 1, "expected": 2}
+            ]}, "logs": {}})
+        );
+
+        let out = super::run_and_diff("nope;".to_string(), String::new(), "{}".to_string());
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(get_field(&body, "diff"), &Value::Null);
+        let Value::String(diagnostics) = get_field(&body, "diagnostics") else {
+            panic!("diagnostics should be string");
+        };
+        assert!(diagnostics.starts_with("program a: "), "{diagnostics}");
+    }
+
+    #[test]
+    fn run_workspace_shares_kv_stores_between_programs() {
+        let programs = serde_json::json!({
+            "load": "input.json(\"users\") |> json |> kv.load(store=\"users\");",
+            "use": "input.json(\"ids\") |> json |> lookup.kv(store=\"users\", key=_) |> ui.table(\"out\");"
+        });
+        let out = super::run_workspace(
+            serde_json::to_string(&programs).expect("programs serialize"),
+            r#"{"users": [{"key": "u1", "value": "Ada"}], "ids": ["u1"]}"#.to_string(),
+            false,
+        );
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(get_field(&body, "ok"), &Value::Bool(true));
+        let programs = get_field(&body, "programs");
+        let tables: Value = match get_field(get_field(programs, "use"), "tables_json") {
+            Value::String(tables) => serde_json::from_str(tables).expect("tables_json is json"),
+            other => panic!("tables_json should be a string: {other:?}"),
+        };
+        assert_eq!(
+            tables,
+            serde_json::json!({"out": [{"left": "u1", "right": "Ada"}]})
+        );
+
+        let out = super::run_workspace(r#"{"a": "nope;"}"#.to_string(), "{}".to_string(), false);
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(get_field(&body, "programs"), &serde_json::json!({}));
+        let Value::String(diagnostics) = get_field(&body, "diagnostics") else {
+            panic!("diagnostics should be string");
+        };
+        assert!(diagnostics.starts_with("a: "), "{diagnostics}");
+    }
+
+    #[test]
+    fn run_with_params_fills_placeholders() {
+        let program = r#"input.json("xs") |> json |> filter(_ > $min) |> ui.table("out");"#;
+        let names = super::param_names(program.to_string());
+        let body: Value = serde_json::from_str(&names.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(get_field(&body, "params"), &serde_json::json!(["min"]));
+
+        let out = super::run_with_params(
+            program.to_string(),
+            "{\"xs\": [1, 2, 3]}".to_string(),
+            "{\"min\": 1}".to_string(),
+            false,
+        );
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        let Value::String(tables_text) = get_field(&body, "tables_json") else {
+            panic!("tables_json should be string");
+        };
+        let tables: Value = serde_json::from_str(tables_text).expect("valid tables json");
+        assert_eq!(get_field(&tables, "out"), &serde_json::json!([2, 3]));
+    }
+
+    #[test]
+    fn run_with_options_applies_explain_level() {
+        let program = r#"input.json("xs") |> json |> ui.table("out");"#;
+        let explain = |options: &str| {
+            let out = super::run_with_options(
+                program.to_string(),
+                "{\"xs\": [1, 2]}".to_string(),
+                options.to_string(),
+                false,
+            );
+            let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+                .expect("valid json object");
+            match get_field(&body, "explain") {
+                Value::String(text) => text.clone(),
+                _ => panic!("explain should be string"),
+            }
+        };
+        assert_eq!(explain(r#"{"explain": "quiet"}"#), "");
+        assert!(explain("{}").contains("[sink] ui.table(out)"));
+        assert!(explain(r#"{"explain": "verbose", "explain_items": 1}"#).contains("... 1 more"));
+        assert!(explain(r#"{"explain": 1}"#).starts_with("error: explain must be a string"));
+
+        let out = super::run_with_options(
+            program.to_string(),
+            "{\"xs\": [1]}".to_string(),
+            r#"{"audit": true}"#.to_string(),
+            false,
+        );
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert!(matches!(get_field(&body, "fingerprint"), Value::String(f) if f.len() == 16));
+
+        let out = super::run_with_options(
+            program.to_string(),
+            "{\"xs\": [1]}".to_string(),
+            r#"{"track_memory": true}"#.to_string(),
+            false,
+        );
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(
+            get_field(&body, "peak_stream_bytes"),
+            &serde_json::json!(17)
+        );
+
+        let out = super::run_with_options(
+            program.to_string(),
+            "{\"xs\": [1, 2]}".to_string(),
+            r#"{"columnar": true}"#.to_string(),
+            false,
+        );
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(get_field(&body, "tables_json"), &serde_json::json!("{}"));
+        let Value::String(columnar) = get_field(&body, "tables_columnar_json") else {
+            panic!("tables_columnar_json should be string");
+        };
+        assert_eq!(
+            columnar,
+            r#"{"out":{"columns":[{"name":"value","type":"number","values":[1,2]}],"row_count":2}}"#
+        );
+    }
+
+    #[test]
+    fn run_pretty_indents_table_and_log_json() {
+        let program = r#"
+input.json("xs") |> json |> ui.table("out");
+input.json("xs") |> json |> ui.log("log");
+"#;
+
+        let out = super::run(program.to_string(), "{\"xs\": [1]}".to_string(), true);
+        let text = out.as_string().expect("run should return string JsValue");
+        let body: Value = serde_json::from_str(&text).expect("valid json object");
+
+        assert_eq!(
+            get_field(&body, "tables_json"),
+            &Value::String("{\n  \"out\": [\n    1\n  ]\n}".to_string())
+        );
+        assert_eq!(
+            get_field(&body, "logs_json"),
+            &Value::String("{\n  \"log\": [\n    \"1\"\n  ]\n}".to_string())
+        );
+    }
+
+    #[test]
+    fn run_streaming_invokes_callback_per_table_row() {
+        let program = r#"
+input.json("xs") |> json |> ui.table("a");
+input.json("xs") |> json |> map(_ + 10) |> ui.table("b");
+"#;
+
+        let mut rows = Vec::new();
+        let out = super::run_streaming(
+            program.to_string(),
+            "{\"xs\": [1, 2]}".to_string(),
+            |table, row| rows.push((table, row)),
+        );
+        let text = out.as_string().expect("run should return string JsValue");
+        let body: Value = serde_json::from_str(&text).expect("valid json object");
+
+        assert_eq!(
+            rows,
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("a".to_string(), "2".to_string()),
+                ("b".to_string(), "11".to_string()),
+                ("b".to_string(), "12".to_string()),
+            ]
+        );
+        assert_eq!(
+            get_field(&body, "tables_json"),
+            &Value::String("{}".to_string())
+        );
+    }
+
+    #[test]
+    fn run_v2_returns_nested_outputs() {
+        let program = r#"
+input.json("xs") |> json |> ui.table("out");
+"#;
+
+        let out = super::run_v2(program.to_string(), "{\"xs\": [{\"a\": 1}]}".to_string());
+        let text = out
+            .as_string()
+            .expect("run_v2 should return string JsValue");
+        let body: Value = serde_json::from_str(&text).expect("valid json object");
+
+        assert_eq!(get_field(&body, "ok"), &Value::Bool(true));
+        assert_eq!(
+            get_field(&body, "tables"),
+            &serde_json::json!({"out": [{"a": 1}]})
+        );
+        assert_eq!(get_field(&body, "logs"), &serde_json::json!({}));
+        match get_field(&body, "explain") {
+            Value::Array(lines) => assert!(!lines.is_empty()),
+            _ => panic!("explain should be an array"),
+        }
+    }
+
+    #[test]
+    fn run_v2_reports_errors_with_ok_false() {
+        let out = super::run_v2("x :=".to_string(), "{}".to_string());
+        let text = out
+            .as_string()
+            .expect("run_v2 should return string JsValue");
+        let body: Value = serde_json::from_str(&text).expect("valid json object");
+
+        assert_eq!(get_field(&body, "ok"), &Value::Bool(false));
+        match get_field(&body, "error") {
+            Value::String(message) => assert!(!message.is_empty()),
+            _ => panic!("error should be a string"),
+        }
+    }
+
+    #[test]
+    fn run_v2_suggests_names_for_unknown_stages() {
+        let program = r#"input.json("xs") |> json |> grup.count(by_key=_) |> ui.table("out");"#;
+        let out = super::run_v2(program.to_string(), "{\"xs\": []}".to_string());
+        let text = out
+            .as_string()
+            .expect("run_v2 should return string JsValue");
+        let body: Value = serde_json::from_str(&text).expect("valid json object");
+
+        assert_eq!(
+            get_field(&body, "suggestions"),
+            &serde_json::json!(["group.count"])
+        );
+        assert_eq!(
+            get_field(&body, "code"),
+            &Value::String("E0006".to_string())
+        );
+        assert_eq!(
+            get_field(&body, "kind"),
+            &Value::String("unknown_stage".to_string())
+        );
+    }
+
+    #[test]
+    fn set_locale_renders_errors_in_that_language() {
+        assert!(!super::set_locale("xx".to_string()));
+        assert!(super::set_locale("es".to_string()));
+        let out = super::run_v2("nope |> ui.table(\"out\");".to_string(), "{}".to_string());
+        assert!(super::set_locale("en".to_string()));
+        let text = out
+            .as_string()
+            .expect("run_v2 should return string JsValue");
+        let body: Value = serde_json::from_str(&text).expect("valid json object");
+
+        assert_eq!(
+            get_field(&body, "error"),
+            &Value::String("identificador desconocido nope en 1:1".to_string())
+        );
+        assert_eq!(
+            get_field(&body, "message_id"),
+            &Value::String("unknown_ident".to_string())
+        );
+        assert_eq!(
+            get_field(&body, "message_args"),
+            &serde_json::json!({"name": "nope"})
+        );
+
+        let out = super::run_v2(
+            "input.json(\"xs\") |> json |> map(_ + 1) |> ui.table(\"out\");".to_string(),
+            "{\"xs\": [9223372036854775807]}".to_string(),
+        );
+        let body: Value =
+            serde_json::from_str(&out.as_string().expect("string JsValue")).expect("valid json");
+        assert_eq!(get_field(&body, "message_id"), get_field(&body, "kind"));
+        assert_eq!(
+            get_field(&body, "message_args"),
+            &serde_json::json!({"message": "operator + overflowed"})
+        );
+    }
+
+    #[test]
+    fn compile_reports_error_codes() {
+        let out = super::compile("x :=".to_string());
+        let text = out
+            .as_string()
+            .expect("compile should return string JsValue");
+        let body: Value = serde_json::from_str(&text).expect("valid json object");
+
+        assert_eq!(get_field(&body, "ok"), &Value::Bool(false));
+        assert_eq!(
+            get_field(&body, "code"),
+            &Value::String("E0001".to_string())
+        );
+        assert_eq!(
+            get_field(&body, "kind"),
+            &Value::String("syntax".to_string())
+        );
+        assert_eq!(
+            get_field(&body, "message_id"),
+            &Value::String("unexpected_end".to_string())
+        );
+        assert_eq!(
+            get_field(&body, "message_args"),
+            &serde_json::json!({"expected": "expression"})
+        );
+    }
+
+    #[test]
+    fn run_cancellable_reports_status() {
+        let program = "input.json(\"xs\") |> json |> ui.table(\"out\");".to_string();
+
+        let out = super::run_cancellable(program.clone(), "{\"xs\": [1]}".to_string(), 7);
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(get_field(&body, "status"), &Value::String("ok".to_string()));
+
+        super::cancel(8);
+        assert!(super::CANCEL_TOKENS.with(|tokens| tokens.borrow().is_empty()));
+        let out = super::run_cancellable(program, "{\"xs\": [1]}".to_string(), 8);
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(
+            get_field(&body, "status"),
+            &Value::String("ok".to_string()),
+            "cancelling a token with no run in progress is a no-op"
+        );
+    }
+
+    #[test]
+    fn run_with_yield_stops_when_the_hook_says_so() {
+        let program = "input.json(\"xs\") |> json |> map(_ + 1) |> ui.table(\"out\");".to_string();
+        let fixtures = "{\"xs\": [1, 2, 3, 4]}".to_string();
+
+        let mut points = Vec::new();
+        let out = super::run_with_yield(program.clone(), fixtures.clone(), 2, |reached| {
+            points.push(reached);
+            true
+        });
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(get_field(&body, "status"), &Value::String("ok".to_string()));
+        assert!(!points.is_empty() && points.iter().all(|p| p % 2 == 0));
+
+        let out = super::run_with_yield(program, fixtures, 2, |_| false);
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(
+            get_field(&body, "status"),
+            &Value::String("cancelled".to_string())
+        );
+    }
+
+    #[test]
+    fn compile_handle_runs_with_different_fixtures_until_invalidated() {
+        let out =
+            super::compile_handle("input.json(\"xs\") |> json |> ui.table(\"out\");".to_string());
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        let handle = match get_field(&body, "handle") {
+            Value::Number(n) => n.as_i64().expect("i64 handle") as u32,
+            _ => panic!("handle should be a number"),
+        };
+
+        for (fixtures, expected) in [
+            ("{\"xs\": [1]}", "{\"out\":[1]}"),
+            ("{\"xs\": [2, 3]}", "{\"out\":[2,3]}"),
+        ] {
+            let out = super::run_handle(handle, fixtures.to_string());
+            let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+                .expect("valid json object");
+            assert_eq!(
+                get_field(&body, "tables_json"),
+                &Value::String(expected.to_string())
+            );
+        }
+
+        assert!(super::invalidate(handle));
+        assert!(!super::invalidate(handle));
+        let out = super::run_handle(handle, "{}".to_string());
+        assert!(out
+            .as_string()
+            .expect("string JsValue")
+            .contains("unknown program handle"));
+    }
+
+    #[test]
+    fn compile_handle_evicts_least_recently_used() {
+        let compile = || {
+            let out = super::compile_handle("input.json(\"xs\") |> ui.table(\"t\");".to_string());
+            let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+                .expect("valid json object");
+            match get_field(&body, "handle") {
+                Value::Number(n) => n.as_i64().expect("i64 handle") as u32,
+                _ => panic!("handle should be a number"),
+            }
+        };
+
+        let first = compile();
+        let second = compile();
+        super::run_handle(first, "{\"xs\": []}".to_string());
+        for _ in 0..super::PROGRAM_CACHE_CAPACITY - 1 {
+            compile();
+        }
+
+        assert!(super::invalidate(first));
+        assert!(!super::invalidate(second));
+    }
+
+    #[test]
+    fn get_table_page_reads_retained_run_rows() {
+        let out = super::run(
+            r#"input.json("xs") |> json |> ui.table("out");"#.to_string(),
+            "{\"xs\": [1, 2, 3, 4, 5]}".to_string(),
+            false,
+        );
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(
+            get_field(&body, "row_counts"),
+            &serde_json::json!({"out": 5})
+        );
+        let run_id = match get_field(&body, "run_id") {
+            Value::Number(n) => n.as_i64().expect("i64 run id") as u32,
+            _ => panic!("run_id should be a number"),
+        };
+
+        let page = |table: &str, offset, limit| {
+            let out = super::get_table_page(run_id, table.to_string(), offset, limit);
+            let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+                .expect("valid json object");
+            body
+        };
+        let body = page("out", 3, 10);
+        assert_eq!(get_field(&body, "rows"), &serde_json::json!([4, 5]));
+        assert_eq!(get_field(&body, "total"), &serde_json::json!(5));
+        assert_eq!(
+            get_field(&page("out", 9, 2), "rows"),
+            &serde_json::json!([])
+        );
+        assert_eq!(
+            get_field(&page("missing", 0, 2), "diagnostics"),
+            &Value::String("unknown table: missing".to_string())
+        );
+
+        assert!(super::release_run(run_id));
+        assert_eq!(
+            get_field(&page("out", 0, 2), "diagnostics"),
+            &Value::String(format!("unknown run id: {run_id}"))
+        );
+    }
+
+    #[test]
+    fn identical_runs_are_served_from_the_result_cache() {
+        let program = r#"input.json("xs") |> json |> ui.table("out");"#;
+        let run = |fixtures: &str| {
+            let out = super::run(program.to_string(), fixtures.to_string(), false);
+            let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+                .expect("valid json object");
+            body
+        };
+        let cached = |body: &Value| match body {
+            Value::Object(map) => map.get("cached").cloned(),
+            _ => None,
+        };
+
+        let first = run("{\"xs\": [1, 2]}");
+        assert_eq!(cached(&first), None);
+        let second = run("{\"xs\": [1, 2]}");
+        assert_eq!(cached(&second), Some(Value::Bool(true)));
+        assert_eq!(
+            get_field(&second, "tables_json"),
+            get_field(&first, "tables_json")
+        );
+        assert_eq!(
+            get_field(&second, "row_counts"),
+            &serde_json::json!({"out": 2})
+        );
+        assert_ne!(
+            get_field(&second, "run_id"),
+            get_field(&first, "run_id"),
+            "a cached run is retained again under a new id"
+        );
+        assert_eq!(cached(&run("{\"xs\": [1, 2, 3]}")), None);
+
+        let options = |options: &str| {
+            let out = super::run_with_options(
+                program.to_string(),
+                "{\"xs\": [1]}".to_string(),
+                options.to_string(),
+                false,
+            );
+            let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+                .expect("valid json object");
+            cached(&body)
+        };
+        assert_eq!(options("{\"explain\": \"quiet\"}"), None);
+        assert_eq!(options("{\"explain\": \"quiet\"}"), Some(Value::Bool(true)));
+        assert_eq!(options("{\"explain\": \"verbose\"}"), None);
+
+        super::clear_result_cache();
+        assert_eq!(cached(&run("{\"xs\": [1, 2]}")), None);
+
+        let key = |fixtures_json: &str| super::RunKey {
+            entry: "run",
+            program: program.to_string(),
+            fixtures_json: fixtures_json.to_string(),
+            extra: None,
+            pretty: false,
+        };
+        assert_eq!(key("{}").digest(), key("{}").digest());
+        let mut cache = super::ResultCache::default();
+        let body = Value::String("first".to_string());
+        let digest = key("{}").digest();
+        cache.insert(
+            digest,
+            key("{}"),
+            super::CachedRun {
+                body: body.clone(),
+                tables: None,
+            },
+        );
+        assert_eq!(
+            cache.get(digest, &key("{}")).map(|run| run.body),
+            Some(body)
+        );
+        assert!(
+            cache.get(digest, &key("[]")).is_none(),
+            "a matching digest still compares the whole key"
+        );
+    }
+
+    #[test]
+    fn to_mermaid_returns_diagram_text() {
+        let out = super::to_mermaid("input.json(\"xs\") |> ui.table(\"out\");".to_string());
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(get_field(&body, "ok"), &Value::Bool(true));
+        match get_field(&body, "diagram") {
+            Value::String(text) => assert!(text.starts_with("flowchart LR\n")),
+            _ => panic!("diagram should be a string"),
+        }
+
+        let out = super::to_dot("x :=".to_string());
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(get_field(&body, "ok"), &Value::Bool(false));
+    }
+
+    #[test]
+    fn run_with_host_reports_pending_calls_then_completes() {
+        let program =
+            r#"input.json("ids") |> json |> host.call("fetch_user", _) |> ui.table("users");"#;
+        let fixtures = r#"{"ids": [1, 2, 1]}"#.to_string();
+
+        let out = super::run_with_host(
+            program.to_string(),
+            fixtures.clone(),
+            r#"{"fetch_user": {}}"#.to_string(),
+        );
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(
+            get_field(&body, "status"),
+            &Value::String("pending".to_string())
+        );
+        match get_field(&body, "pending") {
+            Value::Array(calls) => assert_eq!(calls.len(), 2, "duplicate args are reported once"),
+            _ => panic!("pending should be an array"),
+        }
+
+        let out = super::run_with_host(
+            program.to_string(),
+            fixtures,
+            r#"{"fetch_user": {"1": {"name": "a"}, "2": {"name": "b"}}}"#.to_string(),
+        );
+        let body: Value = serde_json::from_str(&out.as_string().expect("string JsValue"))
+            .expect("valid json object");
+        assert_eq!(get_field(&body, "status"), &Value::String("ok".to_string()));
+        let users = get_field(get_field(&body, "tables"), "users");
+        assert_eq!(
+            super::json_string(users),
+            r#"[{"name":"a"},{"name":"b"},{"name":"a"}]"#
+        );
+    }
 }
@@ -1,4 +1,18 @@
-use std::collections::BTreeMap;
+//! A small JSON value type with a parser and printer. `no_std`; needs only `alloc`.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+
+#[cfg(feature = "serde")]
+mod value_serde;
+#[cfg(feature = "serde")]
+pub use value_serde::{from_value, to_value};
 
 pub type Map = BTreeMap<String, Value>;
 
@@ -30,18 +44,19 @@ pub enum Value {
 #[derive(Debug, Clone)]
 pub struct Error(String);
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.0)
     }
 }
 
-impl std::error::Error for Error {}
+impl core::error::Error for Error {}
 
 pub fn from_str(input: &str) -> Result<Value, Error> {
     let mut p = JsonP {
         b: input.as_bytes(),
         i: 0,
+        depth: 0,
     };
     let value = p.value().map_err(Error)?;
     p.ws();
@@ -52,7 +67,7 @@ pub fn from_str(input: &str) -> Result<Value, Error> {
 }
 
 pub fn from_slice(input: &[u8]) -> Result<Value, Error> {
-    let s = std::str::from_utf8(input).map_err(|e| Error(e.to_string()))?;
+    let s = core::str::from_utf8(input).map_err(|e| Error(e.to_string()))?;
     from_str(s)
 }
 
@@ -60,6 +75,12 @@ pub fn to_string(value: &Value) -> Result<String, Error> {
     Ok(stringify_json(value))
 }
 
+pub fn to_string_pretty(value: &Value) -> Result<String, Error> {
+    let mut out = String::new();
+    stringify_json_pretty(value, 0, &mut out);
+    Ok(out)
+}
+
 pub fn to_vec(value: &Value) -> Result<Vec<u8>, Error> {
     Ok(stringify_json(value).into_bytes())
 }
@@ -69,12 +90,7 @@ fn stringify_json(j: &Value) -> String {
         Value::Null => "null".to_string(),
         Value::Bool(b) => b.to_string(),
         Value::Number(n) => n.0.to_string(),
-        Value::String(s) => format!(
-            "\"{}\"",
-            s.replace('\\', "\\\\")
-                .replace('"', "\\\"")
-                .replace('\n', "\\n")
-        ),
+        Value::String(s) => quote(s),
         Value::Array(a) => format!(
             "[{}]",
             a.iter().map(stringify_json).collect::<Vec<_>>().join(",")
@@ -82,16 +98,73 @@ fn stringify_json(j: &Value) -> String {
         Value::Object(o) => format!(
             "{{{}}}",
             o.iter()
-                .map(|(k, v)| format!("\"{}\":{}", k.replace('"', "\\\""), stringify_json(v)))
+                .map(|(k, v)| format!("{}:{}", quote(k), stringify_json(v)))
                 .collect::<Vec<_>>()
                 .join(",")
         ),
     }
 }
 
+fn stringify_json_pretty(j: &Value, depth: usize, out: &mut String) {
+    const INDENT: &str = "  ";
+    match j {
+        Value::Array(a) if !a.is_empty() => {
+            out.push_str("[\n");
+            for (idx, item) in a.iter().enumerate() {
+                if idx > 0 {
+                    out.push_str(",\n");
+                }
+                out.push_str(&INDENT.repeat(depth + 1));
+                stringify_json_pretty(item, depth + 1, out);
+            }
+            out.push('\n');
+            out.push_str(&INDENT.repeat(depth));
+            out.push(']');
+        }
+        Value::Object(o) if !o.is_empty() => {
+            out.push_str("{\n");
+            for (idx, (k, v)) in o.iter().enumerate() {
+                if idx > 0 {
+                    out.push_str(",\n");
+                }
+                out.push_str(&INDENT.repeat(depth + 1));
+                out.push_str(&format!("{}: ", quote(k)));
+                stringify_json_pretty(v, depth + 1, out);
+            }
+            out.push('\n');
+            out.push_str(&INDENT.repeat(depth));
+            out.push('}');
+        }
+        _ => out.push_str(&stringify_json(j)),
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// How deeply arrays and objects may nest, as in the real crate; deeper input is an
+/// error rather than a stack overflow.
+const RECURSION_LIMIT: usize = 128;
+
 struct JsonP<'a> {
     b: &'a [u8],
     i: usize,
+    depth: usize,
 }
 
 impl<'a> JsonP<'a> {
@@ -120,13 +193,23 @@ impl<'a> JsonP<'a> {
                 Ok(Value::Bool(false))
             }
             b'"' => Ok(Value::String(self.string()?)),
-            b'[' => self.array(),
-            b'{' => self.object(),
+            b'[' => self.nested(Self::array),
+            b'{' => self.nested(Self::object),
             b'-' | b'0'..=b'9' => self.number(),
             _ => Err("bad json value".to_string()),
         }
     }
 
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Value, String>) -> Result<Value, String> {
+        if self.depth == RECURSION_LIMIT {
+            return Err("recursion limit exceeded".to_string());
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
     fn expect(&mut self, s: &[u8]) -> Result<(), String> {
         if self.b.get(self.i..self.i + s.len()) == Some(s) {
             self.i += s.len();
@@ -138,12 +221,13 @@ impl<'a> JsonP<'a> {
 
     fn string(&mut self) -> Result<String, String> {
         self.i += 1;
-        let mut o = String::new();
+        // Raw bytes are collected as-is so multi-byte UTF-8 survives intact.
+        let mut o = Vec::new();
         while self.i < self.b.len() {
             let c = self.b[self.i];
             self.i += 1;
             if c == b'"' {
-                return Ok(o);
+                return String::from_utf8(o).map_err(|e| e.to_string());
             }
             if c == b'\\' {
                 if self.i >= self.b.len() {
@@ -151,20 +235,56 @@ impl<'a> JsonP<'a> {
                 }
                 let e = self.b[self.i];
                 self.i += 1;
-                o.push(match e {
+                let decoded = match e {
                     b'"' => '"',
                     b'\\' => '\\',
+                    b'/' => '/',
                     b'n' => '\n',
+                    b'r' => '\r',
                     b't' => '\t',
+                    b'b' => '\u{8}',
+                    b'f' => '\u{c}',
+                    b'u' => self.unicode_escape()?,
                     _ => return Err("bad escape".to_string()),
-                });
+                };
+                o.extend_from_slice(decoded.encode_utf8(&mut [0; 4]).as_bytes());
             } else {
-                o.push(c as char)
+                o.push(c)
             }
         }
         Err("unterminated string".to_string())
     }
 
+    /// Reads the hex digits after `\u`, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.b.get(self.i..self.i + 2) != Some(b"\\u") {
+                return Err("bad unicode escape".to_string());
+            }
+            self.i += 2;
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err("bad unicode escape".to_string());
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| "bad unicode escape".to_string())
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self
+            .b
+            .get(self.i..self.i + 4)
+            .and_then(|d| core::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok())
+            .ok_or_else(|| "bad unicode escape".to_string())?;
+        self.i += 4;
+        Ok(digits)
+    }
+
     fn number(&mut self) -> Result<Value, String> {
         let s = self.i;
         if self.b[self.i] == b'-' {
@@ -173,7 +293,7 @@ impl<'a> JsonP<'a> {
         while self.i < self.b.len() && self.b[self.i].is_ascii_digit() {
             self.i += 1;
         }
-        let n = std::str::from_utf8(&self.b[s..self.i])
+        let n = core::str::from_utf8(&self.b[s..self.i])
             .map_err(|_| "utf8".to_string())?
             .parse::<i64>()
             .map_err(|_| "num".to_string())?;
@@ -212,6 +332,9 @@ impl<'a> JsonP<'a> {
                 self.i += 1;
                 return Ok(Value::Object(out));
             }
+            if self.b.get(self.i) != Some(&b'"') {
+                return Err("bad object".to_string());
+            }
             let key = self.string()?;
             self.ws();
             if self.i >= self.b.len() || self.b[self.i] != b':' {
@@ -0,0 +1,293 @@
+#![no_main]
+
+//! Random well-formed programs built from a small grammar: each must parse, format
+//! to source that parses, and run against fixed fixtures without panicking. Runtime
+//! errors (type mismatches, overflow) are fine; panics are not.
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+/// Deeper sub-expressions render as `_`, keeping programs under
+/// [`dsl_syntax::MAX_NESTING`].
+const MAX_DEPTH: usize = 12;
+
+#[derive(Arbitrary, Debug)]
+struct Program {
+    statements: Vec<Statement>,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Statement {
+    fixture: Fixture,
+    stages: Vec<Stage>,
+    sink: Sink,
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum Fixture {
+    Numbers,
+    Records,
+    Strings,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Stage {
+    Map(Expr),
+    Filter(Expr),
+    FlatMap(Expr),
+    Select(Vec<(Field, Expr)>),
+    GroupCount(Expr),
+    Codec { codec: Codec, inverse: bool },
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum Codec {
+    Json,
+    Utf8,
+    Base64,
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum Sink {
+    Table,
+    Log,
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum Field {
+    A,
+    B,
+    Tag,
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum Op {
+    Add,
+    Eq,
+    Gt,
+    Coalesce,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Expr {
+    Item,
+    Number(i16),
+    Text(Field),
+    Bool(bool),
+    Null,
+    Get(Box<Expr>, Field),
+    Binary(Box<Expr>, Op, Box<Expr>),
+    Negate(Box<Expr>),
+    Array(Vec<Expr>),
+    Record(Vec<(Field, Expr)>),
+    Let(Box<Expr>, Box<Expr>),
+    Match(Box<Expr>, Vec<(Pattern, Expr)>),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Pattern {
+    Wildcard,
+    Number(i16),
+    Bind,
+    Field(Field),
+}
+
+impl Fixture {
+    fn name(self) -> &'static str {
+        match self {
+            Fixture::Numbers => "numbers",
+            Fixture::Records => "records",
+            Fixture::Strings => "strings",
+        }
+    }
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Field::A => "a",
+            Field::B => "b",
+            Field::Tag => "tag",
+        }
+    }
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Eq => "==",
+            Op::Gt => ">",
+            Op::Coalesce => "??",
+        }
+    }
+}
+
+impl Program {
+    fn render(&self) -> String {
+        let mut out = String::new();
+        for statement in &self.statements {
+            out.push_str(&format!(
+                "input.json(\"{}\") |> json",
+                statement.fixture.name()
+            ));
+            for stage in &statement.stages {
+                out.push_str("\n  |> ");
+                stage.render(&mut out);
+            }
+            out.push_str(match statement.sink {
+                Sink::Table => "\n  |> ui.table(\"out\");\n",
+                Sink::Log => "\n  |> ui.log(\"log\");\n",
+            });
+        }
+        out
+    }
+}
+
+impl Stage {
+    fn render(&self, out: &mut String) {
+        match self {
+            Stage::Map(f) => call(out, "map", f),
+            Stage::Filter(pred) => call(out, "filter", pred),
+            Stage::FlatMap(f) => call(out, "flat_map", f),
+            Stage::Select(columns) if columns.is_empty() => out.push_str("select(a=_)"),
+            Stage::Select(columns) => {
+                out.push_str("select(");
+                for (idx, (field, expr)) in columns.iter().enumerate() {
+                    if idx > 0 {
+                        out.push_str(", ");
+                    }
+                    out.push_str(field.name());
+                    out.push('=');
+                    expr.render(out, 1);
+                }
+                out.push(')');
+            }
+            Stage::GroupCount(key) => {
+                out.push_str("group.count(by_key=");
+                key.render(out, 1);
+                out.push(')');
+            }
+            Stage::Codec { codec, inverse } => {
+                if *inverse {
+                    out.push('~');
+                }
+                out.push_str(match codec {
+                    Codec::Json => "json",
+                    Codec::Utf8 => "utf8",
+                    Codec::Base64 => "base64",
+                });
+            }
+        }
+    }
+}
+
+fn call(out: &mut String, name: &str, arg: &Expr) {
+    out.push_str(name);
+    out.push('(');
+    arg.render(out, 1);
+    out.push(')');
+}
+
+impl Expr {
+    /// Writes the expression fully parenthesized, so precedence never matters.
+    fn render(&self, out: &mut String, depth: usize) {
+        if depth > MAX_DEPTH {
+            out.push('_');
+            return;
+        }
+        let depth = depth + 1;
+        match self {
+            Expr::Item => out.push('_'),
+            Expr::Number(n) => out.push_str(&format!("({n})")),
+            Expr::Text(field) => out.push_str(&format!("\"{}\"", field.name())),
+            Expr::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Expr::Null => out.push_str("null"),
+            Expr::Get(expr, field) => {
+                out.push('(');
+                expr.render(out, depth);
+                out.push_str(&format!(").{}", field.name()));
+            }
+            Expr::Binary(left, op, right) => {
+                out.push('(');
+                left.render(out, depth);
+                out.push_str(&format!(" {} ", op.symbol()));
+                right.render(out, depth);
+                out.push(')');
+            }
+            Expr::Negate(expr) => {
+                out.push_str("-(");
+                expr.render(out, depth);
+                out.push(')');
+            }
+            Expr::Array(items) => {
+                out.push('[');
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        out.push_str(", ");
+                    }
+                    item.render(out, depth);
+                }
+                out.push(']');
+            }
+            Expr::Record(fields) => {
+                out.push('{');
+                for (idx, (field, value)) in fields.iter().enumerate() {
+                    out.push_str(if idx > 0 { ", " } else { " " });
+                    out.push_str(field.name());
+                    out.push_str(": ");
+                    value.render(out, depth);
+                }
+                out.push_str(" }");
+            }
+            Expr::Let(value, body) => {
+                out.push_str("(let v = ");
+                value.render(out, depth);
+                out.push_str(" in ");
+                body.render(out, depth);
+                out.push(')');
+            }
+            Expr::Match(scrutinee, arms) => {
+                out.push_str("(match ");
+                scrutinee.render(out, depth);
+                out.push_str(" {");
+                for (pattern, body) in arms {
+                    out.push(' ');
+                    pattern.render(out);
+                    out.push_str(" => ");
+                    body.render(out, depth);
+                    out.push(',');
+                }
+                out.push_str(" _ => null })");
+            }
+        }
+    }
+}
+
+impl Pattern {
+    fn render(&self, out: &mut String) {
+        match self {
+            Pattern::Wildcard => out.push('_'),
+            Pattern::Number(n) => out.push_str(&n.to_string()),
+            Pattern::Bind => out.push('v'),
+            Pattern::Field(field) => out.push_str(&format!("{{ {}: v, .. }}", field.name())),
+        }
+    }
+}
+
+fuzz_target!(|program: Program| {
+    let src = program.render();
+    if let Err(e) = dsl_syntax::parse_program(&src) {
+        panic!("generated program doesn't parse: {e}\n{src}");
+    }
+    let formatted = dsl_syntax::format_source(&src).expect("a parsed program formats");
+    if let Err(e) = dsl_syntax::parse_program(&formatted) {
+        panic!("formatted program doesn't parse: {e}\n{formatted}");
+    }
+    let fixtures = serde_json::json!({
+        "numbers": [0, 1, -2, 9223372036854775807],
+        "records": [{"a": 1, "b": "x", "tag": "t"}, {"a": null, "tag": "u"}, {}],
+        "strings": ["", "a", "é🦀"]
+    });
+    let _ = dsl_runtime::run(&src, fixtures);
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+//! Arbitrary bytes through the bundled JSON parser. Nothing may panic, and a value
+//! that parses must survive printing and parsing again, compact and pretty.
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(value) = serde_json::from_slice(data) else {
+        return;
+    };
+    let compact = serde_json::to_string(&value).expect("values print");
+    assert_eq!(serde_json::from_str(&compact).expect("compact output parses"), value);
+    let pretty = serde_json::to_string_pretty(&value).expect("values print");
+    assert_eq!(serde_json::from_str(&pretty).expect("pretty output parses"), value);
+});
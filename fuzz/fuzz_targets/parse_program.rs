@@ -0,0 +1,21 @@
+#![no_main]
+
+//! Arbitrary bytes through the lexer, parser, and formatter. Nothing may panic, and
+//! a program that parses must format to source that parses and formats the same.
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(src) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = dsl_syntax::lex(src);
+    let _ = dsl_syntax::migrate(src, 0, dsl_syntax::CURRENT_VERSION);
+    if dsl_syntax::parse_program(src).is_err() {
+        return;
+    }
+    let formatted = dsl_syntax::format_source(src).expect("a parsed program formats");
+    let reformatted = dsl_syntax::format_source(&formatted)
+        .unwrap_or_else(|e| panic!("formatted program doesn't parse: {e}\n{formatted}"));
+    assert_eq!(formatted, reformatted, "formatting is not idempotent");
+});
@@ -1,11 +1,22 @@
 import { useEffect, useMemo, useState } from 'react';
-import { loadWasmApi, type RunOutput, type WasmApi } from './wasm';
+import {
+  loadWasmApi,
+  type ColumnSchema,
+  type ExplainLevel,
+  type GraphOutput,
+  type LogLevel,
+  type LogRecord,
+  type RunOutput,
+  type WasmApi,
+} from './wasm';
 import { CodeEditor } from './CodeEditor';
 
 type Example = {
   name: string;
   program: string;
   fixtures: string;
+  /** JSON object for `$name` placeholders, when the program uses any. */
+  params?: string;
 };
 
 const examples: Example[] = [
@@ -62,6 +73,50 @@ requests
 }`,
   },
 
+  {
+    name: 'D2. RBAC policy diff',
+    program: `input.json("role_perms") |> rbac.load(
+  name="current",
+  principal_bindings="principal_bindings",
+  role_perms="role_perms",
+  resource_ancestors="resource_ancestors"
+);
+input.json("proposed_perms") |> rbac.load(
+  name="proposed",
+  principal_bindings="principal_bindings",
+  role_perms="proposed_perms",
+  resource_ancestors="resource_ancestors"
+);
+
+input.json("requests")
+  |> json
+  |> rbac.diff(before="current", after="proposed")
+  |> ui.table("changed");`,
+    fixtures: `{
+  "principal_bindings": [
+    {"principal": "alice", "role": "reader"},
+    {"principal": "bob", "role": "writer"}
+  ],
+  "role_perms": [
+    {"role": "reader", "action": "read", "resource": "folder:engineering"},
+    {"role": "writer", "action": "write", "resource": "doc:eng-plan"}
+  ],
+  "proposed_perms": [
+    {"role": "reader", "action": "read", "resource": "doc:eng-plan"},
+    {"role": "writer", "action": "write", "resource": "folder:engineering"}
+  ],
+  "resource_ancestors": [
+    {"resource": "doc:eng-plan", "ancestor": "folder:engineering"},
+    {"resource": "doc:eng-notes", "ancestor": "folder:engineering"}
+  ],
+  "requests": [
+    {"principal": "alice", "action": "read", "resource": "doc:eng-plan"},
+    {"principal": "alice", "action": "read", "resource": "doc:eng-notes"},
+    {"principal": "bob", "action": "write", "resource": "doc:eng-notes"}
+  ]
+}`,
+  },
+
   {
     name: 'E. KV + lookup demo',
     program: `input.json("users") |> json |> kv.load(store="users");
@@ -136,19 +191,249 @@ input.json("events")
     {"author_id":"user/a1","story_id":"s4","created_at":"2026-02-21T12:00:00Z"},
     {"author_id":"user/a2","story_id":"s3","created_at":"2026-02-20T23:00:00Z"}
   ]
+}`,
+  },
+  {
+    name: 'J. NDJSON events',
+    program: `input.ndjson("events")
+  |> filter(_.ms > 100)
+  |> ndjson
+  |> utf8
+  |> ui.table("slow_lines");`,
+    fixtures: `{
+  "events": "{\\"id\\":1,\\"ms\\":80}\\n{\\"id\\":2,\\"ms\\":250}\\n{\\"id\\":3,\\"ms\\":120}\\n"
+}`,
+  },
+  {
+    name: 'K. select projection',
+    program: `input.json("orders")
+  |> json
+  |> select(id=_.user_id, total=_.a + _.b)
+  |> ui.table("totals");
+
+input.json("orders")
+  |> json
+  |> where(_.a > 1)
+  |> group.collect_all(by_key=_.user_id, within_ms=1000, limit=10)
+  |> having(array.any(_.items, _.b > 2))
+  |> select(user=_.key)
+  |> ui.table("big_spenders");`,
+    fixtures: `{
+  "orders": [
+    {"user_id": "u1", "a": 2, "b": 3},
+    {"user_id": "u2", "a": 10, "b": 0}
+  ]
+}`,
+  },
+  {
+    name: 'L. Params ($threshold)',
+    program: `input.json("events")
+  |> json
+  |> filter(_.ms > $threshold)
+  |> rank.topk(k=$k, by=_.ms, order="desc")
+  |> ui.table("slowest");`,
+    fixtures: `{
+  "events": [
+    {"id": 1, "ms": 80},
+    {"id": 2, "ms": 250},
+    {"id": 3, "ms": 120},
+    {"id": 4, "ms": 400}
+  ]
+}`,
+    params: `{"threshold": 100, "k": 2}`,
+  },
+  {
+    name: 'M. ui.table columns + format',
+    program: `input.json("scores")
+  |> json
+  |> ui.table("scores", columns=["id", "name", "score"], format={score: "%.2f"});`,
+    fixtures: `{
+  "scores": [
+    {"name": "ada", "id": 1, "score": 97, "team": "x"},
+    {"name": "lin", "id": 2, "score": 88, "team": "y"}
+  ]
+}`,
+  },
+  {
+    name: 'N. ui.diff before/after',
+    program: `input.json("after")
+  |> json
+  |> ui.diff("changes", expected="before", key=_.id);`,
+    fixtures: `{
+  "before": [{"id": 1, "plan": "free"}, {"id": 2, "plan": "pro"}],
+  "after": [{"id": 2, "plan": "team"}, {"id": 3, "plan": "free"}]
+}`,
+  },
+  {
+    name: 'O. ui.graph resource tree',
+    program: `input.json("resource_ancestors")
+  |> json
+  |> ui.graph("resources", node=_.resource, edges=_.ancestor);`,
+    fixtures: `{
+  "resource_ancestors": [
+    {"resource": "doc:1", "ancestor": "folder:a"},
+    {"resource": "folder:a", "ancestor": "org:acme"},
+    {"resource": "doc:2", "ancestor": "folder:a"}
+  ]
+}`,
+  },
+  {
+    name: 'P. fsm.evaluate order lifecycle',
+    program: `input.json("events")
+  |> json
+  |> fsm.evaluate(transitions="transitions", initial="new", event=_.event, by_key=_.order)
+  |> ui.table("orders");`,
+    fixtures: `{
+  "transitions": [
+    {"from": "new", "event": "pay", "to": "paid"},
+    {"from": "paid", "event": "ship", "to": "shipped"},
+    {"from": "new", "event": "cancel", "to": "cancelled"}
+  ],
+  "events": [
+    {"order": "o1", "event": "pay"},
+    {"order": "o2", "event": "cancel"},
+    {"order": "o1", "event": "ship"},
+    {"order": "o2", "event": "pay"}
+  ]
+}`,
+  },
+  {
+    name: 'Q. sessionize clickstream',
+    program: `input.json("clicks")
+  |> json
+  |> sessionize(by_key=_.user, gap_ms=1800000, ts=_.ts)
+  |> select(user=_.key, session=_.session_id, clicks=_.items)
+  |> ui.table("sessions");`,
+    fixtures: `{
+  "clicks": [
+    {"user": "u1", "page": "/", "ts": 0},
+    {"user": "u1", "page": "/pricing", "ts": 600000},
+    {"user": "u2", "page": "/", "ts": 900000},
+    {"user": "u1", "page": "/", "ts": 5400000}
+  ]
+}`,
+  },
+  {
+    name: 'R. throttle token bucket',
+    program: `input.json("requests")
+  |> json
+  |> throttle(by_key=_.user, limit=2, per_ms=1000, ts=_.ts, mode="mark")
+  |> select(user=_.item.user, ts=_.item.ts, allowed=_.allowed)
+  |> ui.table("requests");`,
+    fixtures: `{
+  "requests": [
+    {"user": "a", "ts": 0},
+    {"user": "a", "ts": 100},
+    {"user": "a", "ts": 200},
+    {"user": "b", "ts": 200},
+    {"user": "a", "ts": 700}
+  ]
+}`,
+  },
+  {
+    name: 'S. explicit codec directions',
+    program: `input.json("docs")
+  |> json.decode
+  |> map(_.body)
+  |> utf8.encode
+  |> base64.encode
+  |> ui.table("encoded");`,
+    fixtures: `{
+  "docs": [
+    {"body": "hi"},
+    {"body": "hello"}
+  ]
+}`,
+  },
+  {
+    name: 'T. let-in locals',
+    program: `input.json("orders")
+  |> json
+  |> map(let total = _.price + _.shipping in { id: _.id, total: total })
+  |> filter(_.total > 20)
+  |> ui.table("totals");`,
+    fixtures: `{
+  "orders": [
+    {"id": "a", "price": 18, "shipping": 5},
+    {"id": "b", "price": 9, "shipping": 5}
+  ]
+}`,
+  },
+  {
+    name: 'U. match on event shape',
+    program: `input.json("events")
+  |> json
+  |> map(match _ {
+    { type: "click", target, .. } => "clicked " + target,
+    { type: "view", page, .. } => "viewed " + page,
+    _ => "ignored"
+  })
+  |> ui.table("summary");`,
+    fixtures: `{
+  "events": [
+    {"type": "click", "target": "buy", "ts": 1},
+    {"type": "view", "page": "/home", "ts": 2},
+    {"type": "scroll", "ts": 3}
+  ]
+}`,
+  },
+  {
+    name: 'V. tagged variants',
+    program: `tagged := input.json("events")
+  |> json
+  |> map(match _ {
+    { type: "click", target, .. } => tag(target, "click"),
+    { type: "view", page, .. } => tag({ page: page }, "view"),
+    _ => tag(_, "other")
+  });
+tagged |> ui.table("tagged");
+tagged
+  |> map(match _ {
+    click(target) => "clicked " + target,
+    view({ page }) => "viewed " + page,
+    other(_) => "ignored"
+  })
+  |> ui.table("summary");`,
+    fixtures: `{
+  "events": [
+    {"type": "click", "target": "buy", "ts": 1},
+    {"type": "view", "page": "/home", "ts": 2},
+    {"type": "scroll", "ts": 3}
+  ]
+}`,
+  },
+  {
+    name: 'W. leaderboard with tie-breaks',
+    program: `input.json("scores")
+  |> json
+  |> rank.topk(k=3, by=[_.score, _.at], order=["desc", "asc"])
+  |> ui.table("podium");`,
+    fixtures: `{
+  "scores": [
+    {"player": "ada", "score": 90, "at": 3},
+    {"player": "bo", "score": 95, "at": 5},
+    {"player": "cy", "score": 90, "at": 1},
+    {"player": "di", "score": 70, "at": 2}
+  ]
 }`,
   },
 ];
 
-const pretty = (value: string) => {
+type TableValue = Record<string, unknown[]>;
+
+const LOG_LEVELS: LogLevel[] = ['debug', 'info', 'warn', 'error'];
+
+function parseJsonObject<T>(json: string): T | null {
   try {
-    return JSON.stringify(JSON.parse(value), null, 2);
+    return JSON.parse(json) as T;
   } catch {
-    return value;
+    return null;
   }
-};
+}
 
-type TableValue = Record<string, unknown[]>;
+function parseLogRecordsJson(json: string | undefined): Record<string, LogRecord[]> | null {
+  return json ? parseJsonObject<Record<string, LogRecord[]>>(json) : null;
+}
 
 function parseTablesJson(tablesJson: string): TableValue {
   try {
@@ -165,56 +450,75 @@ function parseTablesJson(tablesJson: string): TableValue {
   return {};
 }
 
-function renderTableRows(name: string, rows: unknown[]) {
+function compareCells(a: unknown, b: unknown, numeric: boolean): number {
+  if (numeric && typeof a === 'number' && typeof b === 'number') {
+    return a - b;
+  }
+  if (a === null || a === undefined) {
+    return b === null || b === undefined ? 0 : 1;
+  }
+  if (b === null || b === undefined) {
+    return -1;
+  }
+  return JSON.stringify(a).localeCompare(JSON.stringify(b));
+}
+
+/** Renders one table; `schema` (from the run output) types the columns. Click a header to sort. */
+function TableView({ rows, schema }: { rows: unknown[]; schema?: ColumnSchema[] }) {
+  const [sort, setSort] = useState<{ column: string; descending: boolean } | null>(null);
   const allRecords = rows.every((row) => row && typeof row === 'object' && !Array.isArray(row));
+  const columns: ColumnSchema[] =
+    schema ??
+    (allRecords
+      ? Array.from(new Set(rows.flatMap((row) => Object.keys(row as Record<string, unknown>))))
+          .sort()
+          .map((name): ColumnSchema => ({ name, type: 'mixed', nullable: true }))
+      : [{ name: 'value', type: 'mixed', nullable: true }]);
+  const cell = (row: unknown, column: string) =>
+    allRecords && rows.length > 0 ? ((row as Record<string, unknown>)[column] ?? null) : row;
 
-  if (!allRecords) {
-    return (
-      <table key={name} border={1} cellPadding={6} style={{ borderCollapse: 'collapse', marginBottom: 12 }}>
-        <thead>
-          <tr>
-            <th>value</th>
-          </tr>
-        </thead>
-        <tbody>
-          {rows.map((row, index) => (
-            <tr key={index}>
-              <td>
-                <code>{JSON.stringify(row)}</code>
-              </td>
-            </tr>
-          ))}
-        </tbody>
-      </table>
-    );
+  const sorted = [...rows];
+  if (sort) {
+    const numeric = columns.find((column) => column.name === sort.column)?.type === 'number';
+    sorted.sort((a, b) => compareCells(cell(a, sort.column), cell(b, sort.column), numeric));
+    if (sort.descending) {
+      sorted.reverse();
+    }
   }
 
-  const columns = Array.from(
-    new Set(rows.flatMap((row) => Object.keys(row as Record<string, unknown>))),
-  ).sort();
-
   return (
-    <table key={name} border={1} cellPadding={6} style={{ borderCollapse: 'collapse', marginBottom: 12 }}>
+    <table border={1} cellPadding={6} style={{ borderCollapse: 'collapse', marginBottom: 12 }}>
       <thead>
         <tr>
           {columns.map((column) => (
-            <th key={column}>{column}</th>
+            <th
+              key={column.name}
+              title={`${column.type}${column.nullable ? ', nullable' : ''}`}
+              style={{ cursor: 'pointer' }}
+              onClick={() =>
+                setSort(
+                  sort?.column === column.name
+                    ? { column: column.name, descending: !sort.descending }
+                    : { column: column.name, descending: false },
+                )
+              }
+            >
+              {column.name}
+              {sort?.column === column.name ? (sort.descending ? ' ▼' : ' ▲') : ''}
+            </th>
           ))}
         </tr>
       </thead>
       <tbody>
-        {rows.map((row, index) => {
-          const record = row as Record<string, unknown>;
-          return (
-            <tr key={index}>
-              {columns.map((column) => (
-                <td key={column}>
-                  <code>{JSON.stringify(record[column] ?? null)}</code>
-                </td>
-              ))}
-            </tr>
-          );
-        })}
+        {sorted.map((row, index) => (
+          <tr key={index}>
+            {columns.map((column) => (
+              <td key={column.name} style={{ textAlign: column.type === 'number' ? 'right' : 'left' }}>
+                <code>{JSON.stringify(cell(row, column.name))}</code>
+              </td>
+            ))}
+          </tr>
+        ))}
       </tbody>
     </table>
   );
@@ -224,6 +528,9 @@ export function App() {
   const [api, setApi] = useState<WasmApi | null>(null);
   const [program, setProgram] = useState(examples[0].program);
   const [fixtures, setFixtures] = useState(examples[0].fixtures);
+  const [params, setParams] = useState(examples[0].params ?? '{}');
+  const [explainLevel, setExplainLevel] = useState<ExplainLevel>('normal');
+  const [minLogLevel, setMinLogLevel] = useState<LogLevel>('debug');
   const [runOutput, setRunOutput] = useState<RunOutput>({
     explain: 'Load WASM and click Run.',
     tables_json: '{}',
@@ -238,9 +545,17 @@ export function App() {
     });
   }, []);
 
-  const prettyTables = useMemo(() => pretty(runOutput.tables_json), [runOutput.tables_json]);
-  const prettyLogs = useMemo(() => pretty(runOutput.logs_json), [runOutput.logs_json]);
   const parsedTables = useMemo(() => parseTablesJson(runOutput.tables_json), [runOutput.tables_json]);
+  const graphs = useMemo(
+    () => (runOutput.graphs_json ? parseJsonObject<Record<string, GraphOutput>>(runOutput.graphs_json) : null),
+    [runOutput.graphs_json],
+  );
+  const logRecords = useMemo(() => parseLogRecordsJson(runOutput.log_records_json), [runOutput.log_records_json]);
+  const paramNames = useMemo(() => (api ? api.paramNames(program).params : []), [api, program]);
+  const diagram = useMemo(
+    () => (api ? api.toMermaid(program) : { ok: false, diagram: '', diagnostics: '' }),
+    [api, program],
+  );
 
   return (
     <main style={{ fontFamily: 'sans-serif', padding: 16, maxWidth: 1000, margin: '0 auto' }}>
@@ -257,6 +572,7 @@ export function App() {
             }
             setProgram(example.program);
             setFixtures(example.fixtures);
+            setParams(example.params ?? '{}');
           }}
           defaultValue={examples[0].name}
         >
@@ -279,6 +595,21 @@ export function App() {
         style={{ width: '100%', fontFamily: 'monospace' }}
       />
 
+      {paramNames.length > 0 && (
+        <>
+          <h2>Params JSON</h2>
+          <p>
+            Program uses: <code>{paramNames.map((name) => `$${name}`).join(', ')}</code>
+          </p>
+          <textarea
+            value={params}
+            onChange={(e) => setParams(e.target.value)}
+            rows={3}
+            style={{ width: '100%', fontFamily: 'monospace' }}
+          />
+        </>
+      )}
+
       <div style={{ marginTop: 10 }}>
         <button
           onClick={() => {
@@ -286,30 +617,98 @@ export function App() {
               setStatus('WASM still loading...');
               return;
             }
-            setRunOutput(api.run(program, fixtures));
+            let parsedParams: Record<string, unknown> = {};
+            try {
+              parsedParams = paramNames.length > 0 ? (JSON.parse(params) as Record<string, unknown>) : {};
+            } catch (error) {
+              setRunOutput({
+                tables_json: '{}',
+                logs_json: '{}',
+                explain: `error: invalid params JSON: ${error instanceof Error ? error.message : String(error)}`,
+              });
+              return;
+            }
+            setRunOutput(api.runWithOptions(program, fixtures, { params: parsedParams, explain: explainLevel }, true));
           }}
         >
           Run
-        </button>
+        </button>{' '}
+        <label>
+          Explain:{' '}
+          <select value={explainLevel} onChange={(e) => setExplainLevel(e.target.value as ExplainLevel)}>
+            <option value="quiet">quiet</option>
+            <option value="normal">normal</option>
+            <option value="verbose">verbose</option>
+          </select>
+        </label>
       </div>
 
+      <details style={{ marginTop: 12 }}>
+        <summary>Pipeline diagram (Mermaid)</summary>
+        <pre style={{ background: '#f5f5f5', padding: 12 }}>
+          {api ? (diagram.ok ? diagram.diagram : diagram.diagnostics) : 'WASM still loading...'}
+        </pre>
+      </details>
+
       <h2>Explain</h2>
       <pre style={{ background: '#f5f5f5', padding: 12 }}>{runOutput.explain}</pre>
 
       <h2>Tables</h2>
       {Object.keys(parsedTables).length === 0 ? (
-        <pre style={{ background: '#f5f5f5', padding: 12 }}>{prettyTables}</pre>
+        <pre style={{ background: '#f5f5f5', padding: 12 }}>{runOutput.tables_json}</pre>
       ) : (
         Object.entries(parsedTables).map(([name, rows]) => (
           <section key={name} style={{ marginBottom: 12 }}>
             <h3 style={{ marginBottom: 8 }}>{name}</h3>
-            {renderTableRows(name, rows)}
+            <TableView key={runOutput.tables_json} rows={rows} schema={runOutput.schemas?.[name]} />
           </section>
         ))
       )}
 
-      <h2>Logs JSON</h2>
-      <pre style={{ background: '#f5f5f5', padding: 12 }}>{prettyLogs}</pre>
+      {graphs && (
+        <>
+          <h2>Graphs</h2>
+          {Object.entries(graphs).map(([name, graph]) => (
+            <section key={name}>
+              <h3 style={{ marginBottom: 8 }}>
+                {name} ({graph.nodes.length} nodes, {graph.edges.length} edges)
+              </h3>
+              <pre style={{ background: '#f5f5f5', padding: 12 }}>
+                {graph.edges.map((edge) => `${edge.from} → ${edge.to}`).join('\n')}
+              </pre>
+            </section>
+          ))}
+        </>
+      )}
+
+      <h2>Logs</h2>
+      {logRecords ? (
+        <>
+          <label>
+            Minimum level:{' '}
+            <select value={minLogLevel} onChange={(e) => setMinLogLevel(e.target.value as LogLevel)}>
+              {LOG_LEVELS.map((level) => (
+                <option key={level} value={level}>
+                  {level}
+                </option>
+              ))}
+            </select>
+          </label>
+          {Object.entries(logRecords).map(([name, records]) => (
+            <section key={name}>
+              <h3 style={{ marginBottom: 8 }}>{name}</h3>
+              <pre style={{ background: '#f5f5f5', padding: 12 }}>
+                {records
+                  .filter((record) => LOG_LEVELS.indexOf(record.level) >= LOG_LEVELS.indexOf(minLogLevel))
+                  .map((record) => `[${record.level}] ${record.message}`)
+                  .join('\n')}
+              </pre>
+            </section>
+          ))}
+        </>
+      ) : (
+        <pre style={{ background: '#f5f5f5', padding: 12 }}>{runOutput.logs_json}</pre>
+      )}
     </main>
   );
 }
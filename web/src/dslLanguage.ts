@@ -2,25 +2,60 @@ import { StreamLanguage, syntaxHighlighting, HighlightStyle } from '@codemirror/
 import { tags } from '@lezer/highlight';
 
 const stageKeywords = new Set([
+  'const',
+  'override',
+  'let',
+  'in',
+  'match',
+  'tag',
+  'untag',
   'input.json',
+  'input.http',
   'map',
   'filter',
   'flat_map',
+  'select',
+  'where',
+  'having',
   'json',
   'utf8',
   'base64',
+  'ndjson',
+  'json.encode',
+  'json.decode',
+  'utf8.encode',
+  'utf8.decode',
+  'base64.encode',
+  'base64.decode',
+  'ndjson.encode',
+  'ndjson.decode',
   'kv.load',
   'lookup.kv',
   'lookup.batch_kv',
   'group.collect_all',
+  'sessionize',
+  'dedupe.within',
+  'throttle',
+  'stats.outliers',
   'group.topn_items',
   'rank.topk',
+  'sort',
   'ui.table',
   'ui.log',
+  'ui.diff',
+  'ui.graph',
+  'rbac.load',
   'rbac.evaluate',
+  'rbac.diff',
+  'fsm.evaluate',
+  'graph.reachable',
+  'graph.shortest_path',
+  'graph.components',
+  'iterate',
+  'lens',
 ]);
 
-const operators = [':=', '|>', '>>', '~'];
+const operators = [':=', '|>', '>>', '==', '??', '~'];
 
 const identifierPattern = /^[A-Za-z_][A-Za-z0-9_.]*/;
 
@@ -54,6 +89,14 @@ export const dslLanguage = StreamLanguage.define({
       return 'string';
     }
 
+    if (stream.match(/^\$[A-Za-z_][A-Za-z0-9_]*/)) {
+      return 'atom';
+    }
+
+    if (stream.match(/^(true|false|null)\b/)) {
+      return 'atom';
+    }
+
     if (stream.match(/^\d+/)) {
       return 'number';
     }
@@ -83,6 +126,7 @@ const dslHighlightStyle = HighlightStyle.define([
   { tag: tags.operator, color: '#2563eb' },
   { tag: tags.string, color: '#047857' },
   { tag: tags.number, color: '#b45309' },
+  { tag: tags.atom, color: '#be185d' },
   { tag: tags.variableName, color: '#111827' },
   { tag: tags.comment, color: '#6b7280', fontStyle: 'italic' },
 ]);
@@ -1,17 +1,241 @@
-export type CompileOutput = {
+/**
+ * Catalog code (`E0005`) and kind (`unknown_ident`) of a failure; see ERRORS.md.
+ * A message without a translation template has its `kind` as `message_id` and its
+ * English text as `message_args.message`.
+ */
+export type ErrorCode = {
+  code?: string;
+  kind?: string;
+  message_id?: string;
+  message_args?: Record<string, string>;
+};
+
+/** Locales `setLocale` accepts, mirroring `dsl_runtime::LOCALES`. */
+export type Locale = 'en' | 'es' | 'fr';
+
+export type CompileOutput = ErrorCode & {
+  ok: boolean;
+  diagnostics: string;
+};
+
+export type CompileHandleOutput = {
+  ok: boolean;
+  handle: number | null;
+  diagnostics: string;
+};
+
+export type DiagramOutput = {
+  ok: boolean;
+  diagram: string;
+  diagnostics: string;
+};
+
+/** `ui.diff`-style rows (`{op, key, expected?, actual?, fields?}`) keyed by row index. */
+export type RunDiffOutput = {
+  ok: boolean;
+  diff: { tables: Record<string, unknown[]>; logs: Record<string, unknown[]> } | null;
+  diagnostics: string;
+};
+
+/** One `RunOutput` per program name; `programs` is empty when any program fails. */
+export type WorkspaceRunOutput = {
   ok: boolean;
+  programs: Record<string, RunOutput>;
   diagnostics: string;
 };
 
+/** `session` is opaque JSON for `restoreSession`; `null` before the first `runStatement`. */
+export type SaveSessionOutput = {
+  ok: boolean;
+  session: unknown;
+  diagnostics: string;
+};
+
+export type RestoreSessionOutput = {
+  ok: boolean;
+  diagnostics: string;
+};
+
+export type ParamNamesOutput = {
+  ok: boolean;
+  params: string[];
+  diagnostics: string;
+};
+
+/** Optional runtime features compiled into the module, and what programs can call. */
+export type Capabilities = {
+  features: string[];
+  stages: string[];
+  sources: string[];
+};
+
+export type ExplainLevel = 'quiet' | 'normal' | 'verbose';
+
+/** Mirrors `dsl_runtime::RunOptions::from_json`; every field is optional. */
+export type RunOptions = {
+  params?: Record<string, unknown>;
+  constants?: Record<string, unknown>;
+  explain?: ExplainLevel;
+  explain_items?: number;
+  audit?: boolean;
+  track_memory?: boolean;
+  memory_limit?: number;
+  columnar?: boolean;
+  /** Bare codecs only encode (decode with `~codec`); fixture values must be arrays. */
+  strict?: boolean;
+  /** `'error'` (the default) rejects binding a name twice without `override`. */
+  rebinding?: 'error' | 'allow';
+  /** Parse limits; default 1 MiB of source, 128 levels of nesting, 10,000 statements. */
+  max_source_len?: number;
+  max_depth?: number;
+  max_statements?: number;
+};
+
+export type ColumnType = 'null' | 'bool' | 'number' | 'string' | 'array' | 'object' | 'mixed';
+
+/** One table of `RunOutput.tables_columnar_json`. */
+export type ColumnarTable = {
+  row_count: number;
+  columns: { name: string; type: ColumnType; values: unknown[] }[];
+};
+
+/** One `ui.graph` output. */
+export type GraphOutput = {
+  nodes: { id: string | number; item: unknown }[];
+  edges: { from: string | number; to: string | number }[];
+};
+
+export type LogLevel = 'debug' | 'info' | 'warn' | 'error';
+
+/** One `ui.log` item (`dsl_runtime::LogRecord`). */
+export type LogRecord = { level: LogLevel; message: string; item: unknown };
+
+export type WarningKind = 'deprecated' | 'mixed_direction' | 'truncated' | 'ancestor_cycle';
+
+/** One run warning (`dsl_runtime::Warning`); `line`/`column` when it has a position. */
+export type WarningRecord = { kind: WarningKind; message: string; line?: number; column?: number };
+
+/** One node of a coverage report (`dsl_runtime::CoverageEntry`). */
+export type CoverageRecord = {
+  kind: 'statement' | 'source' | 'stage' | 'arm';
+  text: string;
+  items: number;
+  line?: number;
+  column?: number;
+};
+
+/** Inferred column of a table (`dsl_runtime::ColumnSchema`). */
+export type ColumnSchema = { name: string; type: ColumnType; nullable: boolean };
+
 export type RunOutput = {
   tables_json: string;
   logs_json: string;
   explain: string;
+  /** `Record<string, GraphOutput>` as JSON when the program has `ui.graph` sinks. */
+  graphs_json?: string;
+  /** `Record<string, LogRecord[]>` as JSON; absent when the run failed. */
+  log_records_json?: string;
+  /** Columns per table; absent when the run failed. */
+  schemas?: Record<string, ColumnSchema[]>;
+  /** Present when the run used `audit: true`. */
+  fingerprint?: string;
+  /** Non-fatal diagnostics (e.g. deprecated stage names) ending in `at line:column`. */
+  warnings?: string[];
+  /** `warnings` as structured records, in the same order. */
+  warning_records?: WarningRecord[];
+  /** With `coverage: true`: items that reached each statement, source, stage, and match arm. */
+  coverage?: CoverageRecord[];
+  /** Present when the run tracked memory (`track_memory` or `memory_limit`). */
+  peak_stream_bytes?: number;
+  /** With `columnar: true`: `Record<string, ColumnarTable>` as JSON; `tables_json` is then `{}`. */
+  tables_columnar_json?: string;
+  /** Set by `run`: pass to `getTablePage` / `releaseRun`. */
+  run_id?: number;
+  /** `true` when an identical recent `run` / `runWithParams` / `runWithOptions` call supplied this output. */
+  cached?: boolean;
+  row_counts?: Record<string, number>;
+};
+
+export type TablePageOutput = {
+  ok: boolean;
+  rows: unknown[];
+  offset: number;
+  total: number;
+  diagnostics: string;
 };
 
+export type CancellableRunOutput = RunOutput & {
+  status: 'ok' | 'cancelled' | 'error';
+};
+
+export type RunV2Output = ErrorCode & {
+  ok: boolean;
+  error?: string;
+  /** Byte range of the failing source or stage, when known. */
+  span?: { start: number; end: number } | null;
+  suggestions?: string[];
+  tables: Record<string, unknown[]>;
+  logs: Record<string, string[]>;
+  explain: string[];
+};
+
+/** Async-capable host functions reachable from `host.call("name", arg)`. */
+export type HostFunctions = Record<string, (arg: unknown) => unknown | Promise<unknown>>;
+
+export type HostRunOutput = RunV2Output & {
+  status: 'ok' | 'error' | 'pending';
+  pending?: { name: string; arg_json: string }[];
+};
+
+/** Resolve-and-rerun rounds before `runWithHost` gives up on dependent calls. */
+const MAX_HOST_ROUNDS = 8;
+
 export type WasmApi = {
   compile: (program: string) => CompileOutput;
-  run: (program: string, fixtures: string) => RunOutput;
+  run: (program: string, fixtures: string, pretty?: boolean) => RunOutput;
+  runWithParams: (program: string, fixtures: string, params: string, pretty?: boolean) => RunOutput;
+  paramNames: (program: string) => ParamNamesOutput;
+  capabilities: () => Capabilities;
+  runWithOptions: (program: string, fixtures: string, options: RunOptions, pretty?: boolean) => RunOutput;
+  runStreaming: (
+    program: string,
+    fixtures: string,
+    onRow: (table: string, rowJson: string) => void,
+  ) => RunOutput;
+  runV2: (program: string, fixtures: string) => RunV2Output;
+  runCancellable: (program: string, fixtures: string, token: number) => CancellableRunOutput;
+  cancel: (token: number) => void;
+  /**
+   * Calls `onYield(points)` every `interval` yield points (items, stages, statements);
+   * returning `false` (e.g. past a deadline) stops the run with status `'cancelled'`.
+   */
+  runWithYield: (
+    program: string,
+    fixtures: string,
+    interval: number,
+    onYield: (points: number) => boolean,
+  ) => CancellableRunOutput;
+  compileHandle: (program: string) => CompileHandleOutput;
+  runHandle: (handle: number, fixtures: string) => RunOutput;
+  /** Runs one statement (0-based), reusing bindings from earlier `runStatement` calls. */
+  runStatement: (program: string, index: number, fixtures: string) => RunOutput;
+  /** The `runStatement` session's bindings, constants, and `kv.load` stores, e.g. for localStorage. */
+  saveSession: () => SaveSessionOutput;
+  /** Replaces the `runStatement` session with one from `saveSession`. */
+  restoreSession: (session: string) => RestoreSessionOutput;
+  invalidate: (handle: number) => boolean;
+  /** Forgets cached run outputs to free memory; the next identical run recomputes. */
+  clearResultCache: () => void;
+  getTablePage: (runId: number, table: string, offset: number, limit: number) => TablePageOutput;
+  releaseRun: (runId: number) => boolean;
+  toDot: (program: string) => DiagramOutput;
+  toMermaid: (program: string) => DiagramOutput;
+  runAndDiff: (programA: string, programB: string, fixtures: string) => RunDiffOutput;
+  /** Runs named programs in name order on shared fixtures, `kv.load` stores, and `rbac.load` policies. */
+  runWorkspace: (programs: Record<string, string>, fixtures: string, pretty?: boolean) => WorkspaceRunOutput;
+  runWithHost: (program: string, fixtures: string, hostFns: HostFunctions) => Promise<HostRunOutput>;
+  /** Renders later `compile` diagnostics and `runV2` errors in `locale`. */
+  setLocale: (locale: Locale) => boolean;
 };
 
 function parseJson<T>(text: string, fallback: T): T {
@@ -37,12 +261,154 @@ export async function loadWasmApi(): Promise<WasmApi> {
           ok: false,
           diagnostics: 'failed to parse compile output',
         }),
-      run: (program: string, fixtures: string) =>
-        parseJson<RunOutput>(module.run(program, fixtures), {
+      run: (program: string, fixtures: string, pretty = false) =>
+        parseJson<RunOutput>(module.run(program, fixtures, pretty), {
+          tables_json: '{}',
+          logs_json: '{}',
+          explain: 'failed to parse run output',
+        }),
+      runWithParams: (program: string, fixtures: string, params: string, pretty = false) =>
+        parseJson<RunOutput>(module.run_with_params(program, fixtures, params, pretty), {
           tables_json: '{}',
           logs_json: '{}',
           explain: 'failed to parse run output',
         }),
+      runWithOptions: (program: string, fixtures: string, options: RunOptions, pretty = false) =>
+        parseJson<RunOutput>(module.run_with_options(program, fixtures, JSON.stringify(options), pretty), {
+          tables_json: '{}',
+          logs_json: '{}',
+          explain: 'failed to parse run output',
+        }),
+      paramNames: (program: string) =>
+        parseJson<ParamNamesOutput>(module.param_names(program), {
+          ok: false,
+          params: [],
+          diagnostics: 'failed to parse param_names output',
+        }),
+      capabilities: () =>
+        parseJson<Capabilities>(module.capabilities(), { features: [], stages: [], sources: [] }),
+      runStreaming: (program: string, fixtures: string, onRow: (table: string, rowJson: string) => void) =>
+        parseJson<RunOutput>(module.run_streaming(program, fixtures, onRow), {
+          tables_json: '{}',
+          logs_json: '{}',
+          explain: 'failed to parse run output',
+        }),
+      setLocale: (locale: Locale) => Boolean(module.set_locale(locale)),
+      runV2: (program: string, fixtures: string) =>
+        parseJson<RunV2Output>(module.run_v2(program, fixtures), {
+          ok: false,
+          error: 'failed to parse run_v2 output',
+          tables: {},
+          logs: {},
+          explain: [],
+        }),
+      runCancellable: (program: string, fixtures: string, token: number) =>
+        parseJson<CancellableRunOutput>(module.run_cancellable(program, fixtures, token), {
+          tables_json: '{}',
+          logs_json: '{}',
+          explain: 'failed to parse run output',
+          status: 'error',
+        }),
+      cancel: (token: number) => module.cancel(token),
+      runWithYield: (program: string, fixtures: string, interval: number, onYield: (points: number) => boolean) =>
+        parseJson<CancellableRunOutput>(module.run_with_yield(program, fixtures, interval, onYield), {
+          tables_json: '{}',
+          logs_json: '{}',
+          explain: 'failed to parse run output',
+          status: 'error',
+        }),
+      compileHandle: (program: string) =>
+        parseJson<CompileHandleOutput>(module.compile_handle(program), {
+          ok: false,
+          handle: null,
+          diagnostics: 'failed to parse compile output',
+        }),
+      runHandle: (handle: number, fixtures: string) =>
+        parseJson<RunOutput>(module.run_handle(handle, fixtures), {
+          tables_json: '{}',
+          logs_json: '{}',
+          explain: 'failed to parse run output',
+        }),
+      runStatement: (program: string, index: number, fixtures: string) =>
+        parseJson<RunOutput>(module.run_statement(program, index, fixtures), {
+          tables_json: '{}',
+          logs_json: '{}',
+          explain: 'failed to parse run output',
+        }),
+      saveSession: () =>
+        parseJson<SaveSessionOutput>(module.save_session(), {
+          ok: false,
+          session: null,
+          diagnostics: 'failed to parse save_session output',
+        }),
+      restoreSession: (session: string) =>
+        parseJson<RestoreSessionOutput>(module.restore_session(session), {
+          ok: false,
+          diagnostics: 'failed to parse restore_session output',
+        }),
+      invalidate: (handle: number) => Boolean(module.invalidate(handle)),
+      clearResultCache: () => module.clear_result_cache(),
+      getTablePage: (runId: number, table: string, offset: number, limit: number) =>
+        parseJson<TablePageOutput>(module.get_table_page(runId, table, offset, limit), {
+          ok: false,
+          rows: [],
+          offset,
+          total: 0,
+          diagnostics: 'failed to parse table page output',
+        }),
+      releaseRun: (runId: number) => Boolean(module.release_run(runId)),
+      toDot: (program: string) =>
+        parseJson<DiagramOutput>(module.to_dot(program), {
+          ok: false,
+          diagram: '',
+          diagnostics: 'failed to parse diagram output',
+        }),
+      toMermaid: (program: string) =>
+        parseJson<DiagramOutput>(module.to_mermaid(program), {
+          ok: false,
+          diagram: '',
+          diagnostics: 'failed to parse diagram output',
+        }),
+      runAndDiff: (programA: string, programB: string, fixtures: string) =>
+        parseJson<RunDiffOutput>(module.run_and_diff(programA, programB, fixtures), {
+          ok: false,
+          diff: null,
+          diagnostics: 'failed to parse run_and_diff output',
+        }),
+      runWorkspace: (programs: Record<string, string>, fixtures: string, pretty = false) =>
+        parseJson<WorkspaceRunOutput>(module.run_workspace(JSON.stringify(programs), fixtures, pretty), {
+          ok: false,
+          programs: {},
+          diagnostics: 'failed to parse run_workspace output',
+        }),
+      runWithHost: async (program: string, fixtures: string, hostFns: HostFunctions) => {
+        const results: Record<string, Record<string, unknown>> = {};
+        for (const name of Object.keys(hostFns)) {
+          results[name] = {};
+        }
+        for (let round = 0; round < MAX_HOST_ROUNDS; round += 1) {
+          const out = parseJson<HostRunOutput>(
+            module.run_with_host(program, fixtures, JSON.stringify(results)),
+            { ok: false, error: 'failed to parse run output', tables: {}, logs: {}, explain: [], status: 'error' },
+          );
+          if (out.status !== 'pending') {
+            return out;
+          }
+          await Promise.all(
+            (out.pending ?? []).map(async ({ name, arg_json }) => {
+              results[name][arg_json] = await hostFns[name](JSON.parse(arg_json));
+            }),
+          );
+        }
+        return {
+          ok: false,
+          error: `host calls did not settle after ${MAX_HOST_ROUNDS} rounds`,
+          tables: {},
+          logs: {},
+          explain: [],
+          status: 'error',
+        };
+      },
     };
   } catch (error) {
     const lastError = error instanceof Error ? error.message : String(error);
@@ -59,6 +425,83 @@ export async function loadWasmApi(): Promise<WasmApi> {
         logs_json: '{}',
         explain: `WASM package not built. Could not load ${wasmModuleUrl} (${lastError})`,
       }),
+      runWithParams: () => ({
+        tables_json: '{}',
+        logs_json: '{}',
+        explain: `WASM package not built. Could not load ${wasmModuleUrl} (${lastError})`,
+      }),
+      paramNames: () => ({ ok: false, params: [], diagnostics: 'WASM package not built.' }),
+      capabilities: () => ({ features: [], stages: [], sources: [] }),
+      runWithOptions: () => ({
+        tables_json: '{}',
+        logs_json: '{}',
+        explain: `WASM package not built. Could not load ${wasmModuleUrl} (${lastError})`,
+      }),
+      runStreaming: () => ({
+        tables_json: '{}',
+        logs_json: '{}',
+        explain: `WASM package not built. Could not load ${wasmModuleUrl} (${lastError})`,
+      }),
+      runV2: () => ({
+        ok: false,
+        error: `WASM package not built. Could not load ${wasmModuleUrl} (${lastError})`,
+        tables: {},
+        logs: {},
+        explain: [],
+      }),
+      runCancellable: () => ({
+        tables_json: '{}',
+        logs_json: '{}',
+        explain: `WASM package not built. Could not load ${wasmModuleUrl} (${lastError})`,
+        status: 'error',
+      }),
+      cancel: () => {},
+      runWithYield: () => ({
+        tables_json: '{}',
+        logs_json: '{}',
+        explain: `WASM package not built. Could not load ${wasmModuleUrl} (${lastError})`,
+        status: 'error',
+      }),
+      compileHandle: () => ({
+        ok: false,
+        handle: null,
+        diagnostics: `WASM package not built. Could not load ${wasmModuleUrl} (${lastError})`,
+      }),
+      runHandle: () => ({
+        tables_json: '{}',
+        logs_json: '{}',
+        explain: `WASM package not built. Could not load ${wasmModuleUrl} (${lastError})`,
+      }),
+      runStatement: () => ({
+        tables_json: '{}',
+        logs_json: '{}',
+        explain: `WASM package not built. Could not load ${wasmModuleUrl} (${lastError})`,
+      }),
+      saveSession: () => ({ ok: false, session: null, diagnostics: 'WASM package not built.' }),
+      restoreSession: () => ({ ok: false, diagnostics: 'WASM package not built.' }),
+      invalidate: () => false,
+      clearResultCache: () => {},
+      getTablePage: (_runId: number, _table: string, offset: number) => ({
+        ok: false,
+        rows: [],
+        offset,
+        total: 0,
+        diagnostics: 'WASM package not built.',
+      }),
+      releaseRun: () => false,
+      setLocale: () => false,
+      toDot: () => ({ ok: false, diagram: '', diagnostics: 'WASM package not built.' }),
+      toMermaid: () => ({ ok: false, diagram: '', diagnostics: 'WASM package not built.' }),
+      runAndDiff: () => ({ ok: false, diff: null, diagnostics: 'WASM package not built.' }),
+      runWorkspace: () => ({ ok: false, programs: {}, diagnostics: 'WASM package not built.' }),
+      runWithHost: async () => ({
+        ok: false,
+        error: `WASM package not built. Could not load ${wasmModuleUrl} (${lastError})`,
+        tables: {},
+        logs: {},
+        explain: [],
+        status: 'error',
+      }),
     };
   }
 }